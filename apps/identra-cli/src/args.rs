@@ -0,0 +1,162 @@
+//! Command-line parsing: configuration flags are split off for `identra_core::config`,
+//! the rest are positional arguments and `--option value` pairs of the command.
+
+use crate::CliError;
+use std::collections::VecDeque;
+
+/// Options that take no value
+const SWITCHES: &[&str] = &["json", "help", "force", "password_stdin", "whole_memories", "dry_run"];
+
+/// Split out `--config`, `--print-config` and `--<section>.<key>` flags, which are
+/// passed to the configuration loader with their values
+pub fn split_config(args: impl IntoIterator<Item = String>) -> (Vec<String>, Vec<String>) {
+    let mut config = Vec::new();
+    let mut rest = Vec::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let Some(flag) = arg.strip_prefix("--") else {
+            rest.push(arg);
+            continue;
+        };
+        let name = flag.split_once('=').map_or(flag, |(name, _)| name);
+        if name == "print-config" || name == "print_config" {
+            config.push(arg);
+        } else if name == "config" || name.contains('.') {
+            let inline = flag.contains('=');
+            config.push(arg);
+            if !inline {
+                config.extend(args.next());
+            }
+        } else {
+            rest.push(arg);
+        }
+    }
+    (config, rest)
+}
+
+/// Positional arguments and options of one command line
+#[derive(Debug, Default)]
+pub struct Args {
+    positional: VecDeque<String>,
+    options: Vec<(String, Option<String>)>,
+}
+
+impl Args {
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, CliError> {
+        let mut parsed = Self::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            if arg == "--" {
+                parsed.positional.extend(args.by_ref());
+                break;
+            }
+            let Some(flag) = arg.strip_prefix("--").filter(|flag| !flag.is_empty()) else {
+                parsed.positional.push_back(arg);
+                continue;
+            };
+            let (name, inline) = match flag.split_once('=') {
+                Some((name, value)) => (name.replace('-', "_"), Some(value.to_string())),
+                None => (flag.replace('-', "_"), None),
+            };
+            let value = if SWITCHES.contains(&name.as_str()) {
+                inline
+            } else {
+                match inline.or_else(|| args.next()) {
+                    Some(value) => Some(value),
+                    None => return Err(CliError::Usage(format!("--{} needs a value", flag))),
+                }
+            };
+            parsed.options.push((name, value));
+        }
+        Ok(parsed)
+    }
+
+    /// Next positional argument
+    pub fn next(&mut self) -> Option<String> {
+        self.positional.pop_front()
+    }
+
+    /// Next positional argument, which the command requires
+    pub fn require(&mut self, what: &str) -> Result<String, CliError> {
+        self.next().ok_or_else(|| CliError::Usage(format!("missing <{}>", what)))
+    }
+
+    pub fn switch(&self, name: &str) -> bool {
+        self.options.iter().any(|(option, _)| option == name)
+    }
+
+    /// Last value given for `--name`
+    pub fn value(&self, name: &str) -> Option<&str> {
+        self.options.iter().rev().find(|(option, _)| option == name).and_then(|(_, value)| value.as_deref())
+    }
+
+    /// Every value given for a repeatable `--name`
+    pub fn values(&self, name: &str) -> Vec<&str> {
+        self.options.iter().filter(|(option, _)| option == name).filter_map(|(_, value)| value.as_deref()).collect()
+    }
+
+    pub fn number(&self, name: &str, default: u32) -> Result<u32, CliError> {
+        match self.value(name) {
+            Some(value) => value
+                .parse()
+                .map_err(|_| CliError::Usage(format!("--{} must be a non-negative integer, got {:?}", name, value))),
+            None => Ok(default),
+        }
+    }
+
+    /// Fail on options the command does not know and on leftover positional arguments
+    pub fn finish(&self, known: &[&str]) -> Result<(), CliError> {
+        if let Some((name, _)) = self.options.iter().find(|(name, _)| !known.contains(&name.as_str()) && name != "json") {
+            return Err(CliError::Usage(format!("unknown option --{}", name.replace('_', "-"))));
+        }
+        if let Some(extra) = self.positional.front() {
+            return Err(CliError::Usage(format!("unexpected argument {:?}", extra)));
+        }
+        Ok(())
+    }
+}
+
+/// `key=value` pairs of a repeatable `--meta` option
+pub fn pairs(values: &[&str]) -> Result<std::collections::HashMap<String, String>, CliError> {
+    values
+        .iter()
+        .map(|pair| {
+            pair.split_once('=')
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .ok_or_else(|| CliError::Usage(format!("expected key=value, got {:?}", pair)))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn splits_config_flags_from_command() {
+        let (config, rest) = split_config(strings(&[
+            "--desktop.gateway-address", "https://identra.example:443", "memory", "--config=dev.toml", "ls", "--limit", "5",
+        ]));
+        assert_eq!(config, strings(&["--desktop.gateway-address", "https://identra.example:443", "--config=dev.toml"]));
+        assert_eq!(rest, strings(&["memory", "ls", "--limit", "5"]));
+    }
+
+    #[test]
+    fn parses_positionals_options_and_switches() {
+        let mut args = Args::parse(strings(&["add", "--tag", "a", "--json", "--tag=b", "--meta", "k=v", "--", "--not-a-flag"])).unwrap();
+        assert!(args.switch("json"));
+        assert_eq!(args.values("tag"), ["a", "b"]);
+        assert_eq!(pairs(&args.values("meta")).unwrap()["k"], "v");
+        assert_eq!(args.next().as_deref(), Some("add"));
+        assert_eq!(args.next().as_deref(), Some("--not-a-flag"));
+        args.finish(&["tag", "meta"]).unwrap();
+        assert!(args.finish(&["tag"]).is_err());
+
+        assert!(Args::parse(strings(&["ls", "--limit"])).is_err());
+        assert!(Args::parse(strings(&["ls", "--limit", "x"])).unwrap().number("limit", 10).is_err());
+    }
+}
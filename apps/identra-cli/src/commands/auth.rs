@@ -0,0 +1,91 @@
+use crate::args::Args;
+use crate::output::Output;
+use crate::session::CachedLogin;
+use crate::{CliError, Context};
+use std::io::{BufRead, IsTerminal, Write};
+
+/// Environment variable read for the password before prompting
+const PASSWORD_ENV: &str = "IDENTRA_PASSWORD";
+
+pub async fn login(context: &mut Context, mut args: Args, output: &Output) -> Result<(), CliError> {
+    let username = match args.next() {
+        Some(username) => username,
+        None => prompt("Username: ", false)?,
+    };
+    let password_stdin = args.switch("password_stdin");
+    args.finish(&["password_stdin"])?;
+
+    let password = if password_stdin {
+        read_line()?
+    } else if let Ok(password) = std::env::var(PASSWORD_ENV) {
+        password
+    } else {
+        prompt("Password: ", true)?
+    };
+    if username.is_empty() || password.is_empty() {
+        return Err(CliError::Usage("username and password are required".to_string()));
+    }
+
+    let session = context.client.auth().login(&username, &password).await?;
+    let login = CachedLogin { username, session };
+    context.cache.save(&login).await?;
+
+    let summary = serde_json::json!({ "username": login.username, "expires_at": login.session.expires_at });
+    output.emit(&summary, || {
+        println!("✅ Logged in as {}", login.username);
+        println!("   Session cached in the vault daemon, access token valid until {}", login.session.expires_at.format("%Y-%m-%d %H:%M UTC"));
+    })
+}
+
+pub async fn logout(context: &mut Context, args: Args, output: &Output) -> Result<(), CliError> {
+    args.finish(&[])?;
+    let removed = context.cache.clear().await?;
+    context.client.auth().logout();
+
+    output.emit(&serde_json::json!({ "logged_out": removed }), || {
+        if removed {
+            println!("✅ Logged out");
+        } else {
+            println!("Not logged in");
+        }
+    })
+}
+
+/// Ask on stderr and read one line, without echo for secrets when stdin is a terminal
+fn prompt(label: &str, secret: bool) -> Result<String, CliError> {
+    let stdin = std::io::stdin();
+    if !stdin.is_terminal() {
+        return Err(CliError::Usage(format!("{} needed but stdin is not a terminal", label.trim_end_matches([':', ' ']))));
+    }
+    eprint!("{}", label);
+    std::io::stderr().flush()?;
+
+    let hidden = secret && set_echo(false);
+    let line = read_line();
+    if hidden {
+        set_echo(true);
+        eprintln!();
+    }
+    line
+}
+
+fn read_line() -> Result<String, CliError> {
+    let mut line = String::new();
+    std::io::stdin().lock().read_line(&mut line)?;
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+/// Turn terminal echo on or off; `false` when it could not be changed
+#[cfg(unix)]
+fn set_echo(on: bool) -> bool {
+    std::process::Command::new("stty")
+        .arg(if on { "echo" } else { "-echo" })
+        .stdin(std::process::Stdio::inherit())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+#[cfg(not(unix))]
+fn set_echo(_on: bool) -> bool {
+    false
+}
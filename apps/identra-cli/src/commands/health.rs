@@ -0,0 +1,181 @@
+use crate::args::Args;
+use crate::output::Output;
+use crate::{CliError, Context};
+use identra_client::ipc::VaultClient;
+use identra_client::{HealthReport, ServingStatus};
+use serde::Serialize;
+
+/// Gateway dependencies reported by the Health service
+const COMPONENTS: &[&str] = &["database", "vault", "embeddings", "identity"];
+
+pub async fn health(context: &mut Context, args: Args, output: &Output) -> Result<(), CliError> {
+    args.finish(&[])?;
+    let health = context.client.health();
+    let overall = health.check().await?;
+    let mut components = Vec::with_capacity(COMPONENTS.len());
+    for &name in COMPONENTS {
+        let report = health.check_service(name).await?;
+        if report.status != ServingStatus::ServiceUnknown {
+            components.push((name, report));
+        }
+    }
+
+    let body = serde_json::json!({
+        "status": overall.status,
+        "message": overall.message,
+        "uptime_seconds": overall.uptime.as_secs(),
+        "components": components.iter()
+            .map(|(name, report)| (name.to_string(), serde_json::json!({ "status": report.status, "message": report.message })))
+            .collect::<serde_json::Map<_, _>>(),
+    });
+    output.emit(&body, || {
+        println!("{} gateway: {}", mark(&overall), describe(&overall));
+        println!("   up for {}", uptime(overall.uptime.as_secs()));
+        for (name, report) in &components {
+            println!("{} {}: {}", mark(report), name, describe(report));
+        }
+    })?;
+
+    if overall.is_serving() {
+        Ok(())
+    } else {
+        Err(CliError::Failed(format!("gateway is not serving: {}", describe(&overall))))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Status {
+    Ok,
+    Warn,
+    Fail,
+}
+
+#[derive(Debug, Serialize)]
+struct Check {
+    name: String,
+    status: Status,
+    detail: String,
+}
+
+impl Check {
+    fn new(name: impl Into<String>, status: Status, detail: impl Into<String>) -> Self {
+        Self { name: name.into(), status, detail: detail.into() }
+    }
+}
+
+/// Walk configuration, vault daemon, gateway, its dependencies and the cached login,
+/// reporting every check rather than stopping at the first failure
+pub async fn doctor(context: &mut Context, args: Args, output: &Output) -> Result<(), CliError> {
+    args.finish(&[])?;
+    let mut checks = vec![Check::new(
+        "config",
+        Status::Ok,
+        format!("gateway {}, vault daemon {}", context.config.desktop.gateway_address, context.config.vault.socket),
+    )];
+
+    checks.push(match VaultClient::connect_to(&context.config.vault.socket).await {
+        Ok(mut vault) => match vault.ping().await {
+            Ok(()) => Check::new("vault daemon", Status::Ok, format!("responding on {}", context.config.vault.socket)),
+            Err(e) => Check::new("vault daemon", Status::Fail, e.to_string()),
+        },
+        Err(e) => Check::new("vault daemon", Status::Fail, format!("{}; start vault-daemon or set --vault.socket", e)),
+    });
+
+    let health = context.client.health();
+    match health.check().await {
+        Ok(report) => {
+            let status = if report.is_serving() { Status::Ok } else { Status::Fail };
+            checks.push(Check::new("gateway", status, format!("{}, up for {}", describe(&report), uptime(report.uptime.as_secs()))));
+            for &name in COMPONENTS {
+                checks.push(match health.check_service(name).await {
+                    Ok(report) if report.is_serving() => Check::new(name, Status::Ok, describe(&report)),
+                    // Gateways that predate the component can't say either way
+                    Ok(report) if report.status == ServingStatus::ServiceUnknown => {
+                        Check::new(name, Status::Warn, "not reported by this gateway")
+                    }
+                    Ok(report) => Check::new(name, Status::Fail, describe(&report)),
+                    Err(e) => Check::new(name, Status::Fail, e.to_string()),
+                });
+            }
+        }
+        Err(e) => checks.push(Check::new(
+            "gateway",
+            Status::Fail,
+            format!("{} at {}", e, context.config.desktop.gateway_address),
+        )),
+    }
+
+    checks.push(session_check(context).await);
+
+    let failed = checks.iter().filter(|check| check.status == Status::Fail).count();
+    let body = serde_json::json!({ "ok": failed == 0, "checks": checks });
+    output.emit(&body, || {
+        for check in &checks {
+            let mark = match check.status {
+                Status::Ok => "✅",
+                Status::Warn => "⚠️ ",
+                Status::Fail => "❌",
+            };
+            println!("{} {}: {}", mark, check.name, check.detail);
+        }
+    })?;
+
+    match failed {
+        0 => Ok(()),
+        1 => Err(CliError::Failed("1 check failed".to_string())),
+        n => Err(CliError::Failed(format!("{} checks failed", n))),
+    }
+}
+
+/// Whether a login is cached and the gateway still accepts it
+async fn session_check(context: &Context) -> Check {
+    let login = match context.cache.load().await {
+        Ok(Some(login)) => login,
+        Ok(None) => return Check::new("session", Status::Warn, "not logged in; run `identra login`"),
+        // Already reported by the vault daemon check
+        Err(_) => return Check::new("session", Status::Warn, "cached login unreadable without the vault daemon"),
+    };
+    if login.session.is_expired() && !login.session.can_refresh() {
+        return Check::new("session", Status::Warn, format!("session of {} expired; run `identra login`", login.username));
+    }
+    if login.session.is_expired() {
+        return Check::new("session", Status::Ok, format!("logged in as {}, token will be refreshed on next use", login.username));
+    }
+    match context.client.auth().verify(&login.session.access_token).await {
+        Ok(Some(identity)) => Check::new(
+            "session",
+            Status::Ok,
+            format!("logged in as {} until {}", identity.username, identity.expires_at.format("%Y-%m-%d %H:%M UTC")),
+        ),
+        Ok(None) => Check::new("session", Status::Warn, format!("gateway rejected the session of {}; run `identra login`", login.username)),
+        Err(e) => Check::new("session", Status::Fail, format!("could not verify the session: {}", e)),
+    }
+}
+
+fn mark(report: &HealthReport) -> &'static str {
+    if report.is_serving() { "✅" } else { "❌" }
+}
+
+fn describe(report: &HealthReport) -> String {
+    let status = match report.status {
+        ServingStatus::Serving => "serving",
+        ServingStatus::NotServing => "not serving",
+        ServingStatus::ServiceUnknown => "unknown service",
+        ServingStatus::Unknown => "unknown",
+    };
+    if report.message.is_empty() {
+        status.to_string()
+    } else {
+        format!("{} ({})", status, report.message)
+    }
+}
+
+fn uptime(seconds: u64) -> String {
+    match seconds {
+        0..=59 => format!("{}s", seconds),
+        60..=3599 => format!("{}m", seconds / 60),
+        3600..=86399 => format!("{}h {}m", seconds / 3600, seconds % 3600 / 60),
+        _ => format!("{}d {}h", seconds / 86400, seconds % 86400 / 3600),
+    }
+}
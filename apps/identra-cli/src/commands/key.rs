@@ -0,0 +1,90 @@
+use crate::args::{pairs, Args};
+use crate::output::Output;
+use crate::{CliError, Context};
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use identra_client::Vault;
+use std::io::Write;
+
+pub async fn run(context: &mut Context, mut args: Args, output: &Output) -> Result<(), CliError> {
+    let command = args.require("key command")?;
+    let vault = context.authenticated().await?.vault();
+
+    match command.as_str() {
+        "put" => put(&vault, args, output).await,
+        "get" => get(&vault, args, output).await,
+        "ls" => list(&vault, args, output).await,
+        "rm" => remove(&vault, args, output).await,
+        other => Err(CliError::Usage(format!("unknown key command {:?}", other))),
+    }
+}
+
+async fn put(vault: &Vault, mut args: Args, output: &Output) -> Result<(), CliError> {
+    let key_id = args.require("id")?;
+    args.finish(&["value", "file", "meta", "expires"])?;
+
+    let data = match (args.value("value"), args.value("file")) {
+        (Some(_), Some(_)) => return Err(CliError::Usage("give either --value or --file, not both".to_string())),
+        (Some(value), None) => value.as_bytes().to_vec(),
+        (None, file) => super::read_input(file)?,
+    };
+    let expires_at = args
+        .value("expires")
+        .map(|value| {
+            DateTime::parse_from_rfc3339(value)
+                .map(|time| time.with_timezone(&Utc))
+                .map_err(|e| CliError::Usage(format!("--expires must be an RFC 3339 time: {}", e)))
+        })
+        .transpose()?;
+
+    vault.put(&key_id, data, pairs(&args.values("meta"))?, expires_at).await?;
+    output.emit(&serde_json::json!({ "key_id": key_id }), || println!("✅ Stored key {}", key_id))
+}
+
+/// Raw key bytes to stdout or `--out`; with `--json`, base64 with its metadata
+async fn get(vault: &Vault, mut args: Args, output: &Output) -> Result<(), CliError> {
+    let key_id = args.require("id")?;
+    let out = args.value("out").map(str::to_string);
+    args.finish(&["out"])?;
+    let key = vault.get(&key_id).await?;
+
+    if output.is_json() {
+        let body = serde_json::json!({
+            "key_id": key_id,
+            "data": base64::engine::general_purpose::STANDARD.encode(&key.data),
+            "metadata": key.metadata,
+            "created_at": key.created_at,
+        });
+        return output.emit(&body, || {});
+    }
+    match out.as_deref() {
+        Some(path) if path != "-" => {
+            std::fs::write(path, &key.data)?;
+            eprintln!("✅ Wrote {} bytes to {}", key.data.len(), path);
+        }
+        _ => std::io::stdout().write_all(&key.data)?,
+    }
+    Ok(())
+}
+
+async fn list(vault: &Vault, args: Args, output: &Output) -> Result<(), CliError> {
+    args.finish(&[])?;
+    let keys = vault.list().await?;
+
+    output.emit(&keys, || {
+        for key in &keys {
+            println!("{}", key);
+        }
+        if keys.is_empty() {
+            println!("No keys stored");
+        }
+    })
+}
+
+async fn remove(vault: &Vault, mut args: Args, output: &Output) -> Result<(), CliError> {
+    let key_id = args.require("id")?;
+    args.finish(&[])?;
+    vault.delete(&key_id).await?;
+
+    output.emit(&serde_json::json!({ "deleted": key_id }), || println!("✅ Deleted key {}", key_id))
+}
@@ -0,0 +1,386 @@
+use crate::args::{pairs, Args};
+use crate::ingest::{self, DOCUMENT_TYPE, HASH_KEY, SOURCE_KEY};
+use crate::output::{memory_line, Output};
+use crate::{CliError, Context};
+use identra_client::{ContextOrder, ContextPolicy, ErrorCode, Memories, MemoryFields, MemoryFilter, NewMemory, TimeRange};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write;
+use std::path::Path;
+
+pub async fn run(context: &mut Context, mut args: Args, output: &Output) -> Result<(), CliError> {
+    let command = args.require("memory command")?;
+    let namespace = args.value("namespace").map(str::to_string);
+    let client = context.authenticated().await?;
+    let memories = match namespace {
+        Some(namespace) => client.namespace(namespace),
+        None => client.memories(),
+    };
+
+    match command.as_str() {
+        "add" => add(&memories, args, output).await,
+        "get" => get(&memories, args, output).await,
+        "search" => search(&memories, args, output).await,
+        "ls" => list(&memories, args, output).await,
+        "context" => build_context(&memories, args, output).await,
+        "stats" => stats(&memories, args, output).await,
+        "rm" => remove(&memories, args, output).await,
+        "export" => export(&memories, args).await,
+        "import" => import(&memories, args, output).await,
+        "ingest" => ingest(&memories, args, output).await,
+        other => Err(CliError::Usage(format!("unknown memory command {:?}", other))),
+    }
+}
+
+/// Narrow to memories created in the last `--since` days, when given
+fn since(memories: &Memories, args: &Args) -> Result<Memories, CliError> {
+    if args.value("since").is_none() {
+        return Ok(memories.clone());
+    }
+    let days = args.number("since", 0)?;
+    let created = TimeRange::last(std::time::Duration::from_secs(u64::from(days) * 86_400));
+    Ok(memories.filter(MemoryFilter { created: Some(created), ..Default::default() }))
+}
+
+async fn add(memories: &Memories, mut args: Args, output: &Output) -> Result<(), CliError> {
+    let content = args.require("content")?;
+    args.finish(&["namespace", "tag", "meta"])?;
+    let content = if content == "-" {
+        String::from_utf8(super::read_input(None)?)
+            .map_err(|_| CliError::Usage("memory content must be UTF-8".to_string()))?
+    } else {
+        content
+    };
+
+    let mut memory = NewMemory::new(content);
+    memory.metadata = pairs(&args.values("meta"))?;
+    for tag in args.values("tag") {
+        memory = memory.tag(tag);
+    }
+    let id = memories.store(memory).await?;
+
+    output.emit(&serde_json::json!({ "id": id }), || println!("✅ Stored memory {}", id))
+}
+
+async fn get(memories: &Memories, mut args: Args, output: &Output) -> Result<(), CliError> {
+    let id = args.require("id")?;
+    args.finish(&["namespace"])?;
+    let memory = memories.get(&id).await?;
+
+    output.emit(&memory, || {
+        println!("{}", memory.content);
+        println!();
+        println!("id:         {}", memory.id);
+        println!("namespace:  {}", memory.namespace);
+        println!("created:    {}", memory.created_at.to_rfc3339());
+        if !memory.tags.is_empty() {
+            println!("tags:       {}", memory.tags.join(", "));
+        }
+        let mut metadata: Vec<_> = memory.metadata.iter().collect();
+        metadata.sort();
+        for (key, value) in metadata {
+            println!("{:<11} {}", format!("{}:", key), value);
+        }
+    })
+}
+
+async fn search(memories: &Memories, mut args: Args, output: &Output) -> Result<(), CliError> {
+    let text = args.require("text")?;
+    let limit = args.number("limit", 10)?;
+    args.finish(&["namespace", "limit", "since"])?;
+    let found = since(memories, &args)?.query(&text, limit).await?;
+
+    output.emit(&found, || {
+        for memory in &found {
+            println!("{}", memory_line(memory));
+        }
+        if found.is_empty() {
+            println!("No memories match {:?}", text);
+        }
+    })
+}
+
+async fn list(memories: &Memories, args: Args, output: &Output) -> Result<(), CliError> {
+    let limit = args.number("limit", 20)?;
+    args.finish(&["namespace", "limit", "since"])?;
+    let recent = since(memories, &args)?.recent(limit).await?;
+
+    output.emit(&recent, || {
+        for memory in &recent {
+            println!("{}", memory_line(memory));
+        }
+        if recent.is_empty() {
+            println!("No memories in {}", namespace(memories));
+        }
+    })
+}
+
+/// Context text on stdout, ready to paste into a prompt; citations on stderr
+async fn build_context(memories: &Memories, mut args: Args, output: &Output) -> Result<(), CliError> {
+    let query = args.require("query")?;
+    let budget = args.number("budget", 2000)?;
+    let order = match args.value("order") {
+        None | Some("relevance") => ContextOrder::Relevance,
+        Some("chronological") => ContextOrder::Chronological,
+        Some(other) => return Err(CliError::Usage(format!("--order must be relevance or chronological, got {:?}", other))),
+    };
+    let policy = ContextPolicy {
+        order,
+        tokenizer: args.value("tokenizer").map(str::to_string),
+        whole_memories_only: args.switch("whole_memories"),
+        ..Default::default()
+    };
+    args.finish(&["namespace", "budget", "order", "tokenizer", "whole_memories"])?;
+    let context = memories.context(&query, budget, policy).await?;
+
+    output.emit(&context, || {
+        println!("{}", context.text);
+        eprintln!();
+        for citation in &context.citations {
+            let cut = if citation.truncated { ", truncated" } else { "" };
+            eprintln!("[{}] {} (score {:.2}{})", citation.index, citation.memory_id, citation.score, cut);
+        }
+        eprintln!(
+            "{} of {} tokens from {} memories ({} duplicates, {} did not fit)",
+            context.token_count, budget, context.citations.len(), context.duplicates, context.omitted
+        );
+    })
+}
+
+async fn stats(memories: &Memories, args: Args, output: &Output) -> Result<(), CliError> {
+    args.finish(&["namespace"])?;
+    let stats = memories.stats().await?;
+
+    output.emit(&stats, || {
+        println!("{} memories, {} bytes in {}", stats.total_count, stats.total_bytes, namespace(memories));
+        println!("embedded:   {} ({:.0}%)", stats.embedded_count, stats.embedding_coverage * 100.0);
+        println!("untagged:   {}", stats.untagged_count);
+        println!("in trash:   {}", stats.trashed_count);
+        if let (Some(oldest), Some(newest)) = (stats.oldest, stats.newest) {
+            println!("span:       {} to {}", oldest.format("%Y-%m-%d"), newest.format("%Y-%m-%d"));
+        }
+        let sections = [("By tag", &stats.by_tag), ("By type", &stats.by_type), ("By month", &stats.by_month)];
+        for (title, buckets) in sections {
+            if buckets.is_empty() {
+                continue;
+            }
+            println!();
+            println!("{}:", title);
+            for bucket in buckets {
+                let key = if bucket.key.is_empty() { "(none)" } else { bucket.key.as_str() };
+                println!("{:>7}  {:<24} {:>10} bytes", bucket.count, key, bucket.bytes);
+            }
+        }
+    })
+}
+
+async fn remove(memories: &Memories, mut args: Args, output: &Output) -> Result<(), CliError> {
+    let id = args.require("id")?;
+    args.finish(&["namespace"])?;
+    memories.delete(&id).await?;
+
+    output.emit(&serde_json::json!({ "deleted": id }), || println!("✅ Deleted memory {}", id))
+}
+
+/// Every memory of the namespace as one JSON object per line
+async fn export(memories: &Memories, args: Args) -> Result<(), CliError> {
+    let out = args.value("out").map(str::to_string);
+    args.finish(&["namespace", "out"])?;
+    let all = memories.recent(u32::MAX).await?;
+
+    let mut lines = Vec::new();
+    for memory in &all {
+        serde_json::to_writer(&mut lines, memory)?;
+        lines.push(b'\n');
+    }
+    match out.as_deref() {
+        Some(path) if path != "-" => std::fs::write(path, &lines)?,
+        _ => std::io::stdout().write_all(&lines)?,
+    }
+    eprintln!("Exported {} memories from {}", all.len(), namespace(memories));
+    Ok(())
+}
+
+/// Store memories from JSON lines, as written by `export` or with just `content`
+async fn import(memories: &Memories, args: Args, output: &Output) -> Result<(), CliError> {
+    let file = args.value("file").map(str::to_string);
+    args.finish(&["namespace", "file"])?;
+    let input = String::from_utf8(super::read_input(file.as_deref())?)
+        .map_err(|_| CliError::Usage("import input must be UTF-8".to_string()))?;
+
+    // Parse everything first so a bad line stores nothing
+    let mut parsed = Vec::new();
+    for (number, line) in input.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let memory: NewMemory = serde_json::from_str(line)
+            .map_err(|e| CliError::Usage(format!("line {}: {}", number + 1, e)))?;
+        parsed.push(memory);
+    }
+
+    let mut ids = Vec::with_capacity(parsed.len());
+    for memory in parsed {
+        ids.push(memories.store(memory).await?);
+    }
+
+    let summary = serde_json::json!({ "imported": ids.len(), "ids": ids });
+    output.emit(&summary, || println!("✅ Imported {} memories into {}", ids.len(), namespace(memories)))
+}
+
+/// What `ingest` did, or would do with `--dry-run`, by source path
+#[derive(Debug, Default, Serialize)]
+struct IngestSummary {
+    added: Vec<String>,
+    updated: Vec<String>,
+    removed: Vec<String>,
+    /// Not UTF-8, too large, without any text or refused for containing credentials
+    skipped: Vec<String>,
+    unchanged: usize,
+    /// Memories stored
+    chunks: usize,
+    dry_run: bool,
+}
+
+/// Store local files as chunked memories. Files ingested before are left alone while
+/// their content is unchanged and replaced when it changed; memories of files that are
+/// gone from under the given paths are dropped. Dropped memories are purged, so they stop
+/// counting against the quota, unless `--trash` keeps them restorable.
+async fn ingest(memories: &Memories, mut args: Args, output: &Output) -> Result<(), CliError> {
+    let mut roots = Vec::new();
+    while let Some(path) = args.next() {
+        let root = std::fs::canonicalize(&path).map_err(|e| CliError::Usage(format!("{}: {}", path, e)))?;
+        roots.push(root);
+    }
+    if roots.is_empty() {
+        return Err(CliError::Usage("missing <path>".to_string()));
+    }
+    let chunk_chars = args.number("chunk_size", ingest::DEFAULT_CHUNK_CHARS)?;
+    if !ingest::CHUNK_CHARS.contains(&chunk_chars) {
+        return Err(CliError::Usage(format!(
+            "--chunk-size must be between {} and {}", ingest::CHUNK_CHARS.start(), ingest::CHUNK_CHARS.end()
+        )));
+    }
+    let tags: Vec<String> = args.values("tag").into_iter().map(str::to_string).collect();
+    let dry_run = args.switch("dry_run");
+    let trash = args.switch("trash");
+    args.finish(&["namespace", "tag", "chunk_size", "dry_run", "trash"])?;
+
+    let mut files = BTreeMap::new();
+    for root in &roots {
+        files.extend(ingest::discover(root)?);
+    }
+
+    // Memories earlier runs stored from files under the roots, by source path
+    let documents = memories.fields(MemoryFields { omit_content: true, ..Default::default() })
+        .query_all("", HashMap::from([("type".to_string(), DOCUMENT_TYPE.to_string())]))
+        .await?;
+    let mut stored: HashMap<String, Vec<(String, String)>> = HashMap::new();
+    for memory in documents {
+        let (Some(source), Some(hash)) = (memory.metadata.get(SOURCE_KEY), memory.metadata.get(HASH_KEY)) else {
+            continue;
+        };
+        if roots.iter().any(|root| Path::new(source).starts_with(root)) {
+            stored.entry(source.clone()).or_default().push((memory.id, hash.clone()));
+        }
+    }
+
+    let mut summary = IngestSummary { dry_run, ..Default::default() };
+    for (path, format) in files {
+        let source = path.to_string_lossy().into_owned();
+        let bytes = std::fs::read(&path)?;
+        let text = (bytes.len() <= ingest::MAX_FILE_BYTES).then(|| std::str::from_utf8(&bytes).ok()).flatten();
+        let hash = ingest::content_hash(&bytes);
+        let chunks = match text {
+            Some(text) => ingest::memories(&path, &hash, format, ingest::parse(format, text), chunk_chars as usize, &tags),
+            None => Vec::new(),
+        };
+        if chunks.is_empty() {
+            // Left in `stored`, so anything ingested from it before is removed below
+            summary.skipped.push(source);
+            continue;
+        }
+
+        let earlier = stored.remove(&source).unwrap_or_default();
+        if earlier.len() == chunks.len() && earlier.iter().all(|(_, stored_hash)| *stored_hash == hash) {
+            summary.unchanged += 1;
+            continue;
+        }
+        let count = chunks.len();
+        if !dry_run {
+            // Store the new chunks before dropping the old ones, so a failure loses nothing
+            let mut added = Vec::with_capacity(count);
+            let mut rejected = false;
+            for memory in chunks {
+                match memories.store(memory).await {
+                    Ok(id) => added.push((id, hash.clone())),
+                    // The namespace's secret policy refuses the file; its earlier version stays
+                    Err(e) if e.code() == Some(ErrorCode::SecretDetected) => {
+                        rejected = true;
+                        break;
+                    }
+                    Err(e) => return Err(e.into()),
+                }
+            }
+            if rejected {
+                forget(memories, &added, false).await?;
+                summary.skipped.push(source);
+                continue;
+            }
+            forget(memories, &earlier, trash).await?;
+        }
+        summary.chunks += count;
+        if earlier.is_empty() {
+            summary.added.push(source);
+        } else {
+            summary.updated.push(source);
+        }
+    }
+    for (source, earlier) in stored {
+        if !dry_run {
+            forget(memories, &earlier, trash).await?;
+        }
+        summary.removed.push(source);
+    }
+    summary.removed.sort();
+
+    output.emit(&summary, || {
+        let marked = [("+", &summary.added), ("~", &summary.updated), ("-", &summary.removed), ("!", &summary.skipped)];
+        for (mark, paths) in marked {
+            for path in paths {
+                println!("{} {}", mark, path);
+            }
+        }
+        let verb = if dry_run { "Would ingest" } else { "Ingested" };
+        println!(
+            "{} {} chunks into {}: {} added, {} updated, {} removed, {} unchanged, {} skipped",
+            verb, summary.chunks, namespace(memories), summary.added.len(), summary.updated.len(),
+            summary.removed.len(), summary.unchanged, summary.skipped.len()
+        );
+    })
+}
+
+/// Purge the `(id, hash)` memories of a source, or only trash them with `trash`, ignoring
+/// any already deleted
+async fn forget(memories: &Memories, earlier: &[(String, String)], trash: bool) -> Result<(), CliError> {
+    for (id, _) in earlier {
+        match memories.delete(id).await {
+            Err(e) if matches!(e.code(), Some(ErrorCode::MemoryNotFound | ErrorCode::NotFound)) => continue,
+            result => result?,
+        }
+        if !trash {
+            match memories.purge(id).await {
+                Err(e) if matches!(e.code(), Some(ErrorCode::MemoryNotFound | ErrorCode::NotFound)) => {}
+                result => result?,
+            }
+        }
+    }
+    Ok(())
+}
+
+fn namespace(memories: &Memories) -> &str {
+    match memories.namespace() {
+        "" => "the default namespace",
+        name => name,
+    }
+}
@@ -0,0 +1,20 @@
+pub mod auth;
+pub mod health;
+pub mod key;
+pub mod memory;
+pub mod tag;
+
+use crate::CliError;
+use std::io::Read;
+
+/// `path`'s contents, or stdin for `None` and `-`
+pub(crate) fn read_input(path: Option<&str>) -> Result<Vec<u8>, CliError> {
+    match path {
+        Some(path) if path != "-" => Ok(std::fs::read(path)?),
+        _ => {
+            let mut data = Vec::new();
+            std::io::stdin().read_to_end(&mut data)?;
+            Ok(data)
+        }
+    }
+}
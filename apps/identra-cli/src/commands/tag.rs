@@ -0,0 +1,78 @@
+use crate::args::Args;
+use crate::output::Output;
+use crate::{CliError, Context};
+use identra_client::Memories;
+
+pub async fn run(context: &mut Context, mut args: Args, output: &Output) -> Result<(), CliError> {
+    let command = args.require("tag command")?;
+    let namespace = args.value("namespace").map(str::to_string);
+    let client = context.authenticated().await?;
+    let memories = match namespace {
+        Some(namespace) => client.namespace(namespace),
+        None => client.memories(),
+    };
+
+    match command.as_str() {
+        "ls" => list(&memories, args, output).await,
+        "mv" => rename(&memories, args, output).await,
+        "merge" => merge(&memories, args, output).await,
+        "apply" => apply(&memories, args, output).await,
+        other => Err(CliError::Usage(format!("unknown tag command {:?}", other))),
+    }
+}
+
+async fn list(memories: &Memories, args: Args, output: &Output) -> Result<(), CliError> {
+    let limit = args.number("limit", 100)?;
+    let prefix = args.value("prefix").unwrap_or_default().to_string();
+    args.finish(&["namespace", "limit", "prefix"])?;
+    let tags = memories.tags(&prefix, limit).await?;
+
+    output.emit(&tags, || {
+        for tag in &tags {
+            println!("{:>7}  {}", tag.count, tag.tag);
+        }
+        if tags.is_empty() {
+            println!("No tags");
+        }
+    })
+}
+
+async fn rename(memories: &Memories, mut args: Args, output: &Output) -> Result<(), CliError> {
+    let tag = args.require("tag")?;
+    let new_name = args.require("new name")?;
+    args.finish(&["namespace"])?;
+    let updated = memories.rename_tag(&tag, &new_name).await?;
+
+    output.emit(&serde_json::json!({ "memories_updated": updated }), || {
+        println!("✅ Renamed {} to {} on {} memories", tag, new_name, updated)
+    })
+}
+
+/// `tag merge <into> <tag>...`
+async fn merge(memories: &Memories, mut args: Args, output: &Output) -> Result<(), CliError> {
+    let into = args.require("target tag")?;
+    let sources: Vec<String> = std::iter::from_fn(|| args.next()).collect();
+    if sources.is_empty() {
+        return Err(CliError::Usage("missing <tag> to merge".to_string()));
+    }
+    args.finish(&["namespace"])?;
+    let sources: Vec<&str> = sources.iter().map(String::as_str).collect();
+    let updated = memories.merge_tags(&sources, &into).await?;
+
+    output.emit(&serde_json::json!({ "memories_updated": updated }), || {
+        println!("✅ Merged {} into {} on {} memories", sources.join(", "), into, updated)
+    })
+}
+
+/// `tag apply <id>... [--add <tag>]... [--remove <tag>]...`
+async fn apply(memories: &Memories, mut args: Args, output: &Output) -> Result<(), CliError> {
+    let ids: Vec<String> = std::iter::from_fn(|| args.next()).collect();
+    if ids.is_empty() {
+        return Err(CliError::Usage("missing <id>".to_string()));
+    }
+    args.finish(&["namespace", "add", "remove"])?;
+    let ids: Vec<&str> = ids.iter().map(String::as_str).collect();
+    let updated = memories.retag(&ids, &args.values("add"), &args.values("remove")).await?;
+
+    output.emit(&serde_json::json!({ "memories_updated": updated }), || println!("✅ Retagged {} memories", updated))
+}
@@ -0,0 +1,127 @@
+//! Packing a document's blocks into memory-sized chunks.
+
+/// A paragraph, heading or code block; only split when longer than a whole chunk
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block {
+    pub text: String,
+    /// Heading the block sits under
+    pub section: Option<String>,
+    /// Start a new chunk here, as at a heading
+    pub breaks: bool,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Chunk {
+    pub text: String,
+    /// Section of the chunk's first block
+    pub section: Option<String>,
+}
+
+/// Blank-line separated paragraphs of plain text or code
+pub fn paragraphs(text: &str) -> Vec<Block> {
+    let mut blocks = Vec::new();
+    let mut lines: Vec<&str> = Vec::new();
+    for line in text.lines().map(str::trim_end).chain([""]) {
+        if !line.is_empty() {
+            lines.push(line);
+        } else if !lines.is_empty() {
+            blocks.push(Block { text: lines.join("\n"), section: None, breaks: false });
+            lines.clear();
+        }
+    }
+    blocks
+}
+
+/// Join consecutive blocks, separated by a blank line, into chunks of at most `max_chars`
+/// characters
+pub fn pack(blocks: Vec<Block>, max_chars: usize) -> Vec<Chunk> {
+    let mut chunks = Vec::new();
+    let mut current: Option<(Chunk, usize)> = None;
+    for block in blocks {
+        for (index, piece) in split(&block.text, max_chars).into_iter().enumerate() {
+            let chars = piece.chars().count();
+            match &mut current {
+                Some((chunk, length)) if !(block.breaks && index == 0) && *length + 2 + chars <= max_chars => {
+                    chunk.text.push_str("\n\n");
+                    chunk.text.push_str(&piece);
+                    *length += 2 + chars;
+                }
+                _ => {
+                    chunks.extend(current.take().map(|(chunk, _)| chunk));
+                    current = Some((Chunk { text: piece, section: block.section.clone() }, chars));
+                }
+            }
+        }
+    }
+    chunks.extend(current.map(|(chunk, _)| chunk));
+    chunks
+}
+
+/// `text` in pieces of at most `max_chars` characters, cut between lines where possible
+fn split(text: &str, max_chars: usize) -> Vec<String> {
+    if text.chars().count() <= max_chars {
+        return vec![text.to_string()];
+    }
+    let mut pieces = Vec::new();
+    let mut piece = String::new();
+    let mut length = 0;
+    for line in text.lines().flat_map(|line| wrap(line, max_chars)) {
+        let chars = line.chars().count();
+        if length > 0 && length + 1 + chars > max_chars {
+            pieces.push(std::mem::take(&mut piece));
+            length = 0;
+        }
+        if length > 0 {
+            piece.push('\n');
+            length += 1;
+        }
+        piece.push_str(line);
+        length += chars;
+    }
+    if length > 0 {
+        pieces.push(piece);
+    }
+    pieces
+}
+
+/// `line` in pieces of at most `max_chars` characters, cut at whitespace where there is some
+fn wrap(line: &str, max_chars: usize) -> Vec<&str> {
+    let mut pieces = Vec::new();
+    let mut rest = line;
+    while let Some((limit, _)) = rest.char_indices().nth(max_chars) {
+        let cut = rest[..limit].rfind(char::is_whitespace).filter(|&space| space > 0).unwrap_or(limit);
+        pieces.push(rest[..cut].trim_end());
+        rest = rest[cut..].trim_start();
+    }
+    pieces.push(rest);
+    pieces
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(text: &str, breaks: bool) -> Block {
+        Block { text: text.to_string(), section: breaks.then(|| text.to_string()), breaks }
+    }
+
+    #[test]
+    fn packs_blocks_up_to_the_chunk_size() {
+        assert_eq!(paragraphs("one\ntwo\n\n\nthree  \n").iter().map(|b| b.text.as_str()).collect::<Vec<_>>(), ["one\ntwo", "three"]);
+
+        let chunks = pack(vec![block("aaaa", false), block("bbbb", false), block("cccc", false)], 10);
+        assert_eq!(chunks.iter().map(|c| c.text.as_str()).collect::<Vec<_>>(), ["aaaa\n\nbbbb", "cccc"]);
+
+        let chunks = pack(vec![block("a", false), block("# H", true), block("b", false)], 100);
+        assert_eq!(chunks.iter().map(|c| c.text.as_str()).collect::<Vec<_>>(), ["a", "# H\n\nb"]);
+        assert_eq!(chunks[1].section.as_deref(), Some("# H"));
+    }
+
+    #[test]
+    fn splits_oversized_blocks_between_lines_then_words() {
+        assert_eq!(split("line one\nline two", 10), ["line one", "line two"]);
+        assert_eq!(wrap("the quick brown fox", 10), ["the quick", "brown fox"]);
+        assert_eq!(wrap("abcdefghijkl", 5), ["abcde", "fghij", "kl"]);
+        assert!(pack(vec![block(&"word ".repeat(100), false)], 50).iter().all(|c| c.text.chars().count() <= 50));
+    }
+}
@@ -0,0 +1,161 @@
+//! HTML pages as text. Markup is reduced to markdown-like text, with `#` headings and
+//! fenced `<pre>` blocks, and split into blocks the way markdown is.
+
+use super::{markdown, Document};
+
+/// Elements whose content is never text
+const HIDDEN: &[&str] = &["script", "style", "noscript", "template", "svg"];
+/// Elements that start a new paragraph
+const BLOCKS: &[&str] = &[
+    "p", "div", "section", "article", "main", "header", "footer", "nav", "aside", "blockquote",
+    "ul", "ol", "table", "tr", "dl", "dt", "dd", "figure", "figcaption", "hr", "form",
+];
+
+pub fn parse(html: &str) -> Document {
+    let (text, title) = to_text(html);
+    let mut document = markdown::blocks(&text, false);
+    document.title = title.or(document.title);
+    document
+}
+
+/// Text of `html` and its `<title>`
+fn to_text(html: &str) -> (String, Option<String>) {
+    let mut out = String::new();
+    let mut title = None;
+    let mut pre = 0usize;
+    let mut rest = html;
+    while let Some(open) = rest.find('<') {
+        push_text(&mut out, &rest[..open], pre > 0);
+        rest = &rest[open..];
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+            continue;
+        }
+        let Some(close) = rest.find('>') else {
+            break;
+        };
+        let tag = &rest[1..close];
+        rest = &rest[close + 1..];
+        let closing = tag.starts_with('/');
+        let name = tag.trim_start_matches('/').split(|c: char| c.is_whitespace() || c == '/').next().unwrap_or_default().to_ascii_lowercase();
+
+        match name.as_str() {
+            "title" if !closing => {
+                let (inner, after) = element_text(rest, "title");
+                let mut text = String::new();
+                push_text(&mut text, inner, false);
+                title = Some(text.trim().to_string()).filter(|title| !title.is_empty());
+                rest = after;
+            }
+            name if HIDDEN.contains(&name) && !closing => rest = element_text(rest, name).1,
+            "pre" if closing => {
+                pre = pre.saturating_sub(1);
+                out.push_str("\n```\n\n");
+            }
+            "pre" => {
+                pre += 1;
+                out.push_str("\n\n```\n");
+            }
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" if !closing => {
+                let level = usize::from(name.as_bytes()[1] - b'0');
+                out.push_str("\n\n");
+                out.push_str(&"#".repeat(level));
+                out.push(' ');
+            }
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => out.push_str("\n\n"),
+            "br" => out.push('\n'),
+            "li" if !closing => out.push_str("\n- "),
+            "td" | "th" if !closing => out.push(' '),
+            name if BLOCKS.contains(&name) => out.push_str("\n\n"),
+            _ => {}
+        }
+    }
+    push_text(&mut out, rest, pre > 0);
+    (out, title)
+}
+
+/// Everything up to the closing tag of `name`, and what follows it
+fn element_text<'a>(html: &'a str, name: &str) -> (&'a str, &'a str) {
+    let lower = html.to_ascii_lowercase();
+    let Some(end) = lower.find(&format!("</{}", name)) else {
+        return (html, "");
+    };
+    let after = html[end..].find('>').map_or("", |close| &html[end + close + 1..]);
+    (&html[..end], after)
+}
+
+/// Append decoded text, with whitespace runs collapsed outside `<pre>`
+fn push_text(out: &mut String, text: &str, preformatted: bool) {
+    let text = decode_entities(text);
+    if preformatted {
+        out.push_str(&text);
+        return;
+    }
+    for c in text.chars() {
+        if !c.is_whitespace() {
+            out.push(c);
+        } else if !out.is_empty() && !out.ends_with(char::is_whitespace) {
+            out.push(' ');
+        }
+    }
+}
+
+fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        decoded.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let entity = rest.find(';').filter(|&end| end <= 10).and_then(|end| Some((entity(&rest[1..end])?, end)));
+        match entity {
+            Some((c, end)) => {
+                decoded.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+fn entity(name: &str) -> Option<char> {
+    let code = match name {
+        "amp" => return Some('&'),
+        "lt" => return Some('<'),
+        "gt" => return Some('>'),
+        "quot" => return Some('"'),
+        "apos" => return Some('\''),
+        "nbsp" => return Some(' '),
+        _ => match name.strip_prefix("#x").or_else(|| name.strip_prefix("#X")) {
+            Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+            None => name.strip_prefix('#')?.parse().ok()?,
+        },
+    };
+    char::from_u32(code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reduces_markup_to_blocks() {
+        let page = r#"<!DOCTYPE html><html><head><title>Release  notes</title><style>p { color: red }</style></head>
+            <body><!-- nav --><h2 id="x">What&#39;s <em>new</em></h2><p>Faster
+            search &amp; sync.</p><script>alert("<p>")</script><ul><li>One</li><li>Two</li></ul>
+            <pre>fn main() {
+
+    run();
+}</pre></body></html>"#;
+        let document = parse(page);
+        assert_eq!(document.title.as_deref(), Some("Release notes"));
+        let texts: Vec<&str> = document.blocks.iter().map(|b| b.text.as_str()).collect();
+        assert_eq!(texts, ["## What's new", "Faster search & sync.", "- One\n- Two", "```\nfn main() {\n\n    run();\n}\n```"]);
+        assert_eq!(document.blocks[1].section.as_deref(), Some("What's new"));
+        assert_eq!(decode_entities("a &lt;b&gt; &#x41; & c &bogus;"), "a <b> A & c &bogus;");
+    }
+}
@@ -0,0 +1,205 @@
+//! Markdown and Obsidian-style notes. YAML front matter becomes the title, tags and
+//! properties, `#tags` in the text are collected too, and headings start new chunks.
+
+use super::chunk::Block;
+use super::{add_tag, Document};
+
+pub fn parse(text: &str) -> Document {
+    let (front_matter, body) = split_front_matter(text);
+    let mut document = Document::default();
+    if let Some(yaml) = front_matter {
+        read_front_matter(yaml, &mut document);
+    }
+    let body = blocks(body, true);
+    document.title = document.title.or(body.title);
+    body.tags.iter().for_each(|tag| add_tag(&mut document.tags, tag));
+    document.blocks = body.blocks;
+    document
+}
+
+/// Blocks of a markdown body, titled by its first `#` heading. Fenced code is kept in
+/// one block and never read for headings or tags.
+pub(super) fn blocks(body: &str, inline_tags: bool) -> Document {
+    let mut document = Document::default();
+    let mut section: Option<String> = None;
+    let mut paragraph: Vec<&str> = Vec::new();
+    let mut fence: Option<&str> = None;
+
+    let flush = |paragraph: &mut Vec<&str>, section: &Option<String>, blocks: &mut Vec<Block>| {
+        if !paragraph.is_empty() {
+            blocks.push(Block { text: paragraph.join("\n"), section: section.clone(), breaks: false });
+            paragraph.clear();
+        }
+    };
+
+    for line in body.lines().map(str::trim_end) {
+        let trimmed = line.trim_start();
+        if let Some(marker) = fence {
+            paragraph.push(line);
+            if trimmed.starts_with(marker) {
+                fence = None;
+            }
+            continue;
+        }
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            fence = Some(&trimmed[..3]);
+            paragraph.push(line);
+            continue;
+        }
+        if let Some((level, heading)) = heading(trimmed) {
+            flush(&mut paragraph, &section, &mut document.blocks);
+            if level == 1 && document.title.is_none() {
+                document.title = Some(heading.to_string());
+            }
+            section = Some(heading.to_string());
+            document.blocks.push(Block { text: trimmed.to_string(), section: section.clone(), breaks: true });
+            continue;
+        }
+        if trimmed.is_empty() {
+            flush(&mut paragraph, &section, &mut document.blocks);
+            continue;
+        }
+        if inline_tags {
+            read_inline_tags(trimmed, &mut document.tags);
+        }
+        paragraph.push(line);
+    }
+    flush(&mut paragraph, &section, &mut document.blocks);
+    document
+}
+
+/// Level and text of an ATX heading line
+fn heading(line: &str) -> Option<(usize, &str)> {
+    let level = line.bytes().take_while(|&b| b == b'#').count();
+    let text = &line[level..];
+    if !(1..=6).contains(&level) || !(text.is_empty() || text.starts_with(char::is_whitespace)) {
+        return None;
+    }
+    let text = text.trim().trim_end_matches('#').trim_end();
+    (!text.is_empty()).then_some((level, text))
+}
+
+/// `#tag`s after whitespace, outside inline code, with at least one non-digit
+fn read_inline_tags(line: &str, tags: &mut Vec<String>) {
+    let mut previous = ' ';
+    let mut in_code = false;
+    for (index, c) in line.char_indices() {
+        if c == '`' {
+            in_code = !in_code;
+        } else if c == '#' && !in_code && previous.is_whitespace() {
+            let rest = &line[index + 1..];
+            let end = rest.find(|c: char| !(c.is_alphanumeric() || matches!(c, '_' | '-' | '/'))).unwrap_or(rest.len());
+            let tag = &rest[..end];
+            if tag.chars().any(|c| !c.is_ascii_digit()) {
+                add_tag(tags, tag);
+            }
+        }
+        previous = c;
+    }
+}
+
+/// YAML between a leading `---` line and the next `---` or `...` line, and the rest
+fn split_front_matter(text: &str) -> (Option<&str>, &str) {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    let Some(rest) = text.strip_prefix("---\n").or_else(|| text.strip_prefix("---\r\n")) else {
+        return (None, text);
+    };
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        if matches!(line.trim_end(), "---" | "...") {
+            return (Some(&rest[..offset]), &rest[offset + line.len()..]);
+        }
+        offset += line.len();
+    }
+    (None, text)
+}
+
+/// Top-level `key: value`, `key: [a, b]` and `key:` followed by `- item` lines; nested
+/// mappings are ignored. Lists are joined with ", " in properties.
+fn read_front_matter(yaml: &str, document: &mut Document) {
+    let mut lines = yaml.lines().peekable();
+    while let Some(line) = lines.next() {
+        if line.starts_with(char::is_whitespace) || line.starts_with('#') {
+            continue;
+        }
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let (key, value) = (key.trim(), value.trim());
+        let values: Vec<String> = if value.is_empty() {
+            let mut items = Vec::new();
+            while let Some(item) = lines.peek().and_then(|line| list_item(line)) {
+                items.push(scalar(item));
+                lines.next();
+            }
+            items
+        } else if let Some(inner) = value.strip_prefix('[').and_then(|value| value.strip_suffix(']')) {
+            inner.split(',').map(scalar).collect()
+        } else {
+            vec![scalar(value)]
+        };
+        let values: Vec<String> = values.into_iter().filter(|value| !value.is_empty()).collect();
+
+        match key.to_ascii_lowercase().as_str() {
+            // Obsidian also accepts a single string of comma or space separated tags
+            "tags" | "tag" => values.iter()
+                .flat_map(|value| value.split([',', ' ']))
+                .for_each(|tag| add_tag(&mut document.tags, tag)),
+            "title" => document.title = values.into_iter().next(),
+            _ if !values.is_empty() => {
+                document.properties.insert(key.to_string(), values.join(", "));
+            }
+            _ => {}
+        }
+    }
+}
+
+fn list_item(line: &str) -> Option<&str> {
+    let item = line.trim_start();
+    match item {
+        "-" => Some(""),
+        _ => item.strip_prefix("- "),
+    }
+}
+
+/// A YAML scalar without its quotes
+fn scalar(value: &str) -> String {
+    let value = value.trim();
+    let quoted = value.len() >= 2 && ((value.starts_with('"') && value.ends_with('"')) || (value.starts_with('\'') && value.ends_with('\'')));
+    if quoted { &value[1..value.len() - 1] } else { value }.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn front_matter_becomes_title_tags_and_properties() {
+        let note = "---\ntitle: \"Trip plan\"\ntags:\n  - travel\n  - \"#japan\"\naliases: [Kyoto, Osaka]\nstatus: draft\ncover:\n  url: x\n---\n# Heading\nPack light #packing, see issue #42 and `#not-a-tag`.\n";
+        let document = parse(note);
+        assert_eq!(document.title.as_deref(), Some("Trip plan"));
+        assert_eq!(document.tags, ["travel", "japan", "packing"]);
+        assert_eq!(document.properties["aliases"], "Kyoto, Osaka");
+        assert_eq!(document.properties["status"], "draft");
+        assert!(!document.properties.contains_key("cover"));
+        assert_eq!(document.blocks[0].text, "# Heading");
+
+        let legacy = parse("---\ntags: work, ideas later\n---\nbody");
+        assert_eq!(legacy.tags, ["work", "ideas", "later"]);
+        assert_eq!(parse("---\nnot closed\nbody").blocks[0].text, "---\nnot closed\nbody");
+    }
+
+    #[test]
+    fn headings_start_sections_outside_code_fences() {
+        let document = parse("intro\n\n## Setup ##\nstep one\n\n```sh\n# not a heading\n\necho #nope\n```\n#hashtag-only line");
+        let sections: Vec<_> = document.blocks.iter().map(|b| (b.text.as_str(), b.section.as_deref(), b.breaks)).collect();
+        assert_eq!(sections, [
+            ("intro", None, false),
+            ("## Setup ##", Some("Setup"), true),
+            ("step one", Some("Setup"), false),
+            ("```sh\n# not a heading\n\necho #nope\n```\n#hashtag-only line", Some("Setup"), false),
+        ]);
+        assert_eq!(document.title, None);
+        assert_eq!(document.tags, ["hashtag-only"]);
+    }
+}
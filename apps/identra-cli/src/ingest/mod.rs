@@ -0,0 +1,222 @@
+//! Local files as memories: discovery, parsing by format and chunking.
+//!
+//! Every chunk carries its file's absolute path and content hash in metadata, so
+//! `memory ingest` can tell unchanged, edited and deleted files apart on the next run.
+
+mod chunk;
+mod html;
+mod markdown;
+
+use chunk::Block;
+use identra_client::NewMemory;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// `type` metadata of ingested memories
+pub const DOCUMENT_TYPE: &str = "document";
+/// Metadata holding the absolute path of the file a memory came from
+pub const SOURCE_KEY: &str = "source";
+/// Metadata holding the SHA-256 of that file when it was ingested
+pub const HASH_KEY: &str = "source_hash";
+
+pub const DEFAULT_CHUNK_CHARS: u32 = 1500;
+pub const CHUNK_CHARS: std::ops::RangeInclusive<u32> = 200..=100_000;
+/// Files larger than this are skipped
+pub const MAX_FILE_BYTES: usize = 8 * 1024 * 1024;
+
+/// Set by ingestion; front matter cannot override them
+const RESERVED_KEYS: &[&str] = &["type", "source", "source_hash", "format", "language", "title", "section", "chunk", "encrypted", "secrets"];
+/// Front matter properties kept per note, and their longest value; the rest would push
+/// memories over the gateway's default metadata limits
+const MAX_PROPERTIES: usize = 32;
+const MAX_PROPERTY_CHARS: usize = 256;
+/// The gateway's default tag limits
+const MAX_TAGS: usize = 32;
+const MAX_TAG_CHARS: usize = 64;
+/// Directories never descended into, besides hidden ones such as `.git` and `.obsidian`
+const SKIPPED_DIRS: &[&str] = &["node_modules", "target", "vendor", "__pycache__", "dist", "build"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Markdown,
+    Text,
+    Html,
+    /// Source code in the named language
+    Code(&'static str),
+}
+
+impl Format {
+    /// The format a file's extension suggests, if it is one that is ingested
+    pub fn for_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        Some(match extension.as_str() {
+            "md" | "markdown" | "mdx" => Self::Markdown,
+            "txt" | "text" | "rst" | "org" => Self::Text,
+            "html" | "htm" | "xhtml" => Self::Html,
+            other => Self::Code(language(other)?),
+        })
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Markdown => "markdown",
+            Self::Text => "text",
+            Self::Html => "html",
+            Self::Code(_) => "code",
+        }
+    }
+}
+
+fn language(extension: &str) -> Option<&'static str> {
+    Some(match extension {
+        "rs" => "rust",
+        "py" => "python",
+        "js" | "mjs" | "cjs" | "jsx" => "javascript",
+        "ts" | "tsx" => "typescript",
+        "go" => "go",
+        "java" => "java",
+        "kt" | "kts" => "kotlin",
+        "swift" => "swift",
+        "c" | "h" => "c",
+        "cc" | "cpp" | "cxx" | "hpp" | "hh" => "cpp",
+        "cs" => "csharp",
+        "rb" => "ruby",
+        "php" => "php",
+        "scala" => "scala",
+        "lua" => "lua",
+        "sh" | "bash" | "zsh" => "shell",
+        "sql" => "sql",
+        "proto" => "protobuf",
+        "css" | "scss" => "css",
+        "vue" => "vue",
+        "svelte" => "svelte",
+        "toml" => "toml",
+        "yaml" | "yml" => "yaml",
+        _ => return None,
+    })
+}
+
+/// Files to ingest at or under `root`, sorted. Hidden entries, dependency and build
+/// directories and symlinks are left out; a file named directly is read as plain text
+/// when its extension is not one of the known formats.
+pub fn discover(root: &Path) -> std::io::Result<Vec<(PathBuf, Format)>> {
+    if !root.is_dir() {
+        return Ok(vec![(root.to_path_buf(), Format::for_path(root).unwrap_or(Format::Text))]);
+    }
+    let mut files = Vec::new();
+    let mut pending = vec![root.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        for entry in std::fs::read_dir(&dir)? {
+            let entry = entry?;
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            if name.starts_with('.') {
+                continue;
+            }
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() && !SKIPPED_DIRS.contains(&name.as_ref()) {
+                pending.push(entry.path());
+            } else if file_type.is_file() {
+                let path = entry.path();
+                if let Some(format) = Format::for_path(&path) {
+                    files.push((path, format));
+                }
+            }
+        }
+    }
+    files.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(files)
+}
+
+pub fn content_hash(bytes: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(bytes))
+}
+
+/// A file's text split into blocks, with what its front matter or markup says about it
+#[derive(Debug, Default)]
+pub struct Document {
+    pub title: Option<String>,
+    pub tags: Vec<String>,
+    /// Front matter properties
+    pub properties: HashMap<String, String>,
+    pub blocks: Vec<Block>,
+}
+
+pub fn parse(format: Format, text: &str) -> Document {
+    match format {
+        Format::Markdown => markdown::parse(text),
+        Format::Html => html::parse(text),
+        Format::Text | Format::Code(_) => Document { blocks: chunk::paragraphs(text), ..Default::default() },
+    }
+}
+
+/// One memory per chunk of `document`, carrying `tags` besides the document's own
+pub fn memories(path: &Path, hash: &str, format: Format, document: Document, chunk_chars: usize, tags: &[String]) -> Vec<NewMemory> {
+    let title = document.title.or_else(|| path.file_stem().map(|stem| stem.to_string_lossy().into_owned()));
+    let mut properties: Vec<_> = document.properties.into_iter()
+        .filter(|(key, value)| !RESERVED_KEYS.contains(&key.as_str()) && value.chars().count() <= MAX_PROPERTY_CHARS)
+        .collect();
+    properties.sort();
+    properties.truncate(MAX_PROPERTIES);
+
+    let mut all_tags = document.tags;
+    tags.iter().for_each(|tag| add_tag(&mut all_tags, tag));
+
+    let chunks = chunk::pack(document.blocks, chunk_chars);
+    let count = chunks.len();
+    chunks.into_iter().enumerate().map(|(index, chunk)| {
+        let mut memory = NewMemory::new(chunk.text)
+            .metadata("type", DOCUMENT_TYPE)
+            .metadata(SOURCE_KEY, path.to_string_lossy())
+            .metadata(HASH_KEY, hash)
+            .metadata("format", format.name())
+            .metadata("chunk", format!("{}/{}", index + 1, count));
+        memory.metadata.extend(properties.iter().cloned());
+        memory.tags = all_tags.clone();
+        if let Format::Code(language) = format {
+            memory = memory.metadata("language", language);
+        }
+        if let Some(title) = &title {
+            memory = memory.metadata("title", title);
+        }
+        if let Some(section) = chunk.section {
+            memory = memory.metadata("section", section);
+        }
+        memory
+    }).collect()
+}
+
+/// Add `tag` without a leading `#`, unless it is empty, too long, already there or
+/// the document is out of tags
+fn add_tag(tags: &mut Vec<String>, tag: &str) {
+    let tag = tag.trim().trim_start_matches('#');
+    if !tag.is_empty() && tag.chars().count() <= MAX_TAG_CHARS && tags.len() < MAX_TAGS && !tags.iter().any(|t| t == tag) {
+        tags.push(tag.to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chunks_carry_source_and_document_labels() {
+        let path = Path::new("/notes/trip.md");
+        let document = parse(Format::Markdown, "---\nsource: elsewhere\nstatus: draft\ntags: [travel]\n---\nfirst\n\n## Day two\nsecond");
+        let chunks = memories(path, "abc", Format::Markdown, document, 200, &["import".to_string(), "travel".to_string()]);
+        assert_eq!(chunks.len(), 2);
+        let second = &chunks[1];
+        assert_eq!(second.content, "## Day two\n\nsecond");
+        assert_eq!(second.metadata[SOURCE_KEY], "/notes/trip.md");
+        assert_eq!(second.metadata[HASH_KEY], "abc");
+        assert_eq!(second.metadata["chunk"], "2/2");
+        assert_eq!(second.metadata["title"], "trip");
+        assert_eq!(second.metadata["section"], "Day two");
+        assert_eq!(second.metadata["status"], "draft");
+        assert_eq!(second.tags, ["travel", "import"]);
+
+        assert_eq!(Format::for_path(Path::new("src/main.rs")), Some(Format::Code("rust")));
+        assert_eq!(Format::for_path(Path::new("photo.png")), None);
+    }
+}
@@ -0,0 +1,186 @@
+//! `identra`: memories, vault keys and login from the command line.
+//!
+//! Talks to the gateway through `identra-client`, reading the gateway address and TLS
+//! settings from the `desktop` section of the configuration. The login session is
+//! cached in the local vault daemon and refreshed tokens are written back to it.
+
+mod args;
+mod commands;
+mod ingest;
+mod output;
+mod session;
+
+use args::Args;
+use identra_client::{Client, ClientError};
+use identra_core::config::{Cli, Config, ConfigError, Service};
+use output::Output;
+use session::{CachedLogin, SessionCache};
+
+const USAGE: &str = "\
+Usage: identra [--json] [--config <file>] [--<section>.<key> <value>] <command>
+
+Commands:
+  login [<username>] [--password-stdin]   Log in and cache the session in the vault daemon
+  logout                                  Forget the cached session
+  memory add <content | -> [--tag <tag>]... [--meta <key>=<value>]...
+  memory get <id>
+  memory search <text> [--limit <n>] [--since <days>]
+                                          Memories whose content contains <text>
+  memory ls [--limit <n>] [--since <days>]
+                                          Most recent memories
+  memory context <query> [--budget <tokens>] [--order relevance|chronological]
+                 [--tokenizer cl100k_base|o200k_base] [--whole-memories]
+                                          Prompt context built from the most relevant memories
+  memory stats                            Counts by tag, type and month, sizes and embedding coverage
+  memory rm <id>
+  memory export [--out <file>]            Every memory as JSON lines
+  memory import [--file <file>]           Store memories from JSON lines (stdin by default)
+  memory ingest <path>... [--tag <tag>]... [--chunk-size <chars>] [--dry-run] [--trash]
+                                          Store Markdown, text, HTML and source files in chunks;
+                                          re-runs replace edited files and purge deleted ones,
+                                          or move their memories to the trash with --trash
+  tag ls [--prefix <p>] [--limit <n>]     Tags with how many memories carry them
+  tag mv <tag> <new-name>                 Rename a tag; fails if <new-name> is already in use
+  tag merge <into> <tag>...               Replace the tags with <into>
+  tag apply <id>... [--add <tag>]... [--remove <tag>]...
+  key put <id> [--value <v> | --file <file>] [--meta <key>=<value>]... [--expires <rfc3339>]
+  key get <id> [--out <file>]
+  key ls
+  key rm <id>
+  health                                  Gateway and dependency health
+  doctor                                  Diagnose configuration, gateway, database and vault
+
+Memory and tag commands take --namespace <name>; the default namespace is used without it.
+--json prints results, and errors on stderr, as JSON.
+The password is read from IDENTRA_PASSWORD when set, otherwise prompted for.";
+
+#[derive(Debug, thiserror::Error)]
+pub enum CliError {
+    #[error("{0}")]
+    Usage(String),
+    #[error(transparent)]
+    Config(#[from] ConfigError),
+    #[error(transparent)]
+    Client(#[from] ClientError),
+    #[error("Vault daemon: {0}")]
+    Vault(String),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error("Invalid JSON: {0}")]
+    Json(#[from] serde_json::Error),
+    /// A check the command ran did not pass; details were already printed
+    #[error("{0}")]
+    Failed(String),
+}
+
+impl CliError {
+    /// Stable code printed with `--json`
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Usage(_) => "USAGE",
+            Self::Config(_) => "CONFIG",
+            Self::Client(e) => e.code().map_or("CLIENT", |code| code.as_str()),
+            Self::Vault(_) => "VAULT_UNAVAILABLE",
+            Self::Io(_) => "IO",
+            Self::Json(_) => "INVALID_JSON",
+            Self::Failed(_) => "CHECK_FAILED",
+        }
+    }
+
+    fn exit_code(&self) -> i32 {
+        match self {
+            Self::Usage(_) => 2,
+            _ => 1,
+        }
+    }
+}
+
+/// What a command needs: the configuration, a gateway client and the session cache
+pub struct Context {
+    pub config: Config,
+    pub client: Client,
+    pub cache: SessionCache,
+    /// Session loaded from the cache, to spot refreshes when the command finishes
+    cached: Option<CachedLogin>,
+}
+
+impl Context {
+    fn new(config: Config) -> Result<Self, CliError> {
+        let client = Client::from_config(&config.desktop)?;
+        let cache = SessionCache::new(config.vault.socket.clone());
+        Ok(Self { config, client, cache, cached: None })
+    }
+
+    /// The client with the cached session, when there is one. Without it calls go out
+    /// unauthenticated, which gateways without `gateway.require_auth` accept.
+    pub async fn authenticated(&mut self) -> Result<&Client, CliError> {
+        if self.cached.is_none() {
+            self.cached = self.cache.load().await?;
+            self.client.set_session(self.cached.as_ref().map(|login| login.session.clone()));
+        }
+        Ok(&self.client)
+    }
+
+    /// Write back a session the client refreshed, or drop one the gateway rejected
+    async fn finish(self) -> Result<(), CliError> {
+        let Some(cached) = self.cached else {
+            return Ok(());
+        };
+        match self.client.session() {
+            Some(current) if current != cached.session => {
+                self.cache.save(&CachedLogin { session: current, ..cached }).await
+            }
+            Some(_) => Ok(()),
+            None => self.cache.clear().await.map(drop),
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let (config_args, rest) = args::split_config(std::env::args().skip(1));
+    let parsed = Args::parse(rest);
+    let output = Output::new(parsed.as_ref().is_ok_and(|args| args.switch("json")));
+
+    let result = match parsed {
+        Ok(args) => run(config_args, args, &output).await,
+        Err(e) => Err(e),
+    };
+    if let Err(e) = result {
+        output.error(&e);
+        std::process::exit(e.exit_code());
+    }
+}
+
+async fn run(config_args: Vec<String>, mut args: Args, output: &Output) -> Result<(), CliError> {
+    let cli = Cli::parse(config_args)?;
+    let config = Config::load(&cli)?;
+    if cli.print_config {
+        print!("{}", config);
+        return Ok(());
+    }
+
+    let command = match args.next() {
+        Some(command) if command != "help" && !args.switch("help") => command,
+        _ => {
+            println!("{}", USAGE);
+            return Ok(());
+        }
+    };
+
+    config.validate(Service::Desktop)?;
+    let mut context = Context::new(config)?;
+    let result = match command.as_str() {
+        "login" => commands::auth::login(&mut context, args, output).await,
+        "logout" => commands::auth::logout(&mut context, args, output).await,
+        "memory" => commands::memory::run(&mut context, args, output).await,
+        "tag" => commands::tag::run(&mut context, args, output).await,
+        "key" => commands::key::run(&mut context, args, output).await,
+        "health" => commands::health::health(&mut context, args, output).await,
+        "doctor" => commands::health::doctor(&mut context, args, output).await,
+        other => Err(CliError::Usage(format!("unknown command {:?}", other))),
+    };
+    // Keep refreshed tokens even when the command itself failed
+    let finished = context.finish().await;
+    result.and(finished)
+}
@@ -0,0 +1,90 @@
+use crate::CliError;
+use identra_client::{ClientError, ErrorCode, Memory};
+use serde::Serialize;
+
+/// Human-readable output, or JSON for scripts with `--json`
+pub struct Output {
+    json: bool,
+}
+
+impl Output {
+    pub fn new(json: bool) -> Self {
+        Self { json }
+    }
+
+    pub fn is_json(&self) -> bool {
+        self.json
+    }
+
+    /// Print `value` as JSON, or call `human` to print it for people
+    pub fn emit<T: Serialize>(&self, value: &T, human: impl FnOnce()) -> Result<(), CliError> {
+        if self.json {
+            println!("{}", serde_json::to_string_pretty(value)?);
+        } else {
+            human();
+        }
+        Ok(())
+    }
+
+    /// Report a failed command on stderr
+    pub fn error(&self, error: &CliError) {
+        if self.json {
+            let mut body = serde_json::json!({ "code": error.code(), "message": error.to_string() });
+            if let CliError::Client(ClientError::Api(api)) = error {
+                if !api.field_violations().is_empty() {
+                    body["field_violations"] = api.field_violations().iter()
+                        .map(|v| serde_json::json!({ "field": v.field, "description": v.description }))
+                        .collect();
+                }
+            }
+            eprintln!("{}", serde_json::json!({ "error": body }));
+            return;
+        }
+
+        eprintln!("❌ {}", error);
+        if let CliError::Client(ClientError::Api(api)) = error {
+            for violation in api.field_violations() {
+                eprintln!("   {}: {}", violation.field, violation.description);
+            }
+        }
+        match error.code() {
+            code if code == ErrorCode::Unauthenticated.as_str() || code == ErrorCode::InvalidToken.as_str() => {
+                eprintln!("   Run `identra login` first");
+            }
+            "USAGE" => eprintln!("   See `identra help`"),
+            _ => {}
+        }
+    }
+}
+
+/// One line per memory in listings
+pub fn memory_line(memory: &Memory) -> String {
+    let mut line = format!("{}  {}  {}", memory.id, memory.created_at.format("%Y-%m-%d %H:%M"), preview(&memory.content, 60));
+    if !memory.tags.is_empty() {
+        line.push_str(&format!("  [{}]", memory.tags.join(", ")));
+    }
+    line
+}
+
+/// First line of `text`, cut to `width` characters
+pub fn preview(text: &str, width: usize) -> String {
+    let first = text.lines().next().unwrap_or_default();
+    if first.chars().count() > width || first.len() < text.trim_end().len() {
+        let cut: String = first.chars().take(width.saturating_sub(1)).collect();
+        format!("{}…", cut)
+    } else {
+        first.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn previews_first_line() {
+        assert_eq!(preview("short", 10), "short");
+        assert_eq!(preview("a much longer line", 7), "a much…");
+        assert_eq!(preview("first\nsecond", 20), "first…");
+    }
+}
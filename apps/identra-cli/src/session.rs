@@ -0,0 +1,65 @@
+use crate::CliError;
+use identra_client::ipc::VaultClient;
+use identra_client::Session;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Vault daemon key holding the CLI's login
+const SESSION_KEY: &str = "identra-cli/session";
+
+/// A login as cached in the vault daemon
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedLogin {
+    pub username: String,
+    #[serde(flatten)]
+    pub session: Session,
+}
+
+/// The login cache, kept in the vault daemon so tokens never touch a plain file
+pub struct SessionCache {
+    socket: String,
+}
+
+impl SessionCache {
+    pub fn new(socket: String) -> Self {
+        Self { socket }
+    }
+
+    async fn connect(&self) -> Result<VaultClient, CliError> {
+        VaultClient::connect_to(&self.socket)
+            .await
+            .map_err(|e| CliError::Vault(format!("{} (is vault-daemon running on {}?)", e, self.socket)))
+    }
+
+    pub async fn load(&self) -> Result<Option<CachedLogin>, CliError> {
+        let mut vault = self.connect().await?;
+        if !vault.key_exists(SESSION_KEY.to_string()).await.map_err(vault_error)? {
+            return Ok(None);
+        }
+        let key = vault.retrieve_key(SESSION_KEY.to_string()).await.map_err(vault_error)?;
+        Ok(Some(serde_json::from_slice(&key.key_data)?))
+    }
+
+    pub async fn save(&self, login: &CachedLogin) -> Result<(), CliError> {
+        let mut vault = self.connect().await?;
+        let metadata = HashMap::from([("username".to_string(), login.username.clone())]);
+        vault
+            .store_key(SESSION_KEY.to_string(), serde_json::to_vec(login)?, metadata, None)
+            .await
+            .map_err(vault_error)
+    }
+
+    /// Remove the cached login; `false` when there was none
+    pub async fn clear(&self) -> Result<bool, CliError> {
+        let mut vault = self.connect().await?;
+        if !vault.key_exists(SESSION_KEY.to_string()).await.map_err(vault_error)? {
+            return Ok(false);
+        }
+        vault.delete_key(SESSION_KEY.to_string()).await.map_err(vault_error)?;
+        Ok(true)
+    }
+}
+
+fn vault_error(error: identra_client::ipc::VaultClientError) -> CliError {
+    CliError::Vault(error.to_string())
+}
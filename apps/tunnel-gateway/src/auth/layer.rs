@@ -0,0 +1,120 @@
+use crate::auth::middleware::{AuthClaims, AuthInterceptor};
+use crate::rest::reject;
+use crate::tls::ServiceIdentities;
+use std::sync::Arc;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tonic::body::BoxBody;
+use identra_core::error::{Error, ErrorCode};
+use tower::{Layer, Service};
+
+/// Paths that never require a token (login itself, health probes, the API description)
+const PUBLIC_PREFIXES: &[&str] = &[
+    "/identra.auth.AuthService/",
+    "/identra.health.v1.Health/",
+    crate::services::health::LIVENESS_PATH,
+    crate::services::health::READINESS_PATH,
+    "/v1/auth/",
+    crate::rest::OPENAPI_PATH,
+];
+
+/// Tower layer that validates bearer tokens before requests reach the services.
+///
+/// Valid tokens attach `AuthClaims` to the request extensions. Requests without a
+/// token run as the service identity of their client certificate, if it is mapped,
+/// and are otherwise rejected or run as `AuthClaims::anonymous()` when `require_auth` is off.
+#[derive(Clone)]
+pub struct AuthLayer {
+    interceptor: AuthInterceptor,
+    require_auth: bool,
+    services: Arc<ServiceIdentities>,
+}
+
+impl AuthLayer {
+    pub fn new(interceptor: AuthInterceptor, require_auth: bool) -> Self {
+        Self { interceptor, require_auth, services: Arc::default() }
+    }
+
+    /// Authenticate mTLS clients by certificate subject
+    pub fn with_service_identities(mut self, services: ServiceIdentities) -> Self {
+        self.services = Arc::new(services);
+        self
+    }
+}
+
+impl<S> Layer<S> for AuthLayer {
+    type Service = AuthService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        AuthService {
+            inner,
+            interceptor: self.interceptor.clone(),
+            require_auth: self.require_auth,
+            services: self.services.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct AuthService<S> {
+    inner: S,
+    interceptor: AuthInterceptor,
+    require_auth: bool,
+    services: Arc<ServiceIdentities>,
+}
+
+impl<S, B> Service<http::Request<B>> for AuthService<S>
+where
+    S: Service<http::Request<B>, Response = http::Response<BoxBody>> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+    B: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: http::Request<B>) -> Self::Future {
+        // Take the service that was driven to readiness, leave a fresh clone behind
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let interceptor = self.interceptor.clone();
+        let require_auth = self.require_auth;
+        let services = self.services.clone();
+
+        Box::pin(async move {
+            let path = req.uri().path();
+            if PUBLIC_PREFIXES.iter().any(|prefix| path.starts_with(prefix)) {
+                return inner.call(req).await;
+            }
+
+            let header = req.headers().get(http::header::AUTHORIZATION).cloned();
+            let claims = match header {
+                Some(value) => {
+                    let value = match value.to_str() {
+                        Ok(v) => v,
+                        Err(_) => return Ok(reject(&req, Error::new(ErrorCode::Unauthenticated, "Invalid authorization header").into())),
+                    };
+                    match interceptor.verify_header(value).await {
+                        Ok(claims) => claims,
+                        Err(status) => return Ok(reject(&req, status)),
+                    }
+                }
+                None => match services.claims_for(req.extensions()) {
+                    Some(claims) => claims,
+                    None if require_auth => {
+                        return Ok(reject(&req, Error::new(ErrorCode::Unauthenticated, "Missing authorization token").into()));
+                    }
+                    None => AuthClaims::anonymous(),
+                },
+            };
+
+            req.extensions_mut().insert(claims);
+            inner.call(req).await
+        })
+    }
+}
@@ -1,4 +1,5 @@
-use crate::auth::supabase_client::SupabaseClient;
+use crate::auth::supabase_client::{AuthApiError, SupabaseClient};
+use identra_core::error::{Error, ErrorCode};
 use std::sync::Arc;
 use tonic::{Request, Status};
 use serde::{Deserialize, Serialize};
@@ -8,6 +9,21 @@ pub struct AuthClaims {
     pub sub: String,
     pub email: String,
     pub role: String,
+    /// Sharing groups from the user's `app_metadata.groups`
+    #[serde(default)]
+    pub groups: Vec<String>,
+}
+
+impl AuthClaims {
+    /// Principal used for unauthenticated calls when the gateway allows them
+    pub fn anonymous() -> Self {
+        Self {
+            sub: "anonymous".to_string(),
+            email: String::new(),
+            role: "anon".to_string(),
+            groups: Vec::new(),
+        }
+    }
 }
 
 /// gRPC interceptor for Supabase JWT authentication
@@ -26,34 +42,46 @@ impl AuthInterceptor {
         // Get authorization header
         let token = match req.metadata().get("authorization") {
             Some(t) => t.to_str().map_err(|_| {
-                Status::unauthenticated("Invalid authorization header")
+                Error::new(ErrorCode::Unauthenticated, "Invalid authorization header")
             })?,
             None => {
-                return Err(Status::unauthenticated("Missing authorization token"));
+                return Err(Error::new(ErrorCode::Unauthenticated, "Missing authorization token").into());
             }
         };
         
+        let claims = self.verify_header(token).await?;
+        
+        // Add user info to request extensions for downstream services
+        req.extensions_mut().insert(claims);
+        
+        Ok(req)
+    }
+    
+    /// Validate a raw `authorization` header value and resolve its claims
+    pub async fn verify_header(&self, auth_header: &str) -> Result<AuthClaims, Status> {
         // Extract token from "Bearer <token>" format
-        let token = extract_bearer_token(token)
-            .ok_or_else(|| Status::unauthenticated("Invalid token format. Use: Bearer <token>"))?;
+        let token = extract_bearer_token(auth_header)
+            .ok_or_else(|| Error::new(ErrorCode::InvalidToken, "Invalid token format. Use: Bearer <token>"))?;
         
         // Validate token with Supabase
         let verify_response = self.supabase.verify_token(&token)
             .await
-            .map_err(|e| {
-                tracing::warn!("Token validation failed: {}", e);
-                Status::unauthenticated("Invalid or expired token")
+            .map_err(|e| match e {
+                AuthApiError::Rejected(e) => {
+                    tracing::warn!("Token validation failed: {}", e);
+                    Error::new(ErrorCode::InvalidToken, "Invalid or expired token")
+                }
+                AuthApiError::Unavailable(e) => {
+                    Error::new(ErrorCode::AuthUnavailable, "Authentication service unavailable").with_internal(e)
+                }
             })?;
         
-        // Add user info to request extensions for downstream services
-        let claims = AuthClaims {
+        Ok(AuthClaims {
             sub: verify_response.sub,
             email: verify_response.email,
             role: verify_response.role,
-        };
-        req.extensions_mut().insert(claims);
-        
-        Ok(req)
+            groups: verify_response.groups,
+        })
     }
 }
 
@@ -71,7 +99,7 @@ pub fn get_user_id_from_request<T>(req: &Request<T>) -> Result<String, Status> {
     req.extensions()
         .get::<AuthClaims>()
         .map(|claims| claims.sub.clone())
-        .ok_or_else(|| Status::unauthenticated("User not authenticated"))
+        .ok_or_else(|| Error::new(ErrorCode::Unauthenticated, "User not authenticated").into())
 }
 
 /// Helper function to extract email from request extensions
@@ -79,5 +107,5 @@ pub fn get_email_from_request<T>(req: &Request<T>) -> Result<String, Status> {
     req.extensions()
         .get::<AuthClaims>()
         .map(|claims| claims.email.clone())
-        .ok_or_else(|| Status::unauthenticated("User not authenticated"))
+        .ok_or_else(|| Error::new(ErrorCode::Unauthenticated, "User not authenticated").into())
 }
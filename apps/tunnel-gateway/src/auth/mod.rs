@@ -1,6 +1,9 @@
 pub mod service;
 pub mod middleware;
+pub mod layer;
 pub mod supabase_client;
 
 pub use service::AuthServiceImpl;
+pub use layer::AuthLayer;
+pub use middleware::{AuthClaims, AuthInterceptor};
 pub use supabase_client::SupabaseClient;
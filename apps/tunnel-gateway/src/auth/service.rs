@@ -4,7 +4,8 @@ use identra_proto::auth::{
     LoginRequest, LoginResponse, RefreshTokenRequest, RefreshTokenResponse, 
     RegisterRequest, RegisterResponse, VerifyTokenRequest, VerifyTokenResponse,
 };
-use crate::auth::supabase_client::SupabaseClient;
+use crate::auth::supabase_client::{AuthApiError, SupabaseClient};
+use identra_core::error::{Error, ErrorCode};
 use std::sync::Arc;
 
 pub struct AuthServiceImpl {
@@ -17,6 +18,17 @@ impl AuthServiceImpl {
     }
 }
 
+/// Status for a failed auth API call; `rejected` builds the caller-facing error when
+/// the API refused the request rather than failing to answer it
+fn auth_error(error: AuthApiError, rejected: impl FnOnce(String) -> Error) -> Status {
+    match error {
+        AuthApiError::Unavailable(detail) => {
+            Error::new(ErrorCode::AuthUnavailable, "Authentication service unavailable").with_internal(detail).into()
+        }
+        AuthApiError::Rejected(message) => rejected(message).into(),
+    }
+}
+
 #[tonic::async_trait]
 impl AuthService for AuthServiceImpl {
     async fn register(
@@ -27,41 +39,27 @@ impl AuthService for AuthServiceImpl {
         
         // Validation
         if req.username.trim().is_empty() {
-            return Ok(Response::new(RegisterResponse {
-                success: false,
-                message: "Username cannot be empty".to_string(),
-                user_id: String::new(),
-            }));
+            return Err(Error::invalid_field("username", "Username cannot be empty").into());
         }
         
         if req.password.len() < 8 {
-            return Ok(Response::new(RegisterResponse {
-                success: false,
-                message: "Password must be at least 8 characters".to_string(),
-                user_id: String::new(),
-            }));
+            return Err(Error::invalid_field("password", "Password must be at least 8 characters").into());
         }
         
         // Use Supabase Auth for registration
-        match self.supabase.sign_up(&req.email, &req.password, &req.username).await {
-            Ok(auth_response) => {
-                tracing::info!("User registered: {} ({})", req.username, auth_response.user.id);
-                
-                Ok(Response::new(RegisterResponse {
-                    success: true,
-                    message: "User registered successfully".to_string(),
-                    user_id: auth_response.user.id,
-                }))
-            }
-            Err(e) => {
-                tracing::error!("Registration failed: {}", e);
-                Ok(Response::new(RegisterResponse {
-                    success: false,
-                    message: e,
-                    user_id: String::new(),
-                }))
-            }
-        }
+        let auth_response = self.supabase.sign_up(&req.email, &req.password, &req.username)
+            .await
+            .map_err(|e| auth_error(e, |message| {
+                tracing::warn!("Registration rejected for {}: {}", req.username, message);
+                Error::invalid_argument(message)
+            }))?;
+        tracing::info!("User registered: {} ({})", req.username, auth_response.user.id);
+        
+        Ok(Response::new(RegisterResponse {
+            success: true,
+            message: "User registered successfully".to_string(),
+            user_id: auth_response.user.id,
+        }))
     }
     
     async fn login(
@@ -72,29 +70,21 @@ impl AuthService for AuthServiceImpl {
         
         // Supabase uses email for login
         // We treat username field as email
-        match self.supabase.sign_in(&req.username, &req.password).await {
-            Ok(auth_response) => {
-                tracing::info!("User logged in: {}", auth_response.user.id);
-                
-                Ok(Response::new(LoginResponse {
-                    success: true,
-                    message: "Login successful".to_string(),
-                    access_token: auth_response.access_token,
-                    refresh_token: auth_response.refresh_token,
-                    expires_in: auth_response.expires_in as i64,
-                }))
-            }
-            Err(e) => {
+        let auth_response = self.supabase.sign_in(&req.username, &req.password)
+            .await
+            .map_err(|e| auth_error(e, |_| {
                 tracing::warn!("Login failed for user: {}", req.username);
-                Ok(Response::new(LoginResponse {
-                    success: false,
-                    message: "Invalid credentials".to_string(),
-                    access_token: String::new(),
-                    refresh_token: String::new(),
-                    expires_in: 0,
-                }))
-            }
-        }
+                Error::new(ErrorCode::InvalidCredentials, "Invalid credentials")
+            }))?;
+        tracing::info!("User logged in: {}", auth_response.user.id);
+        
+        Ok(Response::new(LoginResponse {
+            success: true,
+            message: "Login successful".to_string(),
+            access_token: auth_response.access_token,
+            refresh_token: auth_response.refresh_token,
+            expires_in: auth_response.expires_in as i64,
+        }))
     }
     
     async fn refresh_token(
@@ -103,23 +93,18 @@ impl AuthService for AuthServiceImpl {
     ) -> Result<Response<RefreshTokenResponse>, Status> {
         let req = request.into_inner();
         
-        match self.supabase.refresh_token(&req.refresh_token).await {
-            Ok(auth_response) => {
-                Ok(Response::new(RefreshTokenResponse {
-                    success: true,
-                    access_token: auth_response.access_token,
-                    expires_in: auth_response.expires_in as i64,
-                }))
-            }
-            Err(e) => {
-                tracing::error!("Token refresh failed: {}", e);
-                Ok(Response::new(RefreshTokenResponse {
-                    success: false,
-                    access_token: String::new(),
-                    expires_in: 0,
-                }))
-            }
-        }
+        let auth_response = self.supabase.refresh_token(&req.refresh_token)
+            .await
+            .map_err(|e| auth_error(e, |message| {
+                tracing::warn!("Token refresh rejected: {}", message);
+                Error::new(ErrorCode::InvalidToken, "Invalid or expired refresh token")
+            }))?;
+        
+        Ok(Response::new(RefreshTokenResponse {
+            success: true,
+            access_token: auth_response.access_token,
+            expires_in: auth_response.expires_in as i64,
+        }))
     }
     
     async fn verify_token(
@@ -128,6 +113,7 @@ impl AuthService for AuthServiceImpl {
     ) -> Result<Response<VerifyTokenResponse>, Status> {
         let req = request.into_inner();
         
+        // An invalid token is an answer, not an error; only an unreachable auth API fails the call
         match self.supabase.verify_token(&req.token).await {
             Ok(verify_response) => {
                 Ok(Response::new(VerifyTokenResponse {
@@ -137,7 +123,7 @@ impl AuthService for AuthServiceImpl {
                     expires_at: verify_response.exp as i64,
                 }))
             }
-            Err(e) => {
+            Err(AuthApiError::Rejected(e)) => {
                 tracing::warn!("Token verification failed: {}", e);
                 Ok(Response::new(VerifyTokenResponse {
                     valid: false,
@@ -146,6 +132,7 @@ impl AuthService for AuthServiceImpl {
                     expires_at: 0,
                 }))
             }
+            Err(e) => Err(auth_error(e, Error::invalid_argument)),
         }
     }
 }
@@ -1,6 +1,19 @@
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
-use std::env;
+use identra_core::config::SupabaseConfig;
+use thiserror::Error;
+
+/// Why a call to the Supabase Auth API failed
+#[derive(Error, Debug)]
+pub enum AuthApiError {
+    /// The API could not be reached or answered with something unreadable
+    #[error("Auth API unavailable: {0}")]
+    Unavailable(String),
+
+    /// The API refused the request; the message is written for the end user
+    #[error("{0}")]
+    Rejected(String),
+}
 
 #[derive(Debug, Clone)]
 pub struct SupabaseClient {
@@ -44,6 +57,9 @@ pub struct SupabaseUser {
     pub email: String,
     #[serde(default)]
     pub user_metadata: serde_json::Value,
+    // Set by the service role only, so users cannot add themselves to groups
+    #[serde(default)]
+    pub app_metadata: serde_json::Value,
     pub created_at: String,
 }
 
@@ -70,22 +86,23 @@ pub struct VerifyResponse {
     pub sub: String,
     pub email: String,
     pub role: String,
+    pub groups: Vec<String>,
 }
 
 impl SupabaseClient {
-    pub fn new() -> Result<Self, String> {
-        let url = env::var("SUPABASE_URL")
-            .map_err(|_| "SUPABASE_URL not set in environment")?;
-        let anon_key = env::var("SUPABASE_ANON_KEY")
-            .map_err(|_| "SUPABASE_ANON_KEY not set in environment")?;
-        let service_role_key = env::var("SUPABASE_SERVICE_ROLE_KEY")
-            .map_err(|_| "SUPABASE_SERVICE_ROLE_KEY not set in environment")?;
+    pub fn new(config: &SupabaseConfig) -> Result<Self, String> {
+        let url = config.url.clone()
+            .ok_or("supabase.url is not set")?;
+        let anon_key = config.anon_key.as_ref()
+            .ok_or("supabase.anon_key is not set")?;
+        let service_role_key = config.service_role_key.as_ref()
+            .ok_or("supabase.service_role_key is not set")?;
 
         Ok(Self {
             client: Client::new(),
             url,
-            anon_key,
-            service_role_key,
+            anon_key: anon_key.expose().to_string(),
+            service_role_key: service_role_key.expose().to_string(),
         })
     }
 
@@ -94,7 +111,7 @@ impl SupabaseClient {
         email: &str,
         password: &str,
         username: &str,
-    ) -> Result<AuthResponse, String> {
+    ) -> Result<AuthResponse, AuthApiError> {
         let signup_url = format!("{}/auth/v1/signup", self.url);
         
         let payload = SignUpRequest {
@@ -112,23 +129,23 @@ impl SupabaseClient {
             .json(&payload)
             .send()
             .await
-            .map_err(|e| format!("Request failed: {}", e))?;
+            .map_err(|e| AuthApiError::Unavailable(format!("Request failed: {}", e)))?;
 
         if response.status().is_success() {
             response
                 .json::<AuthResponse>()
                 .await
-                .map_err(|e| format!("Failed to parse response: {}", e))
+                .map_err(|e| AuthApiError::Unavailable(format!("Failed to parse response: {}", e)))
         } else {
             let error = response
                 .json::<SupabaseError>()
                 .await
-                .map_err(|e| format!("Failed to parse error: {}", e))?;
-            Err(error.error_description.unwrap_or(error.error))
+                .map_err(|e| AuthApiError::Unavailable(format!("Failed to parse error: {}", e)))?;
+            Err(AuthApiError::Rejected(error.error_description.unwrap_or(error.error)))
         }
     }
 
-    pub async fn sign_in(&self, email: &str, password: &str) -> Result<AuthResponse, String> {
+    pub async fn sign_in(&self, email: &str, password: &str) -> Result<AuthResponse, AuthApiError> {
         let signin_url = format!("{}/auth/v1/token?grant_type=password", self.url);
         
         let payload = SignInRequest {
@@ -143,23 +160,23 @@ impl SupabaseClient {
             .json(&payload)
             .send()
             .await
-            .map_err(|e| format!("Request failed: {}", e))?;
+            .map_err(|e| AuthApiError::Unavailable(format!("Request failed: {}", e)))?;
 
         if response.status().is_success() {
             response
                 .json::<AuthResponse>()
                 .await
-                .map_err(|e| format!("Failed to parse response: {}", e))
+                .map_err(|e| AuthApiError::Unavailable(format!("Failed to parse response: {}", e)))
         } else {
             let error = response
                 .json::<SupabaseError>()
                 .await
-                .map_err(|e| format!("Failed to parse error: {}", e))?;
-            Err(error.error_description.unwrap_or(error.error))
+                .map_err(|e| AuthApiError::Unavailable(format!("Failed to parse error: {}", e)))?;
+            Err(AuthApiError::Rejected(error.error_description.unwrap_or(error.error)))
         }
     }
 
-    pub async fn refresh_token(&self, refresh_token: &str) -> Result<AuthResponse, String> {
+    pub async fn refresh_token(&self, refresh_token: &str) -> Result<AuthResponse, AuthApiError> {
         let refresh_url = format!("{}/auth/v1/token?grant_type=refresh_token", self.url);
         
         let payload = RefreshRequest {
@@ -173,23 +190,41 @@ impl SupabaseClient {
             .json(&payload)
             .send()
             .await
-            .map_err(|e| format!("Request failed: {}", e))?;
+            .map_err(|e| AuthApiError::Unavailable(format!("Request failed: {}", e)))?;
 
         if response.status().is_success() {
             response
                 .json::<AuthResponse>()
                 .await
-                .map_err(|e| format!("Failed to parse response: {}", e))
+                .map_err(|e| AuthApiError::Unavailable(format!("Failed to parse response: {}", e)))
         } else {
             let error = response
                 .json::<SupabaseError>()
                 .await
-                .map_err(|e| format!("Failed to parse error: {}", e))?;
-            Err(error.error_description.unwrap_or(error.error))
+                .map_err(|e| AuthApiError::Unavailable(format!("Failed to parse error: {}", e)))?;
+            Err(AuthApiError::Rejected(error.error_description.unwrap_or(error.error)))
+        }
+    }
+
+    /// Check that the Supabase Auth API is reachable, for health checks
+    pub async fn health(&self) -> Result<(), String> {
+        let health_url = format!("{}/auth/v1/health", self.url);
+
+        let response = self.client
+            .get(&health_url)
+            .header("apikey", &self.anon_key)
+            .send()
+            .await
+            .map_err(|e| format!("Request failed: {}", e))?;
+
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(format!("Auth API returned {}", response.status()))
         }
     }
 
-    pub async fn verify_token(&self, token: &str) -> Result<VerifyResponse, String> {
+    pub async fn verify_token(&self, token: &str) -> Result<VerifyResponse, AuthApiError> {
         let user_url = format!("{}/auth/v1/user", self.url);
 
         let response = self.client
@@ -198,27 +233,34 @@ impl SupabaseClient {
             .header("Authorization", format!("Bearer {}", token))
             .send()
             .await
-            .map_err(|e| format!("Request failed: {}", e))?;
+            .map_err(|e| AuthApiError::Unavailable(format!("Request failed: {}", e)))?;
 
         if response.status().is_success() {
             let user = response
                 .json::<SupabaseUser>()
                 .await
-                .map_err(|e| format!("Failed to parse response: {}", e))?;
+                .map_err(|e| AuthApiError::Unavailable(format!("Failed to parse response: {}", e)))?;
             
+            let groups = user.app_metadata.get("groups")
+                .and_then(|g| serde_json::from_value(g.clone()).ok())
+                .unwrap_or_default();
+
             Ok(VerifyResponse {
                 aud: "authenticated".to_string(),
                 exp: 0, // Supabase handles expiration
                 sub: user.id,
                 email: user.email,
                 role: "authenticated".to_string(),
+                groups,
             })
+        } else if response.status().is_server_error() {
+            Err(AuthApiError::Unavailable(format!("Auth API returned {}", response.status())))
         } else {
-            Err("Invalid or expired token".to_string())
+            Err(AuthApiError::Rejected("Invalid or expired token".to_string()))
         }
     }
 
-    pub async fn sign_out(&self, access_token: &str) -> Result<(), String> {
+    pub async fn sign_out(&self, access_token: &str) -> Result<(), AuthApiError> {
         let signout_url = format!("{}/auth/v1/logout", self.url);
 
         let response = self.client
@@ -227,18 +269,12 @@ impl SupabaseClient {
             .header("Authorization", format!("Bearer {}", access_token))
             .send()
             .await
-            .map_err(|e| format!("Request failed: {}", e))?;
+            .map_err(|e| AuthApiError::Unavailable(format!("Request failed: {}", e)))?;
 
         if response.status().is_success() {
             Ok(())
         } else {
-            Err("Failed to sign out".to_string())
+            Err(AuthApiError::Rejected("Failed to sign out".to_string()))
         }
     }
 }
-
-impl Default for SupabaseClient {
-    fn default() -> Self {
-        Self::new().expect("Failed to initialize Supabase client")
-    }
-}
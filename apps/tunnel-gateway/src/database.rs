@@ -1,17 +1,207 @@
-use sqlx::postgres::{PgPoolOptions, PgPool};
+use sqlx::postgres::{PgArguments, PgPoolOptions, PgPool, Postgres};
+use sqlx::query::Query;
 use sqlx::Row; 
 use uuid::Uuid;
 use serde_json::Value;
 use std::collections::HashMap;
+use std::time::Instant;
+use metrics::histogram;
 
 // Shared model for Service <-> DB
 use crate::services::memory::MemoryModel;
+use crate::services::listing::{Listing, TimeFilter};
+use crate::services::projection::Projection;
+use crate::telemetry;
+
+mod audit;
+mod conversations;
+mod grants;
+mod idempotency;
+mod namespaces;
+mod tags;
+
+// Idempotent DDL, applied in order on every startup
+const SCHEMA: &[&str] = &[
+    "CREATE EXTENSION IF NOT EXISTS vector",
+    r#"
+    CREATE TABLE IF NOT EXISTS memories (
+        id UUID PRIMARY KEY,
+        content TEXT NOT NULL,
+        embedding vector(384),
+        metadata JSONB,
+        tags TEXT[],
+        created_at BIGINT,
+        updated_at BIGINT
+    )
+    "#,
+    r#"
+    CREATE TABLE IF NOT EXISTS namespaces (
+        id UUID PRIMARY KEY,
+        owner_id TEXT NOT NULL,
+        name TEXT NOT NULL,
+        default_retention_days INT NOT NULL DEFAULT 0,
+        embedding_model TEXT NOT NULL DEFAULT '',
+        sharing INT NOT NULL DEFAULT 0,
+        created_at BIGINT NOT NULL,
+        updated_at BIGINT NOT NULL,
+        UNIQUE (owner_id, name)
+    )
+    "#,
+    "ALTER TABLE memories ADD COLUMN IF NOT EXISTS namespace_id UUID REFERENCES namespaces(id) ON DELETE CASCADE",
+    "ALTER TABLE memories ADD COLUMN IF NOT EXISTS expires_at BIGINT",
+    "ALTER TABLE memories ADD COLUMN IF NOT EXISTS deleted_at BIGINT",
+    "ALTER TABLE memories ADD COLUMN IF NOT EXISTS last_accessed_at BIGINT",
+    "ALTER TABLE namespaces ADD COLUMN IF NOT EXISTS secret_policy INT NOT NULL DEFAULT 0",
+    r#"
+    CREATE TABLE IF NOT EXISTS conversations (
+        id UUID PRIMARY KEY,
+        namespace_id UUID REFERENCES namespaces(id) ON DELETE CASCADE,
+        title TEXT NOT NULL DEFAULT '',
+        model TEXT NOT NULL DEFAULT '',
+        metadata JSONB NOT NULL DEFAULT '{}'::jsonb,
+        created_at BIGINT NOT NULL,
+        updated_at BIGINT NOT NULL
+    )
+    "#,
+    r#"
+    CREATE TABLE IF NOT EXISTS conversation_turns (
+        id UUID PRIMARY KEY,
+        conversation_id UUID NOT NULL REFERENCES conversations(id) ON DELETE CASCADE,
+        sequence INT NOT NULL,
+        role TEXT NOT NULL,
+        content TEXT NOT NULL,
+        model TEXT NOT NULL DEFAULT '',
+        metadata JSONB NOT NULL DEFAULT '{}'::jsonb,
+        created_at BIGINT NOT NULL,
+        UNIQUE (conversation_id, sequence)
+    )
+    "#,
+    r#"
+    CREATE TABLE IF NOT EXISTS grants (
+        id UUID PRIMARY KEY,
+        namespace_id UUID NOT NULL REFERENCES namespaces(id) ON DELETE CASCADE,
+        memory_id UUID REFERENCES memories(id) ON DELETE CASCADE,
+        grantee TEXT NOT NULL,
+        permission INT NOT NULL,
+        granted_by TEXT NOT NULL,
+        created_at BIGINT NOT NULL
+    )
+    "#,
+    "CREATE UNIQUE INDEX IF NOT EXISTS grants_resource_grantee_idx ON grants (namespace_id, COALESCE(memory_id, '00000000-0000-0000-0000-000000000000'::uuid), grantee)",
+    "CREATE INDEX IF NOT EXISTS grants_grantee_idx ON grants (grantee)",
+    r#"
+    CREATE TABLE IF NOT EXISTS audit_log (
+        sequence BIGINT PRIMARY KEY,
+        occurred_at BIGINT NOT NULL,
+        actor TEXT NOT NULL,
+        service TEXT NOT NULL,
+        operation TEXT NOT NULL,
+        namespace_id TEXT NOT NULL DEFAULT '',
+        target_ids TEXT[] NOT NULL DEFAULT '{}',
+        outcome TEXT NOT NULL,
+        detail TEXT NOT NULL DEFAULT '',
+        prev_hash TEXT NOT NULL,
+        hash TEXT NOT NULL
+    )
+    "#,
+    // The audit log is append-only, even for the gateway's own database role
+    r#"
+    CREATE OR REPLACE FUNCTION audit_log_append_only() RETURNS trigger AS $$
+    BEGIN
+        RAISE EXCEPTION 'audit_log is append-only';
+    END
+    $$ LANGUAGE plpgsql
+    "#,
+    "DROP TRIGGER IF EXISTS audit_log_append_only ON audit_log",
+    "CREATE TRIGGER audit_log_append_only BEFORE UPDATE OR DELETE ON audit_log FOR EACH ROW EXECUTE FUNCTION audit_log_append_only()",
+    "DROP TRIGGER IF EXISTS audit_log_no_truncate ON audit_log",
+    "CREATE TRIGGER audit_log_no_truncate BEFORE TRUNCATE ON audit_log FOR EACH STATEMENT EXECUTE FUNCTION audit_log_append_only()",
+    "CREATE INDEX IF NOT EXISTS audit_log_actor_idx ON audit_log (actor, sequence DESC)",
+    "CREATE INDEX IF NOT EXISTS audit_log_occurred_at_idx ON audit_log (occurred_at)",
+    "CREATE INDEX IF NOT EXISTS memories_namespace_created_idx ON memories (namespace_id, created_at DESC)",
+    "CREATE INDEX IF NOT EXISTS memories_expires_at_idx ON memories (expires_at) WHERE expires_at IS NOT NULL",
+    "CREATE INDEX IF NOT EXISTS memories_deleted_at_idx ON memories (namespace_id, deleted_at DESC) WHERE deleted_at IS NOT NULL",
+    "CREATE INDEX IF NOT EXISTS conversations_namespace_updated_idx ON conversations (namespace_id, updated_at DESC)",
+    // StoreMemory retries; keys older than the idempotency window are ignored and swept.
+    // A key is claimed before its memory is inserted, so the memory reference is checked at commit.
+    r#"
+    CREATE TABLE IF NOT EXISTS idempotency_keys (
+        namespace_id UUID NOT NULL REFERENCES namespaces(id) ON DELETE CASCADE,
+        key TEXT NOT NULL,
+        fingerprint TEXT NOT NULL,
+        memory_id UUID NOT NULL REFERENCES memories(id) ON DELETE CASCADE DEFERRABLE INITIALLY DEFERRED,
+        created_at BIGINT NOT NULL,
+        PRIMARY KEY (namespace_id, key)
+    )
+    "#,
+    "CREATE INDEX IF NOT EXISTS idempotency_keys_created_at_idx ON idempotency_keys (created_at)",
+];
+
+// Trashed rows and rows past their namespace retention are invisible until restored or purged
+const LIVE: &str = "(deleted_at IS NULL AND (expires_at IS NULL OR expires_at > EXTRACT(EPOCH FROM NOW())::BIGINT))";
+
+/// Restrict a memory listing to rows granted individually to the grantees bound at `$param`.
+/// A NULL binding leaves the listing unrestricted (the caller can read the whole namespace).
+fn granted_only(param: usize) -> String {
+    format!(
+        "(${0}::text[] IS NULL OR id IN (SELECT memory_id FROM grants WHERE memory_id IS NOT NULL AND grantee = ANY(${0})))",
+        param
+    )
+}
+
+/// Conditions for a `TimeFilter` bound by `bind_time` at `$param` through `$param + 3`; NULL bounds are open
+fn within_time(param: usize) -> String {
+    format!(
+        "(${0}::bigint IS NULL OR created_at >= ${0}) AND (${1}::bigint IS NULL OR created_at < ${1}) \
+         AND (${2}::bigint IS NULL OR updated_at >= ${2}) AND (${3}::bigint IS NULL OR updated_at < ${3})",
+        param, param + 1, param + 2, param + 3
+    )
+}
+
+fn bind_time<'q>(query: Query<'q, Postgres, PgArguments>, time: &TimeFilter) -> Query<'q, Postgres, PgArguments> {
+    query.bind(time.created_start).bind(time.created_end).bind(time.updated_start).bind(time.updated_end)
+}
+
+/// A memory found by vector search
+#[derive(Debug, Clone)]
+pub struct SimilarMemory {
+    /// Carries the stored embedding
+    pub memory: MemoryModel,
+    pub similarity: f32,
+    /// When a client last read the memory, if ever
+    pub last_accessed_at: Option<i64>,
+}
+
+/// A StoreMemory idempotency key with a fingerprint of the request carrying it
+#[derive(Debug, Clone, Copy)]
+pub struct IdempotencyKey<'a> {
+    pub key: &'a str,
+    pub fingerprint: &'a str,
+    /// Uses of the key before this time have expired
+    pub not_before: i64,
+}
+
+/// The memory stored by an earlier request with the same idempotency key
+#[derive(Debug, Clone, PartialEq)]
+pub struct Replay {
+    pub memory_id: String,
+    pub fingerprint: String,
+}
 
 #[derive(Clone)]
 pub struct MemoryDatabase {
     pool: PgPool,
 }
 
+/// Connection pool usage at one instant
+#[derive(Debug, Clone, Copy)]
+pub struct PoolStats {
+    /// Open connections, idle or in use
+    pub size: u32,
+    pub idle: usize,
+    pub max: u32,
+}
+
 impl MemoryDatabase {
     pub async fn connect(database_url: &str) -> Result<Self, sqlx::Error> {
         tracing::info!("🔌 Connecting to Supabase...");
@@ -23,90 +213,212 @@ impl MemoryDatabase {
 
         tracing::info!("✅ Connected to Supabase Postgres.");
         
-        Ok(Self { pool })
+        let db = Self { pool };
+        db.ensure_schema().await?;
+        Ok(db)
+    }
+
+    /// The database named by `TEST_DATABASE_URL`, for tests that need Postgres with pgvector.
+    /// Those tests are `#[ignore]`d; run them with `cargo test -- --ignored` and the variable set.
+    #[cfg(test)]
+    pub(crate) async fn for_tests() -> Self {
+        // Connections apply the schema, which is not safe to run concurrently
+        static CONNECTING: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+        let url = std::env::var("TEST_DATABASE_URL").expect("TEST_DATABASE_URL must name a Postgres database with pgvector");
+        let _connecting = CONNECTING.lock().await;
+        Self::connect(&url).await.expect("Failed to connect to TEST_DATABASE_URL")
+    }
+
+    /// Round-trip a trivial query, for health checks
+    pub async fn ping(&self) -> Result<(), sqlx::Error> {
+        sqlx::query("SELECT 1").execute(&self.pool).await?;
+        Ok(())
+    }
+
+    pub fn pool_stats(&self) -> PoolStats {
+        PoolStats {
+            size: self.pool.size(),
+            idle: self.pool.num_idle(),
+            max: self.pool.options().get_max_connections(),
+        }
+    }
+
+    async fn ensure_schema(&self) -> Result<(), sqlx::Error> {
+        for statement in SCHEMA {
+            sqlx::query(statement).execute(&self.pool).await?;
+        }
+        Ok(())
     }
 
+    /// Store a memory. With an idempotency key that was already used within its window,
+    /// nothing is stored and the earlier use is returned instead.
+    #[tracing::instrument(skip_all)]
     pub async fn store_memory(
         &self,
-        id: &str,
-        content: &str,
-        embedding: &[f32],
-        metadata: &HashMap<String, String>,
-        tags: &[String],
-        created_at: i64,
-        updated_at: i64,
-    ) -> Result<(), sqlx::Error> {
-        let uuid = Uuid::parse_str(id).unwrap_or_default();
-        let metadata_json = serde_json::to_value(metadata).unwrap();
+        namespace_id: &str,
+        memory: &MemoryModel,
+        expires_at: Option<i64>,
+        idempotency: Option<IdempotencyKey<'_>>,
+    ) -> Result<Option<Replay>, sqlx::Error> {
+        let uuid = Uuid::parse_str(&memory.id).unwrap_or_default();
+        let namespace_uuid = Uuid::parse_str(namespace_id).unwrap_or_default();
+        let metadata_json = serde_json::to_value(&memory.metadata).unwrap();
+        let mut tx = self.pool.begin().await?;
+
+        // Claimed in the same transaction, so a concurrent retry waits and then replays
+        if let Some(key) = idempotency {
+            if let Some(replay) = idempotency::claim(&mut tx, namespace_uuid, key, uuid, memory.created_at).await? {
+                return Ok(Some(replay));
+            }
+        }
 
         // Use pgvector syntax for insertion
         sqlx::query(
             r#"
-            INSERT INTO memories (id, content, embedding, metadata, tags, created_at, updated_at)
-            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            INSERT INTO memories (id, namespace_id, content, embedding, metadata, tags, created_at, updated_at, expires_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
             "#
         )
         .bind(uuid)
-        .bind(content)
-        .bind(embedding) 
+        .bind(namespace_uuid)
+        .bind(&memory.content)
+        .bind(&memory.embedding)
         .bind(metadata_json)
-        .bind(tags)
-        .bind(created_at)
-        .bind(updated_at)
-        .execute(&self.pool)
+        .bind(&memory.tags)
+        .bind(memory.created_at)
+        .bind(memory.updated_at)
+        .bind(expires_at)
+        .execute(&mut *tx)
         .await?;
 
-        Ok(())
+        tx.commit().await?;
+        Ok(None)
     }
 
-    pub async fn search_memories(
+    /// Live memories at least `threshold` similar to `embedding`, most similar first. Their
+    /// stored embeddings are only read `with_embeddings`.
+    #[tracing::instrument(skip_all)]
+    #[allow(clippy::too_many_arguments)]
+    pub async fn similar_memories(
         &self,
+        namespace_id: &str,
         embedding: &[f32],
         limit: i32,
         threshold: f32,
-    ) -> Result<Vec<MemoryModel>, sqlx::Error> {
-        // Native Vector Search: 1 - (embedding <=> query)
-        let rows = sqlx::query(
+        time: &TimeFilter,
+        granted_to: Option<&[String]>,
+        with_embeddings: bool,
+    ) -> Result<Vec<SimilarMemory>, sqlx::Error> {
+        let namespace_uuid = Uuid::parse_str(namespace_id).unwrap_or_default();
+        let started = Instant::now();
+
+        let query = format!(
             r#"
-            SELECT id, content, metadata, tags, created_at, updated_at
+            SELECT {}, last_accessed_at, (1 - (embedding <=> $1))::real AS similarity
             FROM memories
-            WHERE 1 - (embedding <=> $1) > $2
+            WHERE namespace_id = $4 AND {} AND {} AND {}
+              AND 1 - (embedding <=> $1) >= $2
             ORDER BY embedding <=> $1
             LIMIT $3
-            "#
-        )
-        .bind(embedding)
-        .bind(threshold)
-        .bind(limit)
-        .fetch_all(&self.pool)
-        .await?;
+            "#,
+            Projection { embedding: with_embeddings, ..Projection::default() }.columns(),
+            LIVE,
+            granted_only(5),
+            within_time(6)
+        );
+        let query = sqlx::query(&query)
+            .bind(embedding)
+            .bind(threshold)
+            .bind(limit)
+            .bind(namespace_uuid)
+            .bind(granted_to);
+        let rows = bind_time(query, time)
+            .fetch_all(&self.pool)
+            .await;
+        histogram!(telemetry::VECTOR_SEARCH_DURATION, "outcome" => if rows.is_ok() { "ok" } else { "error" })
+            .record(started.elapsed().as_secs_f64());
 
-        self.map_rows(rows)
+        let rows = rows?;
+        let scores: Vec<(f32, Option<i64>)> = rows.iter()
+            .map(|row| (row.get("similarity"), row.get("last_accessed_at")))
+            .collect();
+        let memories = self.map_rows(rows)?;
+        Ok(memories.into_iter()
+            .zip(scores)
+            .map(|(memory, (similarity, last_accessed_at))| SimilarMemory { memory, similarity, last_accessed_at })
+            .collect())
+    }
+
+    /// Record that clients read these memories, for recency ranking on last access
+    #[tracing::instrument(skip_all)]
+    pub async fn touch_memories(&self, namespace_id: &str, ids: &[String], now: i64) -> Result<(), sqlx::Error> {
+        let namespace_uuid = Uuid::parse_str(namespace_id).unwrap_or_default();
+        let uuids: Vec<Uuid> = ids.iter().filter_map(|id| Uuid::parse_str(id).ok()).collect();
+        if uuids.is_empty() {
+            return Ok(());
+        }
+        sqlx::query("UPDATE memories SET last_accessed_at = $3 WHERE namespace_id = $1 AND id = ANY($2)")
+            .bind(namespace_uuid)
+            .bind(uuids)
+            .bind(now)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
     }
 
     // NEW: Fetch recent memories sorted by time
-    pub async fn get_recent_memories(&self, limit: i32) -> Result<Vec<MemoryModel>, sqlx::Error> {
+    #[tracing::instrument(skip_all)]
+    pub async fn get_recent_memories(
+        &self,
+        namespace_id: &str,
+        limit: i32,
+        listing: &Listing,
+        granted_to: Option<&[String]>,
+    ) -> Result<Vec<MemoryModel>, sqlx::Error> {
         let limit = if limit <= 0 { 50 } else { limit };
+        let namespace_uuid = Uuid::parse_str(namespace_id).unwrap_or_default();
         
-        let rows = sqlx::query(
+        let query = format!(
             r#"
-            SELECT id, content, metadata, tags, created_at, updated_at 
+            SELECT {} 
             FROM memories 
-            ORDER BY created_at DESC 
+            WHERE namespace_id = $2 AND {} AND {} AND {}
+            ORDER BY {}
             LIMIT $1
-            "#
-        )
-        .bind(limit)
-        .fetch_all(&self.pool)
-        .await?;
+            "#,
+            listing.projection.columns(),
+            LIVE,
+            granted_only(3),
+            within_time(4),
+            listing.sort.order_by()
+        );
+        let query = sqlx::query(&query)
+            .bind(limit)
+            .bind(namespace_uuid)
+            .bind(granted_to);
+        let rows = bind_time(query, &listing.time)
+            .fetch_all(&self.pool)
+            .await?;
 
         self.map_rows(rows)
     }
 
-    pub async fn get_memory(&self, id: &str) -> Result<Option<MemoryModel>, sqlx::Error> {
+    #[tracing::instrument(skip_all)]
+    pub async fn get_memory(
+        &self,
+        namespace_id: &str,
+        id: &str,
+        projection: &Projection,
+    ) -> Result<Option<MemoryModel>, sqlx::Error> {
         let uuid = Uuid::parse_str(id).unwrap_or_default();
-        let row = sqlx::query("SELECT id, content, metadata, tags, created_at, updated_at FROM memories WHERE id = $1")
+        let namespace_uuid = Uuid::parse_str(namespace_id).unwrap_or_default();
+        let row = sqlx::query(&format!(
+            "SELECT {} FROM memories WHERE id = $1 AND namespace_id = $2 AND {}",
+            projection.columns(),
+            LIVE
+        ))
             .bind(uuid)
+            .bind(namespace_uuid)
             .fetch_optional(&self.pool)
             .await?;
             
@@ -120,28 +432,144 @@ impl MemoryDatabase {
         }
     }
 
-    pub async fn query_memories(&self, query: &str, limit: i32) -> Result<Vec<MemoryModel>, sqlx::Error> {
+    /// Live memories whose content contains `query` and whose metadata has every entry
+    /// of `metadata`, one page of `limit` past `listing.after`
+    #[tracing::instrument(skip_all)]
+    pub async fn query_memories(
+        &self,
+        namespace_id: &str,
+        query: &str,
+        metadata: &HashMap<String, String>,
+        limit: i32,
+        listing: &Listing,
+        granted_to: Option<&[String]>,
+    ) -> Result<Vec<MemoryModel>, sqlx::Error> {
         let pattern = format!("%{}%", query);
-        let rows = sqlx::query(
-            "SELECT id, content, metadata, tags, created_at, updated_at FROM memories WHERE content ILIKE $1 LIMIT $2"
-        )
-        .bind(pattern)
+        let namespace_uuid = Uuid::parse_str(namespace_id).unwrap_or_default();
+        let query = format!(
+            "SELECT {} FROM memories WHERE namespace_id = $3 AND {} AND {} AND {} AND content ILIKE $1 \
+             AND COALESCE(metadata, '{{}}'::jsonb) @> $9 AND {} ORDER BY {} LIMIT $2",
+            listing.projection.columns(),
+            LIVE,
+            granted_only(4),
+            within_time(5),
+            listing.sort.past(10),
+            listing.sort.order_by()
+        );
+        let query = sqlx::query(&query)
+            .bind(pattern)
+            .bind(limit)
+            .bind(namespace_uuid)
+            .bind(granted_to);
+        let rows = bind_time(query, &listing.time)
+            .bind(serde_json::to_value(metadata).unwrap())
+            .bind(listing.after.map(|cursor| cursor.value))
+            .bind(listing.after.map(|cursor| cursor.id))
+            .fetch_all(&self.pool)
+            .await?;
+        
+        self.map_rows(rows)
+    }
+
+    /// Move a memory to the trash. Returns false if it does not exist or is already trashed.
+    #[tracing::instrument(skip_all)]
+    pub async fn delete_memory(&self, namespace_id: &str, id: &str, now: i64) -> Result<bool, sqlx::Error> {
+        let uuid = Uuid::parse_str(id).unwrap_or_default();
+        let namespace_uuid = Uuid::parse_str(namespace_id).unwrap_or_default();
+        let result = sqlx::query("UPDATE memories SET deleted_at = $3 WHERE id = $1 AND namespace_id = $2 AND deleted_at IS NULL")
+            .bind(uuid)
+            .bind(namespace_uuid)
+            .bind(now)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Trashed memories, most recently deleted first, paired with their deletion time
+    #[tracing::instrument(skip_all)]
+    pub async fn list_trash(
+        &self,
+        namespace_id: &str,
+        limit: i32,
+        granted_to: Option<&[String]>,
+    ) -> Result<Vec<(MemoryModel, i64)>, sqlx::Error> {
+        let limit = if limit <= 0 { 50 } else { limit };
+        let namespace_uuid = Uuid::parse_str(namespace_id).unwrap_or_default();
+
+        let rows = sqlx::query(&format!(
+            r#"
+            SELECT id, content, metadata, tags, created_at, updated_at, deleted_at
+            FROM memories
+            WHERE namespace_id = $2 AND deleted_at IS NOT NULL AND {}
+            ORDER BY deleted_at DESC
+            LIMIT $1
+            "#,
+            granted_only(3)
+        ))
         .bind(limit)
+        .bind(namespace_uuid)
+        .bind(granted_to)
         .fetch_all(&self.pool)
         .await?;
-        
-        self.map_rows(rows)
+
+        let deleted_at: Vec<i64> = rows.iter().map(|row| row.get("deleted_at")).collect();
+        Ok(self.map_rows(rows)?.into_iter().zip(deleted_at).collect())
+    }
+
+    /// Take a memory back out of the trash, returning it if it was there
+    #[tracing::instrument(skip_all)]
+    pub async fn restore_memory(&self, namespace_id: &str, id: &str) -> Result<Option<MemoryModel>, sqlx::Error> {
+        let uuid = Uuid::parse_str(id).unwrap_or_default();
+        let namespace_uuid = Uuid::parse_str(namespace_id).unwrap_or_default();
+        let row = sqlx::query(
+            r#"
+            UPDATE memories SET deleted_at = NULL
+            WHERE id = $1 AND namespace_id = $2 AND deleted_at IS NOT NULL
+            RETURNING id, content, metadata, tags, created_at, updated_at
+            "#
+        )
+        .bind(uuid)
+        .bind(namespace_uuid)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(self.map_rows(row.into_iter().collect())?.pop())
     }
 
-    pub async fn delete_memory(&self, id: &str) -> Result<bool, sqlx::Error> {
+    /// Permanently delete a trashed memory. The row carries the embedding, and everything
+    /// keyed on it (grants, and any future chunk or version tables) must use ON DELETE CASCADE.
+    #[tracing::instrument(skip_all)]
+    pub async fn purge_memory(&self, namespace_id: &str, id: &str) -> Result<bool, sqlx::Error> {
         let uuid = Uuid::parse_str(id).unwrap_or_default();
-        let result = sqlx::query("DELETE FROM memories WHERE id = $1")
+        let namespace_uuid = Uuid::parse_str(namespace_id).unwrap_or_default();
+        let result = sqlx::query("DELETE FROM memories WHERE id = $1 AND namespace_id = $2 AND deleted_at IS NOT NULL")
             .bind(uuid)
+            .bind(namespace_uuid)
             .execute(&self.pool)
             .await?;
         Ok(result.rows_affected() > 0)
     }
 
+    /// Permanently delete memories that have been in the trash since before `deleted_before`
+    #[tracing::instrument(skip_all)]
+    pub async fn purge_trash(&self, deleted_before: i64) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM memories WHERE deleted_at IS NOT NULL AND deleted_at <= $1")
+            .bind(deleted_before)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Remove memories whose namespace retention period has elapsed
+    #[tracing::instrument(skip_all)]
+    pub async fn delete_expired_memories(&self, now: i64) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM memories WHERE expires_at IS NOT NULL AND expires_at <= $1")
+            .bind(now)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
     // Helper to map SQL rows to Rust structs
     fn map_rows(&self, rows: Vec<sqlx::postgres::PgRow>) -> Result<Vec<MemoryModel>, sqlx::Error> {
         let results = rows.into_iter().map(|row| {
@@ -153,7 +581,8 @@ impl MemoryDatabase {
                 id: id.to_string(),
                 content: row.get("content"),
                 metadata,
-                embedding: vec![], // Optimization: Don't return vector to client
+                // Only read when the query selected it
+                embedding: row.try_get::<Option<Vec<f32>>, _>("embedding_values").ok().flatten().unwrap_or_default(),
                 tags: row.get::<Option<Vec<String>>, _>("tags").unwrap_or_default(),
                 created_at: row.get("created_at"),
                 updated_at: row.get("updated_at"),
@@ -161,4 +590,50 @@ impl MemoryDatabase {
         }).collect();
         Ok(results)
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::listing::Cursor;
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn queries_filter_on_metadata_and_page_by_cursor() {
+        let db = MemoryDatabase::for_tests().await;
+        let ns = db.get_or_create_namespace(&Uuid::new_v4().to_string(), "default", 100).await.unwrap();
+        let mut documents = Vec::new();
+        for (index, kind) in ["document", "note", "document", "document"].into_iter().enumerate() {
+            let memory = MemoryModel {
+                id: Uuid::new_v4().to_string(),
+                content: format!("chunk {}", index),
+                metadata: HashMap::from([("type".to_string(), kind.to_string())]),
+                embedding: vec![0.1; 384],
+                tags: Vec::new(),
+                // Two share a timestamp, so the id has to break the tie
+                created_at: 1000 + index.min(2) as i64,
+                updated_at: 1000,
+            };
+            db.store_memory(&ns.id, &memory, None, None).await.unwrap();
+            if kind == "document" {
+                documents.push(memory.id);
+            }
+        }
+
+        let filter = HashMap::from([("type".to_string(), "document".to_string())]);
+        let mut listing = Listing::default();
+        let mut paged = Vec::new();
+        loop {
+            let page = db.query_memories(&ns.id, "chunk", &filter, 2, &listing, None).await.unwrap();
+            paged.extend(page.iter().map(|m| m.id.clone()));
+            match page.last() {
+                Some(last) if page.len() == 2 => listing.after = Some(Cursor::new(listing.sort, last)),
+                _ => break,
+            }
+        }
+        paged.sort();
+        documents.sort();
+        assert_eq!(paged, documents);
+        assert_eq!(db.query_memories(&ns.id, "", &HashMap::new(), 10, &Listing::default(), None).await.unwrap().len(), 4);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,168 @@
+use sqlx::Row;
+
+use super::MemoryDatabase;
+use crate::services::audit::{AuditEntryModel, AuditFilterModel, GENESIS_HASH};
+
+// Serializes appends across gateway instances so the chain never forks
+const AUDIT_LOCK_KEY: i64 = 0x0069_6465_6e74_7261;
+
+const AUDIT_COLUMNS: &str = r#"
+    sequence, occurred_at, actor, service, operation, namespace_id, target_ids,
+    outcome, detail, prev_hash, hash
+"#;
+
+// $1..$6 are the filter fields, in `AuditFilterModel` order
+const AUDIT_FILTER: &str = r#"
+    ($1::text IS NULL OR actor = $1)
+    AND ($2::text IS NULL OR operation = $2)
+    AND ($3::text IS NULL OR namespace_id = $3)
+    AND ($4::text IS NULL OR $4 = ANY(target_ids))
+    AND ($5::bigint IS NULL OR occurred_at >= $5)
+    AND ($6::bigint IS NULL OR occurred_at < $6)
+"#;
+
+impl MemoryDatabase {
+    /// Append an entry at the end of the chain, filling in its sequence and hashes
+    #[tracing::instrument(skip_all)]
+    pub async fn append_audit_entry(&self, entry: &mut AuditEntryModel) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query("SELECT pg_advisory_xact_lock($1)")
+            .bind(AUDIT_LOCK_KEY)
+            .execute(&mut *tx)
+            .await?;
+
+        let last = sqlx::query("SELECT sequence, hash FROM audit_log ORDER BY sequence DESC LIMIT 1")
+            .fetch_optional(&mut *tx)
+            .await?;
+        let (sequence, prev_hash) = match last {
+            Some(row) => (row.get::<i64, _>("sequence") + 1, row.get::<String, _>("hash")),
+            None => (1, GENESIS_HASH.to_string()),
+        };
+
+        entry.sequence = sequence;
+        entry.prev_hash = prev_hash;
+        entry.hash = entry.compute_hash();
+
+        sqlx::query(&format!(
+            "INSERT INTO audit_log ({}) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)",
+            AUDIT_COLUMNS
+        ))
+        .bind(entry.sequence)
+        .bind(entry.occurred_at)
+        .bind(&entry.actor)
+        .bind(&entry.service)
+        .bind(&entry.operation)
+        .bind(&entry.namespace_id)
+        .bind(&entry.target_ids)
+        .bind(&entry.outcome)
+        .bind(&entry.detail)
+        .bind(&entry.prev_hash)
+        .bind(&entry.hash)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await
+    }
+
+    /// Matching entries with a sequence below `before`, newest first
+    #[tracing::instrument(skip_all)]
+    pub async fn query_audit_log(
+        &self,
+        filter: &AuditFilterModel,
+        before: Option<i64>,
+        limit: i32,
+    ) -> Result<Vec<AuditEntryModel>, sqlx::Error> {
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM audit_log WHERE {} AND ($7::bigint IS NULL OR sequence < $7) ORDER BY sequence DESC LIMIT $8",
+            AUDIT_COLUMNS, AUDIT_FILTER
+        ))
+        .bind(filter.actor.as_deref())
+        .bind(filter.operation.as_deref())
+        .bind(filter.namespace_id.as_deref())
+        .bind(filter.target_id.as_deref())
+        .bind(filter.since)
+        .bind(filter.until)
+        .bind(before)
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(map_audit_entry).collect())
+    }
+
+    /// Matching entries with a sequence above `after`, oldest first
+    #[tracing::instrument(skip_all)]
+    pub async fn audit_entries_after(
+        &self,
+        filter: &AuditFilterModel,
+        after: i64,
+        limit: i64,
+    ) -> Result<Vec<AuditEntryModel>, sqlx::Error> {
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM audit_log WHERE {} AND sequence > $7 ORDER BY sequence ASC LIMIT $8",
+            AUDIT_COLUMNS, AUDIT_FILTER
+        ))
+        .bind(filter.actor.as_deref())
+        .bind(filter.operation.as_deref())
+        .bind(filter.namespace_id.as_deref())
+        .bind(filter.target_id.as_deref())
+        .bind(filter.since)
+        .bind(filter.until)
+        .bind(after)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(map_audit_entry).collect())
+    }
+}
+
+fn map_audit_entry(row: sqlx::postgres::PgRow) -> AuditEntryModel {
+    AuditEntryModel {
+        sequence: row.get("sequence"),
+        occurred_at: row.get("occurred_at"),
+        actor: row.get("actor"),
+        service: row.get("service"),
+        operation: row.get("operation"),
+        namespace_id: row.get("namespace_id"),
+        target_ids: row.get("target_ids"),
+        outcome: row.get("outcome"),
+        detail: row.get("detail"),
+        prev_hash: row.get("prev_hash"),
+        hash: row.get("hash"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn entries_cannot_be_changed_or_removed() {
+        let db = MemoryDatabase::for_tests().await;
+        let mut entry = AuditEntryModel {
+            sequence: 0,
+            occurred_at: 1000,
+            actor: uuid::Uuid::new_v4().to_string(),
+            service: "memory".into(),
+            operation: "StoreMemory".into(),
+            namespace_id: String::new(),
+            target_ids: vec![],
+            outcome: "OK".into(),
+            detail: String::new(),
+            prev_hash: String::new(),
+            hash: String::new(),
+        };
+        db.append_audit_entry(&mut entry).await.unwrap();
+        assert_eq!(entry.hash, entry.compute_hash());
+
+        for statement in ["UPDATE audit_log SET detail = 'edited' WHERE sequence = $1", "DELETE FROM audit_log WHERE sequence = $1"] {
+            let error = sqlx::query(statement).bind(entry.sequence).execute(&db.pool).await.unwrap_err();
+            assert!(error.to_string().contains("append-only"), "{}", error);
+        }
+        let error = sqlx::query("TRUNCATE audit_log").execute(&db.pool).await.unwrap_err();
+        assert!(error.to_string().contains("append-only"), "{}", error);
+    }
+}
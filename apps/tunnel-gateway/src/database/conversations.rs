@@ -0,0 +1,283 @@
+use sqlx::Row;
+use uuid::Uuid;
+use serde_json::Value;
+use std::collections::HashMap;
+
+use super::MemoryDatabase;
+use crate::services::memory::{ConversationModel, ConversationTurnModel, NewTurnModel};
+
+const CONVERSATION_COLUMNS: &str = r#"
+    c.id, c.title, c.model, c.metadata, c.created_at, c.updated_at,
+    (SELECT COUNT(*) FROM conversation_turns t WHERE t.conversation_id = c.id) AS turn_count
+"#;
+
+impl MemoryDatabase {
+    #[tracing::instrument(skip_all)]
+    pub async fn create_conversation(
+        &self,
+        namespace_id: &str,
+        id: &str,
+        title: &str,
+        model: &str,
+        metadata: &HashMap<String, String>,
+        created_at: i64,
+    ) -> Result<(), sqlx::Error> {
+        let uuid = Uuid::parse_str(id).unwrap_or_default();
+        let namespace_uuid = Uuid::parse_str(namespace_id).unwrap_or_default();
+        let metadata_json = serde_json::to_value(metadata).unwrap();
+
+        sqlx::query(
+            r#"
+            INSERT INTO conversations (id, namespace_id, title, model, metadata, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $6)
+            "#
+        )
+        .bind(uuid)
+        .bind(namespace_uuid)
+        .bind(title)
+        .bind(model)
+        .bind(metadata_json)
+        .bind(created_at)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn get_conversation(&self, namespace_id: &str, id: &str) -> Result<Option<ConversationModel>, sqlx::Error> {
+        let Ok(uuid) = Uuid::parse_str(id) else { return Ok(None) };
+        let namespace_uuid = Uuid::parse_str(namespace_id).unwrap_or_default();
+        let row = sqlx::query(&format!(
+            "SELECT {} FROM conversations c WHERE c.id = $1 AND c.namespace_id = $2",
+            CONVERSATION_COLUMNS
+        ))
+            .bind(uuid)
+            .bind(namespace_uuid)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(row.map(map_conversation))
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn list_conversations(&self, namespace_id: &str, limit: i32) -> Result<Vec<ConversationModel>, sqlx::Error> {
+        let limit = if limit <= 0 { 50 } else { limit };
+        let namespace_uuid = Uuid::parse_str(namespace_id).unwrap_or_default();
+
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM conversations c WHERE c.namespace_id = $2 ORDER BY c.updated_at DESC, c.id LIMIT $1",
+            CONVERSATION_COLUMNS
+        ))
+        .bind(limit)
+        .bind(namespace_uuid)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(map_conversation).collect())
+    }
+
+    /// Fetch turns in conversation order. A positive `limit` keeps only the most recent N.
+    #[tracing::instrument(skip_all)]
+    pub async fn get_conversation_turns(
+        &self,
+        conversation_id: &str,
+        limit: i32,
+    ) -> Result<Vec<ConversationTurnModel>, sqlx::Error> {
+        let uuid = Uuid::parse_str(conversation_id).unwrap_or_default();
+        let limit = if limit <= 0 { None } else { Some(limit as i64) };
+
+        let rows = sqlx::query(
+            r#"
+            SELECT * FROM (
+                SELECT id, conversation_id, sequence, role, content, model, metadata, created_at
+                FROM conversation_turns
+                WHERE conversation_id = $1
+                ORDER BY sequence DESC
+                LIMIT $2
+            ) recent
+            ORDER BY sequence ASC
+            "#
+        )
+        .bind(uuid)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(map_turn).collect())
+    }
+
+    /// Append turns after the current last turn. The conversation row is locked for the
+    /// duration of the transaction so concurrent appends cannot interleave sequences.
+    /// Returns `None` if the conversation does not exist.
+    #[tracing::instrument(skip_all)]
+    pub async fn append_conversation_turns(
+        &self,
+        namespace_id: &str,
+        conversation_id: &str,
+        turns: &[NewTurnModel],
+        now: i64,
+    ) -> Result<Option<Vec<ConversationTurnModel>>, sqlx::Error> {
+        let Ok(uuid) = Uuid::parse_str(conversation_id) else { return Ok(None) };
+        let namespace_uuid = Uuid::parse_str(namespace_id).unwrap_or_default();
+        let mut tx = self.pool.begin().await?;
+
+        let locked = sqlx::query("SELECT id FROM conversations WHERE id = $1 AND namespace_id = $2 FOR UPDATE")
+            .bind(uuid)
+            .bind(namespace_uuid)
+            .fetch_optional(&mut *tx)
+            .await?;
+        if locked.is_none() {
+            return Ok(None);
+        }
+
+        let last: i32 = sqlx::query_scalar(
+            "SELECT COALESCE(MAX(sequence), 0) FROM conversation_turns WHERE conversation_id = $1"
+        )
+        .bind(uuid)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let mut stored = Vec::with_capacity(turns.len());
+        for (offset, turn) in turns.iter().enumerate() {
+            let id = Uuid::new_v4();
+            let sequence = last + offset as i32 + 1;
+            let metadata_json = serde_json::to_value(&turn.metadata).unwrap();
+
+            sqlx::query(
+                r#"
+                INSERT INTO conversation_turns (id, conversation_id, sequence, role, content, model, metadata, created_at)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+                "#
+            )
+            .bind(id)
+            .bind(uuid)
+            .bind(sequence)
+            .bind(&turn.role)
+            .bind(&turn.content)
+            .bind(&turn.model)
+            .bind(metadata_json)
+            .bind(now)
+            .execute(&mut *tx)
+            .await?;
+
+            stored.push(ConversationTurnModel {
+                id: id.to_string(),
+                conversation_id: conversation_id.to_string(),
+                sequence,
+                role: turn.role.clone(),
+                content: turn.content.clone(),
+                model: turn.model.clone(),
+                metadata: turn.metadata.clone(),
+                created_at: now,
+            });
+        }
+
+        sqlx::query("UPDATE conversations SET updated_at = $2 WHERE id = $1")
+            .bind(uuid)
+            .bind(now)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(Some(stored))
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn delete_conversation(&self, namespace_id: &str, id: &str) -> Result<bool, sqlx::Error> {
+        let uuid = Uuid::parse_str(id).unwrap_or_default();
+        let namespace_uuid = Uuid::parse_str(namespace_id).unwrap_or_default();
+        // Turns are removed by ON DELETE CASCADE
+        let result = sqlx::query("DELETE FROM conversations WHERE id = $1 AND namespace_id = $2")
+            .bind(uuid)
+            .bind(namespace_uuid)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+fn map_conversation(row: sqlx::postgres::PgRow) -> ConversationModel {
+    let id: Uuid = row.get("id");
+    let meta_val: Value = row.get("metadata");
+
+    ConversationModel {
+        id: id.to_string(),
+        title: row.get("title"),
+        model: row.get("model"),
+        metadata: serde_json::from_value(meta_val).unwrap_or_default(),
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+        turn_count: row.get::<i64, _>("turn_count") as i32,
+    }
+}
+
+fn map_turn(row: sqlx::postgres::PgRow) -> ConversationTurnModel {
+    let id: Uuid = row.get("id");
+    let conversation_id: Uuid = row.get("conversation_id");
+    let meta_val: Value = row.get("metadata");
+
+    ConversationTurnModel {
+        id: id.to_string(),
+        conversation_id: conversation_id.to_string(),
+        sequence: row.get("sequence"),
+        role: row.get("role"),
+        content: row.get("content"),
+        model: row.get("model"),
+        metadata: serde_json::from_value(meta_val).unwrap_or_default(),
+        created_at: row.get("created_at"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn turn(role: &str, content: &str) -> NewTurnModel {
+        NewTurnModel { role: role.into(), content: content.into(), model: String::new(), metadata: HashMap::new() }
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn turns_are_appended_in_sequence() {
+        let db = MemoryDatabase::for_tests().await;
+        let ns = db.get_or_create_namespace(&Uuid::new_v4().to_string(), "default", 100).await.unwrap();
+        let id = Uuid::new_v4().to_string();
+        let metadata = HashMap::from([("encrypted".to_string(), "true".to_string())]);
+        db.create_conversation(&ns.id, &id, "Trip", "gpt", &metadata, 100).await.unwrap();
+
+        let first = db.append_conversation_turns(&ns.id, &id, &[turn("user", "hi"), turn("assistant", "hello")], 110).await.unwrap().unwrap();
+        let second = db.append_conversation_turns(&ns.id, &id, &[turn("user", "bye")], 120).await.unwrap().unwrap();
+        assert_eq!(first.iter().map(|t| t.sequence).collect::<Vec<_>>(), [1, 2]);
+        assert_eq!(second[0].sequence, 3);
+
+        let recent = db.get_conversation_turns(&id, 2).await.unwrap();
+        assert_eq!(recent.iter().map(|t| t.content.as_str()).collect::<Vec<_>>(), ["hello", "bye"]);
+        assert_eq!(db.get_conversation_turns(&id, 0).await.unwrap().len(), 3);
+
+        let conversation = db.get_conversation(&ns.id, &id).await.unwrap().unwrap();
+        assert_eq!((conversation.turn_count, conversation.updated_at), (3, 120));
+        assert_eq!(conversation.metadata, metadata);
+        assert_eq!(db.list_conversations(&ns.id, 0).await.unwrap()[0].id, id);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn conversations_stay_in_their_namespace() {
+        let db = MemoryDatabase::for_tests().await;
+        let owner = Uuid::new_v4().to_string();
+        let ns = db.get_or_create_namespace(&owner, "default", 100).await.unwrap();
+        let other = db.get_or_create_namespace(&owner, "other", 100).await.unwrap();
+        let id = Uuid::new_v4().to_string();
+        db.create_conversation(&ns.id, &id, "", "", &HashMap::new(), 100).await.unwrap();
+
+        assert!(db.get_conversation(&other.id, &id).await.unwrap().is_none());
+        assert!(db.append_conversation_turns(&other.id, &id, &[turn("user", "hi")], 110).await.unwrap().is_none());
+        assert!(db.append_conversation_turns(&ns.id, "not-a-uuid", &[turn("user", "hi")], 110).await.unwrap().is_none());
+        assert!(!db.delete_conversation(&other.id, &id).await.unwrap());
+
+        db.append_conversation_turns(&ns.id, &id, &[turn("user", "hi")], 110).await.unwrap().unwrap();
+        assert!(db.delete_conversation(&ns.id, &id).await.unwrap());
+        assert!(db.get_conversation_turns(&id, 0).await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,170 @@
+use sqlx::Row;
+use uuid::Uuid;
+
+use super::namespaces::{map_namespace, NAMESPACE_COLUMNS};
+use super::MemoryDatabase;
+use crate::services::acl::GrantModel;
+use crate::services::namespace::NamespaceModel;
+
+const GRANT_COLUMNS: &str = "id, namespace_id, memory_id, grantee, permission, granted_by, created_at";
+
+impl MemoryDatabase {
+    /// Create a grant, or replace the permission of an existing grant to the same grantee
+    #[tracing::instrument(skip_all)]
+    pub async fn upsert_grant(&self, grant: &GrantModel) -> Result<GrantModel, sqlx::Error> {
+        let uuid = Uuid::parse_str(&grant.id).unwrap_or_default();
+        let namespace_uuid = Uuid::parse_str(&grant.namespace_id).unwrap_or_default();
+        let memory_uuid = grant.memory_id.as_deref().map(|id| Uuid::parse_str(id).unwrap_or_default());
+
+        let row = sqlx::query(&format!(
+            r#"
+            INSERT INTO grants ({})
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            ON CONFLICT (namespace_id, COALESCE(memory_id, '00000000-0000-0000-0000-000000000000'::uuid), grantee)
+            DO UPDATE SET permission = EXCLUDED.permission, granted_by = EXCLUDED.granted_by
+            RETURNING {}
+            "#,
+            GRANT_COLUMNS, GRANT_COLUMNS
+        ))
+        .bind(uuid)
+        .bind(namespace_uuid)
+        .bind(memory_uuid)
+        .bind(&grant.grantee)
+        .bind(grant.permission)
+        .bind(&grant.granted_by)
+        .bind(grant.created_at)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(map_grant(row))
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn delete_grant(
+        &self,
+        namespace_id: &str,
+        memory_id: Option<&str>,
+        grantee: &str,
+    ) -> Result<bool, sqlx::Error> {
+        let namespace_uuid = Uuid::parse_str(namespace_id).unwrap_or_default();
+        let memory_uuid = memory_id.map(|id| Uuid::parse_str(id).unwrap_or_default());
+
+        let result = sqlx::query(
+            "DELETE FROM grants WHERE namespace_id = $1 AND memory_id IS NOT DISTINCT FROM $2 AND grantee = $3"
+        )
+        .bind(namespace_uuid)
+        .bind(memory_uuid)
+        .bind(grantee)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Grants on a namespace, or on one memory when `memory_id` is set
+    #[tracing::instrument(skip_all)]
+    pub async fn list_grants(&self, namespace_id: &str, memory_id: Option<&str>) -> Result<Vec<GrantModel>, sqlx::Error> {
+        let namespace_uuid = Uuid::parse_str(namespace_id).unwrap_or_default();
+        let memory_uuid = memory_id.map(|id| Uuid::parse_str(id).unwrap_or_default());
+
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM grants WHERE namespace_id = $1 AND memory_id IS NOT DISTINCT FROM $2 ORDER BY created_at, grantee",
+            GRANT_COLUMNS
+        ))
+        .bind(namespace_uuid)
+        .bind(memory_uuid)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(map_grant).collect())
+    }
+
+    /// Highest permission any of `grantees` holds on a namespace (or one of its memories); 0 if none
+    #[tracing::instrument(skip_all)]
+    pub async fn granted_permission(
+        &self,
+        namespace_id: &str,
+        memory_id: Option<&str>,
+        grantees: &[String],
+    ) -> Result<i32, sqlx::Error> {
+        let namespace_uuid = Uuid::parse_str(namespace_id).unwrap_or_default();
+        let memory_uuid = memory_id.map(|id| Uuid::parse_str(id).unwrap_or_default());
+
+        sqlx::query_scalar(
+            r#"
+            SELECT COALESCE(MAX(permission), 0) FROM grants
+            WHERE namespace_id = $1 AND memory_id IS NOT DISTINCT FROM $2 AND grantee = ANY($3)
+            "#
+        )
+        .bind(namespace_uuid)
+        .bind(memory_uuid)
+        .bind(grantees)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    /// Ids of memories in a namespace that were shared individually with any of `grantees`
+    #[tracing::instrument(skip_all)]
+    pub async fn granted_memory_ids(&self, namespace_id: &str, grantees: &[String]) -> Result<Vec<String>, sqlx::Error> {
+        let namespace_uuid = Uuid::parse_str(namespace_id).unwrap_or_default();
+
+        let ids: Vec<Uuid> = sqlx::query_scalar(
+            "SELECT DISTINCT memory_id FROM grants WHERE namespace_id = $1 AND memory_id IS NOT NULL AND grantee = ANY($2)"
+        )
+        .bind(namespace_uuid)
+        .bind(grantees)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(ids.into_iter().map(|id| id.to_string()).collect())
+    }
+
+    /// Shareable namespaces of other owners in which any of `grantees` holds a grant,
+    /// paired with the namespace-wide permission (0 when only single memories are shared)
+    #[tracing::instrument(skip_all)]
+    pub async fn list_shared_namespaces(
+        &self,
+        owner_id: &str,
+        grantees: &[String],
+    ) -> Result<Vec<(NamespaceModel, i32)>, sqlx::Error> {
+        let rows = sqlx::query(&format!(
+            r#"
+            SELECT {}, a.permission
+            FROM namespaces n
+            JOIN (
+                SELECT namespace_id, MAX(CASE WHEN memory_id IS NULL THEN permission ELSE 0 END) AS permission
+                FROM grants
+                WHERE grantee = ANY($2)
+                GROUP BY namespace_id
+            ) a ON a.namespace_id = n.id
+            WHERE n.owner_id <> $1 AND n.sharing = 1 -- NAMESPACE_SHARING_SHAREABLE
+            ORDER BY n.name, n.id
+            "#,
+            NAMESPACE_COLUMNS
+        ))
+        .bind(owner_id)
+        .bind(grantees)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(|row| {
+            let permission: i32 = row.get("permission");
+            (map_namespace(row), permission)
+        }).collect())
+    }
+}
+
+fn map_grant(row: sqlx::postgres::PgRow) -> GrantModel {
+    let id: Uuid = row.get("id");
+    let namespace_id: Uuid = row.get("namespace_id");
+    let memory_id: Option<Uuid> = row.get("memory_id");
+
+    GrantModel {
+        id: id.to_string(),
+        namespace_id: namespace_id.to_string(),
+        memory_id: memory_id.map(|id| id.to_string()),
+        grantee: row.get("grantee"),
+        permission: row.get("permission"),
+        granted_by: row.get("granted_by"),
+        created_at: row.get("created_at"),
+    }
+}
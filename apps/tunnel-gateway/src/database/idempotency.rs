@@ -0,0 +1,144 @@
+use sqlx::{Postgres, Row, Transaction};
+use uuid::Uuid;
+
+use super::{IdempotencyKey, MemoryDatabase, Replay};
+
+/// Take `key` for `memory_id`, or return the earlier use if it is still within its window
+pub(super) async fn claim(
+    tx: &mut Transaction<'_, Postgres>,
+    namespace_uuid: Uuid,
+    key: IdempotencyKey<'_>,
+    memory_id: Uuid,
+    now: i64,
+) -> Result<Option<Replay>, sqlx::Error> {
+    sqlx::query("DELETE FROM idempotency_keys WHERE namespace_id = $1 AND key = $2 AND created_at < $3")
+        .bind(namespace_uuid)
+        .bind(key.key)
+        .bind(key.not_before)
+        .execute(&mut **tx)
+        .await?;
+
+    let claimed = sqlx::query(
+        r#"
+        INSERT INTO idempotency_keys (namespace_id, key, fingerprint, memory_id, created_at)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT (namespace_id, key) DO NOTHING
+        "#
+    )
+    .bind(namespace_uuid)
+    .bind(key.key)
+    .bind(key.fingerprint)
+    .bind(memory_id)
+    .bind(now)
+    .execute(&mut **tx)
+    .await?;
+    if claimed.rows_affected() > 0 {
+        return Ok(None);
+    }
+
+    let row = sqlx::query("SELECT memory_id, fingerprint FROM idempotency_keys WHERE namespace_id = $1 AND key = $2")
+        .bind(namespace_uuid)
+        .bind(key.key)
+        .fetch_one(&mut **tx)
+        .await?;
+    Ok(Some(map_replay(row)))
+}
+
+fn map_replay(row: sqlx::postgres::PgRow) -> Replay {
+    Replay {
+        memory_id: row.get::<Uuid, _>("memory_id").to_string(),
+        fingerprint: row.get("fingerprint"),
+    }
+}
+
+impl MemoryDatabase {
+    /// The earlier use of an idempotency key within its window, if any
+    #[tracing::instrument(skip_all)]
+    pub async fn find_idempotency_key(&self, namespace_id: &str, key: &str, not_before: i64) -> Result<Option<Replay>, sqlx::Error> {
+        let namespace_uuid = Uuid::parse_str(namespace_id).unwrap_or_default();
+        let row = sqlx::query(
+            "SELECT memory_id, fingerprint FROM idempotency_keys WHERE namespace_id = $1 AND key = $2 AND created_at >= $3"
+        )
+        .bind(namespace_uuid)
+        .bind(key)
+        .bind(not_before)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row.map(map_replay))
+    }
+
+    /// Forget idempotency keys first used before `before`
+    #[tracing::instrument(skip_all)]
+    pub async fn purge_idempotency_keys(&self, before: i64) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM idempotency_keys WHERE created_at < $1")
+            .bind(before)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::memory::MemoryModel;
+    use std::collections::HashMap;
+
+    fn memory(created_at: i64) -> MemoryModel {
+        MemoryModel {
+            id: Uuid::new_v4().to_string(),
+            content: "note".into(),
+            metadata: HashMap::new(),
+            embedding: vec![0.1; 384],
+            tags: Vec::new(),
+            created_at,
+            updated_at: created_at,
+        }
+    }
+
+    fn key<'a>(key: &'a str, fingerprint: &'a str, not_before: i64) -> Option<IdempotencyKey<'a>> {
+        Some(IdempotencyKey { key, fingerprint, not_before })
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn retries_replay_the_first_memory_within_the_window() {
+        let db = MemoryDatabase::for_tests().await;
+        let ns = db.get_or_create_namespace(&Uuid::new_v4().to_string(), "default", 100).await.unwrap();
+        let first = memory(1000);
+        assert_eq!(db.store_memory(&ns.id, &first, None, key("k", "f1", 0)).await.unwrap(), None);
+
+        let replay = Replay { memory_id: first.id.clone(), fingerprint: "f1".into() };
+        assert_eq!(db.store_memory(&ns.id, &memory(1010), None, key("k", "f2", 0)).await.unwrap(), Some(replay.clone()));
+        assert_eq!(db.find_idempotency_key(&ns.id, "k", 0).await.unwrap(), Some(replay));
+
+        // Past the window the key is taken afresh
+        let later = memory(5000);
+        assert_eq!(db.find_idempotency_key(&ns.id, "k", 2000).await.unwrap(), None);
+        assert_eq!(db.store_memory(&ns.id, &later, None, key("k", "f3", 2000)).await.unwrap(), None);
+        assert_eq!(db.find_idempotency_key(&ns.id, "k", 2000).await.unwrap().unwrap().memory_id, later.id);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn keys_go_with_their_memory() {
+        let db = MemoryDatabase::for_tests().await;
+        let ns = db.get_or_create_namespace(&Uuid::new_v4().to_string(), "default", 100).await.unwrap();
+        // Older than any key the other tests use, since the sweep covers every namespace
+        let stored = memory(10);
+        db.store_memory(&ns.id, &stored, None, key("purged", "f", 0)).await.unwrap();
+        db.store_memory(&ns.id, &memory(10), None, key("swept", "f", 0)).await.unwrap();
+
+        assert!(db.delete_memory(&ns.id, &stored.id, 1100).await.unwrap());
+        assert!(db.purge_memory(&ns.id, &stored.id).await.unwrap());
+        assert_eq!(db.find_idempotency_key(&ns.id, "purged", 0).await.unwrap(), None);
+
+        assert!(db.purge_idempotency_keys(11).await.unwrap() >= 1);
+        assert_eq!(db.find_idempotency_key(&ns.id, "swept", 0).await.unwrap(), None);
+
+        // A key can only point at a memory that exists once the transaction commits
+        let mut tx = db.pool.begin().await.unwrap();
+        claim(&mut tx, Uuid::parse_str(&ns.id).unwrap(), key("dangling", "f", 0).unwrap(), Uuid::new_v4(), 10).await.unwrap();
+        assert!(tx.commit().await.is_err());
+    }
+}
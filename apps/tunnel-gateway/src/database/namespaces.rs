@@ -0,0 +1,289 @@
+use sqlx::Row;
+use uuid::Uuid;
+
+use super::MemoryDatabase;
+use crate::services::namespace::{NamespaceModel, NamespaceSettingsModel};
+
+pub(super) const NAMESPACE_COLUMNS: &str = r#"
+    n.id, n.owner_id, n.name, n.default_retention_days, n.embedding_model, n.sharing, n.secret_policy,
+    n.created_at, n.updated_at,
+    (SELECT COUNT(*) FROM memories m WHERE m.namespace_id = n.id) AS memory_count
+"#;
+
+impl MemoryDatabase {
+    #[tracing::instrument(skip_all)]
+    pub async fn create_namespace(
+        &self,
+        id: &str,
+        owner_id: &str,
+        name: &str,
+        settings: &NamespaceSettingsModel,
+        created_at: i64,
+    ) -> Result<(), sqlx::Error> {
+        let uuid = Uuid::parse_str(id).unwrap_or_default();
+
+        sqlx::query(
+            r#"
+            INSERT INTO namespaces (id, owner_id, name, default_retention_days, embedding_model, sharing, secret_policy, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $8)
+            "#
+        )
+        .bind(uuid)
+        .bind(owner_id)
+        .bind(name)
+        .bind(settings.default_retention_days)
+        .bind(&settings.embedding_model)
+        .bind(settings.sharing)
+        .bind(settings.secret_policy)
+        .bind(created_at)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Look up a namespace by its owner and name
+    #[tracing::instrument(skip_all)]
+    pub async fn get_namespace(&self, owner_id: &str, name: &str) -> Result<Option<NamespaceModel>, sqlx::Error> {
+        let row = sqlx::query(&format!(
+            "SELECT {} FROM namespaces n WHERE n.owner_id = $1 AND n.name = $2",
+            NAMESPACE_COLUMNS
+        ))
+        .bind(owner_id)
+        .bind(name)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.map(map_namespace))
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn get_namespace_by_id(&self, id: &str) -> Result<Option<NamespaceModel>, sqlx::Error> {
+        let Ok(uuid) = Uuid::parse_str(id) else { return Ok(None) };
+        let row = sqlx::query(&format!("SELECT {} FROM namespaces n WHERE n.id = $1", NAMESPACE_COLUMNS))
+            .bind(uuid)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(row.map(map_namespace))
+    }
+
+    /// Return the named namespace, creating it with default settings if it does not exist yet
+    #[tracing::instrument(skip_all)]
+    pub async fn get_or_create_namespace(
+        &self,
+        owner_id: &str,
+        name: &str,
+        now: i64,
+    ) -> Result<NamespaceModel, sqlx::Error> {
+        sqlx::query(
+            r#"
+            INSERT INTO namespaces (id, owner_id, name, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $4)
+            ON CONFLICT (owner_id, name) DO NOTHING
+            "#
+        )
+        .bind(Uuid::new_v4())
+        .bind(owner_id)
+        .bind(name)
+        .bind(now)
+        .execute(&self.pool)
+        .await?;
+
+        self.get_namespace(owner_id, name)
+            .await?
+            .ok_or(sqlx::Error::RowNotFound)
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn list_namespaces(&self, owner_id: &str) -> Result<Vec<NamespaceModel>, sqlx::Error> {
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM namespaces n WHERE n.owner_id = $1 ORDER BY n.name",
+            NAMESPACE_COLUMNS
+        ))
+        .bind(owner_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(map_namespace).collect())
+    }
+
+    /// Rename or reconfigure a namespace. A new retention period applies to the memories
+    /// already stored too: their expiry is recomputed from creation time in the same transaction.
+    #[tracing::instrument(skip_all)]
+    pub async fn update_namespace(
+        &self,
+        id: &str,
+        name: &str,
+        settings: &NamespaceSettingsModel,
+        updated_at: i64,
+    ) -> Result<bool, sqlx::Error> {
+        let uuid = Uuid::parse_str(id).unwrap_or_default();
+        let mut tx = self.pool.begin().await?;
+        let result = sqlx::query(
+            r#"
+            UPDATE namespaces
+            SET name = $2, default_retention_days = $3, embedding_model = $4, sharing = $5, secret_policy = $6, updated_at = $7
+            WHERE id = $1
+            "#
+        )
+        .bind(uuid)
+        .bind(name)
+        .bind(settings.default_retention_days)
+        .bind(&settings.embedding_model)
+        .bind(settings.sharing)
+        .bind(settings.secret_policy)
+        .bind(updated_at)
+        .execute(&mut *tx)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Ok(false);
+        }
+
+        // Same rule as `NamespaceModel::expires_at`; 0 keeps memories forever
+        sqlx::query(
+            "UPDATE memories SET expires_at = CASE WHEN $2 > 0 THEN created_at + $2::BIGINT * 86400 END WHERE namespace_id = $1"
+        )
+        .bind(uuid)
+        .bind(settings.default_retention_days)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    /// Number of conversations stored in a namespace (memories are counted in `NamespaceModel`)
+    #[tracing::instrument(skip_all)]
+    pub async fn count_namespace_conversations(&self, id: &str) -> Result<i64, sqlx::Error> {
+        let uuid = Uuid::parse_str(id).unwrap_or_default();
+        sqlx::query_scalar("SELECT COUNT(*) FROM conversations WHERE namespace_id = $1")
+            .bind(uuid)
+            .fetch_one(&self.pool)
+            .await
+    }
+
+    /// Delete a namespace; its memories and conversations go with it (ON DELETE CASCADE)
+    #[tracing::instrument(skip_all)]
+    pub async fn delete_namespace(&self, id: &str) -> Result<bool, sqlx::Error> {
+        let uuid = Uuid::parse_str(id).unwrap_or_default();
+        let result = sqlx::query("DELETE FROM namespaces WHERE id = $1")
+            .bind(uuid)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Number of memories and total content bytes across all of an owner's namespaces.
+    /// Conversation turns count towards the bytes.
+    #[tracing::instrument(skip_all)]
+    pub async fn owner_usage(&self, owner_id: &str) -> Result<(i64, i64), sqlx::Error> {
+        let row = sqlx::query(
+            r#"
+            SELECT
+                (SELECT COUNT(*) FROM memories m JOIN namespaces n ON n.id = m.namespace_id WHERE n.owner_id = $1) AS memories,
+                (
+                    (SELECT COALESCE(SUM(octet_length(m.content)), 0)
+                     FROM memories m JOIN namespaces n ON n.id = m.namespace_id WHERE n.owner_id = $1)
+                    + (SELECT COALESCE(SUM(octet_length(t.content)), 0)
+                       FROM conversation_turns t
+                       JOIN conversations c ON c.id = t.conversation_id
+                       JOIN namespaces n ON n.id = c.namespace_id
+                       WHERE n.owner_id = $1)
+                )::BIGINT AS bytes
+            "#
+        )
+        .bind(owner_id)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok((row.get("memories"), row.get("bytes")))
+    }
+
+    /// Move rows written before namespaces existed into the given namespace
+    #[tracing::instrument(skip_all)]
+    pub async fn adopt_unscoped_rows(&self, namespace_id: &str) -> Result<u64, sqlx::Error> {
+        let uuid = Uuid::parse_str(namespace_id).unwrap_or_default();
+        let memories = sqlx::query("UPDATE memories SET namespace_id = $1 WHERE namespace_id IS NULL")
+            .bind(uuid)
+            .execute(&self.pool)
+            .await?;
+        let conversations = sqlx::query("UPDATE conversations SET namespace_id = $1 WHERE namespace_id IS NULL")
+            .bind(uuid)
+            .execute(&self.pool)
+            .await?;
+        Ok(memories.rows_affected() + conversations.rows_affected())
+    }
+}
+
+pub(super) fn map_namespace(row: sqlx::postgres::PgRow) -> NamespaceModel {
+    let id: Uuid = row.get("id");
+
+    NamespaceModel {
+        id: id.to_string(),
+        owner_id: row.get("owner_id"),
+        name: row.get("name"),
+        settings: NamespaceSettingsModel {
+            default_retention_days: row.get("default_retention_days"),
+            embedding_model: row.get("embedding_model"),
+            sharing: row.get("sharing"),
+            secret_policy: row.get("secret_policy"),
+        },
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+        memory_count: row.get("memory_count"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::memory::NewTurnModel;
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn retention_changes_apply_to_stored_memories() {
+        let db = MemoryDatabase::for_tests().await;
+        let ns = db.get_or_create_namespace(&Uuid::new_v4().to_string(), "default", 100).await.unwrap();
+        let memory = Uuid::new_v4();
+        sqlx::query("INSERT INTO memories (id, namespace_id, content, created_at, updated_at) VALUES ($1, $2, 'note', 1000, 1000)")
+            .bind(memory)
+            .bind(Uuid::parse_str(&ns.id).unwrap())
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        let expires_at = || sqlx::query_scalar::<_, Option<i64>>("SELECT expires_at FROM memories WHERE id = $1")
+            .bind(memory)
+            .fetch_one(&db.pool);
+
+        let mut settings = ns.settings.clone();
+        settings.default_retention_days = 2;
+        assert!(db.update_namespace(&ns.id, &ns.name, &settings, 200).await.unwrap());
+        assert_eq!(expires_at().await.unwrap(), Some(1000 + 2 * 86_400));
+
+        settings.default_retention_days = 0;
+        assert!(db.update_namespace(&ns.id, &ns.name, &settings, 300).await.unwrap());
+        assert_eq!(expires_at().await.unwrap(), None);
+        assert!(!db.update_namespace(&Uuid::new_v4().to_string(), "gone", &settings, 300).await.unwrap());
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn usage_counts_conversation_turns() {
+        let db = MemoryDatabase::for_tests().await;
+        let owner = Uuid::new_v4().to_string();
+        let ns = db.get_or_create_namespace(&owner, "default", 100).await.unwrap();
+        sqlx::query("INSERT INTO memories (id, namespace_id, content, created_at, updated_at) VALUES ($1, $2, 'note', 100, 100)")
+            .bind(Uuid::new_v4())
+            .bind(Uuid::parse_str(&ns.id).unwrap())
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        let conversation = Uuid::new_v4().to_string();
+        db.create_conversation(&ns.id, &conversation, "", "", &Default::default(), 100).await.unwrap();
+        let turn = NewTurnModel { role: "user".into(), content: "héllo".into(), model: String::new(), metadata: Default::default() };
+        db.append_conversation_turns(&ns.id, &conversation, &[turn], 110).await.unwrap().unwrap();
+
+        assert_eq!(db.owner_usage(&owner).await.unwrap(), (1, 4 + 6));
+        assert_eq!(db.owner_usage(&Uuid::new_v4().to_string()).await.unwrap(), (0, 0));
+    }
+}
@@ -0,0 +1,182 @@
+use sqlx::Row;
+use uuid::Uuid;
+
+use super::{granted_only, MemoryDatabase, LIVE};
+use crate::services::tags::{MemoryStatsModel, StatsBucketModel};
+
+/// Rebuild a tags array from `tags_expr`, keeping tags that match `keep` once, at their first position
+fn dedupe_tags(tags_expr: &str, keep: &str) -> String {
+    format!(
+        r#"ARRAY(
+            SELECT tag FROM (
+                SELECT tag, MIN(position) AS first
+                FROM unnest({}) WITH ORDINALITY AS t(tag, position)
+                WHERE {}
+                GROUP BY tag
+            ) kept ORDER BY first
+        )"#,
+        tags_expr, keep
+    )
+}
+
+impl MemoryDatabase {
+    /// Counts and sizes of the live memories in a namespace the caller can read
+    #[tracing::instrument(skip_all)]
+    pub async fn memory_stats(&self, namespace_id: &str, granted_to: Option<&[String]>) -> Result<MemoryStatsModel, sqlx::Error> {
+        let namespace_uuid = Uuid::parse_str(namespace_id).unwrap_or_default();
+        let visible = format!("namespace_id = $1 AND {}", granted_only(2));
+
+        let totals = sqlx::query(&format!(
+            r#"
+            SELECT COUNT(*) AS total,
+                   COALESCE(SUM(octet_length(content)), 0)::BIGINT AS bytes,
+                   COUNT(embedding) AS embedded,
+                   COUNT(*) FILTER (WHERE COALESCE(cardinality(tags), 0) = 0) AS untagged,
+                   MIN(created_at) AS oldest,
+                   MAX(created_at) AS newest
+            FROM memories WHERE {} AND {}
+            "#,
+            visible, LIVE
+        ))
+        .bind(namespace_uuid)
+        .bind(granted_to)
+        .fetch_one(&self.pool)
+        .await?;
+
+        let trashed: i64 = sqlx::query_scalar(&format!("SELECT COUNT(*) FROM memories WHERE {} AND deleted_at IS NOT NULL", visible))
+            .bind(namespace_uuid)
+            .bind(granted_to)
+            .fetch_one(&self.pool)
+            .await?;
+
+        let buckets = |key: &str, from: &str, order: &str| format!(
+            r#"
+            SELECT {} AS key, COUNT(*) AS count, COALESCE(SUM(octet_length(content)), 0)::BIGINT AS bytes
+            FROM {} WHERE {} AND {}
+            GROUP BY 1 ORDER BY {}
+            "#,
+            key, from, visible, LIVE, order
+        );
+        let by_tag = self.stats_buckets(&buckets("tag", "memories, unnest(tags) AS tag", "count DESC, key"), namespace_uuid, granted_to).await?;
+        let by_type = self.stats_buckets(&buckets("COALESCE(metadata->>'type', '')", "memories", "count DESC, key"), namespace_uuid, granted_to).await?;
+        let by_month = self.stats_buckets(
+            &buckets("to_char(to_timestamp(created_at) AT TIME ZONE 'UTC', 'YYYY-MM')", "memories", "key"),
+            namespace_uuid,
+            granted_to,
+        ).await?;
+
+        Ok(MemoryStatsModel {
+            total_count: totals.get("total"),
+            total_bytes: totals.get("bytes"),
+            embedded_count: totals.get("embedded"),
+            untagged_count: totals.get("untagged"),
+            trashed_count: trashed,
+            oldest: totals.get("oldest"),
+            newest: totals.get("newest"),
+            by_tag,
+            by_type,
+            by_month,
+        })
+    }
+
+    async fn stats_buckets(&self, query: &str, namespace_uuid: Uuid, granted_to: Option<&[String]>) -> Result<Vec<StatsBucketModel>, sqlx::Error> {
+        let rows = sqlx::query(query)
+            .bind(namespace_uuid)
+            .bind(granted_to)
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(rows.into_iter().map(|row| StatsBucketModel {
+            key: row.get("key"),
+            count: row.get("count"),
+            bytes: row.get("bytes"),
+        }).collect())
+    }
+
+    /// Tags on live memories the caller can read, with how many carry each, most used first
+    #[tracing::instrument(skip_all)]
+    pub async fn list_tags(
+        &self,
+        namespace_id: &str,
+        prefix: &str,
+        limit: i32,
+        granted_to: Option<&[String]>,
+    ) -> Result<Vec<(String, i64)>, sqlx::Error> {
+        let namespace_uuid = Uuid::parse_str(namespace_id).unwrap_or_default();
+        let pattern = format!("{}%", prefix.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_"));
+        let rows = sqlx::query(&format!(
+            r#"
+            SELECT tag, COUNT(*) AS count
+            FROM memories, unnest(tags) AS tag
+            WHERE namespace_id = $1 AND {} AND {} AND tag LIKE $3
+            GROUP BY tag ORDER BY count DESC, tag
+            LIMIT $4
+            "#,
+            LIVE,
+            granted_only(2)
+        ))
+        .bind(namespace_uuid)
+        .bind(granted_to)
+        .bind(pattern)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows.into_iter().map(|row| (row.get("tag"), row.get("count"))).collect())
+    }
+
+    /// Whether any memory in the namespace, trashed ones included, carries the tag
+    #[tracing::instrument(skip_all)]
+    pub async fn tag_exists(&self, namespace_id: &str, tag: &str) -> Result<bool, sqlx::Error> {
+        let namespace_uuid = Uuid::parse_str(namespace_id).unwrap_or_default();
+        sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM memories WHERE namespace_id = $1 AND $2 = ANY(tags))")
+            .bind(namespace_uuid)
+            .bind(tag)
+            .fetch_one(&self.pool)
+            .await
+    }
+
+    /// Replace each of `tags` with `into` on every memory of the namespace, trashed ones
+    /// included so a restore does not bring an old name back. Returns the memories changed.
+    #[tracing::instrument(skip_all)]
+    pub async fn replace_tags(&self, namespace_id: &str, tags: &[String], into: &str, now: i64) -> Result<u64, sqlx::Error> {
+        let namespace_uuid = Uuid::parse_str(namespace_id).unwrap_or_default();
+        let renamed = "ARRAY(SELECT CASE WHEN tag = ANY($2) THEN $3 ELSE tag END FROM unnest(tags) AS tag)";
+        let result = sqlx::query(&format!(
+            "UPDATE memories SET tags = {}, updated_at = $4 WHERE namespace_id = $1 AND tags && $2",
+            dedupe_tags(renamed, "TRUE")
+        ))
+        .bind(namespace_uuid)
+        .bind(tags)
+        .bind(into)
+        .bind(now)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Add and remove tags on live memories of the namespace. Returns the memories changed.
+    #[tracing::instrument(skip_all)]
+    pub async fn retag_memories(
+        &self,
+        namespace_id: &str,
+        ids: &[String],
+        add: &[String],
+        remove: &[String],
+        now: i64,
+    ) -> Result<u64, sqlx::Error> {
+        let namespace_uuid = Uuid::parse_str(namespace_id).unwrap_or_default();
+        let uuids: Vec<Uuid> = ids.iter().filter_map(|id| Uuid::parse_str(id).ok()).collect();
+        let result = sqlx::query(&format!(
+            "UPDATE memories SET tags = {}, updated_at = $5 WHERE namespace_id = $1 AND id = ANY($2) AND {}",
+            dedupe_tags("COALESCE(tags, '{}') || $3::text[]", "NOT tag = ANY($4)"),
+            LIVE
+        ))
+        .bind(namespace_uuid)
+        .bind(uuids)
+        .bind(add)
+        .bind(remove)
+        .bind(now)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected())
+    }
+}
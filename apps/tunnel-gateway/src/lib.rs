@@ -1 +0,0 @@
-pub mod ipc_client;
@@ -0,0 +1,7 @@
+pub mod quota;
+pub mod rate;
+pub mod request;
+
+pub use quota::StorageQuota;
+pub use rate::{RateLimitConfig, RateLimitLayer};
+pub use request::RequestLimits;
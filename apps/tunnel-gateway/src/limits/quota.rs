@@ -0,0 +1,56 @@
+use identra_core::config::QuotaConfig;
+use identra_core::error::{Error, ErrorCode};
+use tonic::Status;
+
+/// Per-owner storage limits, checked before a memory or conversation turns are stored.
+/// Memories in the trash still count until they are purged.
+#[derive(Debug, Clone, Copy)]
+pub struct StorageQuota {
+    /// Maximum number of memories across all of an owner's namespaces; 0 disables the limit
+    pub max_memories: i64,
+    /// Maximum total content size in bytes, conversation turns included; 0 disables the limit
+    pub max_bytes: i64,
+}
+
+impl From<&QuotaConfig> for StorageQuota {
+    fn from(config: &QuotaConfig) -> Self {
+        // Configured limits come from TOML integers, so they always fit in an i64
+        Self { max_memories: config.max_memories as i64, max_bytes: config.max_bytes as i64 }
+    }
+}
+
+impl StorageQuota {
+    /// Check whether an owner currently holding `memories` / `bytes` can add `new_bytes` more
+    pub fn check(&self, memories: i64, bytes: i64, new_bytes: i64) -> Result<(), Status> {
+        if self.max_memories > 0 && memories >= self.max_memories {
+            return Err(Error::new(ErrorCode::QuotaExceeded, format!(
+                "Memory quota exceeded ({} of {} memories)", memories, self.max_memories
+            )).into());
+        }
+        if self.max_bytes > 0 && bytes + new_bytes > self.max_bytes {
+            return Err(Error::new(ErrorCode::QuotaExceeded, format!(
+                "Storage quota exceeded ({} + {} of {} bytes)", bytes, new_bytes, self.max_bytes
+            )).into());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn enforces_count_and_bytes() {
+        let quota = StorageQuota { max_memories: 2, max_bytes: 100 };
+        assert!(quota.check(1, 50, 50).is_ok());
+        assert_eq!(quota.check(2, 0, 1).unwrap_err().code(), tonic::Code::ResourceExhausted);
+        assert_eq!(quota.check(0, 60, 41).unwrap_err().code(), tonic::Code::ResourceExhausted);
+    }
+
+    #[test]
+    fn zero_disables_limits() {
+        let quota = StorageQuota { max_memories: 0, max_bytes: 0 };
+        assert!(quota.check(i64::MAX - 1, i64::MAX / 2, 10).is_ok());
+    }
+}
@@ -0,0 +1,264 @@
+use crate::auth::middleware::AuthClaims;
+use crate::rest;
+use crate::tls;
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tonic::body::BoxBody;
+use identra_core::error::{Error, ErrorCode};
+use tower::{Layer, Service};
+
+// Buckets are pruned once this many keys are tracked
+const MAX_TRACKED_BUCKETS: usize = 10_000;
+
+/// Token bucket parameters: refills `per_minute` tokens a minute, holds at most `burst`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rate {
+    pub per_minute: u32,
+    pub burst: u32,
+}
+
+impl Rate {
+    fn refill_per_sec(&self) -> f64 {
+        self.per_minute as f64 / 60.0
+    }
+}
+
+/// Rate limits per RPC method name, with an optional `*` fallback
+#[derive(Debug, Clone, Default)]
+pub struct RateLimitConfig {
+    default: Option<Rate>,
+    per_method: HashMap<String, Rate>,
+}
+
+impl RateLimitConfig {
+    /// Parse `Method=per_minute[/burst]` entries separated by commas, e.g.
+    /// `*=600,StoreMemory=60/10`. `*` applies to methods without their own entry;
+    /// the burst defaults to the per-minute rate.
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut config = Self::default();
+        for entry in spec.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let (method, rate) = entry.split_once('=')
+                .ok_or_else(|| format!("Invalid rate limit '{}', expected Method=per_minute[/burst]", entry))?;
+            let (per_minute, burst) = match rate.split_once('/') {
+                Some((per_minute, burst)) => (per_minute, Some(burst)),
+                None => (rate, None),
+            };
+            let per_minute: u32 = per_minute.trim().parse()
+                .map_err(|_| format!("Invalid rate in '{}'", entry))?;
+            let burst: u32 = match burst {
+                Some(b) => b.trim().parse().map_err(|_| format!("Invalid burst in '{}'", entry))?,
+                None => per_minute,
+            };
+            if per_minute == 0 || burst == 0 {
+                return Err(format!("Rate and burst must be positive in '{}'", entry));
+            }
+
+            let rate = Rate { per_minute, burst };
+            match method.trim() {
+                "*" => config.default = Some(rate),
+                name => { config.per_method.insert(name.to_string(), rate); }
+            }
+        }
+        Ok(config)
+    }
+
+    fn rate_for(&self, method: &str) -> Option<(&str, Rate)> {
+        match self.per_method.get_key_value(method) {
+            Some((name, rate)) => Some((name.as_str(), *rate)),
+            None => self.default.map(|rate| ("*", rate)),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+    rate: Rate,
+}
+
+impl Bucket {
+    fn full(rate: Rate, now: Instant) -> Self {
+        Self { tokens: rate.burst as f64, updated: now, rate }
+    }
+
+    /// Whether enough time has passed for the bucket to have refilled completely
+    fn is_idle(&self, now: Instant) -> bool {
+        let idle = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens + idle * self.rate.refill_per_sec() >= self.rate.burst as f64
+    }
+
+    /// Take one token, or return how long until one is available
+    fn take(&mut self, rate: Rate, now: Instant) -> Result<(), Duration> {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate.refill_per_sec()).min(rate.burst as f64);
+        self.updated = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - self.tokens) / rate.refill_per_sec()))
+        }
+    }
+}
+
+/// Shared bucket state keyed by caller and method
+#[derive(Debug, Default)]
+struct Limiter {
+    config: RateLimitConfig,
+    buckets: Mutex<HashMap<(String, String), Bucket>>,
+}
+
+impl Limiter {
+    fn check(&self, caller: &str, method: &str, now: Instant) -> Result<(), Duration> {
+        let Some((bucket_name, rate)) = self.config.rate_for(method) else { return Ok(()) };
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+
+        if buckets.len() >= MAX_TRACKED_BUCKETS {
+            // A full bucket behaves exactly like a new one, so it can be dropped
+            buckets.retain(|_, b| !b.is_idle(now));
+        }
+
+        buckets
+            .entry((caller.to_string(), bucket_name.to_string()))
+            .or_insert_with(|| Bucket::full(rate, now))
+            .take(rate, now)
+    }
+}
+
+/// Tower layer applying token-bucket limits per caller and RPC.
+///
+/// Callers are identified by their user id, or by peer address when they are
+/// unauthenticated. Must sit inside `AuthLayer` so the claims are available.
+#[derive(Clone)]
+pub struct RateLimitLayer {
+    limiter: Arc<Limiter>,
+}
+
+impl RateLimitLayer {
+    pub fn new(config: RateLimitConfig) -> Self {
+        Self { limiter: Arc::new(Limiter { config, buckets: Mutex::default() }) }
+    }
+}
+
+impl<S> Layer<S> for RateLimitLayer {
+    type Service = RateLimitService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RateLimitService { inner, limiter: self.limiter.clone() }
+    }
+}
+
+#[derive(Clone)]
+pub struct RateLimitService<S> {
+    inner: S,
+    limiter: Arc<Limiter>,
+}
+
+fn caller_key<B>(req: &http::Request<B>) -> String {
+    match req.extensions().get::<AuthClaims>() {
+        Some(claims) if claims.sub != AuthClaims::anonymous().sub => format!("user:{}", claims.sub),
+        _ => match tls::remote_addr(req.extensions()) {
+            Some(addr) => format!("ip:{}", addr.ip()),
+            None => "ip:unknown".to_string(),
+        },
+    }
+}
+
+impl<S, B> Service<http::Request<B>> for RateLimitService<S>
+where
+    S: Service<http::Request<B>, Response = http::Response<BoxBody>> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+    B: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<B>) -> Self::Future {
+        // REST routes are limited under the name of the RPC they map to
+        let method = match rest::route_for(req.method(), req.uri().path()) {
+            Some(route) => route.rpc.to_string(),
+            None => req.uri().path().rsplit('/').next().unwrap_or_default().to_string(),
+        };
+        let caller = caller_key(&req);
+
+        if let Err(wait) = self.limiter.check(&caller, &method, Instant::now()) {
+            let retry_after = wait.as_secs_f64().ceil().max(1.0) as u64;
+            tracing::warn!("Rate limited {} on {} for {}s", caller, method, retry_after);
+
+            let error = Error::new(
+                ErrorCode::RateLimited,
+                format!("Rate limit exceeded for {}, retry after {}s", method, retry_after),
+            ).with_retry_after(Duration::from_secs(retry_after));
+            let response = rest::reject(&req, error.into());
+            return Box::pin(async move { Ok(response) });
+        }
+
+        // Take the service that was driven to readiness, leave a fresh clone behind
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        Box::pin(async move { inner.call(req).await })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_methods_and_default() {
+        let config = RateLimitConfig::parse("*=600, StoreMemory=60/10,Login=5").unwrap();
+        assert_eq!(config.rate_for("StoreMemory"), Some(("StoreMemory", Rate { per_minute: 60, burst: 10 })));
+        assert_eq!(config.rate_for("Login"), Some(("Login", Rate { per_minute: 5, burst: 5 })));
+        assert_eq!(config.rate_for("GetMemory"), Some(("*", Rate { per_minute: 600, burst: 600 })));
+    }
+
+    #[test]
+    fn rejects_malformed_entries() {
+        assert!(RateLimitConfig::parse("StoreMemory").is_err());
+        assert!(RateLimitConfig::parse("StoreMemory=fast").is_err());
+        assert!(RateLimitConfig::parse("StoreMemory=0").is_err());
+        assert!(RateLimitConfig::parse("").unwrap().rate_for("StoreMemory").is_none());
+    }
+
+    #[test]
+    fn bucket_allows_burst_then_refills() {
+        let limiter = Limiter {
+            config: RateLimitConfig::parse("StoreMemory=60/2").unwrap(),
+            buckets: Mutex::default(),
+        };
+        let start = Instant::now();
+
+        assert!(limiter.check("user:a", "StoreMemory", start).is_ok());
+        assert!(limiter.check("user:a", "StoreMemory", start).is_ok());
+        let wait = limiter.check("user:a", "StoreMemory", start).unwrap_err();
+        assert!(wait <= Duration::from_secs(1));
+
+        // Other callers have their own bucket
+        assert!(limiter.check("user:b", "StoreMemory", start).is_ok());
+        // One token a second at 60/min
+        assert!(limiter.check("user:a", "StoreMemory", start + Duration::from_secs(1)).is_ok());
+    }
+
+    #[test]
+    fn unlisted_methods_are_unlimited_without_default() {
+        let limiter = Limiter {
+            config: RateLimitConfig::parse("Login=1").unwrap(),
+            buckets: Mutex::default(),
+        };
+        let now = Instant::now();
+        for _ in 0..10 {
+            assert!(limiter.check("ip:127.0.0.1", "GetMemory", now).is_ok());
+        }
+    }
+}
@@ -0,0 +1,122 @@
+use identra_core::config::RequestLimitsConfig;
+use identra_core::error::Error;
+use identra_proto::memory::StoreMemoryRequest;
+use tonic::Status;
+
+use crate::services::namespace::EMBEDDING_DIMENSIONS;
+
+/// Longest StoreMemory idempotency key, in bytes
+pub const MAX_IDEMPOTENCY_KEY_LEN: usize = 128;
+
+/// Bounds on what a single request may carry; 0 disables a limit
+#[derive(Debug, Clone, Copy)]
+pub struct RequestLimits {
+    pub max_content_bytes: usize,
+    pub max_metadata_entries: usize,
+    /// Metadata keys and values together
+    pub max_metadata_bytes: usize,
+    pub max_tags: usize,
+    /// Seconds a StoreMemory idempotency key is remembered; 0 ignores keys
+    pub idempotency_window_secs: i64,
+}
+
+impl From<&RequestLimitsConfig> for RequestLimits {
+    fn from(config: &RequestLimitsConfig) -> Self {
+        let size = |value: u64| usize::try_from(value).unwrap_or(usize::MAX);
+        Self {
+            max_content_bytes: size(config.max_content_bytes),
+            max_metadata_entries: size(config.max_metadata_entries),
+            max_metadata_bytes: size(config.max_metadata_bytes),
+            max_tags: size(config.max_tags),
+            idempotency_window_secs: i64::try_from(config.idempotency_window_secs).unwrap_or(i64::MAX),
+        }
+    }
+}
+
+impl RequestLimits {
+    /// Check a StoreMemory request, reporting every field over its limit in one error
+    pub fn check_store(&self, r: &StoreMemoryRequest) -> Result<(), Status> {
+        let mut violations = Vec::new();
+        let mut over = |field: &str, limit: usize, actual: usize, what: &str| {
+            if limit > 0 && actual > limit {
+                violations.push((field.to_string(), format!("At most {} {} allowed, got {}", limit, what, actual)));
+            }
+        };
+        over("content", self.max_content_bytes, r.content.len(), "bytes are");
+        over("metadata", self.max_metadata_entries, r.metadata.len(), "entries are");
+        let metadata_bytes = r.metadata.iter().map(|(k, v)| k.len() + v.len()).sum();
+        over("metadata", self.max_metadata_bytes, metadata_bytes, "bytes of keys and values are");
+        over("tags", self.max_tags, r.tags.len(), "tags are");
+
+        let key = &r.idempotency_key;
+        if key.len() > MAX_IDEMPOTENCY_KEY_LEN {
+            violations.push(("idempotency_key".to_string(), format!("Must be at most {} characters", MAX_IDEMPOTENCY_KEY_LEN)));
+        } else if !key.bytes().all(|b| b.is_ascii_graphic()) {
+            violations.push(("idempotency_key".to_string(), "Must be printable ASCII without spaces".to_string()));
+        }
+        into_error(violations)
+    }
+}
+
+/// A query vector must match the stored embeddings and hold only finite values
+pub fn check_embedding(field: &str, embedding: &[f32]) -> Result<(), Status> {
+    let mut violations = Vec::new();
+    if embedding.len() != EMBEDDING_DIMENSIONS {
+        violations.push((field.to_string(), format!("Must have {} dimensions, got {}", EMBEDDING_DIMENSIONS, embedding.len())));
+    }
+    if embedding.iter().any(|v| !v.is_finite()) {
+        violations.push((field.to_string(), "Must not contain NaN or infinite values".to_string()));
+    }
+    into_error(violations)
+}
+
+fn into_error(violations: Vec<(String, String)>) -> Result<(), Status> {
+    let mut violations = violations.into_iter();
+    let Some((field, description)) = violations.next() else {
+        return Ok(());
+    };
+    let error = violations.fold(Error::invalid_field(field, description), |error, (field, description)| {
+        error.with_field(field, description)
+    });
+    Err(error.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn limits() -> RequestLimits {
+        RequestLimits { max_content_bytes: 10, max_metadata_entries: 2, max_metadata_bytes: 8, max_tags: 1, idempotency_window_secs: 60 }
+    }
+
+    #[test]
+    fn store_requests_report_every_violation() {
+        let ok = StoreMemoryRequest { content: "short".to_string(), idempotency_key: "retry-1".to_string(), ..Default::default() };
+        assert!(limits().check_store(&ok).is_ok());
+
+        let request = StoreMemoryRequest {
+            content: "far too long".to_string(),
+            metadata: HashMap::from([("source".to_string(), "desktop".to_string())]),
+            tags: vec!["a".to_string(), "b".to_string()],
+            idempotency_key: "has space".to_string(),
+            ..Default::default()
+        };
+        let error = Error::from_status(&limits().check_store(&request).unwrap_err());
+        let fields: Vec<&str> = error.field_violations().iter().map(|v| v.field.as_str()).collect();
+        assert_eq!(fields, ["content", "metadata", "tags", "idempotency_key"]);
+
+        let unlimited = RequestLimits { max_content_bytes: 0, max_metadata_entries: 0, max_metadata_bytes: 0, max_tags: 0, ..limits() };
+        assert!(unlimited.check_store(&StoreMemoryRequest { idempotency_key: String::new(), ..request }).is_ok());
+    }
+
+    #[test]
+    fn embeddings_must_match_the_stored_dimension() {
+        assert!(check_embedding("query_embedding", &vec![0.1; EMBEDDING_DIMENSIONS]).is_ok());
+        let short = Error::from_status(&check_embedding("query_embedding", &[0.1; 3]).unwrap_err());
+        assert_eq!(short.field_violations()[0].field, "query_embedding");
+        let mut nan = vec![0.1; EMBEDDING_DIMENSIONS];
+        nan[7] = f32::NAN;
+        assert!(check_embedding("query_embedding", &nan).is_err());
+    }
+}
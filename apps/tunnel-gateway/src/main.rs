@@ -1,49 +1,257 @@
+use tonic::service::Routes;
 use tonic::transport::Server;
+use tonic_web::GrpcWebLayer;
+use tokio_rustls::TlsAcceptor;
+use std::future::Future;
 use std::sync::Arc;
+use std::time::Duration;
 use dotenvy::dotenv;
-use std::env;
+use identra_core::config::{self, Service};
+use identra_core::{shutdown, trace};
+use tokio::sync::watch;
+use tower_http::trace::TraceLayer;
 
 mod database;
 mod services;
-pub mod ipc_client;
 mod auth;
+mod limits;
+mod rest;
+mod telemetry;
+mod tls;
 
 use database::MemoryDatabase;
 use services::memory::MemoryServiceImpl;
 use services::vault::VaultServiceImpl;
-use auth::{SupabaseClient, AuthServiceImpl};
+use services::audit::{AuditLog, AuditServiceImpl};
+use services::namespace::DEFAULT_NAMESPACE;
+use services::health::{HealthChecker, HealthService};
+use identra_client::ipc::VaultClient;
+use auth::{SupabaseClient, AuthServiceImpl, AuthClaims, AuthInterceptor, AuthLayer};
+use limits::{RateLimitConfig, RateLimitLayer, RequestLimits, StorageQuota};
 use identra_proto::auth::auth_service_server::AuthServiceServer;
+use rest::{RestServices, schema::ProtoSchema};
+
+/// Time the audit writer gets to persist queued entries on shutdown
+const AUDIT_FLUSH_TIMEOUT: Duration = Duration::from_secs(10);
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Load .env variables
     dotenv().ok(); 
-    tracing_subscriber::fmt::init();
+
+    // Defaults, identra.toml, environment and flags; exits early on --print-config
+    let config = config::init(Service::Gateway)?;
+    let gateway = &config.gateway;
+
+    // Logs, and spans exported to telemetry.otlp_endpoint when set
+    let _trace = trace::init("tunnel-gateway", &config.telemetry)?;
 
     tracing::info!("Starting Gateway...");
 
+    // Prometheus scrape endpoint, separate from the public listener
+    telemetry::install(gateway.metrics_addr)?;
+    tracing::info!("Serving metrics on http://{}/metrics", gateway.metrics_addr);
+
     // Connect to Postgres
-    let db_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set in .env");
-    let db = Arc::new(MemoryDatabase::connect(&db_url).await?);
+    let db_url = gateway.database_url.as_ref().ok_or("gateway.database_url is not set")?;
+    let db = Arc::new(MemoryDatabase::connect(db_url.expose()).await?);
+    telemetry::watch_pool(db.clone());
 
     // Initialize Supabase client for authentication
-    let supabase = Arc::new(SupabaseClient::new()?);
+    let supabase = Arc::new(SupabaseClient::new(&config.supabase)?);
     tracing::info!("Supabase Auth client initialized");
 
+    // Tokens are optional unless gateway.require_auth is set; anonymous callers share one owner
+    if !gateway.require_auth {
+        tracing::warn!("gateway.require_auth is off, unauthenticated requests use the anonymous namespace owner");
+    }
+    // Client certificate subjects that authenticate as services, e.g. "brain-service=brain"
+    let service_identities = tls::ServiceIdentities::parse(&gateway.mtls_identities)?;
+    let auth_layer = AuthLayer::new(AuthInterceptor::new(supabase.clone()), gateway.require_auth)
+        .with_service_identities(service_identities);
+
+    // Token buckets per caller and RPC, see limits::rate for the format
+    let rate_limit_layer = RateLimitLayer::new(RateLimitConfig::parse(&gateway.rate_limits)?);
+
+    let quota = StorageQuota::from(&gateway.quota);
+    let request_limits = RequestLimits::from(&gateway.limits);
+
+    // Memories stored before namespaces existed belong to the anonymous default namespace
+    let now = chrono::Utc::now().timestamp();
+    let default_ns = db.get_or_create_namespace(&AuthClaims::anonymous().sub, DEFAULT_NAMESPACE, now).await?;
+    let adopted = db.adopt_unscoped_rows(&default_ns.id).await?;
+    if adopted > 0 {
+        tracing::info!("Moved {} existing rows into the default namespace", adopted);
+    }
+
+    // Trashed memories are purged for good after this many days
+    let trash_retention_days = i64::from(gateway.trash_retention_days);
+
+    // Purge memories past their namespace retention period or trash grace period
+    let sweeper_db = db.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(3600));
+        loop {
+            interval.tick().await;
+            match sweeper_db.delete_expired_memories(chrono::Utc::now().timestamp()).await {
+                Ok(0) => {}
+                Ok(n) => tracing::info!("Removed {} expired memories", n),
+                Err(e) => tracing::error!("Retention sweep failed: {}", e),
+            }
+            let deleted_before = chrono::Utc::now().timestamp() - trash_retention_days * 86_400;
+            match sweeper_db.purge_trash(deleted_before).await {
+                Ok(0) => {}
+                Ok(n) => tracing::info!("Purged {} memories from trash", n),
+                Err(e) => tracing::error!("Trash purge failed: {}", e),
+            }
+            let window = request_limits.idempotency_window_secs;
+            if let Err(e) = sweeper_db.purge_idempotency_keys(chrono::Utc::now().timestamp().saturating_sub(window)).await {
+                tracing::error!("Idempotency key sweep failed: {}", e);
+            }
+        }
+    });
+
     // Initialize services
-    let memory_service = MemoryServiceImpl::new(db.clone());
-    let auth_service = AuthServiceImpl::new(supabase);
-    let vault_service = VaultServiceImpl::new();
+    let audit_log = AuditLog::new(db.clone());
+    let memory_service = Arc::new(MemoryServiceImpl::new(db.clone(), trash_retention_days, audit_log.clone(), quota, request_limits));
+    let auth_service = Arc::new(AuthServiceImpl::new(supabase.clone()));
+    let vault_service = Arc::new(VaultServiceImpl::new(audit_log.clone(), config.vault.socket.clone()));
+    let audit_service = AuditServiceImpl::new(db.clone(), audit_log.clone());
 
-    let addr = "[::1]:50051".parse()?;
-    tracing::info!("Listening on {}", addr);
+    // Dependency probes behind the Health service and the /healthz and /readyz endpoints
+    let health = {
+        let (db, memory, vault_socket) = (db.clone(), memory_service.clone(), config.vault.socket.clone());
+        Arc::new(
+            HealthChecker::new(std::time::Duration::from_secs(gateway.health_interval_secs))
+                .probe("database", true, move || {
+                    let db = db.clone();
+                    async move { db.ping().await.map_err(|e| e.to_string()) }
+                })
+                // The vault daemon only backs the VaultService RPCs
+                .probe("vault", false, move || {
+                    let socket = vault_socket.clone();
+                    async move {
+                        let mut client = VaultClient::connect_to(&socket).await.map_err(|e| e.to_string())?;
+                        client.ping().await.map_err(|e| e.to_string())
+                    }
+                })
+                .probe("embeddings", true, move || {
+                    let memory = memory.clone();
+                    async move {
+                        tokio::task::spawn_blocking(move || memory.check_embedder())
+                            .await
+                            .map_err(|e| e.to_string())?
+                    }
+                })
+                // Supabase is only needed to serve requests when every request carries a token
+                .probe("identity", gateway.require_auth, move || {
+                    let supabase = supabase.clone();
+                    async move { supabase.health().await }
+                }),
+        )
+    };
+    health.spawn();
 
-    Server::builder()
-        .add_service(memory_service.into_server())
-        .add_service(AuthServiceServer::new(auth_service))
+    let schema = Arc::new(ProtoSchema::load()?);
+    // The REST API calls the same service instances as gRPC
+    let rest_api = rest::router(
+        RestServices {
+            memory: memory_service.clone(),
+            auth: auth_service.clone(),
+            vault: vault_service.clone(),
+        },
+        schema.clone(),
+    );
+    let mut grpc = Routes::builder();
+    grpc.add_service(memory_service.into_server())
+        .add_service(AuthServiceServer::from_arc(auth_service))
         .add_service(vault_service.into_server())
-        .serve(addr)
-        .await?;
+        .add_service(audit_service.into_server())
+        .add_service(HealthService::new(health.clone()).into_server());
+    let routes = Routes::from(
+        grpc.routes()
+            .into_axum_router()
+            .merge(rest_api)
+            .merge(services::health::http_router(health)),
+    );
+
+    let addr = gateway.listen_addr;
+
+    // gRPC-Web and REST arrive over HTTP/1.1
+    let server = Server::builder()
+        .accept_http1(true)
+        .layer(telemetry::MetricsLayer::new(schema))
+        .layer(TraceLayer::new_for_grpc().make_span_with(telemetry::RpcSpan))
+        .layer(rest::cors_layer(&gateway.cors_origins))
+        .layer(GrpcWebLayer::new())
+        .layer(auth_layer)
+        .layer(rate_limit_layer)
+        .add_routes(routes);
+
+    // SIGTERM/SIGINT stops accepting connections; in-flight requests get
+    // gateway.shutdown_grace_secs to finish
+    let (stop_tx, stopping) = watch::channel(false);
+    tokio::spawn(async move {
+        shutdown::signal().await;
+        tracing::info!("Shutdown signal received, draining in-flight requests");
+        let _ = stop_tx.send(true);
+    });
+    let grace = Duration::from_secs(gateway.shutdown_grace_secs);
+
+    match tls::TlsSettings::from_config(&gateway.tls) {
+        Some(settings) => {
+            let cert = Arc::new(tls::ReloadingCert::load(&settings.cert_path, &settings.key_path)?);
+            cert.watch();
+            let acceptor = TlsAcceptor::from(Arc::new(tls::server_config(&settings, cert)?));
+            let listener = tokio::net::TcpListener::bind(addr).await?;
+            tracing::info!(
+                "Listening on {} with TLS{} (gRPC, gRPC-Web and REST under /v1)",
+                addr,
+                match (&settings.client_ca_path, settings.require_client_cert) {
+                    (Some(_), true) => ", client certificates required",
+                    (Some(_), false) => ", client certificates optional",
+                    (None, _) => "",
+                }
+            );
+            let serving = server.serve_with_incoming_shutdown(tls::incoming(listener, acceptor), stopped(stopping.clone()));
+            drain(serving, stopping, grace).await?;
+        }
+        None => {
+            tracing::warn!("gateway.tls.cert is not set, serving plaintext");
+            tracing::info!("Listening on {} (gRPC, gRPC-Web and REST under /v1)", addr);
+            drain(server.serve_with_shutdown(addr, stopped(stopping.clone())), stopping, grace).await?;
+        }
+    }
+
+    // Embeddings are computed inside requests, so only the audit writer can still hold work
+    if tokio::time::timeout(AUDIT_FLUSH_TIMEOUT, audit_log.flush()).await.is_err() {
+        tracing::warn!("Audit log not flushed within {}s", AUDIT_FLUSH_TIMEOUT.as_secs());
+    }
+    tracing::info!("Gateway stopped");
 
     Ok(())
+}
+
+/// Resolves once shutdown has begun
+async fn stopped(mut stopping: watch::Receiver<bool>) {
+    let _ = stopping.wait_for(|stop| *stop).await;
+}
+
+/// Run `serving` to completion, or until `grace` after shutdown began if requests
+/// (such as long-lived watch streams) are still running by then
+async fn drain<F>(serving: F, stopping: watch::Receiver<bool>, grace: Duration) -> Result<(), tonic::transport::Error>
+where
+    F: Future<Output = Result<(), tonic::transport::Error>>,
+{
+    let deadline = async {
+        stopped(stopping).await;
+        tokio::time::sleep(grace).await;
+    };
+    tokio::select! {
+        result = serving => result,
+        _ = deadline => {
+            tracing::warn!("Requests still running {}s after shutdown began, closing them", grace.as_secs());
+            Ok(())
+        }
+    }
 }
\ No newline at end of file
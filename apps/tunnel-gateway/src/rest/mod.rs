@@ -0,0 +1,432 @@
+//! JSON REST front door mirroring the gRPC services.
+//!
+//! Each route is transcoded to the matching RPC and served by the same service
+//! implementation, behind the same auth and rate limit layers as gRPC.
+
+pub mod openapi;
+pub mod schema;
+
+use axum::body::Bytes;
+use axum::extract::{Extension, Path, Query};
+use axum::http::{HeaderMap, HeaderValue, Method, StatusCode};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{on, MethodFilter, MethodRouter};
+use axum::{Json, Router};
+use identra_proto::auth::auth_service_server::AuthService;
+use identra_proto::memory::memory_service_server::MemoryService;
+use identra_proto::vault::vault_service_server::VaultService;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::convert::Infallible;
+use std::future::Future;
+use std::sync::Arc;
+use tokio_stream::{Stream, StreamExt};
+use tonic::body::BoxBody;
+use tonic::metadata::MetadataMap;
+use identra_core::error::{Error, ErrorCode};
+use tonic::{Code, Status};
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
+
+use crate::auth::{AuthClaims, AuthServiceImpl};
+use crate::services::audit::code_name;
+use crate::services::memory::MemoryServiceImpl;
+use crate::services::vault::VaultServiceImpl;
+use schema::ProtoSchema;
+
+/// All REST paths live under this prefix
+pub const API_PREFIX: &str = "/v1/";
+pub const OPENAPI_PATH: &str = "/openapi.json";
+
+const MEMORY: &str = "identra.memory.v1.MemoryService";
+const AUTH: &str = "identra.auth.AuthService";
+const VAULT: &str = "identra.vault.v1.VaultService";
+
+/// A REST route and the RPC it is transcoded to. Path parameters are named after
+/// request fields; the body and query string fill in the remaining fields.
+pub struct RestRoute {
+    pub method: Method,
+    pub path: &'static str,
+    pub service: &'static str,
+    pub rpc: &'static str,
+}
+
+const fn route(method: Method, path: &'static str, service: &'static str, rpc: &'static str) -> RestRoute {
+    RestRoute { method, path, service, rpc }
+}
+
+// Static segments must come before parameters at the same position, see `route_for`
+pub const ROUTES: &[RestRoute] = &[
+    route(Method::POST, "/v1/memories", MEMORY, "StoreMemory"),
+    route(Method::POST, "/v1/memories/query", MEMORY, "QueryMemories"),
+    route(Method::POST, "/v1/memories/search", MEMORY, "SearchMemories"),
+    route(Method::POST, "/v1/memories/context", MEMORY, "BuildContext"),
+    route(Method::GET, "/v1/memories/recent", MEMORY, "GetRecentMemories"),
+    route(Method::GET, "/v1/memories/stats", MEMORY, "GetMemoryStats"),
+    route(Method::GET, "/v1/memories/watch", MEMORY, "WatchMemories"),
+    route(Method::GET, "/v1/memories/{memory_id}", MEMORY, "GetMemory"),
+    route(Method::DELETE, "/v1/memories/{memory_id}", MEMORY, "DeleteMemory"),
+    route(Method::POST, "/v1/memories/retag", MEMORY, "RetagMemories"),
+    route(Method::GET, "/v1/tags", MEMORY, "ListTags"),
+    route(Method::POST, "/v1/tags/merge", MEMORY, "MergeTags"),
+    route(Method::PATCH, "/v1/tags/{tag}", MEMORY, "RenameTag"),
+    route(Method::GET, "/v1/trash", MEMORY, "ListTrash"),
+    route(Method::POST, "/v1/trash/{memory_id}/restore", MEMORY, "RestoreMemory"),
+    route(Method::DELETE, "/v1/trash/{memory_id}", MEMORY, "PurgeMemory"),
+    route(Method::POST, "/v1/conversations", MEMORY, "CreateConversation"),
+    route(Method::GET, "/v1/conversations", MEMORY, "ListConversations"),
+    route(Method::GET, "/v1/conversations/{conversation_id}", MEMORY, "GetConversation"),
+    route(Method::DELETE, "/v1/conversations/{conversation_id}", MEMORY, "DeleteConversation"),
+    route(Method::POST, "/v1/conversations/{conversation_id}/turns", MEMORY, "AppendConversationTurns"),
+    route(Method::POST, "/v1/namespaces", MEMORY, "CreateNamespace"),
+    route(Method::GET, "/v1/namespaces", MEMORY, "ListNamespaces"),
+    route(Method::PATCH, "/v1/namespaces/{namespace}", MEMORY, "UpdateNamespace"),
+    route(Method::DELETE, "/v1/namespaces/{namespace}", MEMORY, "DeleteNamespace"),
+    route(Method::POST, "/v1/namespaces/{namespace}/grants", MEMORY, "GrantAccess"),
+    route(Method::GET, "/v1/namespaces/{namespace}/grants", MEMORY, "ListGrants"),
+    route(Method::POST, "/v1/namespaces/{namespace}/grants/revoke", MEMORY, "RevokeAccess"),
+    route(Method::POST, "/v1/auth/register", AUTH, "Register"),
+    route(Method::POST, "/v1/auth/login", AUTH, "Login"),
+    route(Method::POST, "/v1/auth/verify", AUTH, "VerifyToken"),
+    route(Method::POST, "/v1/auth/refresh", AUTH, "RefreshToken"),
+    route(Method::POST, "/v1/vault/keys", VAULT, "StoreKey"),
+    route(Method::GET, "/v1/vault/keys", VAULT, "ListKeys"),
+    route(Method::GET, "/v1/vault/keys/{key_id}", VAULT, "RetrieveKey"),
+    route(Method::DELETE, "/v1/vault/keys/{key_id}", VAULT, "DeleteKey"),
+    route(Method::GET, "/v1/vault/keys/{key_id}/exists", VAULT, "KeyExists"),
+];
+
+/// Whether a request path belongs to the REST API rather than gRPC
+pub fn is_rest_path(path: &str) -> bool {
+    path.starts_with(API_PREFIX) || path == OPENAPI_PATH
+}
+
+/// The route a request would be served by, if any
+pub fn route_for(method: &Method, path: &str) -> Option<&'static RestRoute> {
+    let segments: Vec<&str> = path.trim_end_matches('/').split('/').collect();
+    ROUTES.iter().find(|route| {
+        let template: Vec<&str> = route.path.split('/').collect();
+        route.method == method
+            && template.len() == segments.len()
+            && template.iter().zip(&segments).all(|(t, s)| t.starts_with('{') || t == s)
+    })
+}
+
+/// Services the REST routes are transcoded to
+#[derive(Clone)]
+pub struct RestServices {
+    pub memory: Arc<MemoryServiceImpl>,
+    pub auth: Arc<AuthServiceImpl>,
+    pub vault: Arc<VaultServiceImpl>,
+}
+
+// Wrap a service method as a 'static closure over a shared service
+macro_rules! rpc {
+    ($service:expr, $method:ident) => {{
+        let service = $service.clone();
+        move |req| {
+            let service = service.clone();
+            async move { service.$method(req).await }
+        }
+    }};
+}
+
+pub fn router(services: RestServices, schema: Arc<ProtoSchema>) -> Router {
+    let s = &services;
+    let mut api = Router::new();
+    for route in ROUTES {
+        let handler = match route.rpc {
+            "StoreMemory" => unary(route, &schema, rpc!(s.memory, store_memory)),
+            "QueryMemories" => unary(route, &schema, rpc!(s.memory, query_memories)),
+            "SearchMemories" => unary(route, &schema, rpc!(s.memory, search_memories)),
+            "GetRecentMemories" => unary(route, &schema, rpc!(s.memory, get_recent_memories)),
+            "BuildContext" => unary(route, &schema, rpc!(s.memory, build_context)),
+            "GetMemoryStats" => unary(route, &schema, rpc!(s.memory, get_memory_stats)),
+            "ListTags" => unary(route, &schema, rpc!(s.memory, list_tags)),
+            "RenameTag" => unary(route, &schema, rpc!(s.memory, rename_tag)),
+            "MergeTags" => unary(route, &schema, rpc!(s.memory, merge_tags)),
+            "RetagMemories" => unary(route, &schema, rpc!(s.memory, retag_memories)),
+            "WatchMemories" => server_stream(route, &schema, rpc!(s.memory, watch_memories)),
+            "GetMemory" => unary(route, &schema, rpc!(s.memory, get_memory)),
+            "DeleteMemory" => unary(route, &schema, rpc!(s.memory, delete_memory)),
+            "ListTrash" => unary(route, &schema, rpc!(s.memory, list_trash)),
+            "RestoreMemory" => unary(route, &schema, rpc!(s.memory, restore_memory)),
+            "PurgeMemory" => unary(route, &schema, rpc!(s.memory, purge_memory)),
+            "CreateConversation" => unary(route, &schema, rpc!(s.memory, create_conversation)),
+            "ListConversations" => unary(route, &schema, rpc!(s.memory, list_conversations)),
+            "GetConversation" => unary(route, &schema, rpc!(s.memory, get_conversation)),
+            "DeleteConversation" => unary(route, &schema, rpc!(s.memory, delete_conversation)),
+            "AppendConversationTurns" => unary(route, &schema, rpc!(s.memory, append_conversation_turns)),
+            "CreateNamespace" => unary(route, &schema, rpc!(s.memory, create_namespace)),
+            "ListNamespaces" => unary(route, &schema, rpc!(s.memory, list_namespaces)),
+            "UpdateNamespace" => unary(route, &schema, rpc!(s.memory, update_namespace)),
+            "DeleteNamespace" => unary(route, &schema, rpc!(s.memory, delete_namespace)),
+            "GrantAccess" => unary(route, &schema, rpc!(s.memory, grant_access)),
+            "ListGrants" => unary(route, &schema, rpc!(s.memory, list_grants)),
+            "RevokeAccess" => unary(route, &schema, rpc!(s.memory, revoke_access)),
+            "Register" => unary(route, &schema, rpc!(s.auth, register)),
+            "Login" => unary(route, &schema, rpc!(s.auth, login)),
+            "VerifyToken" => unary(route, &schema, rpc!(s.auth, verify_token)),
+            "RefreshToken" => unary(route, &schema, rpc!(s.auth, refresh_token)),
+            "StoreKey" => unary(route, &schema, rpc!(s.vault, store_key)),
+            "ListKeys" => unary(route, &schema, rpc!(s.vault, list_keys)),
+            "RetrieveKey" => unary(route, &schema, rpc!(s.vault, retrieve_key)),
+            "DeleteKey" => unary(route, &schema, rpc!(s.vault, delete_key)),
+            "KeyExists" => unary(route, &schema, rpc!(s.vault, key_exists)),
+            other => unreachable!("REST route for unknown RPC {}", other),
+        };
+        let path = route.path.trim_start_matches("/v1").replace('{', ":").replace('}', "");
+        api = api.route(&path, handler);
+    }
+
+    let document = Arc::new(openapi::document(&schema));
+    Router::new()
+        .nest("/v1", api.fallback(|| async { status_response(&Error::new(ErrorCode::NotFound, "No such route").into()) }))
+        .route(OPENAPI_PATH, axum::routing::get(move || async move { Json(document.as_ref().clone()) }))
+}
+
+/// Everything a REST request carries, gathered by each handler
+struct RestCall {
+    headers: HeaderMap,
+    claims: Option<AuthClaims>,
+    params: Vec<(String, String)>,
+    body: Bytes,
+}
+
+impl RestCall {
+    /// Decode the request message and wrap it like tonic would
+    fn into_request<T: DeserializeOwned>(self, schema: &ProtoSchema, input: &str) -> Result<tonic::Request<T>, Status> {
+        let body = if self.body.iter().all(u8::is_ascii_whitespace) {
+            None
+        } else {
+            Some(serde_json::from_slice(&self.body)
+                .map_err(|e| Error::invalid_argument(format!("Invalid JSON body: {}", e)))?)
+        };
+        let json = schema.request_json(input, body, &self.params)?;
+        let message = serde_json::from_value(json)
+            .map_err(|e| Error::invalid_argument(format!("Invalid request: {}", e)))?;
+
+        let mut req = tonic::Request::new(message);
+        *req.metadata_mut() = MetadataMap::from_headers(self.headers);
+        if let Some(claims) = self.claims {
+            req.extensions_mut().insert(claims);
+        }
+        Ok(req)
+    }
+}
+
+fn method_filter(method: &Method) -> MethodFilter {
+    MethodFilter::try_from(method.clone()).expect("REST routes use standard methods")
+}
+
+fn input_type(schema: &ProtoSchema, route: &RestRoute) -> String {
+    schema.method(route.service, route.rpc)
+        .map(|m| m.input_type().to_string())
+        .unwrap_or_else(|| panic!("REST route for unknown RPC {}.{}", route.service, route.rpc))
+}
+
+fn unary<Req, Resp, F, Fut>(route: &RestRoute, schema: &Arc<ProtoSchema>, call: F) -> MethodRouter
+where
+    Req: DeserializeOwned + Send + 'static,
+    Resp: Serialize + Send + 'static,
+    F: Fn(tonic::Request<Req>) -> Fut + Clone + Send + Sync + 'static,
+    Fut: Future<Output = Result<tonic::Response<Resp>, Status>> + Send,
+{
+    let schema = schema.clone();
+    let input = input_type(&schema, route);
+    on(method_filter(&route.method), move |headers: HeaderMap,
+                                           claims: Option<Extension<AuthClaims>>,
+                                           path: Option<Path<Vec<(String, String)>>>,
+                                           Query(mut params): Query<Vec<(String, String)>>,
+                                           body: Bytes| async move {
+        params.extend(path.map(|Path(p)| p).unwrap_or_default());
+        let call_args = RestCall { headers, claims: claims.map(|Extension(c)| c), params, body };
+        let result = match call_args.into_request::<Req>(&schema, &input) {
+            Ok(req) => call(req).await,
+            Err(status) => Err(status),
+        };
+        match result {
+            Ok(resp) => Json(resp.into_inner()).into_response(),
+            Err(status) => status_response(&status),
+        }
+    })
+}
+
+/// Server-streaming RPCs are served as server-sent events, one `message` event per
+/// item. A failure ends the stream with an `error` event holding the error body.
+fn server_stream<Req, Item, S, F, Fut>(route: &RestRoute, schema: &Arc<ProtoSchema>, call: F) -> MethodRouter
+where
+    Req: DeserializeOwned + Send + 'static,
+    Item: Serialize + Send + 'static,
+    S: Stream<Item = Result<Item, Status>> + Send + 'static,
+    F: Fn(tonic::Request<Req>) -> Fut + Clone + Send + Sync + 'static,
+    Fut: Future<Output = Result<tonic::Response<S>, Status>> + Send,
+{
+    let schema = schema.clone();
+    let input = input_type(&schema, route);
+    on(method_filter(&route.method), move |headers: HeaderMap,
+                                           claims: Option<Extension<AuthClaims>>,
+                                           path: Option<Path<Vec<(String, String)>>>,
+                                           Query(mut params): Query<Vec<(String, String)>>,
+                                           body: Bytes| async move {
+        params.extend(path.map(|Path(p)| p).unwrap_or_default());
+        let call_args = RestCall { headers, claims: claims.map(|Extension(c)| c), params, body };
+        let result = match call_args.into_request::<Req>(&schema, &input) {
+            Ok(req) => call(req).await,
+            Err(status) => Err(status),
+        };
+        let stream = match result {
+            Ok(resp) => resp.into_inner(),
+            Err(status) => return status_response(&status),
+        };
+        let events = stream.map(|item| {
+            let event = match item {
+                Ok(item) => Event::default().event("message").json_data(item),
+                Err(status) => Event::default().event("error").json_data(error_body(&status)),
+            };
+            Ok::<_, Infallible>(event.unwrap_or_else(|e| Event::default().event("error").data(e.to_string())))
+        });
+        Sse::new(events).keep_alive(KeepAlive::default()).into_response()
+    })
+}
+
+/// HTTP status for a gRPC code, following the usual gRPC-HTTP mapping
+fn http_status(code: Code) -> StatusCode {
+    match code {
+        Code::Ok => StatusCode::OK,
+        Code::Cancelled => StatusCode::from_u16(499).unwrap_or(StatusCode::BAD_REQUEST),
+        Code::InvalidArgument | Code::FailedPrecondition | Code::OutOfRange => StatusCode::BAD_REQUEST,
+        Code::DeadlineExceeded => StatusCode::GATEWAY_TIMEOUT,
+        Code::NotFound => StatusCode::NOT_FOUND,
+        Code::AlreadyExists | Code::Aborted => StatusCode::CONFLICT,
+        Code::PermissionDenied => StatusCode::FORBIDDEN,
+        Code::Unauthenticated => StatusCode::UNAUTHORIZED,
+        Code::ResourceExhausted => StatusCode::TOO_MANY_REQUESTS,
+        Code::Unimplemented => StatusCode::NOT_IMPLEMENTED,
+        Code::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+        Code::Unknown | Code::Internal | Code::DataLoss => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+/// JSON form of a failed call: the gRPC code, the stable Identra error code as
+/// `reason`, and any invalid fields
+fn error_body(status: &Status) -> serde_json::Value {
+    let error = Error::from_status(status);
+    let mut body = serde_json::json!({
+        "code": status.code() as i32,
+        "status": code_name(status.code()),
+        "reason": error.code().as_str(),
+        "message": status.message(),
+    });
+    if !error.field_violations().is_empty() {
+        body["field_violations"] = error.field_violations().iter()
+            .map(|v| serde_json::json!({ "field": v.field, "description": v.description }))
+            .collect();
+    }
+    body
+}
+
+/// JSON error response for a failed call; `retry-after` metadata becomes a header
+pub fn status_response(status: &Status) -> Response {
+    let mut response = (http_status(status.code()), Json(error_body(status))).into_response();
+    if let Some(retry_after) = status.metadata().get("retry-after") {
+        if let Ok(value) = HeaderValue::from_bytes(retry_after.as_bytes()) {
+            response.headers_mut().insert(axum::http::header::RETRY_AFTER, value);
+        }
+    }
+    response
+}
+
+/// Reject a request from a tower layer in the protocol the caller speaks
+pub fn reject<B>(req: &http::Request<B>, status: Status) -> http::Response<BoxBody> {
+    if is_rest_path(req.uri().path()) {
+        status_response(&status).map(tonic::body::boxed)
+    } else {
+        status.into_http()
+    }
+}
+
+/// CORS for browser clients of the REST API and gRPC-Web. `origins` is a comma
+/// separated list, or `*` to allow any origin; empty allows none.
+pub fn cors_layer(origins: &str) -> CorsLayer {
+    let allow_origin = if origins.trim() == "*" {
+        AllowOrigin::from(Any)
+    } else {
+        AllowOrigin::list(
+            origins.split(',')
+                .map(str::trim)
+                .filter(|o| !o.is_empty())
+                .filter_map(|o| HeaderValue::from_str(o).ok()),
+        )
+    };
+    CorsLayer::new()
+        .allow_origin(allow_origin)
+        .allow_methods([Method::GET, Method::POST, Method::PATCH, Method::DELETE, Method::OPTIONS])
+        .allow_headers([
+            http::header::AUTHORIZATION,
+            http::header::CONTENT_TYPE,
+            http::HeaderName::from_static("x-grpc-web"),
+            http::HeaderName::from_static("x-user-agent"),
+            http::HeaderName::from_static("grpc-timeout"),
+        ])
+        .expose_headers([
+            http::HeaderName::from_static("grpc-status"),
+            http::HeaderName::from_static("grpc-message"),
+            http::HeaderName::from_static("grpc-status-details-bin"),
+            http::header::RETRY_AFTER,
+        ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn routes_match_templates_and_prefer_static_segments() {
+        assert_eq!(route_for(&Method::GET, "/v1/memories/recent").unwrap().rpc, "GetRecentMemories");
+        assert_eq!(route_for(&Method::GET, "/v1/memories/abc").unwrap().rpc, "GetMemory");
+        assert_eq!(route_for(&Method::GET, "/v1/memories/stats").unwrap().rpc, "GetMemoryStats");
+        assert_eq!(route_for(&Method::DELETE, "/v1/memories/abc").unwrap().rpc, "DeleteMemory");
+        assert_eq!(route_for(&Method::POST, "/v1/namespaces/work/grants/revoke").unwrap().rpc, "RevokeAccess");
+        assert!(route_for(&Method::PUT, "/v1/memories/abc").is_none());
+        assert!(route_for(&Method::GET, "/identra.memory.v1.MemoryService/GetMemory").is_none());
+    }
+
+    #[test]
+    fn every_route_has_an_rpc_with_matching_path_fields() {
+        let schema = ProtoSchema::load().unwrap();
+        for route in ROUTES {
+            let method = schema.method(route.service, route.rpc)
+                .unwrap_or_else(|| panic!("{} has no RPC", route.path));
+            let input = schema.message(method.input_type()).unwrap();
+            for param in route.path.split('/').filter(|s| s.starts_with('{')) {
+                let name = param.trim_matches(|c| c == '{' || c == '}');
+                assert!(input.field.iter().any(|f| f.name() == name), "{} has no field {}", route.path, name);
+            }
+        }
+    }
+
+    #[test]
+    fn errors_map_to_http_status() {
+        let mut status = Status::resource_exhausted("slow down");
+        status.metadata_mut().insert("retry-after", "3".parse().unwrap());
+        let response = status_response(&status);
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()[axum::http::header::RETRY_AFTER], "3");
+        assert_eq!(http_status(Code::Unauthenticated), StatusCode::UNAUTHORIZED);
+    }
+
+    #[test]
+    fn error_body_carries_reason_and_field_violations() {
+        let status = Error::invalid_field("content", "Content required").into();
+        let body = error_body(&status);
+        assert_eq!(body["status"], "INVALID_ARGUMENT");
+        assert_eq!(body["reason"], "INVALID_ARGUMENT");
+        assert_eq!(body["field_violations"][0]["field"], "content");
+
+        let status = Error::new(ErrorCode::MemoryNotFound, "Memory not found").into();
+        let body = error_body(&status);
+        assert_eq!(body["reason"], "MEMORY_NOT_FOUND");
+        assert!(body.get("field_violations").is_none());
+    }
+}
@@ -0,0 +1,118 @@
+use serde_json::{json, Map, Value};
+
+use super::schema::ProtoSchema;
+use super::{RestRoute, ROUTES};
+
+/// Routes that work without a bearer token
+fn is_public(route: &RestRoute) -> bool {
+    route.path.starts_with("/v1/auth/")
+}
+
+/// OpenAPI 3 document for the REST routes, generated from the proto descriptors
+pub fn document(schema: &ProtoSchema) -> Value {
+    let mut paths: Map<String, Value> = Map::new();
+    for route in ROUTES {
+        let Some(method) = schema.method(route.service, route.rpc) else { continue };
+        let input = method.input_type();
+        let output = method.output_type();
+        let path_params: Vec<&str> = route.path.split('/')
+            .filter_map(|s| s.strip_prefix('{').and_then(|s| s.strip_suffix('}')))
+            .collect();
+        let has_body = matches!(route.method.as_str(), "POST" | "PATCH" | "PUT");
+
+        let mut parameters: Vec<Value> = path_params.iter()
+            .map(|name| json!({ "name": name, "in": "path", "required": true, "schema": { "type": "string" } }))
+            .collect();
+        if !has_body {
+            parameters.extend(schema.query_fields(input).into_iter()
+                .filter(|f| !path_params.contains(&f.name()))
+                .map(|f| json!({ "name": f.name(), "in": "query", "schema": schema.field_schema(f) })));
+        }
+
+        let output_ref = json!({ "$ref": format!("#/components/schemas/{}", ProtoSchema::component_name(output)) });
+        let success = if method.server_streaming() {
+            json!({
+                "description": format!("Server-sent events, one `message` event per {}", ProtoSchema::component_name(output)),
+                "content": { "text/event-stream": { "schema": output_ref } },
+            })
+        } else {
+            json!({ "description": "OK", "content": { "application/json": { "schema": output_ref } } })
+        };
+
+        let mut operation = json!({
+            "operationId": route.rpc,
+            "tags": [route.service.rsplit('.').next().unwrap_or(route.service)],
+            "parameters": parameters,
+            "responses": {
+                "200": success,
+                "default": {
+                    "description": "Error",
+                    "content": { "application/json": { "schema": { "$ref": "#/components/schemas/Error" } } },
+                },
+            },
+        });
+        if has_body {
+            operation["requestBody"] = json!({
+                "content": { "application/json": { "schema": {
+                    "$ref": format!("#/components/schemas/{}", ProtoSchema::component_name(input))
+                } } },
+            });
+        }
+        if is_public(route) {
+            operation["security"] = json!([]);
+        }
+
+        let item = paths.entry(route.path.to_string()).or_insert_with(|| json!({}));
+        item[route.method.as_str().to_lowercase()] = operation;
+    }
+
+    let mut schemas = schema.component_schemas();
+    schemas.insert("Error".to_string(), json!({
+        "type": "object",
+        "properties": {
+            "code": { "type": "integer", "description": "gRPC status code" },
+            "status": { "type": "string", "description": "gRPC status code name, e.g. NOT_FOUND" },
+            "message": { "type": "string" },
+        },
+    }));
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "Identra Gateway",
+            "version": env!("CARGO_PKG_VERSION"),
+            "description": "JSON mapping of the Identra gRPC services. Fields use their proto names; \
+                            enums are numbers, timestamps RFC 3339 strings and bytes base64.",
+        },
+        "paths": paths,
+        "components": {
+            "schemas": schemas,
+            "securitySchemes": { "bearer": { "type": "http", "scheme": "bearer" } },
+        },
+        "security": [{ "bearer": [] }],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn documents_every_route_with_resolvable_refs() {
+        let schema = ProtoSchema::load().unwrap();
+        let doc = document(&schema);
+
+        let get_memory = &doc["paths"]["/v1/memories/{memory_id}"]["get"];
+        assert_eq!(get_memory["operationId"], "GetMemory");
+        let params: Vec<&str> = get_memory["parameters"].as_array().unwrap()
+            .iter().map(|p| p["name"].as_str().unwrap()).collect();
+        assert_eq!(params, vec!["memory_id", "namespace"]);
+        assert_eq!(doc["paths"]["/v1/auth/login"]["post"]["security"], json!([]));
+
+        let text = doc.to_string();
+        for reference in text.split("\"#/components/schemas/").skip(1) {
+            let name = &reference[..reference.find('"').unwrap()];
+            assert!(doc["components"]["schemas"].get(name).is_some(), "dangling $ref {}", name);
+        }
+    }
+}
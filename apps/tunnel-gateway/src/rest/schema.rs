@@ -0,0 +1,290 @@
+use prost::Message;
+use prost_types::field_descriptor_proto::{Label, Type};
+use prost_types::{DescriptorProto, FieldDescriptorProto, FileDescriptorSet, MethodDescriptorProto};
+use serde_json::{json, Map, Value};
+use std::collections::{BTreeMap, HashMap};
+use identra_core::error::Error;
+use tonic::Status;
+
+const TIMESTAMP: &str = ".google.protobuf.Timestamp";
+
+/// Index over the compiled proto descriptors, used to turn path and query parameters into
+/// request messages and to describe messages in the OpenAPI document
+pub struct ProtoSchema {
+    /// Keyed by fully qualified name with the leading dot, e.g. `.identra.memory.v1.Memory`
+    messages: BTreeMap<String, DescriptorProto>,
+    enums: HashMap<String, Vec<(String, i32)>>,
+    /// Keyed by (`identra.memory.v1.MemoryService`, `StoreMemory`)
+    methods: HashMap<(String, String), MethodDescriptorProto>,
+}
+
+impl ProtoSchema {
+    pub fn load() -> Result<Self, prost::DecodeError> {
+        let set = FileDescriptorSet::decode(identra_proto::FILE_DESCRIPTOR_SET)?;
+        let mut schema = Self { messages: BTreeMap::new(), enums: HashMap::new(), methods: HashMap::new() };
+
+        for file in set.file {
+            let package = file.package().to_string();
+            let prefix = if package.is_empty() { String::new() } else { format!(".{}", package) };
+            for message in file.message_type {
+                schema.index_message(&prefix, message);
+            }
+            for e in file.enum_type {
+                schema.index_enum(&prefix, e);
+            }
+            for service in file.service {
+                let service_name = format!("{}.{}", package, service.name());
+                for method in service.method {
+                    schema.methods.insert((service_name.clone(), method.name().to_string()), method);
+                }
+            }
+        }
+        Ok(schema)
+    }
+
+    fn index_message(&mut self, prefix: &str, mut message: DescriptorProto) {
+        let name = format!("{}.{}", prefix, message.name());
+        for nested in std::mem::take(&mut message.nested_type) {
+            self.index_message(&name, nested);
+        }
+        for e in std::mem::take(&mut message.enum_type) {
+            self.index_enum(&name, e);
+        }
+        self.messages.insert(name, message);
+    }
+
+    fn index_enum(&mut self, prefix: &str, e: prost_types::EnumDescriptorProto) {
+        let values = e.value.iter().map(|v| (v.name().to_string(), v.number())).collect();
+        self.enums.insert(format!("{}.{}", prefix, e.name()), values);
+    }
+
+    pub fn method(&self, service: &str, rpc: &str) -> Option<&MethodDescriptorProto> {
+        self.methods.get(&(service.to_string(), rpc.to_string()))
+    }
+
+    pub fn message(&self, name: &str) -> Option<&DescriptorProto> {
+        self.messages.get(name)
+    }
+
+    fn map_entry(&self, field: &FieldDescriptorProto) -> Option<&DescriptorProto> {
+        if field.r#type() != Type::Message {
+            return None;
+        }
+        self.messages
+            .get(field.type_name())
+            .filter(|m| m.options.as_ref().and_then(|o| o.map_entry).unwrap_or(false))
+    }
+
+    /// Build the JSON form of request message `input` from an optional body plus path and
+    /// query parameters. Parameters are converted to the field's type and override the body;
+    /// dotted names such as `fields.omit_content` reach into message fields.
+    pub fn request_json(
+        &self,
+        input: &str,
+        body: Option<Value>,
+        params: &[(String, String)],
+    ) -> Result<Value, Status> {
+        let message = self.message(input)
+            .ok_or_else(|| Error::internal(format!("Unknown message {}", input)))?;
+        let mut object = match body {
+            None => Map::new(),
+            Some(Value::Object(object)) => object,
+            Some(_) => return Err(Error::invalid_argument("Request body must be a JSON object").into()),
+        };
+
+        let mut grouped: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+        for (name, value) in params {
+            grouped.entry(name.as_str()).or_default().push(value.as_str());
+        }
+        for (name, values) in grouped {
+            self.insert_param(message, &mut object, name, name, &values)?;
+        }
+        Ok(Value::Object(object))
+    }
+
+    /// Set the field at `path` within `object`, a `message`, creating nested messages on the way
+    fn insert_param(
+        &self,
+        message: &DescriptorProto,
+        object: &mut Map<String, Value>,
+        param: &str,
+        path: &str,
+        values: &[&str],
+    ) -> Result<(), Status> {
+        let unknown = || -> Status { Error::invalid_field(param, format!("Unknown parameter '{}'", param)).into() };
+        let (name, rest) = match path.split_once('.') {
+            Some((name, rest)) => (name, Some(rest)),
+            None => (path, None),
+        };
+        let field = message.field.iter().find(|f| f.name() == name).ok_or_else(unknown)?;
+        let Some(rest) = rest else {
+            object.insert(name.to_string(), self.param_value(field, values)?);
+            return Ok(());
+        };
+
+        let singular_message = field.r#type() == Type::Message && field.label() != Label::Repeated && field.type_name() != TIMESTAMP;
+        let nested = singular_message.then(|| self.message(field.type_name())).flatten().ok_or_else(unknown)?;
+        let entry = object.entry(name.to_string()).or_insert(Value::Null);
+        if entry.is_null() {
+            *entry = Value::Object(Map::new());
+        }
+        let Value::Object(inner) = entry else {
+            return Err(Error::invalid_field(param, format!("'{}' must be an object in the request body", name)).into());
+        };
+        self.insert_param(nested, inner, param, rest, values)
+    }
+
+    fn param_value(&self, field: &FieldDescriptorProto, values: &[&str]) -> Result<Value, Status> {
+        if field.label() == Label::Repeated {
+            if self.map_entry(field).is_some() {
+                return Err(Error::invalid_field(field.name(), format!("'{}' can only be set in the request body", field.name())).into());
+            }
+            // Repeated parameters may be given several times or comma separated
+            return values.iter()
+                .flat_map(|v| v.split(','))
+                .filter(|v| !v.is_empty())
+                .map(|v| self.scalar_value(field, v))
+                .collect::<Result<Vec<_>, _>>()
+                .map(Value::Array);
+        }
+        match values {
+            [value] => self.scalar_value(field, value),
+            _ => Err(Error::invalid_field(field.name(), format!("'{}' given more than once", field.name())).into()),
+        }
+    }
+
+    fn scalar_value(&self, field: &FieldDescriptorProto, raw: &str) -> Result<Value, Status> {
+        let invalid = || -> Status { Error::invalid_field(field.name(), format!("Invalid value '{}' for '{}'", raw, field.name())).into() };
+        let value = match field.r#type() {
+            Type::String => Value::String(raw.to_string()),
+            Type::Bool => Value::Bool(raw.parse().map_err(|_| invalid())?),
+            Type::Int32 | Type::Sint32 | Type::Sfixed32 | Type::Int64 | Type::Sint64 | Type::Sfixed64 => {
+                json!(raw.parse::<i64>().map_err(|_| invalid())?)
+            }
+            Type::Uint32 | Type::Fixed32 | Type::Uint64 | Type::Fixed64 => json!(raw.parse::<u64>().map_err(|_| invalid())?),
+            Type::Float | Type::Double => json!(raw.parse::<f64>().map_err(|_| invalid())?),
+            // Enums accept their number or value name
+            Type::Enum => match raw.parse::<i32>() {
+                Ok(number) => json!(number),
+                Err(_) => self.enums.get(field.type_name())
+                    .and_then(|values| values.iter().find(|(name, _)| name == raw))
+                    .map(|(_, number)| json!(number))
+                    .ok_or_else(invalid)?,
+            },
+            Type::Message if field.type_name() == TIMESTAMP => Value::String(raw.to_string()),
+            _ => return Err(Error::invalid_field(field.name(), format!("'{}' can only be set in the request body", field.name())).into()),
+        };
+        Ok(value)
+    }
+
+    /// Fields of `input` that can be passed as query parameters
+    pub fn query_fields(&self, input: &str) -> Vec<&FieldDescriptorProto> {
+        let Some(message) = self.message(input) else { return Vec::new() };
+        message.field.iter()
+            .filter(|f| f.r#type() != Type::Message || f.type_name() == TIMESTAMP)
+            .collect()
+    }
+
+    /// OpenAPI component name of a message
+    pub fn component_name(message: &str) -> &str {
+        message.trim_start_matches('.')
+    }
+
+    /// OpenAPI schemas for every message except map entries and well-known types
+    pub fn component_schemas(&self) -> Map<String, Value> {
+        self.messages.iter()
+            .filter(|(name, m)| !name.starts_with(".google.") && !m.options.as_ref().and_then(|o| o.map_entry).unwrap_or(false))
+            .map(|(name, m)| {
+                let properties: Map<String, Value> = m.field.iter()
+                    .map(|f| (f.name().to_string(), self.field_schema(f)))
+                    .collect();
+                (Self::component_name(name).to_string(), json!({ "type": "object", "properties": properties }))
+            })
+            .collect()
+    }
+
+    pub fn field_schema(&self, field: &FieldDescriptorProto) -> Value {
+        if let Some(entry) = self.map_entry(field) {
+            let value = entry.field.iter().find(|f| f.number() == 2);
+            return json!({
+                "type": "object",
+                "additionalProperties": value.map(|v| self.type_schema(v)).unwrap_or_else(|| json!({})),
+            });
+        }
+        let schema = self.type_schema(field);
+        if field.label() == Label::Repeated {
+            json!({ "type": "array", "items": schema })
+        } else {
+            schema
+        }
+    }
+
+    fn type_schema(&self, field: &FieldDescriptorProto) -> Value {
+        match field.r#type() {
+            Type::String => json!({ "type": "string" }),
+            Type::Bytes => json!({ "type": "string", "format": "byte" }),
+            Type::Bool => json!({ "type": "boolean" }),
+            Type::Int32 | Type::Sint32 | Type::Sfixed32 | Type::Uint32 | Type::Fixed32 => json!({ "type": "integer", "format": "int32" }),
+            Type::Int64 | Type::Sint64 | Type::Sfixed64 | Type::Uint64 | Type::Fixed64 => json!({ "type": "integer", "format": "int64" }),
+            Type::Float => json!({ "type": "number", "format": "float" }),
+            Type::Double => json!({ "type": "number", "format": "double" }),
+            Type::Enum => {
+                let values = self.enums.get(field.type_name()).cloned().unwrap_or_default();
+                let names: Vec<String> = values.iter().map(|(name, number)| format!("{} = {}", number, name)).collect();
+                json!({
+                    "type": "integer",
+                    "enum": values.iter().map(|(_, number)| number).collect::<Vec<_>>(),
+                    "description": names.join(", "),
+                })
+            }
+            Type::Message if field.type_name() == TIMESTAMP => json!({ "type": "string", "format": "date-time" }),
+            Type::Message | Type::Group => json!({
+                "$ref": format!("#/components/schemas/{}", Self::component_name(field.type_name()))
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_params_to_field_types() {
+        let schema = ProtoSchema::load().unwrap();
+        let params = vec![
+            ("limit".to_string(), "5".to_string()),
+            ("namespace".to_string(), "work".to_string()),
+        ];
+        let json = schema.request_json(".identra.memory.v1.ListTrashRequest", None, &params).unwrap();
+        assert_eq!(json, json!({ "limit": 5, "namespace": "work" }));
+
+        let body = json!({ "permission": 1 });
+        let params = vec![("permission".to_string(), "PERMISSION_WRITE".to_string())];
+        let json = schema.request_json(".identra.memory.v1.GrantAccessRequest", Some(body), &params).unwrap();
+        assert_eq!(json["permission"], json!(2));
+
+        let body = json!({ "fields": { "include_embedding": true } });
+        let params = vec![
+            ("fields.omit_content".to_string(), "true".to_string()),
+            ("created.start".to_string(), "2024-01-01T00:00:00Z".to_string()),
+        ];
+        let json = schema.request_json(".identra.memory.v1.GetRecentMemoriesRequest", Some(body), &params).unwrap();
+        assert_eq!(json["fields"], json!({ "include_embedding": true, "omit_content": true }));
+        assert_eq!(json["created"]["start"], json!("2024-01-01T00:00:00Z"));
+    }
+
+    #[test]
+    fn rejects_unknown_and_malformed_params() {
+        let schema = ProtoSchema::load().unwrap();
+        let unknown = vec![("colour".to_string(), "red".to_string())];
+        assert!(schema.request_json(".identra.memory.v1.ListTrashRequest", None, &unknown).is_err());
+        let malformed = vec![("limit".to_string(), "many".to_string())];
+        assert!(schema.request_json(".identra.memory.v1.ListTrashRequest", None, &malformed).is_err());
+        assert!(schema.request_json(".identra.memory.v1.ListTrashRequest", Some(json!([1])), &[]).is_err());
+        let through_scalar = vec![("limit.value".to_string(), "1".to_string())];
+        assert!(schema.request_json(".identra.memory.v1.ListTrashRequest", None, &through_scalar).is_err());
+        let unknown_nested = vec![("fields.colour".to_string(), "red".to_string())];
+        assert!(schema.request_json(".identra.memory.v1.GetMemoryRequest", None, &unknown_nested).is_err());
+    }
+}
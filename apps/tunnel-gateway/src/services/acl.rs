@@ -0,0 +1,147 @@
+use identra_proto::memory::{Grant, Grantee, NamespaceSharing, Permission, PrincipalType};
+use identra_core::error::{Error, ErrorCode};
+use tonic::{Request, Status};
+
+use crate::auth::AuthClaims;
+use crate::database::MemoryDatabase;
+use crate::services::namespace::NamespaceModel;
+
+#[derive(Debug, Clone)]
+pub struct GrantModel {
+    pub id: String,
+    pub namespace_id: String,
+    pub memory_id: Option<String>,
+    /// Principal key, see `grantee_key`
+    pub grantee: String,
+    pub permission: i32,
+    pub granted_by: String,
+    pub created_at: i64,
+}
+
+/// The calling user and the groups they belong to
+#[derive(Debug, Clone)]
+pub struct Principal {
+    pub user_id: String,
+    pub groups: Vec<String>,
+}
+
+impl Principal {
+    pub fn from_request<T>(req: &Request<T>) -> Result<Self, Status> {
+        let claims = req.extensions()
+            .get::<AuthClaims>()
+            .ok_or_else(|| Error::new(ErrorCode::Unauthenticated, "User not authenticated"))?;
+        Ok(Self { user_id: claims.sub.clone(), groups: claims.groups.clone() })
+    }
+
+    /// Keys that grants to this principal are stored under
+    pub fn grantees(&self) -> Vec<String> {
+        std::iter::once(format!("user:{}", self.user_id))
+            .chain(self.groups.iter().map(|g| format!("group:{}", g)))
+            .collect()
+    }
+}
+
+pub fn grantee_key(grantee: Option<&Grantee>) -> Result<String, Status> {
+    let grantee = grantee.ok_or_else(|| Error::invalid_field("grantee", "Grantee required"))?;
+    let id = grantee.id.trim();
+    if id.is_empty() {
+        return Err(Error::invalid_field("grantee.id", "Grantee id required").into());
+    }
+    match PrincipalType::try_from(grantee.r#type) {
+        Ok(PrincipalType::User) => Ok(format!("user:{}", id)),
+        Ok(PrincipalType::Group) => Ok(format!("group:{}", id)),
+        _ => Err(Error::invalid_field("grantee.type", "Grantee type must be USER or GROUP").into()),
+    }
+}
+
+fn grantee_from_key(key: &str) -> Grantee {
+    let (kind, id) = key.split_once(':').unwrap_or(("", key));
+    let kind = match kind {
+        "user" => PrincipalType::User,
+        "group" => PrincipalType::Group,
+        _ => PrincipalType::Unspecified,
+    };
+    Grantee { r#type: kind as i32, id: id.to_string() }
+}
+
+pub fn grant_to_proto(g: GrantModel) -> Grant {
+    Grant {
+        id: g.id,
+        namespace_id: g.namespace_id,
+        memory_id: g.memory_id.unwrap_or_default(),
+        grantee: Some(grantee_from_key(&g.grantee)),
+        permission: g.permission,
+        granted_by: g.granted_by,
+        created_at: Some(prost_types::Timestamp { seconds: g.created_at, nanos: 0 }),
+    }
+}
+
+fn permission_from_i32(value: i32) -> Permission {
+    Permission::try_from(value).unwrap_or(Permission::Unspecified)
+}
+
+/// What a principal may do in one namespace
+#[derive(Debug, Clone)]
+pub struct NamespaceAccess {
+    pub namespace: NamespaceModel,
+    /// Namespace-wide permission; `Unspecified` when only single memories are shared
+    pub permission: Permission,
+    pub grantees: Vec<String>,
+}
+
+impl NamespaceAccess {
+    /// Work out a principal's access to a namespace from ownership and grants.
+    /// Returns `None` when the namespace is invisible to them.
+    pub async fn resolve(
+        db: &MemoryDatabase,
+        principal: &Principal,
+        namespace: NamespaceModel,
+    ) -> Result<Option<Self>, sqlx::Error> {
+        let grantees = principal.grantees();
+        if namespace.owner_id == principal.user_id {
+            return Ok(Some(Self { namespace, permission: Permission::Admin, grantees }));
+        }
+        // Grants are dormant while the owner keeps the namespace private
+        if namespace.settings.sharing != NamespaceSharing::Shareable as i32 {
+            return Ok(None);
+        }
+
+        let permission = permission_from_i32(db.granted_permission(&namespace.id, None, &grantees).await?);
+        if permission == Permission::Unspecified
+            && db.granted_memory_ids(&namespace.id, &grantees).await?.is_empty()
+        {
+            return Ok(None);
+        }
+        Ok(Some(Self { namespace, permission, grantees }))
+    }
+
+    pub fn is_owner(&self, principal: &Principal) -> bool {
+        self.namespace.owner_id == principal.user_id
+    }
+
+    pub fn require(&self, needed: Permission) -> Result<(), Status> {
+        if self.permission >= needed {
+            Ok(())
+        } else {
+            Err(Error::permission_denied(format!(
+                "{} access to namespace '{}' required",
+                needed.as_str_name(), self.namespace.name
+            )).into())
+        }
+    }
+
+    /// Grantee filter for memory listings: `None` when the whole namespace is readable,
+    /// otherwise only memories shared individually with these grantees are visible
+    pub fn memory_filter(&self) -> Option<&[String]> {
+        if self.permission >= Permission::Read { None } else { Some(&self.grantees) }
+    }
+
+    /// Effective permission on one memory: the namespace permission or a grant on the memory itself
+    pub async fn memory_permission(&self, db: &MemoryDatabase, memory_id: &str) -> Result<Permission, sqlx::Error> {
+        if self.permission == Permission::Admin {
+            return Ok(self.permission);
+        }
+        let granted = permission_from_i32(db.granted_permission(&self.namespace.id, Some(memory_id), &self.grantees).await?);
+        Ok(self.permission.max(granted))
+    }
+}
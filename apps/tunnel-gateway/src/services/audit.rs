@@ -0,0 +1,586 @@
+use identra_proto::audit::{
+    audit_service_server::{AuditService, AuditServiceServer},
+    AuditEntry, AuditFilter,
+    QueryAuditLogRequest, QueryAuditLogResponse,
+    ExportAuditLogRequest, ExportAuditLogChunk,
+    VerifyAuditLogRequest, VerifyAuditLogResponse,
+};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::{mpsc, oneshot};
+use tokio_stream::wrappers::ReceiverStream;
+use identra_core::error::Error;
+use tonic::{Code, Request, Response, Status};
+
+use crate::database::MemoryDatabase;
+use crate::services::acl::Principal;
+use crate::telemetry;
+
+/// `prev_hash` of the first entry in the chain
+pub const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// Members of this group (Supabase `app_metadata.groups`) may read the audit log
+pub const ADMIN_GROUP: &str = "admin";
+
+const DEFAULT_PAGE_SIZE: i32 = 100;
+const MAX_PAGE_SIZE: i32 = 1000;
+const BATCH_SIZE: i64 = 500;
+
+/// Entries waiting for the writer; past this, new entries are dropped and counted
+const QUEUE_CAPACITY: usize = 10_000;
+/// Delay before retrying a transient write failure, doubling up to the maximum
+const RETRY_DELAY: Duration = Duration::from_millis(100);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+/// Writes of one entry before it is given up and a gap marker is written in its place
+const MAX_WRITE_ATTEMPTS: u32 = 10;
+
+/// `operation` of the entry written in place of one that could not be
+pub const GAP_OPERATION: &str = "AuditGap";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditEntryModel {
+    pub sequence: i64,
+    pub occurred_at: i64,
+    pub actor: String,
+    pub service: String,
+    pub operation: String,
+    pub namespace_id: String,
+    pub target_ids: Vec<String>,
+    pub outcome: String,
+    pub detail: String,
+    pub prev_hash: String,
+    pub hash: String,
+}
+
+impl AuditEntryModel {
+    /// Hex SHA-256 over `prev_hash` and every other field, serialized as JSON
+    pub fn compute_hash(&self) -> String {
+        #[derive(Serialize)]
+        struct Hashed<'a> {
+            prev_hash: &'a str,
+            sequence: i64,
+            occurred_at: i64,
+            actor: &'a str,
+            service: &'a str,
+            operation: &'a str,
+            namespace_id: &'a str,
+            target_ids: &'a [String],
+            outcome: &'a str,
+            detail: &'a str,
+        }
+
+        let input = serde_json::to_vec(&Hashed {
+            prev_hash: &self.prev_hash,
+            sequence: self.sequence,
+            occurred_at: self.occurred_at,
+            actor: &self.actor,
+            service: &self.service,
+            operation: &self.operation,
+            namespace_id: &self.namespace_id,
+            target_ids: &self.target_ids,
+            outcome: &self.outcome,
+            detail: &self.detail,
+        }).expect("audit entry serializes");
+        hex::encode(Sha256::digest(input))
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct AuditFilterModel {
+    pub actor: Option<String>,
+    pub operation: Option<String>,
+    pub namespace_id: Option<String>,
+    pub target_id: Option<String>,
+    pub since: Option<i64>,
+    pub until: Option<i64>,
+}
+
+/// One operation being audited; filled in while the request is handled
+#[derive(Debug, Clone)]
+pub struct AuditEvent {
+    pub actor: String,
+    pub service: &'static str,
+    pub operation: &'static str,
+    pub namespace_id: String,
+    pub target_ids: Vec<String>,
+}
+
+impl AuditEvent {
+    pub fn new(service: &'static str, operation: &'static str, principal: &Principal) -> Self {
+        Self {
+            actor: principal.user_id.clone(),
+            service,
+            operation,
+            namespace_id: String::new(),
+            target_ids: Vec::new(),
+        }
+    }
+
+    pub fn target(mut self, id: &str) -> Self {
+        self.add_target(id);
+        self
+    }
+
+    pub fn add_target(&mut self, id: &str) {
+        if !id.is_empty() {
+            self.target_ids.push(id.to_string());
+        }
+    }
+}
+
+/// Handle for recording audit entries. Entries are appended in order by a single
+/// background writer so requests never wait on the chain lock. Transient write failures
+/// are retried with backoff, holding back the entries queued behind; an entry that still
+/// cannot be written is replaced by a gap marker, so the loss shows in the chain.
+#[derive(Clone)]
+pub struct AuditLog {
+    tx: mpsc::Sender<WriterMessage>,
+}
+
+enum WriterMessage {
+    Entry(Box<AuditEntryModel>),
+    /// Acknowledged once every entry queued before it is written
+    Flush(oneshot::Sender<()>),
+}
+
+impl AuditLog {
+    pub fn new(db: Arc<MemoryDatabase>) -> Self {
+        let (tx, mut rx) = mpsc::channel::<WriterMessage>(QUEUE_CAPACITY);
+        tokio::spawn(async move {
+            while let Some(message) = rx.recv().await {
+                match message {
+                    WriterMessage::Entry(mut entry) => {
+                        let Err(e) = write(&db, &mut entry).await else { continue };
+                        tracing::error!("Audit entry ({} {} by {}) lost: {}", entry.service, entry.operation, entry.actor, e);
+                        telemetry::record_audit_dropped("write_failed");
+                        if let Err(e) = write(&db, &mut gap_marker(&entry, &e)).await {
+                            tracing::error!("Failed to record the audit gap: {}", e);
+                        }
+                    }
+                    WriterMessage::Flush(done) => {
+                        let _ = done.send(());
+                    }
+                }
+            }
+        });
+        Self { tx }
+    }
+
+    /// Wait until every entry recorded so far has been written
+    pub async fn flush(&self) {
+        let (done, written) = oneshot::channel();
+        if self.tx.send(WriterMessage::Flush(done)).await.is_ok() {
+            let _ = written.await;
+        }
+    }
+
+    /// Record the outcome of an operation
+    pub fn record<T>(&self, event: AuditEvent, result: &Result<T, Status>) {
+        let (outcome, detail) = match result {
+            Ok(_) => (code_name(Code::Ok), String::new()),
+            Err(status) => (code_name(status.code()), status.message().to_string()),
+        };
+        let entry = AuditEntryModel {
+            sequence: 0,
+            occurred_at: chrono::Utc::now().timestamp(),
+            actor: event.actor,
+            service: event.service.to_string(),
+            operation: event.operation.to_string(),
+            namespace_id: event.namespace_id,
+            target_ids: event.target_ids,
+            outcome: outcome.to_string(),
+            detail,
+            prev_hash: String::new(),
+            hash: String::new(),
+        };
+        let reason = match self.tx.try_send(WriterMessage::Entry(Box::new(entry))) {
+            Ok(()) => return,
+            Err(TrySendError::Full(_)) => "queue_full",
+            Err(TrySendError::Closed(_)) => "writer_stopped",
+        };
+        tracing::error!("Audit entry for {} {} dropped: {}", event.service, event.operation, reason);
+        telemetry::record_audit_dropped(reason);
+    }
+}
+
+/// Append `entry`, retrying transient failures with backoff up to `MAX_WRITE_ATTEMPTS` times
+async fn write(db: &MemoryDatabase, entry: &mut AuditEntryModel) -> Result<(), sqlx::Error> {
+    let (mut attempt, mut delay) = (1, RETRY_DELAY);
+    loop {
+        match db.append_audit_entry(entry).await {
+            Err(e) if is_transient(&e) && attempt < MAX_WRITE_ATTEMPTS => {
+                tracing::warn!(
+                    "Failed to write audit entry ({} {} by {}), retrying in {:?}: {}",
+                    entry.service, entry.operation, entry.actor, delay, e
+                );
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+                delay = (delay * 2).min(MAX_RETRY_DELAY);
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Failures a later attempt may not hit: lost connections, an exhausted pool, and
+/// serialization failures, deadlocks, shutdowns or resource shortages reported by Postgres
+fn is_transient(e: &sqlx::Error) -> bool {
+    match e {
+        sqlx::Error::Io(_) | sqlx::Error::PoolTimedOut => true,
+        sqlx::Error::Database(db) => db.code().is_some_and(|code| {
+            matches!(code.as_ref(), "40001" | "40P01") || ["08", "53", "57P"].iter().any(|class| code.starts_with(class))
+        }),
+        _ => false,
+    }
+}
+
+/// Entry recording that `lost` was never written. Its fields are only quoted in `detail`,
+/// escaped, in case one of them is why the write failed.
+fn gap_marker(lost: &AuditEntryModel, error: &sqlx::Error) -> AuditEntryModel {
+    AuditEntryModel {
+        sequence: 0,
+        occurred_at: chrono::Utc::now().timestamp(),
+        actor: String::new(),
+        service: "audit".to_string(),
+        operation: GAP_OPERATION.to_string(),
+        namespace_id: String::new(),
+        target_ids: Vec::new(),
+        outcome: code_name(Code::DataLoss).to_string(),
+        detail: format!(
+            "Lost entry: {:?} {:?} by {:?} in namespace {:?} at {}, outcome {:?}, targets {:?}: {}",
+            lost.service, lost.operation, lost.actor, lost.namespace_id, lost.occurred_at, lost.outcome, lost.target_ids, error
+        ),
+        prev_hash: String::new(),
+        hash: String::new(),
+    }
+}
+
+/// Canonical gRPC code name, as used in the `outcome` column
+pub(crate) fn code_name(code: Code) -> &'static str {
+    match code {
+        Code::Ok => "OK",
+        Code::Cancelled => "CANCELLED",
+        Code::Unknown => "UNKNOWN",
+        Code::InvalidArgument => "INVALID_ARGUMENT",
+        Code::DeadlineExceeded => "DEADLINE_EXCEEDED",
+        Code::NotFound => "NOT_FOUND",
+        Code::AlreadyExists => "ALREADY_EXISTS",
+        Code::PermissionDenied => "PERMISSION_DENIED",
+        Code::ResourceExhausted => "RESOURCE_EXHAUSTED",
+        Code::FailedPrecondition => "FAILED_PRECONDITION",
+        Code::Aborted => "ABORTED",
+        Code::OutOfRange => "OUT_OF_RANGE",
+        Code::Unimplemented => "UNIMPLEMENTED",
+        Code::Internal => "INTERNAL",
+        Code::Unavailable => "UNAVAILABLE",
+        Code::DataLoss => "DATA_LOSS",
+        Code::Unauthenticated => "UNAUTHENTICATED",
+    }
+}
+
+fn filter_from_proto(filter: Option<AuditFilter>) -> AuditFilterModel {
+    let filter = filter.unwrap_or_default();
+    let non_empty = |s: String| if s.is_empty() { None } else { Some(s) };
+    AuditFilterModel {
+        actor: non_empty(filter.actor),
+        operation: non_empty(filter.operation),
+        namespace_id: non_empty(filter.namespace_id),
+        target_id: non_empty(filter.target_id),
+        since: filter.since.map(|ts| ts.seconds),
+        until: filter.until.map(|ts| ts.seconds),
+    }
+}
+
+fn entry_to_proto(e: AuditEntryModel) -> AuditEntry {
+    AuditEntry {
+        sequence: e.sequence,
+        occurred_at: Some(prost_types::Timestamp { seconds: e.occurred_at, nanos: 0 }),
+        actor: e.actor,
+        service: e.service,
+        operation: e.operation,
+        namespace_id: e.namespace_id,
+        target_ids: e.target_ids,
+        outcome: e.outcome,
+        detail: e.detail,
+        prev_hash: e.prev_hash,
+        hash: e.hash,
+    }
+}
+
+pub struct AuditServiceImpl {
+    db: Arc<MemoryDatabase>,
+    audit: AuditLog,
+}
+
+impl AuditServiceImpl {
+    pub fn new(db: Arc<MemoryDatabase>, audit: AuditLog) -> Self {
+        Self { db, audit }
+    }
+
+    pub fn into_server(self) -> AuditServiceServer<Self> {
+        AuditServiceServer::new(self)
+    }
+
+    fn require_admin(principal: &Principal) -> Result<(), Status> {
+        if principal.groups.iter().any(|g| g == ADMIN_GROUP) {
+            Ok(())
+        } else {
+            Err(Error::permission_denied("Audit log access requires the admin group").into())
+        }
+    }
+
+    /// Walk the whole chain, checking sequence continuity, links and hashes
+    async fn verify_chain(&self) -> Result<VerifyAuditLogResponse, sqlx::Error> {
+        let everything = AuditFilterModel::default();
+        let mut chain = ChainCheck::default();
+
+        loop {
+            let batch = self.db.audit_entries_after(&everything, chain.checked, BATCH_SIZE).await?;
+            if batch.is_empty() {
+                break;
+            }
+            for entry in batch {
+                if let Err(problem) = chain.check(entry) {
+                    return Ok(VerifyAuditLogResponse {
+                        valid: false,
+                        entries_checked: chain.checked,
+                        first_invalid_sequence: chain.checked + 1,
+                        message: format!("Entry {}: {}", chain.checked + 1, problem),
+                    });
+                }
+            }
+        }
+
+        Ok(VerifyAuditLogResponse {
+            valid: true,
+            entries_checked: chain.checked,
+            first_invalid_sequence: 0,
+            message: format!("{} entries verified", chain.checked),
+        })
+    }
+}
+
+/// The chain verified so far, fed entries in sequence order
+struct ChainCheck {
+    expected_prev: String,
+    /// Sequence of the last entry verified
+    checked: i64,
+}
+
+impl Default for ChainCheck {
+    fn default() -> Self {
+        Self { expected_prev: GENESIS_HASH.to_string(), checked: 0 }
+    }
+}
+
+impl ChainCheck {
+    /// Verify the next entry, or say what is wrong with it
+    fn check(&mut self, entry: AuditEntryModel) -> Result<(), String> {
+        if entry.sequence != self.checked + 1 {
+            return Err(format!("expected sequence {}, found {}", self.checked + 1, entry.sequence));
+        }
+        if entry.prev_hash != self.expected_prev {
+            return Err("prev_hash does not match the previous entry".to_string());
+        }
+        if entry.compute_hash() != entry.hash {
+            return Err("hash does not match the entry contents".to_string());
+        }
+        self.expected_prev = entry.hash;
+        self.checked = entry.sequence;
+        Ok(())
+    }
+}
+
+#[tonic::async_trait]
+impl AuditService for AuditServiceImpl {
+    type ExportAuditLogStream = ReceiverStream<Result<ExportAuditLogChunk, Status>>;
+
+    async fn query_audit_log(&self, req: Request<QueryAuditLogRequest>) -> Result<Response<QueryAuditLogResponse>, Status> {
+        let principal = Principal::from_request(&req)?;
+        let r = req.into_inner();
+        let audit = AuditEvent::new("audit", "QueryAuditLog", &principal);
+
+        let result: Result<Response<QueryAuditLogResponse>, Status> = async {
+            Self::require_admin(&principal)?;
+            let limit = if r.limit > 0 { r.limit.min(MAX_PAGE_SIZE) } else { DEFAULT_PAGE_SIZE };
+            let before = match r.page_token.as_str() {
+                "" => None,
+                token => Some(token.parse::<i64>().map_err(|_| Error::invalid_field("page_token", "Invalid page token"))?),
+            };
+
+            let entries = self.db.query_audit_log(&filter_from_proto(r.filter), before, limit)
+                .await
+                .map_err(|e| Error::internal(format!("Database error: {}", e)))?;
+
+            let next_page_token = match entries.last() {
+                Some(last) if entries.len() == limit as usize => last.sequence.to_string(),
+                _ => String::new(),
+            };
+            Ok(Response::new(QueryAuditLogResponse {
+                entries: entries.into_iter().map(entry_to_proto).collect(),
+                next_page_token,
+            }))
+        }.await;
+
+        self.audit.record(audit, &result);
+        result
+    }
+
+    async fn export_audit_log(&self, req: Request<ExportAuditLogRequest>) -> Result<Response<Self::ExportAuditLogStream>, Status> {
+        let principal = Principal::from_request(&req)?;
+        let r = req.into_inner();
+        let audit = AuditEvent::new("audit", "ExportAuditLog", &principal);
+
+        let result = Self::require_admin(&principal);
+        self.audit.record(audit, &result);
+        result?;
+
+        let filter = filter_from_proto(r.filter);
+        let db = self.db.clone();
+        let (tx, rx) = mpsc::channel(4);
+        tokio::spawn(async move {
+            let mut after = 0;
+            loop {
+                let batch = match db.audit_entries_after(&filter, after, BATCH_SIZE).await {
+                    Ok(batch) => batch,
+                    Err(e) => {
+                        let _ = tx.send(Err(Error::internal(format!("Database error: {}", e)).into())).await;
+                        return;
+                    }
+                };
+                let Some(last) = batch.last() else { return };
+                after = last.sequence;
+
+                let mut data = Vec::new();
+                for entry in &batch {
+                    serde_json::to_writer(&mut data, entry).expect("audit entry serializes");
+                    data.push(b'\n');
+                }
+                if tx.send(Ok(ExportAuditLogChunk { data })).await.is_err() {
+                    return;
+                }
+            }
+        });
+
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+
+    async fn verify_audit_log(&self, req: Request<VerifyAuditLogRequest>) -> Result<Response<VerifyAuditLogResponse>, Status> {
+        let principal = Principal::from_request(&req)?;
+        let audit = AuditEvent::new("audit", "VerifyAuditLog", &principal);
+
+        let result: Result<Response<VerifyAuditLogResponse>, Status> = async {
+            Self::require_admin(&principal)?;
+            let report = self.verify_chain()
+                .await
+                .map_err(|e| Error::internal(format!("Database error: {}", e)))?;
+            if !report.valid {
+                tracing::error!("Audit log verification failed: {}", report.message);
+            }
+            Ok(Response::new(report))
+        }.await;
+
+        self.audit.record(audit, &result);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A valid chain of `len` entries
+    fn chain(len: i64) -> Vec<AuditEntryModel> {
+        let mut prev_hash = GENESIS_HASH.to_string();
+        (1..=len).map(|sequence| {
+            let mut entry = AuditEntryModel {
+                sequence,
+                occurred_at: 1_700_000_000 + sequence,
+                actor: "user-1".into(),
+                service: "memory".into(),
+                operation: "StoreMemory".into(),
+                namespace_id: "ns-1".into(),
+                target_ids: vec![format!("memory-{}", sequence)],
+                outcome: "OK".into(),
+                detail: String::new(),
+                prev_hash: prev_hash.clone(),
+                hash: String::new(),
+            };
+            entry.hash = entry.compute_hash();
+            prev_hash = entry.hash.clone();
+            entry
+        }).collect()
+    }
+
+    /// Sequence of the first entry `ChainCheck` rejects, with its reason
+    fn verify(entries: Vec<AuditEntryModel>) -> Option<(i64, String)> {
+        let mut chain = ChainCheck::default();
+        entries.into_iter().find_map(|entry| chain.check(entry).err().map(|problem| (chain.checked + 1, problem)))
+    }
+
+    #[test]
+    fn hash_covers_every_field_and_the_link() {
+        let entry = &chain(1)[0];
+        assert_eq!(entry.hash, entry.compute_hash());
+        assert_eq!(entry.hash.len(), 64);
+
+        let changed: [fn(&mut AuditEntryModel); 5] = [
+            |e| e.prev_hash = "1".repeat(64),
+            |e| e.sequence += 1,
+            |e| e.actor.push('x'),
+            |e| e.target_ids.clear(),
+            |e| e.outcome = "INTERNAL".into(),
+        ];
+        for change in changed {
+            let mut tampered = entry.clone();
+            change(&mut tampered);
+            assert_ne!(tampered.compute_hash(), entry.hash);
+        }
+    }
+
+    #[test]
+    fn verification_finds_tampering_and_gaps() {
+        assert_eq!(verify(chain(3)), None);
+
+        let mut tampered = chain(3);
+        tampered[1].detail = "edited".into();
+        assert_eq!(verify(tampered), Some((2, "hash does not match the entry contents".into())));
+
+        // Rehashing an edited entry breaks the next link instead
+        let mut rehashed = chain(3);
+        rehashed[1].detail = "edited".into();
+        rehashed[1].hash = rehashed[1].compute_hash();
+        assert_eq!(verify(rehashed), Some((3, "prev_hash does not match the previous entry".into())));
+
+        let mut gap = chain(3);
+        gap.remove(1);
+        assert_eq!(verify(gap), Some((2, "expected sequence 2, found 3".into())));
+
+        let mut missing_start = chain(2);
+        missing_start.remove(0);
+        assert_eq!(verify(missing_start), Some((1, "expected sequence 1, found 2".into())));
+    }
+
+    #[test]
+    fn only_transient_write_failures_are_retried() {
+        let io = std::io::Error::new(std::io::ErrorKind::ConnectionReset, "reset");
+        assert!(is_transient(&sqlx::Error::Io(io)));
+        assert!(is_transient(&sqlx::Error::PoolTimedOut));
+        assert!(!is_transient(&sqlx::Error::PoolClosed));
+        assert!(!is_transient(&sqlx::Error::ColumnNotFound("hash".into())));
+    }
+
+    #[test]
+    fn gap_marker_describes_the_lost_entry() {
+        let mut lost = chain(1).remove(0);
+        lost.actor = "user-\0".into();
+        let marker = gap_marker(&lost, &sqlx::Error::PoolClosed);
+        assert_eq!((marker.operation.as_str(), marker.outcome.as_str()), (GAP_OPERATION, "DATA_LOSS"));
+        assert!(marker.actor.is_empty() && marker.target_ids.is_empty());
+        assert!(marker.detail.starts_with(r#"Lost entry: "memory" "StoreMemory" by "user-\0" in namespace "ns-1""#), "{}", marker.detail);
+        assert!(marker.detail.contains(r#"targets ["memory-1"]"#));
+    }
+}
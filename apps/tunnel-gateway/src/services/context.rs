@@ -0,0 +1,322 @@
+//! Context assembly for BuildContext: the memories most relevant to a query, de-duplicated
+//! and laid out as numbered prompt text that fits a token budget.
+
+use identra_proto::memory::{BuildContextResponse, ContextCitation, ContextOrder, ContextPolicy};
+use crate::services::memory::MemoryModel;
+use crate::services::ranking::cosine;
+use identra_core::error::Error;
+use std::sync::OnceLock;
+use tiktoken_rs::CoreBPE;
+use tonic::Status;
+
+/// Largest budget a request may ask for, about the biggest model context window
+pub const MAX_TOKEN_BUDGET: i32 = 200_000;
+
+const DEFAULT_MAX_CANDIDATES: i32 = 50;
+const MAX_CANDIDATES: i32 = 200;
+const DEFAULT_DUPLICATE_SIMILARITY: f32 = 0.95;
+
+/// A memory cut shorter than this is not worth its marker
+const MIN_TRUNCATED_TOKENS: usize = 16;
+
+const SEPARATOR: &str = "\n\n";
+
+/// A BPE tokenizer matching the one of the model the context is for
+pub struct Tokenizer {
+    bpe: &'static CoreBPE,
+}
+
+impl Tokenizer {
+    /// Tokenizers are built once from the vocabularies bundled with tiktoken-rs
+    pub fn named(name: &str) -> Option<Self> {
+        static CL100K: OnceLock<CoreBPE> = OnceLock::new();
+        static O200K: OnceLock<CoreBPE> = OnceLock::new();
+
+        let bpe = match name {
+            "" | "cl100k_base" => CL100K.get_or_init(|| tiktoken_rs::cl100k_base().expect("bundled cl100k_base vocabulary")),
+            "o200k_base" => O200K.get_or_init(|| tiktoken_rs::o200k_base().expect("bundled o200k_base vocabulary")),
+            _ => return None,
+        };
+        Some(Self { bpe })
+    }
+
+    pub fn count(&self, text: &str) -> usize {
+        self.bpe.encode_ordinary(text).len()
+    }
+
+    /// Longest prefix of `text` within `max_tokens`, cut on a token and character boundary
+    pub fn truncate<'a>(&self, text: &'a str, max_tokens: usize) -> &'a str {
+        let tokens = self.bpe.encode_ordinary(text);
+        if tokens.len() <= max_tokens {
+            return text;
+        }
+        // A token may end inside a multi-byte character, which does not decode
+        (1..=max_tokens)
+            .rev()
+            .find_map(|n| self.bpe.decode(tokens[..n].to_vec()).ok())
+            .filter(|prefix| text.starts_with(prefix.as_str()))
+            .map_or("", |prefix| &text[..prefix.len()])
+    }
+}
+
+/// A BuildContext policy with defaults filled in
+pub struct Policy {
+    pub max_candidates: i32,
+    pub min_similarity: f32,
+    pub duplicate_similarity: f32,
+    pub order: ContextOrder,
+    pub tokenizer: Tokenizer,
+    /// Cut the first memory that does not fit instead of skipping it
+    pub truncate: bool,
+}
+
+impl Policy {
+    pub fn from_proto(policy: Option<ContextPolicy>) -> Result<Self, Status> {
+        let policy = policy.unwrap_or_default();
+        if !(0..=MAX_CANDIDATES).contains(&policy.max_candidates) {
+            return Err(Error::invalid_field("policy.max_candidates", format!("max_candidates must be 0-{}", MAX_CANDIDATES)).into());
+        }
+        if !(0.0..=1.0).contains(&policy.min_similarity) {
+            return Err(Error::invalid_field("policy.min_similarity", "min_similarity must be between 0 and 1").into());
+        }
+        if !(0.0..=1.0).contains(&policy.duplicate_similarity) {
+            return Err(Error::invalid_field("policy.duplicate_similarity", "duplicate_similarity must be between 0 and 1").into());
+        }
+        let order = ContextOrder::try_from(policy.order)
+            .map_err(|_| Error::invalid_field("policy.order", "Unknown context order"))?;
+        let tokenizer = Tokenizer::named(&policy.tokenizer)
+            .ok_or_else(|| Error::invalid_field("policy.tokenizer", format!("Unknown tokenizer '{}'", policy.tokenizer)))?;
+
+        Ok(Self {
+            max_candidates: if policy.max_candidates > 0 { policy.max_candidates } else { DEFAULT_MAX_CANDIDATES },
+            min_similarity: policy.min_similarity,
+            duplicate_similarity: if policy.duplicate_similarity > 0.0 { policy.duplicate_similarity } else { DEFAULT_DUPLICATE_SIMILARITY },
+            order,
+            tokenizer,
+            truncate: !policy.whole_memories_only,
+        })
+    }
+}
+
+/// A memory found for the query, with its stored embedding
+pub struct Candidate {
+    pub memory: MemoryModel,
+    pub similarity: f32,
+}
+
+struct Picked<'a> {
+    candidate: &'a Candidate,
+    text: &'a str,
+}
+
+/// Pick candidates by relevance until the budget is spent, skipping near-duplicates of
+/// memories already picked, then lay them out in the policy's order
+pub fn assemble(mut candidates: Vec<Candidate>, budget: usize, policy: &Policy) -> BuildContextResponse {
+    candidates.sort_by(|a, b| b.similarity.total_cmp(&a.similarity));
+    let tokenizer = &policy.tokenizer;
+    // Every memory is charged for the widest marker and a separator, so the sum can only overestimate
+    let overhead = tokenizer.count(SEPARATOR) + tokenizer.count(&marker(candidates.len().max(1)));
+
+    let (mut picked, mut used, mut duplicates, mut omitted) = (Vec::<Picked>::new(), 0, 0, 0usize);
+    for (position, candidate) in candidates.iter().enumerate() {
+        if used >= budget {
+            omitted += candidates.len() - position;
+            break;
+        }
+        if picked.iter().any(|p| is_duplicate(p.candidate, candidate, policy.duplicate_similarity)) {
+            duplicates += 1;
+            continue;
+        }
+
+        let content = candidate.memory.content.as_str();
+        let cost = overhead + tokenizer.count(content);
+        if used + cost <= budget {
+            picked.push(Picked { candidate, text: content });
+            used += cost;
+        } else if policy.truncate && budget - used >= overhead + MIN_TRUNCATED_TOKENS {
+            picked.push(Picked { candidate, text: tokenizer.truncate(content, budget - used - overhead) });
+            used = budget;
+        } else {
+            omitted += 1;
+        }
+    }
+
+    if policy.order == ContextOrder::Chronological {
+        picked.sort_by_key(|p| p.candidate.memory.created_at);
+    }
+
+    // Tokens can merge across joins; drop the least relevant memory in the rare case that overflows
+    loop {
+        let mut response = render(&picked);
+        response.token_count = tokenizer.count(&response.context) as i32;
+        if response.token_count as usize <= budget || picked.is_empty() {
+            response.candidates = candidates.len() as i32;
+            response.duplicates = duplicates;
+            response.omitted = omitted as i32;
+            return response;
+        }
+        let least = picked.iter()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| a.candidate.similarity.total_cmp(&b.candidate.similarity))
+            .map(|(i, _)| i)
+            .unwrap_or_default();
+        picked.remove(least);
+        omitted += 1;
+    }
+}
+
+fn render(picked: &[Picked]) -> BuildContextResponse {
+    let mut context = String::new();
+    let mut citations = Vec::with_capacity(picked.len());
+    for (i, p) in picked.iter().enumerate() {
+        if i > 0 {
+            context.push_str(SEPARATOR);
+        }
+        context.push_str(&marker(i + 1));
+        let start = context.len();
+        context.push_str(p.text);
+        citations.push(ContextCitation {
+            index: i as i32 + 1,
+            memory_id: p.candidate.memory.id.clone(),
+            start: start as i32,
+            end: context.len() as i32,
+            similarity_score: p.candidate.similarity,
+            truncated: p.text.len() < p.candidate.memory.content.len(),
+        });
+    }
+    BuildContextResponse { context, citations, ..Default::default() }
+}
+
+fn marker(index: usize) -> String {
+    format!("[{}] ", index)
+}
+
+/// Same text up to case and whitespace, or embeddings at least `threshold` alike
+fn is_duplicate(a: &Candidate, b: &Candidate, threshold: f32) -> bool {
+    let (a, b) = (&a.memory, &b.memory);
+    if normalized(&a.content) == normalized(&b.content) {
+        return true;
+    }
+    !a.embedding.is_empty() && cosine(&a.embedding, &b.embedding) >= threshold
+}
+
+fn normalized(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn candidate(id: &str, content: &str, embedding: Vec<f32>, similarity: f32, created_at: i64) -> Candidate {
+        Candidate {
+            memory: MemoryModel {
+                id: id.to_string(),
+                content: content.to_string(),
+                metadata: HashMap::new(),
+                embedding,
+                tags: vec![],
+                created_at,
+                updated_at: created_at,
+            },
+            similarity,
+        }
+    }
+
+    fn policy(order: ContextOrder, whole_memories_only: bool) -> Policy {
+        Policy::from_proto(Some(ContextPolicy { order: order as i32, whole_memories_only, ..Default::default() })).unwrap()
+    }
+
+    #[test]
+    fn orders_by_relevance_and_cites_spans() {
+        let response = assemble(
+            vec![
+                candidate("b", "Bob prefers tea.", vec![0.0, 1.0], 0.6, 2),
+                candidate("a", "Alice prefers coffee.", vec![1.0, 0.0], 0.9, 1),
+            ],
+            100,
+            &policy(ContextOrder::Relevance, false),
+        );
+
+        assert_eq!(response.context, "[1] Alice prefers coffee.\n\n[2] Bob prefers tea.");
+        let ids: Vec<_> = response.citations.iter().map(|c| c.memory_id.as_str()).collect();
+        assert_eq!(ids, ["a", "b"]);
+        for citation in &response.citations {
+            let span = &response.context[citation.start as usize..citation.end as usize];
+            assert!(span.ends_with("prefers coffee.") || span.ends_with("prefers tea."), "{:?}", span);
+            assert!(!citation.truncated);
+        }
+        assert_eq!(response.token_count as usize, Tokenizer::named("").unwrap().count(&response.context));
+    }
+
+    #[test]
+    fn skips_exact_and_near_duplicates() {
+        let response = assemble(
+            vec![
+                candidate("a", "The meeting is on Friday.", vec![1.0, 0.0], 0.9, 1),
+                candidate("b", "the meeting  is on friday.", vec![0.0, 1.0], 0.8, 2),
+                candidate("c", "Meeting moved to Friday!", vec![0.99, 0.05], 0.7, 3),
+                candidate("d", "Lunch is at noon.", vec![0.0, 1.0], 0.5, 4),
+            ],
+            100,
+            &policy(ContextOrder::Relevance, false),
+        );
+
+        let ids: Vec<_> = response.citations.iter().map(|c| c.memory_id.as_str()).collect();
+        assert_eq!(ids, ["a", "d"]);
+        assert_eq!((response.candidates, response.duplicates, response.omitted), (4, 2, 0));
+    }
+
+    #[test]
+    fn truncates_the_first_memory_that_does_not_fit() {
+        let long = "word ".repeat(200);
+        let candidates = || vec![
+            candidate("short", "A short note.", vec![], 0.9, 1),
+            candidate("long", &long, vec![], 0.8, 2),
+            candidate("tiny", "Hi.", vec![], 0.1, 3),
+        ];
+
+        let response = assemble(candidates(), 60, &policy(ContextOrder::Relevance, false));
+        assert!(response.token_count <= 60, "{} tokens", response.token_count);
+        let ids: Vec<_> = response.citations.iter().map(|c| c.memory_id.as_str()).collect();
+        assert_eq!(ids, ["short", "long"]);
+        assert!(response.citations[1].truncated);
+        assert!(long.starts_with(&response.context[response.citations[1].start as usize..]));
+        assert_eq!(response.omitted, 1);
+
+        // Without truncation the long memory is skipped and the tiny one still fits
+        let response = assemble(candidates(), 60, &policy(ContextOrder::Relevance, true));
+        let ids: Vec<_> = response.citations.iter().map(|c| c.memory_id.as_str()).collect();
+        assert_eq!(ids, ["short", "tiny"]);
+        assert_eq!(response.omitted, 1);
+    }
+
+    #[test]
+    fn chronological_order_keeps_relevance_selection() {
+        let response = assemble(
+            vec![
+                candidate("new", "Moved to Berlin.", vec![], 0.9, 20),
+                candidate("old", "Lived in Paris.", vec![], 0.8, 10),
+                candidate("off", "Unrelated and long enough to miss the budget entirely.", vec![], 0.1, 5),
+            ],
+            20,
+            &policy(ContextOrder::Chronological, true),
+        );
+
+        assert_eq!(response.context, "[1] Lived in Paris.\n\n[2] Moved to Berlin.");
+        assert_eq!(response.omitted, 1);
+    }
+
+    #[test]
+    fn truncation_respects_character_boundaries() {
+        let tokenizer = Tokenizer::named("cl100k_base").unwrap();
+        let text = "héllo wörld ".repeat(20);
+        for max in 1..12 {
+            let prefix = tokenizer.truncate(&text, max);
+            assert!(text.starts_with(prefix));
+            assert!(tokenizer.count(prefix) <= max);
+        }
+        assert!(Tokenizer::named("gpt2-made-up").is_none());
+    }
+}
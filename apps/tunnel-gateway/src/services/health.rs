@@ -1,26 +1,194 @@
+//! Gateway health: dependency probes run on an interval, served over the gRPC
+//! `Health` service per component and as Kubernetes liveness/readiness endpoints.
+
 use identra_proto::health::{
     health_server::{Health, HealthServer},
     HealthCheckRequest, HealthCheckResponse,
     health_check_response::ServingStatus,
 };
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::routing::get;
+use axum::{Json, Router};
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::pin::Pin;
 use std::sync::Arc;
-use std::time::Instant;
-use tokio::sync::RwLock;
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, watch};
+use tokio_stream::wrappers::ReceiverStream;
 use tonic::{Request, Response, Status};
 
-pub struct HealthService {
+pub const LIVENESS_PATH: &str = "/healthz";
+pub const READINESS_PATH: &str = "/readyz";
+
+/// A probe taking longer than this counts as failed
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+type ProbeFuture = Pin<Box<dyn Future<Output = Result<(), String>> + Send>>;
+type Probe = Box<dyn Fn() -> ProbeFuture + Send + Sync>;
+
+struct Component {
+    name: &'static str,
+    /// The gateway is not ready while a critical component is down
+    critical: bool,
+    probe: Probe,
+}
+
+/// Result of the latest probe of one component
+#[derive(Debug, Clone, PartialEq)]
+pub struct ComponentHealth {
+    pub status: ServingStatus,
+    pub message: String,
+    pub critical: bool,
+}
+
+/// Status of every component after a probe round
+#[derive(Debug, Clone, Default)]
+pub struct HealthSnapshot {
+    pub components: BTreeMap<&'static str, ComponentHealth>,
+    /// When the last probe round finished; `None` before the first one
+    pub checked_at: Option<Instant>,
+}
+
+impl HealthSnapshot {
+    /// Overall status: serving once every critical component is
+    pub fn overall(&self) -> ServingStatus {
+        if self.checked_at.is_none() {
+            return ServingStatus::Unknown;
+        }
+        let down = self.components.values().any(|c| c.critical && c.status != ServingStatus::Serving);
+        if down { ServingStatus::NotServing } else { ServingStatus::Serving }
+    }
+
+    fn overall_message(&self) -> String {
+        if self.checked_at.is_none() {
+            return "Health checks have not run yet".to_string();
+        }
+        let failing: Vec<String> = self.components.iter()
+            .filter(|(_, c)| c.status != ServingStatus::Serving)
+            .map(|(name, c)| format!("{}: {}", name, c.message))
+            .collect();
+        if failing.is_empty() {
+            "Gateway is healthy".to_string()
+        } else {
+            format!("Unhealthy components: {}", failing.join("; "))
+        }
+    }
+}
+
+/// Runs the dependency probes and publishes each round to subscribers
+pub struct HealthChecker {
+    components: Vec<Component>,
+    interval: Duration,
     start_time: Instant,
-    status: Arc<RwLock<ServingStatus>>,
+    snapshot: watch::Sender<HealthSnapshot>,
 }
 
-impl HealthService {
-    pub fn new() -> Self {
+impl HealthChecker {
+    pub fn new(interval: Duration) -> Self {
         Self {
+            components: Vec::new(),
+            interval,
             start_time: Instant::now(),
-            status: Arc::new(RwLock::new(ServingStatus::Serving)),
+            snapshot: watch::Sender::new(HealthSnapshot::default()),
         }
     }
-    
+
+    /// Add a component checked by `probe`, reported under `name`
+    pub fn probe<F, Fut>(mut self, name: &'static str, critical: bool, probe: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), String>> + Send + 'static,
+    {
+        self.components.push(Component { name, critical, probe: Box::new(move || Box::pin(probe())) });
+        self
+    }
+
+    pub fn subscribe(&self) -> watch::Receiver<HealthSnapshot> {
+        self.snapshot.subscribe()
+    }
+
+    pub fn snapshot(&self) -> HealthSnapshot {
+        self.snapshot.borrow().clone()
+    }
+
+    /// Probe every component once and publish the result
+    pub async fn check_now(&self) {
+        let mut components = BTreeMap::new();
+        for component in &self.components {
+            let result = match tokio::time::timeout(PROBE_TIMEOUT, (component.probe)()).await {
+                Ok(result) => result,
+                Err(_) => Err(format!("no response within {}s", PROBE_TIMEOUT.as_secs())),
+            };
+            let (status, message) = match result {
+                Ok(()) => (ServingStatus::Serving, "ok".to_string()),
+                Err(e) => (ServingStatus::NotServing, e),
+            };
+            components.insert(component.name, ComponentHealth { status, message, critical: component.critical });
+        }
+
+        self.snapshot.send_modify(|snapshot| {
+            for (name, health) in &components {
+                let previous = snapshot.components.get(name).map(|c| c.status);
+                if previous.is_some_and(|previous| previous != health.status) {
+                    tracing::warn!("Health of {} changed to {:?}: {}", name, health.status, health.message);
+                }
+            }
+            snapshot.components = components;
+            snapshot.checked_at = Some(Instant::now());
+        });
+    }
+
+    /// Probe every `interval` in the background
+    pub fn spawn(self: &Arc<Self>) {
+        let checker = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(checker.interval);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                interval.tick().await;
+                checker.check_now().await;
+            }
+        });
+    }
+
+    /// Live while probe rounds keep completing; a stuck checker means a stuck runtime
+    pub fn is_live(&self) -> bool {
+        let deadline = self.interval * 3 + PROBE_TIMEOUT * self.components.len() as u32;
+        let last = self.snapshot.borrow().checked_at.unwrap_or(self.start_time);
+        last.elapsed() <= deadline
+    }
+
+    /// Health of `service`: empty or `gateway` for the whole gateway, otherwise a component name
+    pub fn response(&self, service: &str, snapshot: &HealthSnapshot) -> HealthCheckResponse {
+        let (status, message) = match service {
+            "" | "gateway" => (snapshot.overall(), snapshot.overall_message()),
+            name => match snapshot.components.get(name) {
+                Some(component) => (component.status, component.message.clone()),
+                None if snapshot.checked_at.is_none() && self.components.iter().any(|c| c.name == name) => {
+                    (ServingStatus::Unknown, "Health checks have not run yet".to_string())
+                }
+                None => (ServingStatus::ServiceUnknown, format!("Unknown service '{}'", name)),
+            },
+        };
+        HealthCheckResponse {
+            status: status as i32,
+            message,
+            uptime_seconds: self.start_time.elapsed().as_secs() as i64,
+        }
+    }
+}
+
+pub struct HealthService {
+    checker: Arc<HealthChecker>,
+}
+
+impl HealthService {
+    pub fn new(checker: Arc<HealthChecker>) -> Self {
+        Self { checker }
+    }
+
     pub fn into_server(self) -> HealthServer<Self> {
         HealthServer::new(self)
     }
@@ -28,39 +196,157 @@ impl HealthService {
 
 #[tonic::async_trait]
 impl Health for HealthService {
-    type WatchStream = tokio_stream::wrappers::ReceiverStream<Result<HealthCheckResponse, Status>>;
-    
+    type WatchStream = ReceiverStream<Result<HealthCheckResponse, Status>>;
+
     async fn check(
         &self,
-        _request: Request<HealthCheckRequest>,
+        request: Request<HealthCheckRequest>,
     ) -> Result<Response<HealthCheckResponse>, Status> {
-        let status = *self.status.read().await;
-        let uptime = self.start_time.elapsed().as_secs() as i64;
-        
-        let response = HealthCheckResponse {
-            status: status as i32,
-            message: match status {
-                ServingStatus::Serving => "Gateway is healthy".to_string(),
-                ServingStatus::NotServing => "Gateway is not serving".to_string(),
-                _ => "Unknown status".to_string(),
-            },
-            uptime_seconds: uptime,
-        };
-        
-        Ok(Response::new(response))
+        let service = request.into_inner().service;
+        let snapshot = self.checker.snapshot();
+        Ok(Response::new(self.checker.response(&service, &snapshot)))
     }
-    
+
     async fn watch(
         &self,
-        _request: Request<HealthCheckRequest>,
+        request: Request<HealthCheckRequest>,
     ) -> Result<Response<Self::WatchStream>, Status> {
-        // TODO: Implement streaming health updates
-        Err(Status::unimplemented("Watch not yet implemented"))
+        let service = request.into_inner().service;
+        let checker = self.checker.clone();
+        let mut updates = checker.subscribe();
+        let (tx, rx) = mpsc::channel(4);
+
+        // Send the current status, then one message per status transition
+        tokio::spawn(async move {
+            let mut last = None;
+            loop {
+                let response = {
+                    let snapshot = updates.borrow_and_update();
+                    checker.response(&service, &snapshot)
+                };
+                if last != Some(response.status) {
+                    last = Some(response.status);
+                    if tx.send(Ok(response)).await.is_err() {
+                        break;
+                    }
+                }
+                tokio::select! {
+                    changed = updates.changed() => {
+                        if changed.is_err() {
+                            break;
+                        }
+                    }
+                    _ = tx.closed() => break,
+                }
+            }
+        });
+
+        Ok(Response::new(ReceiverStream::new(rx)))
     }
 }
 
-impl Default for HealthService {
-    fn default() -> Self {
-        Self::new()
+/// `GET /healthz` and `GET /readyz` for Kubernetes probes
+pub fn http_router(checker: Arc<HealthChecker>) -> Router {
+    let live = checker.clone();
+    Router::new()
+        .route(LIVENESS_PATH, get(move || {
+            let live = live.clone();
+            async move {
+                if live.is_live() {
+                    (StatusCode::OK, "ok")
+                } else {
+                    (StatusCode::SERVICE_UNAVAILABLE, "health checks stalled")
+                }
+            }
+        }))
+        .route(READINESS_PATH, get(move || {
+            let checker = checker.clone();
+            async move { readiness(&checker.snapshot()) }
+        }))
+}
+
+fn readiness(snapshot: &HealthSnapshot) -> impl IntoResponse {
+    let status = match snapshot.overall() {
+        ServingStatus::Serving => StatusCode::OK,
+        _ => StatusCode::SERVICE_UNAVAILABLE,
+    };
+    let components: serde_json::Map<String, serde_json::Value> = snapshot.components.iter()
+        .map(|(name, c)| {
+            let value = serde_json::json!({
+                "status": c.status.as_str_name(),
+                "message": c.message,
+                "critical": c.critical,
+            });
+            (name.to_string(), value)
+        })
+        .collect();
+    (status, Json(serde_json::json!({
+        "status": snapshot.overall().as_str_name(),
+        "message": snapshot.overall_message(),
+        "components": components,
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use tokio_stream::StreamExt;
+
+    fn checker(vault_up: Arc<AtomicBool>) -> Arc<HealthChecker> {
+        Arc::new(
+            HealthChecker::new(Duration::from_secs(60))
+                .probe("database", true, || async { Ok(()) })
+                .probe("vault", false, move || {
+                    let up = vault_up.load(Ordering::SeqCst);
+                    async move { if up { Ok(()) } else { Err("connection refused".to_string()) } }
+                }),
+        )
+    }
+
+    #[tokio::test]
+    async fn reports_overall_and_component_status() {
+        let vault_up = Arc::new(AtomicBool::new(false));
+        let checker = checker(vault_up);
+        assert_eq!(checker.response("", &checker.snapshot()).status, ServingStatus::Unknown as i32);
+
+        checker.check_now().await;
+        let snapshot = checker.snapshot();
+        // The vault is not critical, so the gateway still serves
+        assert_eq!(checker.response("", &snapshot).status, ServingStatus::Serving as i32);
+        assert!(checker.response("", &snapshot).message.contains("vault: connection refused"));
+        assert_eq!(checker.response("database", &snapshot).status, ServingStatus::Serving as i32);
+        assert_eq!(checker.response("vault", &snapshot).status, ServingStatus::NotServing as i32);
+        assert_eq!(checker.response("cache", &snapshot).status, ServingStatus::ServiceUnknown as i32);
+    }
+
+    #[tokio::test]
+    async fn critical_failures_fail_readiness() {
+        let checker = Arc::new(
+            HealthChecker::new(Duration::from_secs(60))
+                .probe("database", true, || async { Err("timed out".to_string()) }),
+        );
+        checker.check_now().await;
+        let response = readiness(&checker.snapshot()).into_response();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert!(checker.is_live());
+    }
+
+    #[tokio::test]
+    async fn watch_streams_transitions_only() {
+        let vault_up = Arc::new(AtomicBool::new(false));
+        let checker = checker(vault_up.clone());
+        checker.check_now().await;
+
+        let service = HealthService::new(checker.clone());
+        let request = Request::new(HealthCheckRequest { service: "vault".to_string() });
+        let mut stream = service.watch(request).await.unwrap().into_inner();
+        assert_eq!(stream.next().await.unwrap().unwrap().status, ServingStatus::NotServing as i32);
+
+        // An unchanged round sends nothing, a recovery is streamed
+        checker.check_now().await;
+        vault_up.store(true, Ordering::SeqCst);
+        checker.check_now().await;
+        assert_eq!(stream.next().await.unwrap().unwrap().status, ServingStatus::Serving as i32);
     }
 }
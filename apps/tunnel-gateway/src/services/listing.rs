@@ -0,0 +1,247 @@
+//! Time-range filters and sort orders shared by the memory listing RPCs.
+
+use identra_proto::memory::{SortDirection, SortField, SortOrder, TimeRange};
+use identra_core::error::Error;
+use tonic::Status;
+use uuid::Uuid;
+
+use crate::services::memory::MemoryModel;
+use crate::services::projection::Projection;
+use crate::services::ranking::Ranked;
+
+/// What a listing query narrows to, orders by and returns of each memory
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Listing {
+    pub time: TimeFilter,
+    pub sort: Sort,
+    pub projection: Projection,
+    /// Where the page starts; only QueryMemories pages
+    pub after: Option<Cursor>,
+}
+
+/// The last memory of a page, by its sort value and id; the next page starts past it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cursor {
+    pub value: i64,
+    pub id: Uuid,
+}
+
+impl Cursor {
+    pub fn new(sort: Sort, last: &MemoryModel) -> Self {
+        let value = match sort.key {
+            SortKey::UpdatedAt => last.updated_at,
+            SortKey::CreatedAt | SortKey::Relevance => last.created_at,
+        };
+        Self { value, id: Uuid::parse_str(&last.id).unwrap_or_default() }
+    }
+
+    /// Parse a page token; empty means the first page
+    pub fn from_token(token: &str) -> Result<Option<Self>, Status> {
+        if token.is_empty() {
+            return Ok(None);
+        }
+        let invalid = || Error::invalid_field("page_token", "Invalid page token");
+        let (value, id) = token.split_once('_').ok_or_else(invalid)?;
+        Ok(Some(Self {
+            value: value.parse().map_err(|_| invalid())?,
+            id: Uuid::parse_str(id).map_err(|_| invalid())?,
+        }))
+    }
+
+    pub fn token(&self) -> String {
+        format!("{}_{}", self.value, self.id)
+    }
+}
+
+/// Bounds on `created_at` and `updated_at` in unix seconds: starts inclusive, ends exclusive
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TimeFilter {
+    pub created_start: Option<i64>,
+    pub created_end: Option<i64>,
+    pub updated_start: Option<i64>,
+    pub updated_end: Option<i64>,
+}
+
+impl TimeFilter {
+    pub fn from_proto(created: Option<TimeRange>, updated: Option<TimeRange>) -> Result<Self, Status> {
+        let (created_start, created_end) = bounds("created", created)?;
+        let (updated_start, updated_end) = bounds("updated", updated)?;
+        Ok(Self { created_start, created_end, updated_start, updated_end })
+    }
+}
+
+fn bounds(field: &str, range: Option<TimeRange>) -> Result<(Option<i64>, Option<i64>), Status> {
+    let Some(range) = range else {
+        return Ok((None, None));
+    };
+    let start = range.start.map(|ts| ts.seconds);
+    let end = range.end.map(|ts| ts.seconds);
+    if let (Some(start), Some(end)) = (start, end) {
+        if start > end {
+            return Err(Error::invalid_field(format!("{}.start", field), "start must not be after end").into());
+        }
+    }
+    Ok((start, end))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    CreatedAt,
+    UpdatedAt,
+    /// Ranking score, for vector search
+    Relevance,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sort {
+    pub key: SortKey,
+    pub descending: bool,
+}
+
+impl Default for Sort {
+    fn default() -> Self {
+        Self { key: SortKey::CreatedAt, descending: true }
+    }
+}
+
+impl Sort {
+    /// `default` applies when the field is unspecified; relevance is only accepted when `ranked`
+    pub fn from_proto(sort: Option<SortOrder>, default: SortKey, ranked: bool) -> Result<Self, Status> {
+        let sort = sort.unwrap_or_default();
+        let key = match SortField::try_from(sort.field) {
+            Ok(SortField::Unspecified) => default,
+            Ok(SortField::CreatedAt) => SortKey::CreatedAt,
+            Ok(SortField::UpdatedAt) => SortKey::UpdatedAt,
+            Ok(SortField::Relevance) if ranked => SortKey::Relevance,
+            Ok(SortField::Relevance) => {
+                return Err(Error::invalid_field("sort.field", "Relevance ordering needs SearchMemories").into());
+            }
+            Err(_) => return Err(Error::invalid_field("sort.field", "Unknown sort field").into()),
+        };
+        let descending = match SortDirection::try_from(sort.direction) {
+            Ok(direction) => direction == SortDirection::Descending,
+            Err(_) => return Err(Error::invalid_field("sort.direction", "Unknown sort direction").into()),
+        };
+        Ok(Self { key, descending })
+    }
+
+    /// SQL ORDER BY clause; ids break ties so pages are stable
+    pub fn order_by(&self) -> &'static str {
+        match (self.key, self.descending) {
+            (SortKey::CreatedAt, true) => "created_at DESC, id DESC",
+            (SortKey::CreatedAt, false) => "created_at ASC, id ASC",
+            (SortKey::UpdatedAt, true) => "updated_at DESC, id DESC",
+            (SortKey::UpdatedAt, false) => "updated_at ASC, id ASC",
+            // Listings have no score to order by; `from_proto` keeps relevance to search
+            (SortKey::Relevance, _) => "created_at DESC, id DESC",
+        }
+    }
+
+    /// SQL condition keeping rows past a `Cursor` bound at `$param` (value) and `$param + 1`
+    /// (id) in `order_by` order; NULL bindings keep every row
+    pub fn past(&self, param: usize) -> String {
+        let (column, op) = match (self.key, self.descending) {
+            (SortKey::UpdatedAt, true) => ("updated_at", "<"),
+            (SortKey::UpdatedAt, false) => ("updated_at", ">"),
+            (SortKey::CreatedAt, false) => ("created_at", ">"),
+            (SortKey::CreatedAt, true) | (SortKey::Relevance, _) => ("created_at", "<"),
+        };
+        format!("(${0}::bigint IS NULL OR ({1}, id) {2} (${0}, ${3}::uuid))", param, column, op, param + 1)
+    }
+
+    /// Reorder ranked search results; relevance descending keeps the ranking's own order,
+    /// which for MMR is the pick order rather than strictly by score
+    pub fn apply(&self, ranked: &mut [Ranked]) {
+        match (self.key, self.descending) {
+            (SortKey::Relevance, true) => {}
+            (SortKey::Relevance, false) => ranked.reverse(),
+            (key, descending) => {
+                ranked.sort_by(|a, b| {
+                    let (a, b) = (&a.found.memory, &b.found.memory);
+                    let order = match key {
+                        SortKey::UpdatedAt => a.updated_at.cmp(&b.updated_at),
+                        _ => a.created_at.cmp(&b.created_at),
+                    }
+                    .then_with(|| a.id.cmp(&b.id));
+                    if descending { order.reverse() } else { order }
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::SimilarMemory;
+    use crate::services::memory::MemoryModel;
+    use std::collections::HashMap;
+
+    fn ranked(id: &str, created_at: i64, score: f32) -> Ranked {
+        let memory = MemoryModel {
+            id: id.to_string(),
+            content: String::new(),
+            metadata: HashMap::new(),
+            embedding: vec![],
+            tags: vec![],
+            created_at,
+            updated_at: created_at,
+        };
+        Ranked {
+            found: SimilarMemory { memory, similarity: score, last_accessed_at: None },
+            rerank_score: None,
+            recency: 1.0,
+            relevance: score,
+            redundancy: 0.0,
+            score,
+        }
+    }
+
+    fn ids(ranked: &[Ranked]) -> Vec<&str> {
+        ranked.iter().map(|r| r.found.memory.id.as_str()).collect()
+    }
+
+    #[test]
+    fn sort_defaults_and_rejects_relevance_outside_search() {
+        assert_eq!(Sort::from_proto(None, SortKey::CreatedAt, false).unwrap(), Sort::default());
+        let relevance = SortOrder { field: SortField::Relevance as i32, direction: SortDirection::Descending as i32 };
+        assert!(Sort::from_proto(Some(relevance), SortKey::CreatedAt, false).is_err());
+        assert_eq!(Sort::from_proto(Some(relevance), SortKey::Relevance, true).unwrap().key, SortKey::Relevance);
+
+        let oldest = SortOrder { field: SortField::UpdatedAt as i32, direction: SortDirection::Ascending as i32 };
+        assert_eq!(Sort::from_proto(Some(oldest), SortKey::CreatedAt, false).unwrap().order_by(), "updated_at ASC, id ASC");
+    }
+
+    #[test]
+    fn search_results_reorder_by_time_with_id_tie_break() {
+        let mut results = vec![ranked("b", 10, 0.9), ranked("c", 20, 0.8), ranked("a", 10, 0.7)];
+        Sort { key: SortKey::CreatedAt, descending: false }.apply(&mut results);
+        assert_eq!(ids(&results), ["a", "b", "c"]);
+
+        Sort { key: SortKey::Relevance, descending: false }.apply(&mut results);
+        assert_eq!(ids(&results), ["c", "b", "a"]);
+    }
+
+    #[test]
+    fn cursors_round_trip_through_page_tokens() {
+        let last = ranked("6f1c2a4e-8b0d-4c7e-9a51-3d2f1e0b7c68", -5, 0.0).found.memory;
+        let cursor = Cursor::new(Sort::default(), &last);
+        assert_eq!(cursor.token(), "-5_6f1c2a4e-8b0d-4c7e-9a51-3d2f1e0b7c68");
+        assert_eq!(Cursor::from_token(&cursor.token()).unwrap(), Some(cursor));
+        assert_eq!(Cursor::from_token("").unwrap(), None);
+        for token in ["5", "x_6f1c2a4e-8b0d-4c7e-9a51-3d2f1e0b7c68", "5_not-a-uuid"] {
+            assert_eq!(Error::from_status(&Cursor::from_token(token).unwrap_err()).field_violations()[0].field, "page_token");
+        }
+
+        let oldest = Sort { key: SortKey::UpdatedAt, descending: false };
+        assert_eq!(oldest.past(7), "($7::bigint IS NULL OR (updated_at, id) > ($7, $8::uuid))");
+    }
+
+    #[test]
+    fn time_ranges_must_not_be_inverted() {
+        let at = |seconds| Some(prost_types::Timestamp { seconds, nanos: 0 });
+        let filter = TimeFilter::from_proto(Some(TimeRange { start: at(10), end: None }), None).unwrap();
+        assert_eq!(filter, TimeFilter { created_start: Some(10), ..Default::default() });
+        assert!(TimeFilter::from_proto(None, Some(TimeRange { start: at(20), end: at(10) })).is_err());
+    }
+}
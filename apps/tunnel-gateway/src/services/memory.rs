@@ -7,13 +7,54 @@ use identra_proto::memory::{
     DeleteMemoryRequest, DeleteMemoryResponse,
     SearchMemoriesRequest, SearchMemoriesResponse,
     GetRecentMemoriesRequest, GetRecentMemoriesResponse,
+    Conversation, ConversationTurn,
+    CreateConversationRequest, CreateConversationResponse,
+    GetConversationRequest, GetConversationResponse,
+    ListConversationsRequest, ListConversationsResponse,
+    AppendConversationTurnsRequest, AppendConversationTurnsResponse, NewConversationTurn,
+    DeleteConversationRequest, DeleteConversationResponse,
+    CreateNamespaceRequest, CreateNamespaceResponse,
+    ListNamespacesRequest, ListNamespacesResponse,
+    UpdateNamespaceRequest, UpdateNamespaceResponse,
+    DeleteNamespaceRequest, DeleteNamespaceResponse,
+    GrantAccessRequest, GrantAccessResponse,
+    RevokeAccessRequest, RevokeAccessResponse,
+    ListGrantsRequest, ListGrantsResponse,
+    WatchMemoriesRequest, MemoryEvent,
+    ListTrashRequest, ListTrashResponse, TrashedMemory,
+    RestoreMemoryRequest, RestoreMemoryResponse,
+    PurgeMemoryRequest, PurgeMemoryResponse,
+    NamespaceSharing, Permission, SecretPolicy,
+    BuildContextRequest, BuildContextResponse,
+    GetMemoryStatsRequest, GetMemoryStatsResponse,
+    ListTagsRequest, ListTagsResponse,
+    RenameTagRequest, RenameTagResponse,
+    MergeTagsRequest, MergeTagsResponse,
+    RetagMemoriesRequest, RetagMemoriesResponse,
 };
-use crate::database::MemoryDatabase;
+use crate::database::{IdempotencyKey, MemoryDatabase, Replay, SimilarMemory};
+use crate::services::acl::{self, GrantModel, NamespaceAccess, Principal};
+use crate::services::audit::{AuditEvent, AuditLog};
+use crate::services::context::{self, Candidate, Policy};
+use crate::services::listing::{Cursor, Listing, Sort, SortKey, TimeFilter};
+use crate::services::projection::Projection;
+use crate::services::ranking::{self, Ranking, Rerank};
+use crate::services::secrets::{self, SECRETS_METADATA_KEY};
+use crate::services::tags;
+use crate::limits::request::check_embedding;
+use crate::limits::{RequestLimits, StorageQuota};
+use crate::services::namespace::{self, NamespaceModel, DEFAULT_NAMESPACE};
+use crate::services::watch::{ChangeFeed, MemoryChange};
+use crate::telemetry;
 use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use identra_core::error::{Error, ErrorCode};
 use tonic::{Request, Response, Status};
 use uuid::Uuid;
-use fastembed::{TextEmbedding, InitOptions, EmbeddingModel};
+use fastembed::{TextEmbedding, InitOptions, EmbeddingModel, RerankInitOptions, TextRerank};
 use std::collections::HashMap;
+use sha2::{Digest, Sha256};
+use tokio_stream::wrappers::ReceiverStream;
 
 // Shared model for Database <-> Service communication
 #[derive(Debug, Clone)]
@@ -27,13 +68,144 @@ pub struct MemoryModel {
     pub updated_at: i64,
 }
 
+#[derive(Debug, Clone)]
+pub struct ConversationModel {
+    pub id: String,
+    pub title: String,
+    pub model: String,
+    pub metadata: HashMap<String, String>,
+    pub created_at: i64,
+    pub updated_at: i64,
+    pub turn_count: i32,
+}
+
+#[derive(Debug, Clone)]
+pub struct ConversationTurnModel {
+    pub id: String,
+    pub conversation_id: String,
+    pub sequence: i32,
+    pub role: String,
+    pub content: String,
+    pub model: String,
+    pub metadata: HashMap<String, String>,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct NewTurnModel {
+    pub role: String,
+    pub content: String,
+    pub model: String,
+    pub metadata: HashMap<String, String>,
+}
+
+const TURN_ROLES: &[&str] = &["user", "assistant", "system"];
+
+pub fn memory_to_proto(m: MemoryModel, namespace: &str) -> Memory {
+    Memory {
+        id: m.id,
+        content: m.content,
+        metadata: m.metadata,
+        embedding: m.embedding,
+        created_at: Some(prost_types::Timestamp { seconds: m.created_at, nanos: 0 }),
+        updated_at: Some(prost_types::Timestamp { seconds: m.updated_at, nanos: 0 }),
+        tags: m.tags,
+        namespace: namespace.to_string(),
+        snippet: String::new(),
+    }
+}
+
+/// Hash of what a StoreMemory request stores, to tell a retry from a reused idempotency key
+fn store_fingerprint(content: &str, metadata: &HashMap<String, String>, tags: &[String]) -> String {
+    let metadata: std::collections::BTreeMap<_, _> = metadata.iter().collect();
+    let canonical = serde_json::json!([content, metadata, tags]).to_string();
+    hex::encode(Sha256::digest(canonical.as_bytes()))
+}
+
+/// Answer a StoreMemory retry with the memory its first attempt stored
+fn replayed(replay: Replay, fingerprint: &str, audit: &mut AuditEvent) -> Result<Response<StoreMemoryResponse>, Status> {
+    if replay.fingerprint != fingerprint {
+        return Err(Error::invalid_field("idempotency_key", "Key was already used for a different request").into());
+    }
+    audit.add_target(&replay.memory_id);
+    Ok(Response::new(StoreMemoryResponse {
+        memory_id: replay.memory_id,
+        success: true,
+        message: "Already stored".into(),
+        replayed: true,
+    }))
+}
+
+/// Validate the turns of an AppendConversationTurns request and screen their content
+/// for credentials as `policy` says, like StoreMemory does; encrypted turns are not scanned
+fn new_turns(turns: Vec<NewConversationTurn>, policy: SecretPolicy) -> Result<Vec<NewTurnModel>, Status> {
+    turns.into_iter().map(|t| {
+        if !TURN_ROLES.contains(&t.role.as_str()) {
+            return Err(Error::invalid_field("turns.role", format!("Unknown turn role '{}'", t.role)).into());
+        }
+        if t.content.trim().is_empty() { return Err(Error::invalid_field("turns.content", "Turn content required").into()); }
+        let encrypted = t.metadata.get("encrypted").map(String::as_str) == Some("true");
+        let screened = secrets::screen(if encrypted { SecretPolicy::Off } else { policy }, t.content)?;
+        let mut metadata = t.metadata;
+        if let Some(findings) = screened.metadata() {
+            metadata.insert(SECRETS_METADATA_KEY.to_string(), findings);
+        }
+        Ok(NewTurnModel { role: t.role, content: screened.content, model: t.model, metadata })
+    }).collect()
+}
+
+fn conversation_to_proto(c: ConversationModel, turns: Vec<ConversationTurnModel>, namespace: &str) -> Conversation {
+    Conversation {
+        id: c.id,
+        title: c.title,
+        model: c.model,
+        metadata: c.metadata,
+        created_at: Some(prost_types::Timestamp { seconds: c.created_at, nanos: 0 }),
+        updated_at: Some(prost_types::Timestamp { seconds: c.updated_at, nanos: 0 }),
+        turn_count: c.turn_count,
+        turns: turns.into_iter().map(turn_to_proto).collect(),
+        namespace: namespace.to_string(),
+    }
+}
+
+fn turn_to_proto(t: ConversationTurnModel) -> ConversationTurn {
+    ConversationTurn {
+        id: t.id,
+        conversation_id: t.conversation_id,
+        sequence: t.sequence,
+        role: t.role,
+        content: t.content,
+        model: t.model,
+        metadata: t.metadata,
+        created_at: Some(prost_types::Timestamp { seconds: t.created_at, nanos: 0 }),
+    }
+}
+
+type SharedEmbedder = Arc<Mutex<TextEmbedding>>;
+type SharedReranker = Arc<Mutex<TextRerank>>;
+
 pub struct MemoryServiceImpl {
     db: Arc<MemoryDatabase>,
-    embedder: Arc<Mutex<TextEmbedding>>, 
+    // Loaded embedding models keyed by namespace `embedding_model` name
+    embedders: Mutex<HashMap<String, SharedEmbedder>>,
+    // Cross-encoders keyed by model name, loaded on the first search that asks for one
+    rerankers: Mutex<HashMap<String, SharedReranker>>,
+    changes: ChangeFeed,
+    // How long trashed memories are kept before the scheduled purge
+    trash_retention_secs: i64,
+    audit: AuditLog,
+    quota: StorageQuota,
+    limits: RequestLimits,
 }
 
 impl MemoryServiceImpl {
-    pub fn new(db: Arc<MemoryDatabase>) -> Self {
+    pub fn new(
+        db: Arc<MemoryDatabase>,
+        trash_retention_days: i64,
+        audit: AuditLog,
+        quota: StorageQuota,
+        limits: RequestLimits,
+    ) -> Self {
         tracing::info!("🧠 Initializing Neural Engine...");
         
         let options = InitOptions::new(EmbeddingModel::AllMiniLML6V2)
@@ -42,133 +214,1162 @@ impl MemoryServiceImpl {
         let embedder = TextEmbedding::try_new(options)
             .expect("Failed to load local embedding model");
 
+        let embedders = HashMap::from([
+            (namespace::DEFAULT_EMBEDDING_MODEL.to_string(), Arc::new(Mutex::new(embedder))),
+        ]);
+
         Self { 
             db, 
-            embedder: Arc::new(Mutex::new(embedder)) 
+            embedders: Mutex::new(embedders),
+            rerankers: Mutex::new(HashMap::new()),
+            changes: ChangeFeed::new(),
+            trash_retention_secs: trash_retention_days * 86_400,
+            audit,
+            quota,
+            limits,
         }
     }
     
-    pub fn into_server(self) -> MemoryServiceServer<Self> {
-        MemoryServiceServer::new(self)
+    /// Shared so the REST API can call the same instance
+    pub fn into_server(self: Arc<Self>) -> MemoryServiceServer<Self> {
+        MemoryServiceServer::from_arc(self)
     }
     
-    fn generate_embedding(&self, content: &str) -> Result<Vec<f32>, Status> {
+    /// Embed a short text with the default model, for health checks
+    pub fn check_embedder(&self) -> Result<(), String> {
+        self.generate_embedding(namespace::DEFAULT_EMBEDDING_MODEL, "health check")
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+
+    /// Get the embedder for a model name, loading it on first use
+    fn embedder(&self, model: &str) -> Result<SharedEmbedder, Error> {
+        let mut embedders = self.embedders.lock()
+            .map_err(|_| Error::internal("AI Engine lock failure"))?;
+        if let Some(embedder) = embedders.get(model) {
+            return Ok(embedder.clone());
+        }
+
+        let model_kind = namespace::parse_embedding_model(model)
+            .ok_or_else(|| Error::new(ErrorCode::UnsupportedEmbeddingModel, format!("Unsupported embedding model '{}'", model)))?;
+        tracing::info!("🧠 Loading embedding model {}", model);
+        let embedder = TextEmbedding::try_new(InitOptions::new(model_kind))
+            .map_err(|e| Error::internal(format!("Failed to load embedding model: {}", e)))?;
+
+        let embedder = Arc::new(Mutex::new(embedder));
+        embedders.insert(model.to_string(), embedder.clone());
+        Ok(embedder)
+    }
+    
+    #[tracing::instrument(skip_all, fields(model = model))]
+    fn generate_embedding(&self, model: &str, content: &str) -> Result<Vec<f32>, Error> {
         let documents = vec![content.to_string()];
+        let embedder = self.embedder(model)?;
         // FIX: Added 'mut' here because fastembed v5 requires mutable access
-        let mut embedder = self.embedder.lock()
-            .map_err(|_| Status::internal("AI Engine lock failure"))?;
+        let mut embedder = embedder.lock()
+            .map_err(|_| Error::internal("AI Engine lock failure"))?;
         
+        let (batch, started) = (documents.len(), Instant::now());
         let embeddings = embedder.embed(documents, None)
-            .map_err(|e| Status::internal(format!("Embedding failed: {}", e)))?;
+            .map_err(|e| Error::internal(format!("Embedding failed: {}", e)))?;
+        telemetry::record_embedding(model, batch, started.elapsed());
         
         embeddings.into_iter().next()
-            .ok_or_else(|| Status::internal("No embedding generated"))
+            .ok_or_else(|| Error::internal("No embedding generated"))
+    }
+
+    /// Get the cross-encoder for a model name, loading it on first use
+    fn reranker(&self, model: &str) -> Result<SharedReranker, Error> {
+        let mut rerankers = self.rerankers.lock()
+            .map_err(|_| Error::internal("AI Engine lock failure"))?;
+        if let Some(reranker) = rerankers.get(model) {
+            return Ok(reranker.clone());
+        }
+
+        let model_kind = ranking::parse_reranker_model(model)
+            .ok_or_else(|| Error::invalid_field("ranking.rerank.model", format!("Unsupported re-ranking model '{}'", model)))?;
+        tracing::info!("🧠 Loading re-ranking model {}", model);
+        let reranker = TextRerank::try_new(RerankInitOptions::new(model_kind))
+            .map_err(|e| Error::internal(format!("Failed to load re-ranking model: {}", e)))?;
+
+        let reranker = Arc::new(Mutex::new(reranker));
+        rerankers.insert(model.to_string(), reranker.clone());
+        Ok(reranker)
+    }
+
+    /// Cross-encoder relevance of each memory to `query` in (0, 1), in the order given
+    #[tracing::instrument(skip_all, fields(model = %rerank.model, batch = found.len()))]
+    async fn rerank(&self, rerank: &Rerank, found: &[SimilarMemory]) -> Result<Vec<f32>, Error> {
+        if found.is_empty() {
+            return Ok(Vec::new());
+        }
+        let documents: Vec<String> = found.iter().map(|f| f.memory.content.clone()).collect();
+        let reranker = self.reranker(&rerank.model)?;
+        let (model, query) = (rerank.model.clone(), rerank.query.clone());
+
+        // A model pass per document; keep it off the async workers
+        let results = tokio::task::spawn_blocking(move || {
+            let mut reranker = reranker.lock()
+                .map_err(|_| Error::internal("AI Engine lock failure"))?;
+            let started = Instant::now();
+            let documents: Vec<&str> = documents.iter().map(String::as_str).collect();
+            let results = reranker.rerank(query.as_str(), documents, false, None)
+                .map_err(|e| Error::internal(format!("Re-ranking failed: {}", e)))?;
+            telemetry::record_rerank(&model, started.elapsed());
+            Ok::<_, Error>(results)
+        })
+        .await
+        .map_err(|e| Error::internal(format!("Re-ranking task failed: {}", e)))??;
+
+        // Results come back best first; the models emit logits
+        let mut scores = vec![0.0; found.len()];
+        for result in results {
+            scores[result.index] = 1.0 / (1.0 + (-result.score).exp());
+        }
+        Ok(scores)
+    }
+
+    /// Resolve a request's namespace for the calling user. A name refers to one of the
+    /// caller's own namespaces (the default one is created on first use); an id refers to
+    /// a namespace shared with them. Namespaces the caller cannot see are NOT_FOUND.
+    async fn resolve_namespace(&self, principal: &Principal, requested: &str) -> Result<NamespaceAccess, Status> {
+        let name = namespace::requested_name(requested);
+        let found = if Uuid::parse_str(name).is_ok() {
+            self.db.get_namespace_by_id(name)
+                .await
+                .map_err(|e| Error::internal(format!("DB Error: {}", e)))?
+        } else if name == DEFAULT_NAMESPACE {
+            let now = chrono::Utc::now().timestamp();
+            Some(self.db.get_or_create_namespace(&principal.user_id, name, now)
+                .await
+                .map_err(|e| Error::internal(format!("DB Error: {}", e)))?)
+        } else {
+            self.db.get_namespace(&principal.user_id, name)
+                .await
+                .map_err(|e| Error::internal(format!("DB Error: {}", e)))?
+        };
+
+        let access = match found {
+            Some(ns) => NamespaceAccess::resolve(&self.db, principal, ns)
+                .await
+                .map_err(|e| Error::internal(format!("DB Error: {}", e)))?,
+            None => None,
+        };
+        access.ok_or_else(|| Error::new(ErrorCode::NamespaceNotFound, format!("Namespace '{}' not found", name)).into())
+    }
+
+    /// Stamp memories as read for recency ranking. Best effort: a failed stamp never fails the read.
+    async fn record_access(&self, namespace_id: &str, ids: &[String], now: i64) {
+        if let Err(e) = self.db.touch_memories(namespace_id, ids, now).await {
+            tracing::warn!("Failed to record memory access: {}", e);
+        }
+    }
+
+    /// Check the caller holds `needed` on a single memory, by namespace or per-memory grant
+    async fn require_memory(&self, access: &NamespaceAccess, memory_id: &str, needed: Permission) -> Result<(), Status> {
+        let held = access.memory_permission(&self.db, memory_id)
+            .await
+            .map_err(Error::internal)?;
+        if held >= needed {
+            Ok(())
+        } else if held >= Permission::Read {
+            Err(Error::permission_denied(format!("{} access to memory required", needed.as_str_name())).into())
+        } else {
+            Err(Error::new(ErrorCode::MemoryNotFound, "Memory not found").into())
+        }
+    }
+
+    /// Resolve the resource a grant RPC refers to and check the caller may manage its grants
+    async fn resolve_grant_target(
+        &self,
+        principal: &Principal,
+        namespace: &str,
+        memory_id: &str,
+    ) -> Result<(NamespaceAccess, Option<String>), Status> {
+        let access = self.resolve_namespace(principal, namespace).await?;
+        let memory_id = memory_id.trim();
+        if memory_id.is_empty() {
+            access.require(Permission::Admin)?;
+            return Ok((access, None));
+        }
+
+        self.require_memory(&access, memory_id, Permission::Admin).await?;
+        self.db.get_memory(&access.namespace.id, memory_id, &Projection::default())
+            .await
+            .map_err(Error::internal)?
+            .ok_or_else(|| Error::new(ErrorCode::MemoryNotFound, "Memory not found"))?;
+        Ok((access, Some(memory_id.to_string())))
     }
 }
 
 #[tonic::async_trait]
 impl MemoryService for MemoryServiceImpl {
+    type WatchMemoriesStream = ReceiverStream<Result<MemoryEvent, Status>>;
+
     async fn store_memory(&self, req: Request<StoreMemoryRequest>) -> Result<Response<StoreMemoryResponse>, Status> {
+        let principal = Principal::from_request(&req)?;
         let r = req.into_inner();
-        if r.content.trim().is_empty() { return Err(Status::invalid_argument("Content required")); }
+        let mut audit = AuditEvent::new("memory", "StoreMemory", &principal);
+
+        let result: Result<Response<StoreMemoryResponse>, Status> = async {
+            if r.content.trim().is_empty() { return Err(Error::invalid_field("content", "Content required").into()); }
+            self.limits.check_store(&r)?;
+            let tags = tags::tag_names("tags", &r.tags)?;
+            let access = self.resolve_namespace(&principal, &r.namespace).await?;
+            audit.namespace_id = access.namespace.id.clone();
+            access.require(Permission::Write)?;
+            let ns = &access.namespace;
+            let now = chrono::Utc::now().timestamp();
+
+            // A retry of a request that already stored its memory gets that memory back
+            let fingerprint = store_fingerprint(&r.content, &r.metadata, &tags);
+            let idempotency = (!r.idempotency_key.is_empty() && self.limits.idempotency_window_secs > 0).then(|| IdempotencyKey {
+                key: &r.idempotency_key,
+                fingerprint: &fingerprint,
+                not_before: now.saturating_sub(self.limits.idempotency_window_secs),
+            });
+            if let Some(key) = idempotency {
+                let earlier = self.db.find_idempotency_key(&ns.id, key.key, key.not_before)
+                    .await
+                    .map_err(Error::internal)?;
+                if let Some(replay) = earlier {
+                    return replayed(replay, &fingerprint, &mut audit);
+                }
+            }
+
+            // Credentials are caught before anything is embedded or stored; the fingerprint
+            // above covers the request as sent, so retries match whatever the policy did
+            let encrypted = r.metadata.get("encrypted").map(String::as_str) == Some("true");
+            let policy = if encrypted { SecretPolicy::Off } else { ns.secret_policy() };
+            let screened = secrets::screen(policy, r.content)?;
+            let mut metadata = r.metadata;
+            if let Some(findings) = screened.metadata() {
+                metadata.insert(SECRETS_METADATA_KEY.to_string(), findings);
+            }
+
+            // Storage is charged to the namespace owner, also when a grantee writes
+            let (memories, bytes) = self.db.owner_usage(&ns.owner_id)
+                .await
+                .map_err(|e| Error::internal(format!("DB Error: {}", e)))?;
+            self.quota.check(memories, bytes, screened.content.len() as i64)?;
         
-        let id = Uuid::new_v4().to_string();
-        let now = chrono::Utc::now().timestamp();
+            let id = Uuid::new_v4().to_string();
         
-        let embedding = self.generate_embedding(&r.content)?;
+            let embedding = self.generate_embedding(ns.embedding_model(), &screened.content)?;
+            let memory = MemoryModel {
+                id: id.clone(),
+                content: screened.content,
+                metadata,
+                embedding,
+                tags,
+                created_at: now,
+                updated_at: now,
+            };
         
-        self.db.store_memory(&id, &r.content, &embedding, &r.metadata, &r.tags, now, now)
-            .await
-            .map_err(|e| Status::internal(format!("DB Error: {}", e)))?;
+            let earlier = self.db.store_memory(&ns.id, &memory, ns.expires_at(now), idempotency)
+                .await
+                .map_err(|e| Error::internal(format!("DB Error: {}", e)))?;
+            if let Some(replay) = earlier {
+                return replayed(replay, &fingerprint, &mut audit);
+            }
+            audit.add_target(&id);
         
-        tracing::info!("Indexed memory {}", id);
-        Ok(Response::new(StoreMemoryResponse { memory_id: id, success: true, message: "Saved to Cloud".into() }))
+            tracing::info!("Indexed memory {} in namespace {}", id, ns.name);
+            self.changes.publish(MemoryChange::Stored {
+                namespace_id: ns.id.clone(),
+                memory: MemoryModel { embedding: vec![], ..memory },
+            });
+            Ok(Response::new(StoreMemoryResponse { memory_id: id, success: true, message: "Saved to Cloud".into(), replayed: false }))
+        }.await;
+
+        self.audit.record(audit, &result);
+        result
     }
     
     async fn search_memories(&self, req: Request<SearchMemoriesRequest>) -> Result<Response<SearchMemoriesResponse>, Status> {
+        let principal = Principal::from_request(&req)?;
         let r = req.into_inner();
+        let mut audit = AuditEvent::new("memory", "SearchMemories", &principal);
+
+        let result: Result<Response<SearchMemoriesResponse>, Status> = async {
+            check_embedding("query_embedding", &r.query_embedding)?;
+            let access = self.resolve_namespace(&principal, &r.namespace).await?;
+            audit.namespace_id = access.namespace.id.clone();
         
-        let matches = self.db.search_memories(&r.query_embedding, r.limit, r.similarity_threshold)
-            .await
-            .map_err(|e| Status::internal(format!("Search failed: {}", e)))?;
-        
-        let proto_matches = matches.into_iter().map(|m| MemoryMatch {
-            memory: Some(Memory {
-                id: m.id,
-                content: m.content,
-                metadata: m.metadata,
-                embedding: vec![],
-                created_at: Some(prost_types::Timestamp { seconds: m.created_at, nanos: 0 }),
-                updated_at: Some(prost_types::Timestamp { seconds: m.updated_at, nanos: 0 }),
-                tags: m.tags,
-            }),
-            similarity_score: 0.99,
-        }).collect();
-        
-        Ok(Response::new(SearchMemoriesResponse { matches: proto_matches }))
+            let ranking = Ranking::from_proto(r.ranking)?;
+            let time = TimeFilter::from_proto(r.created, r.updated)?;
+            let sort = Sort::from_proto(r.sort, SortKey::Relevance, true)?;
+            let projection = Projection::from_proto(r.fields)?;
+            let limit = if r.limit > 0 { r.limit } else { 50 };
+            let found = self.db.similar_memories(&access.namespace.id, &r.query_embedding, ranking.candidates(limit), r.similarity_threshold, &time, access.memory_filter(), ranking.mmr_lambda.is_some() || projection.embedding)
+                .await
+                .map_err(|e| Error::internal(format!("Search failed: {}", e)))?;
+
+            let rerank_scores = match &ranking.rerank {
+                Some(rerank) => {
+                    let depth = rerank.depth(limit).min(found.len());
+                    Some(self.rerank(rerank, &found[..depth]).await?)
+                }
+                None => None,
+            };
+            let now = chrono::Utc::now().timestamp();
+            let mut ranked = ranking::rank(found, rerank_scores, limit as usize, &ranking, now);
+            sort.apply(&mut ranked);
+            let ids: Vec<String> = ranked.iter().map(|r| r.found.memory.id.clone()).collect();
+            ids.iter().for_each(|id| audit.add_target(id));
+            self.record_access(&access.namespace.id, &ids, now).await;
+
+            let proto_matches = ranked.into_iter().map(|r| MemoryMatch {
+                similarity_score: r.found.similarity,
+                score: r.score,
+                recency: r.recency,
+                relevance: r.relevance,
+                redundancy: r.redundancy,
+                rerank_score: r.rerank_score.unwrap_or_default(),
+                memory: Some(projection.apply(memory_to_proto(r.found.memory, &access.namespace.name))),
+            }).collect();
+
+            Ok(Response::new(SearchMemoriesResponse { matches: proto_matches }))
+        }.await;
+
+        self.audit.record(audit, &result);
+        result
     }
 
     async fn query_memories(&self, req: Request<QueryMemoriesRequest>) -> Result<Response<QueryMemoriesResponse>, Status> {
+        let principal = Principal::from_request(&req)?;
         let r = req.into_inner();
-        let limit = if r.limit > 0 { r.limit } else { 50 };
+        let mut audit = AuditEvent::new("memory", "QueryMemories", &principal);
+
+        let result: Result<Response<QueryMemoriesResponse>, Status> = async {
+            let limit = if r.limit > 0 { r.limit } else { 50 };
+            let listing = Listing {
+                time: TimeFilter::from_proto(r.created, r.updated)?,
+                sort: Sort::from_proto(r.sort, SortKey::CreatedAt, false)?,
+                projection: Projection::from_proto(r.fields)?,
+                after: Cursor::from_token(&r.page_token)?,
+            };
+            let access = self.resolve_namespace(&principal, &r.namespace).await?;
+            audit.namespace_id = access.namespace.id.clone();
         
-        let results = self.db.query_memories(&r.query, limit)
-            .await
-            .map_err(|e| Status::internal(e.to_string()))?;
+            let results = self.db.query_memories(&access.namespace.id, &r.query, &r.filters, limit, &listing, access.memory_filter())
+                .await
+                .map_err(Error::internal)?;
+            results.iter().for_each(|m| audit.add_target(&m.id));
+            let next_page_token = match results.last() {
+                Some(last) if results.len() == limit as usize => Cursor::new(listing.sort, last).token(),
+                _ => String::new(),
+            };
+            let ids: Vec<String> = results.iter().map(|m| m.id.clone()).collect();
+            self.record_access(&access.namespace.id, &ids, chrono::Utc::now().timestamp()).await;
             
-        let memories: Vec<Memory> = results.into_iter().map(|m| Memory {
-            id: m.id, content: m.content, metadata: m.metadata, embedding: vec![],
-            created_at: Some(prost_types::Timestamp { seconds: m.created_at, nanos: 0 }),
-            updated_at: Some(prost_types::Timestamp { seconds: m.updated_at, nanos: 0 }),
-            tags: m.tags,
-        }).collect();
+            let memories: Vec<Memory> = results.into_iter().map(|m| listing.projection.apply(memory_to_proto(m, &access.namespace.name))).collect();
         
-        Ok(Response::new(QueryMemoriesResponse { total_count: memories.len() as i32, memories }))
+            Ok(Response::new(QueryMemoriesResponse { total_count: memories.len() as i32, memories, next_page_token }))
+        }.await;
+
+        self.audit.record(audit, &result);
+        result
     }
     
     async fn get_memory(&self, req: Request<GetMemoryRequest>) -> Result<Response<GetMemoryResponse>, Status> {
+        let principal = Principal::from_request(&req)?;
         let r = req.into_inner();
-        let result = self.db.get_memory(&r.memory_id)
-            .await
-            .map_err(|e| Status::internal(e.to_string()))?;
+        let mut audit = AuditEvent::new("memory", "GetMemory", &principal).target(&r.memory_id);
+
+        let result: Result<Response<GetMemoryResponse>, Status> = async {
+            let access = self.resolve_namespace(&principal, &r.namespace).await?;
+            audit.namespace_id = access.namespace.id.clone();
+            self.require_memory(&access, &r.memory_id, Permission::Read).await?;
+            let projection = Projection::from_proto(r.fields)?;
+
+            let result = self.db.get_memory(&access.namespace.id, &r.memory_id, &projection)
+                .await
+                .map_err(Error::internal)?;
         
-        match result {
-            Some(m) => Ok(Response::new(GetMemoryResponse { memory: Some(Memory {
-                id: m.id, content: m.content, metadata: m.metadata, embedding: vec![],
-                created_at: Some(prost_types::Timestamp { seconds: m.created_at, nanos: 0 }),
-                updated_at: Some(prost_types::Timestamp { seconds: m.updated_at, nanos: 0 }),
-                tags: m.tags,
-            })})),
-            None => Err(Status::not_found("Not found")),
-        }
+            match result {
+                Some(m) => {
+                    self.record_access(&access.namespace.id, std::slice::from_ref(&m.id), chrono::Utc::now().timestamp()).await;
+                    Ok(Response::new(GetMemoryResponse { memory: Some(projection.apply(memory_to_proto(m, &access.namespace.name))) }))
+                }
+                None => Err(Error::new(ErrorCode::MemoryNotFound, "Memory not found").into()),
+            }
+        }.await;
+
+        self.audit.record(audit, &result);
+        result
     }
 
     async fn delete_memory(&self, req: Request<DeleteMemoryRequest>) -> Result<Response<DeleteMemoryResponse>, Status> {
+        let principal = Principal::from_request(&req)?;
         let r = req.into_inner();
-        let success = self.db.delete_memory(&r.memory_id)
-            .await
-            .map_err(|e| Status::internal(e.to_string()))?;
+        let mut audit = AuditEvent::new("memory", "DeleteMemory", &principal).target(&r.memory_id);
+
+        let result: Result<Response<DeleteMemoryResponse>, Status> = async {
+            let access = self.resolve_namespace(&principal, &r.namespace).await?;
+            audit.namespace_id = access.namespace.id.clone();
+            self.require_memory(&access, &r.memory_id, Permission::Write).await?;
+
+            let now = chrono::Utc::now().timestamp();
+            let success = self.db.delete_memory(&access.namespace.id, &r.memory_id, now)
+                .await
+                .map_err(Error::internal)?;
+            if success {
+                self.changes.publish(MemoryChange::Deleted {
+                    namespace_id: access.namespace.id.clone(),
+                    memory_id: r.memory_id,
+                });
+            }
             
-        Ok(Response::new(DeleteMemoryResponse { success, message: if success { "Deleted".into() } else { "Not found".into() } }))
+            Ok(Response::new(DeleteMemoryResponse { success, message: if success { "Moved to trash".into() } else { "Not found".into() } }))
+        }.await;
+
+        self.audit.record(audit, &result);
+        result
+    }
+
+    async fn list_trash(&self, req: Request<ListTrashRequest>) -> Result<Response<ListTrashResponse>, Status> {
+        let principal = Principal::from_request(&req)?;
+        let r = req.into_inner();
+        let mut audit = AuditEvent::new("memory", "ListTrash", &principal);
+
+        let result: Result<Response<ListTrashResponse>, Status> = async {
+            let access = self.resolve_namespace(&principal, &r.namespace).await?;
+            audit.namespace_id = access.namespace.id.clone();
+
+            let results = self.db.list_trash(&access.namespace.id, r.limit, access.memory_filter())
+                .await
+                .map_err(|e| Error::internal(format!("Database error: {}", e)))?;
+
+            let memories = results.into_iter().map(|(m, deleted_at)| TrashedMemory {
+                memory: Some(memory_to_proto(m, &access.namespace.name)),
+                deleted_at: Some(prost_types::Timestamp { seconds: deleted_at, nanos: 0 }),
+                purge_at: Some(prost_types::Timestamp { seconds: deleted_at + self.trash_retention_secs, nanos: 0 }),
+            }).collect();
+
+            Ok(Response::new(ListTrashResponse { memories }))
+        }.await;
+
+        self.audit.record(audit, &result);
+        result
+    }
+
+    async fn restore_memory(&self, req: Request<RestoreMemoryRequest>) -> Result<Response<RestoreMemoryResponse>, Status> {
+        let principal = Principal::from_request(&req)?;
+        let r = req.into_inner();
+        let mut audit = AuditEvent::new("memory", "RestoreMemory", &principal).target(&r.memory_id);
+
+        let result: Result<Response<RestoreMemoryResponse>, Status> = async {
+            let access = self.resolve_namespace(&principal, &r.namespace).await?;
+            audit.namespace_id = access.namespace.id.clone();
+            self.require_memory(&access, &r.memory_id, Permission::Write).await?;
+
+            let memory = self.db.restore_memory(&access.namespace.id, &r.memory_id)
+                .await
+                .map_err(Error::internal)?
+                .ok_or_else(|| Error::new(ErrorCode::MemoryNotFound, "Memory not in trash"))?;
+
+            tracing::info!("Restored memory {} from trash", memory.id);
+            self.changes.publish(MemoryChange::Restored {
+                namespace_id: access.namespace.id.clone(),
+                memory: memory.clone(),
+            });
+            Ok(Response::new(RestoreMemoryResponse { memory: Some(memory_to_proto(memory, &access.namespace.name)) }))
+        }.await;
+
+        self.audit.record(audit, &result);
+        result
+    }
+
+    async fn purge_memory(&self, req: Request<PurgeMemoryRequest>) -> Result<Response<PurgeMemoryResponse>, Status> {
+        let principal = Principal::from_request(&req)?;
+        let r = req.into_inner();
+        let mut audit = AuditEvent::new("memory", "PurgeMemory", &principal).target(&r.memory_id);
+
+        let result: Result<Response<PurgeMemoryResponse>, Status> = async {
+            let access = self.resolve_namespace(&principal, &r.namespace).await?;
+            audit.namespace_id = access.namespace.id.clone();
+            self.require_memory(&access, &r.memory_id, Permission::Write).await?;
+
+            let success = self.db.purge_memory(&access.namespace.id, &r.memory_id)
+                .await
+                .map_err(Error::internal)?;
+            if success {
+                tracing::info!("Purged memory {}", r.memory_id);
+            }
+
+            Ok(Response::new(PurgeMemoryResponse { success, message: if success { "Purged".into() } else { "Not in trash".into() } }))
+        }.await;
+
+        self.audit.record(audit, &result);
+        result
     }
 
     async fn get_recent_memories(&self, req: Request<GetRecentMemoriesRequest>) -> Result<Response<GetRecentMemoriesResponse>, Status> {
+        let principal = Principal::from_request(&req)?;
         let r = req.into_inner();
+        let mut audit = AuditEvent::new("memory", "GetRecentMemories", &principal);
+
+        let result: Result<Response<GetRecentMemoriesResponse>, Status> = async {
+            let listing = Listing {
+                time: TimeFilter::from_proto(r.created, r.updated)?,
+                sort: Sort::from_proto(r.sort, SortKey::CreatedAt, false)?,
+                projection: Projection::from_proto(r.fields)?,
+                after: None,
+            };
+            let access = self.resolve_namespace(&principal, &r.namespace).await?;
+            audit.namespace_id = access.namespace.id.clone();
         
-        let results = self.db.get_recent_memories(r.limit)
-            .await
-            .map_err(|e| Status::internal(format!("Database error: {}", e)))?;
-
-        let memories: Vec<Memory> = results.into_iter().map(|m| Memory {
-            id: m.id,
-            content: m.content,
-            metadata: m.metadata,
-            embedding: vec![], 
-            created_at: Some(prost_types::Timestamp { seconds: m.created_at, nanos: 0 }),
-            updated_at: Some(prost_types::Timestamp { seconds: m.updated_at, nanos: 0 }),
-            tags: m.tags,
-        }).collect();
+            let results = self.db.get_recent_memories(&access.namespace.id, r.limit, &listing, access.memory_filter())
+                .await
+                .map_err(|e| Error::internal(format!("Database error: {}", e)))?;
+            results.iter().for_each(|m| audit.add_target(&m.id));
+            let ids: Vec<String> = results.iter().map(|m| m.id.clone()).collect();
+            self.record_access(&access.namespace.id, &ids, chrono::Utc::now().timestamp()).await;
+
+            let memories: Vec<Memory> = results.into_iter().map(|m| listing.projection.apply(memory_to_proto(m, &access.namespace.name))).collect();
         
-        Ok(Response::new(GetRecentMemoriesResponse { memories }))
+            Ok(Response::new(GetRecentMemoriesResponse { memories }))
+        }.await;
+
+        self.audit.record(audit, &result);
+        result
     }
-}
\ No newline at end of file
+
+    async fn build_context(&self, req: Request<BuildContextRequest>) -> Result<Response<BuildContextResponse>, Status> {
+        let principal = Principal::from_request(&req)?;
+        let r = req.into_inner();
+        let mut audit = AuditEvent::new("memory", "BuildContext", &principal);
+
+        let result: Result<Response<BuildContextResponse>, Status> = async {
+            if r.query.trim().is_empty() { return Err(Error::invalid_field("query", "Query required").into()); }
+            if !(1..=context::MAX_TOKEN_BUDGET).contains(&r.token_budget) {
+                return Err(Error::invalid_field("token_budget", format!("token_budget must be 1-{}", context::MAX_TOKEN_BUDGET)).into());
+            }
+            let policy = Policy::from_proto(r.policy)?;
+            let access = self.resolve_namespace(&principal, &r.namespace).await?;
+            audit.namespace_id = access.namespace.id.clone();
+
+            let embedding = self.generate_embedding(access.namespace.embedding_model(), &r.query)?;
+            let found = self.db.similar_memories(&access.namespace.id, &embedding, policy.max_candidates, policy.min_similarity, &TimeFilter::default(), access.memory_filter(), true)
+                .await
+                .map_err(|e| Error::internal(format!("Search failed: {}", e)))?;
+
+            // Client-side encrypted memories are ciphertext to the gateway and useless in a prompt
+            let candidates = found.into_iter()
+                .filter(|f| f.memory.metadata.get("encrypted").map(String::as_str) != Some("true"))
+                .map(|f| Candidate { memory: f.memory, similarity: f.similarity })
+                .collect();
+            let response = context::assemble(candidates, r.token_budget as usize, &policy);
+            let cited: Vec<String> = response.citations.iter().map(|c| c.memory_id.clone()).collect();
+            cited.iter().for_each(|id| audit.add_target(id));
+            self.record_access(&access.namespace.id, &cited, chrono::Utc::now().timestamp()).await;
+
+            Ok(Response::new(response))
+        }.await;
+
+        self.audit.record(audit, &result);
+        result
+    }
+
+    async fn get_memory_stats(&self, req: Request<GetMemoryStatsRequest>) -> Result<Response<GetMemoryStatsResponse>, Status> {
+        let principal = Principal::from_request(&req)?;
+        let r = req.into_inner();
+        let mut audit = AuditEvent::new("memory", "GetMemoryStats", &principal);
+
+        let result: Result<Response<GetMemoryStatsResponse>, Status> = async {
+            let access = self.resolve_namespace(&principal, &r.namespace).await?;
+            audit.namespace_id = access.namespace.id.clone();
+
+            let stats = self.db.memory_stats(&access.namespace.id, access.memory_filter())
+                .await
+                .map_err(|e| Error::internal(format!("DB Error: {}", e)))?;
+
+            Ok(Response::new(GetMemoryStatsResponse { stats: Some(tags::stats_to_proto(stats)) }))
+        }.await;
+
+        self.audit.record(audit, &result);
+        result
+    }
+
+    async fn list_tags(&self, req: Request<ListTagsRequest>) -> Result<Response<ListTagsResponse>, Status> {
+        let principal = Principal::from_request(&req)?;
+        let r = req.into_inner();
+        let mut audit = AuditEvent::new("memory", "ListTags", &principal);
+
+        let result: Result<Response<ListTagsResponse>, Status> = async {
+            let limit = if r.limit > 0 { r.limit.min(tags::MAX_TAG_LIMIT) } else { tags::DEFAULT_TAG_LIMIT };
+            let access = self.resolve_namespace(&principal, &r.namespace).await?;
+            audit.namespace_id = access.namespace.id.clone();
+
+            let found = self.db.list_tags(&access.namespace.id, r.prefix.trim_start(), limit, access.memory_filter())
+                .await
+                .map_err(|e| Error::internal(format!("DB Error: {}", e)))?;
+
+            Ok(Response::new(ListTagsResponse { tags: found.into_iter().map(tags::tag_count_to_proto).collect() }))
+        }.await;
+
+        self.audit.record(audit, &result);
+        result
+    }
+
+    async fn rename_tag(&self, req: Request<RenameTagRequest>) -> Result<Response<RenameTagResponse>, Status> {
+        let principal = Principal::from_request(&req)?;
+        let r = req.into_inner();
+        let mut audit = AuditEvent::new("memory", "RenameTag", &principal).target(&r.tag);
+
+        let result: Result<Response<RenameTagResponse>, Status> = async {
+            let tag = tags::tag_name("tag", &r.tag)?;
+            let new_name = tags::tag_name("new_name", &r.new_name)?;
+            let access = self.resolve_namespace(&principal, &r.namespace).await?;
+            audit.namespace_id = access.namespace.id.clone();
+            access.require(Permission::Write)?;
+            if new_name == tag {
+                return Ok(Response::new(RenameTagResponse { memories_updated: 0 }));
+            }
+
+            let taken = self.db.tag_exists(&access.namespace.id, &new_name)
+                .await
+                .map_err(|e| Error::internal(format!("DB Error: {}", e)))?;
+            if taken {
+                return Err(Error::new(ErrorCode::AlreadyExists, format!("Tag '{}' is already in use; merge the tags instead", new_name)).into());
+            }
+            let updated = self.db.replace_tags(&access.namespace.id, std::slice::from_ref(&tag), &new_name, chrono::Utc::now().timestamp())
+                .await
+                .map_err(|e| Error::internal(format!("DB Error: {}", e)))?;
+            if updated == 0 {
+                return Err(Error::new(ErrorCode::NotFound, format!("Tag '{}' not found", tag)).into());
+            }
+
+            tracing::info!("Renamed tag on {} memories in namespace {}", updated, access.namespace.name);
+            Ok(Response::new(RenameTagResponse { memories_updated: updated as i64 }))
+        }.await;
+
+        self.audit.record(audit, &result);
+        result
+    }
+
+    async fn merge_tags(&self, req: Request<MergeTagsRequest>) -> Result<Response<MergeTagsResponse>, Status> {
+        let principal = Principal::from_request(&req)?;
+        let r = req.into_inner();
+        let mut audit = AuditEvent::new("memory", "MergeTags", &principal).target(&r.into);
+
+        let result: Result<Response<MergeTagsResponse>, Status> = async {
+            let into = tags::tag_name("into", &r.into)?;
+            let sources = tags::tag_names("tags", &r.tags)?;
+            if sources.is_empty() {
+                return Err(Error::invalid_field("tags", "At least one tag to merge is required").into());
+            }
+            let access = self.resolve_namespace(&principal, &r.namespace).await?;
+            audit.namespace_id = access.namespace.id.clone();
+            access.require(Permission::Write)?;
+
+            let updated = self.db.replace_tags(&access.namespace.id, &sources, &into, chrono::Utc::now().timestamp())
+                .await
+                .map_err(|e| Error::internal(format!("DB Error: {}", e)))?;
+
+            tracing::info!("Merged {} tags on {} memories in namespace {}", sources.len(), updated, access.namespace.name);
+            Ok(Response::new(MergeTagsResponse { memories_updated: updated as i64 }))
+        }.await;
+
+        self.audit.record(audit, &result);
+        result
+    }
+
+    async fn retag_memories(&self, req: Request<RetagMemoriesRequest>) -> Result<Response<RetagMemoriesResponse>, Status> {
+        let principal = Principal::from_request(&req)?;
+        let r = req.into_inner();
+        let mut audit = AuditEvent::new("memory", "RetagMemories", &principal);
+
+        let result: Result<Response<RetagMemoriesResponse>, Status> = async {
+            if r.memory_ids.is_empty() || r.memory_ids.len() > tags::MAX_RETAG_MEMORIES {
+                return Err(Error::invalid_field("memory_ids", format!("Between 1 and {} memory ids required", tags::MAX_RETAG_MEMORIES)).into());
+            }
+            let add = tags::tag_names("add_tags", &r.add_tags)?;
+            let remove = tags::tag_names("remove_tags", &r.remove_tags)?;
+            if add.is_empty() && remove.is_empty() {
+                return Err(Error::invalid_field("add_tags", "Nothing to add or remove").into());
+            }
+            let access = self.resolve_namespace(&principal, &r.namespace).await?;
+            audit.namespace_id = access.namespace.id.clone();
+            access.require(Permission::Write)?;
+            r.memory_ids.iter().for_each(|id| audit.add_target(id));
+
+            let updated = self.db.retag_memories(&access.namespace.id, &r.memory_ids, &add, &remove, chrono::Utc::now().timestamp())
+                .await
+                .map_err(|e| Error::internal(format!("DB Error: {}", e)))?;
+
+            Ok(Response::new(RetagMemoriesResponse { memories_updated: updated as i64 }))
+        }.await;
+
+        self.audit.record(audit, &result);
+        result
+    }
+
+    async fn create_conversation(&self, req: Request<CreateConversationRequest>) -> Result<Response<CreateConversationResponse>, Status> {
+        let principal = Principal::from_request(&req)?;
+        let r = req.into_inner();
+        let mut audit = AuditEvent::new("memory", "CreateConversation", &principal);
+
+        let result: Result<Response<CreateConversationResponse>, Status> = async {
+            let access = self.resolve_namespace(&principal, &r.namespace).await?;
+            audit.namespace_id = access.namespace.id.clone();
+            access.require(Permission::Write)?;
+            let ns = &access.namespace;
+            let id = Uuid::new_v4().to_string();
+            audit.add_target(&id);
+            let now = chrono::Utc::now().timestamp();
+
+            self.db.create_conversation(&ns.id, &id, &r.title, &r.model, &r.metadata, now)
+                .await
+                .map_err(|e| Error::internal(format!("DB Error: {}", e)))?;
+
+            tracing::info!("Created conversation {} in namespace {}", id, ns.name);
+            let conversation = ConversationModel {
+                id,
+                title: r.title,
+                model: r.model,
+                metadata: r.metadata,
+                created_at: now,
+                updated_at: now,
+                turn_count: 0,
+            };
+            Ok(Response::new(CreateConversationResponse { conversation: Some(conversation_to_proto(conversation, vec![], &ns.name)) }))
+        }.await;
+
+        self.audit.record(audit, &result);
+        result
+    }
+
+    async fn get_conversation(&self, req: Request<GetConversationRequest>) -> Result<Response<GetConversationResponse>, Status> {
+        let principal = Principal::from_request(&req)?;
+        let r = req.into_inner();
+        let mut audit = AuditEvent::new("memory", "GetConversation", &principal).target(&r.conversation_id);
+
+        let result: Result<Response<GetConversationResponse>, Status> = async {
+            let access = self.resolve_namespace(&principal, &r.namespace).await?;
+            audit.namespace_id = access.namespace.id.clone();
+            access.require(Permission::Read)?;
+            let conversation = self.db.get_conversation(&access.namespace.id, &r.conversation_id)
+                .await
+                .map_err(Error::internal)?
+                .ok_or_else(|| Error::new(ErrorCode::ConversationNotFound, "Conversation not found"))?;
+
+            let turns = self.db.get_conversation_turns(&r.conversation_id, r.turn_limit)
+                .await
+                .map_err(Error::internal)?;
+
+            Ok(Response::new(GetConversationResponse { conversation: Some(conversation_to_proto(conversation, turns, &access.namespace.name)) }))
+        }.await;
+
+        self.audit.record(audit, &result);
+        result
+    }
+
+    async fn list_conversations(&self, req: Request<ListConversationsRequest>) -> Result<Response<ListConversationsResponse>, Status> {
+        let principal = Principal::from_request(&req)?;
+        let r = req.into_inner();
+        let mut audit = AuditEvent::new("memory", "ListConversations", &principal);
+
+        let result: Result<Response<ListConversationsResponse>, Status> = async {
+            let access = self.resolve_namespace(&principal, &r.namespace).await?;
+            audit.namespace_id = access.namespace.id.clone();
+            access.require(Permission::Read)?;
+            let results = self.db.list_conversations(&access.namespace.id, r.limit)
+                .await
+                .map_err(|e| Error::internal(format!("Database error: {}", e)))?;
+
+            let conversations = results.into_iter().map(|c| conversation_to_proto(c, vec![], &access.namespace.name)).collect();
+            Ok(Response::new(ListConversationsResponse { conversations }))
+        }.await;
+
+        self.audit.record(audit, &result);
+        result
+    }
+
+    async fn append_conversation_turns(&self, req: Request<AppendConversationTurnsRequest>) -> Result<Response<AppendConversationTurnsResponse>, Status> {
+        let principal = Principal::from_request(&req)?;
+        let r = req.into_inner();
+        let mut audit = AuditEvent::new("memory", "AppendConversationTurns", &principal).target(&r.conversation_id);
+
+        let result: Result<Response<AppendConversationTurnsResponse>, Status> = async {
+            if r.turns.is_empty() { return Err(Error::invalid_field("turns", "At least one turn required").into()); }
+            let access = self.resolve_namespace(&principal, &r.namespace).await?;
+            audit.namespace_id = access.namespace.id.clone();
+            access.require(Permission::Write)?;
+
+            let turns = new_turns(r.turns, access.namespace.secret_policy())?;
+
+            // Turns are charged to the namespace owner like memory content
+            let (memories, bytes) = self.db.owner_usage(&access.namespace.owner_id)
+                .await
+                .map_err(|e| Error::internal(format!("DB Error: {}", e)))?;
+            self.quota.check(memories, bytes, turns.iter().map(|t| t.content.len() as i64).sum())?;
+            let now = chrono::Utc::now().timestamp();
+            let stored = self.db.append_conversation_turns(&access.namespace.id, &r.conversation_id, &turns, now)
+                .await
+                .map_err(|e| Error::internal(format!("DB Error: {}", e)))?
+                .ok_or_else(|| Error::new(ErrorCode::ConversationNotFound, "Conversation not found"))?;
+
+            tracing::info!("Appended {} turns to conversation {}", stored.len(), r.conversation_id);
+            Ok(Response::new(AppendConversationTurnsResponse { turns: stored.into_iter().map(turn_to_proto).collect() }))
+        }.await;
+
+        self.audit.record(audit, &result);
+        result
+    }
+
+    async fn delete_conversation(&self, req: Request<DeleteConversationRequest>) -> Result<Response<DeleteConversationResponse>, Status> {
+        let principal = Principal::from_request(&req)?;
+        let r = req.into_inner();
+        let mut audit = AuditEvent::new("memory", "DeleteConversation", &principal).target(&r.conversation_id);
+
+        let result: Result<Response<DeleteConversationResponse>, Status> = async {
+            let access = self.resolve_namespace(&principal, &r.namespace).await?;
+            audit.namespace_id = access.namespace.id.clone();
+            access.require(Permission::Write)?;
+            let success = self.db.delete_conversation(&access.namespace.id, &r.conversation_id)
+                .await
+                .map_err(Error::internal)?;
+
+            Ok(Response::new(DeleteConversationResponse { success, message: if success { "Deleted".into() } else { "Not found".into() } }))
+        }.await;
+
+        self.audit.record(audit, &result);
+        result
+    }
+
+    async fn create_namespace(&self, req: Request<CreateNamespaceRequest>) -> Result<Response<CreateNamespaceResponse>, Status> {
+        let principal = Principal::from_request(&req)?;
+        let r = req.into_inner();
+        let mut audit = AuditEvent::new("memory", "CreateNamespace", &principal);
+
+        let result: Result<Response<CreateNamespaceResponse>, Status> = async {
+            let name = r.name.trim();
+            namespace::validate_name(name)?;
+            let settings = namespace::settings_from_proto(r.settings)?;
+
+            if self.db.get_namespace(&principal.user_id, name).await.map_err(Error::internal)?.is_some() {
+                return Err(Error::new(ErrorCode::NamespaceExists, format!("Namespace '{}' already exists", name)).into());
+            }
+
+            let id = Uuid::new_v4().to_string();
+            audit.namespace_id = id.clone();
+            let now = chrono::Utc::now().timestamp();
+            self.db.create_namespace(&id, &principal.user_id, name, &settings, now)
+                .await
+                .map_err(|e| Error::internal(format!("DB Error: {}", e)))?;
+
+            tracing::info!("Created namespace {} ({})", name, id);
+            let created = NamespaceModel {
+                id,
+                owner_id: principal.user_id.clone(),
+                name: name.to_string(),
+                settings,
+                created_at: now,
+                updated_at: now,
+                memory_count: 0,
+            };
+            Ok(Response::new(CreateNamespaceResponse { namespace: Some(namespace::namespace_to_proto(created, Permission::Admin)) }))
+        }.await;
+
+        self.audit.record(audit, &result);
+        result
+    }
+
+    async fn list_namespaces(&self, req: Request<ListNamespacesRequest>) -> Result<Response<ListNamespacesResponse>, Status> {
+        let principal = Principal::from_request(&req)?;
+        let audit = AuditEvent::new("memory", "ListNamespaces", &principal);
+
+        let result: Result<Response<ListNamespacesResponse>, Status> = async {
+            // Make sure the default namespace always shows up
+            self.resolve_namespace(&principal, DEFAULT_NAMESPACE).await?;
+
+            let owned = self.db.list_namespaces(&principal.user_id)
+                .await
+                .map_err(|e| Error::internal(format!("Database error: {}", e)))?;
+            let shared = self.db.list_shared_namespaces(&principal.user_id, &principal.grantees())
+                .await
+                .map_err(|e| Error::internal(format!("Database error: {}", e)))?;
+
+            let namespaces = owned.into_iter()
+                .map(|n| namespace::namespace_to_proto(n, Permission::Admin))
+                .chain(shared.into_iter().map(|(n, permission)| {
+                    namespace::namespace_to_proto(n, Permission::try_from(permission).unwrap_or(Permission::Unspecified))
+                }))
+                .collect();
+            Ok(Response::new(ListNamespacesResponse { namespaces }))
+        }.await;
+
+        self.audit.record(audit, &result);
+        result
+    }
+
+    async fn update_namespace(&self, req: Request<UpdateNamespaceRequest>) -> Result<Response<UpdateNamespaceResponse>, Status> {
+        let principal = Principal::from_request(&req)?;
+        let r = req.into_inner();
+        let mut audit = AuditEvent::new("memory", "UpdateNamespace", &principal);
+
+        let result: Result<Response<UpdateNamespaceResponse>, Status> = async {
+            let access = self.resolve_namespace(&principal, &r.namespace).await?;
+            audit.namespace_id = access.namespace.id.clone();
+            if !access.is_owner(&principal) {
+                return Err(Error::permission_denied("Only the namespace owner can update it").into());
+            }
+            let mut ns = access.namespace;
+
+            let new_name = r.new_name.trim();
+            if !new_name.is_empty() && new_name != ns.name {
+                if ns.name == DEFAULT_NAMESPACE {
+                    return Err(Error::failed_precondition("The default namespace cannot be renamed").into());
+                }
+                namespace::validate_name(new_name)?;
+                if self.db.get_namespace(&ns.owner_id, new_name).await.map_err(Error::internal)?.is_some() {
+                    return Err(Error::new(ErrorCode::NamespaceExists, format!("Namespace '{}' already exists", new_name)).into());
+                }
+                ns.name = new_name.to_string();
+            }
+            if let Some(update) = r.settings {
+                let settings = namespace::merge_settings(&ns.settings, update, &r.update_mask)?;
+                // Stored vectors must stay comparable with new query embeddings
+                let model_changed = namespace::parse_embedding_model(&settings.embedding_model)
+                    != namespace::parse_embedding_model(&ns.settings.embedding_model);
+                if model_changed && ns.memory_count > 0 {
+                    return Err(Error::failed_precondition("Cannot change the embedding model of a namespace that holds memories").into());
+                }
+                ns.settings = settings;
+            }
+
+            ns.updated_at = chrono::Utc::now().timestamp();
+            self.db.update_namespace(&ns.id, &ns.name, &ns.settings, ns.updated_at)
+                .await
+                .map_err(|e| Error::internal(format!("DB Error: {}", e)))?;
+
+            // Switching to private cuts off every grantee
+            self.changes.publish(MemoryChange::AccessChanged { namespace_id: ns.id.clone() });
+            Ok(Response::new(UpdateNamespaceResponse { namespace: Some(namespace::namespace_to_proto(ns, Permission::Admin)) }))
+        }.await;
+
+        self.audit.record(audit, &result);
+        result
+    }
+
+    async fn delete_namespace(&self, req: Request<DeleteNamespaceRequest>) -> Result<Response<DeleteNamespaceResponse>, Status> {
+        let principal = Principal::from_request(&req)?;
+        let r = req.into_inner();
+        let mut audit = AuditEvent::new("memory", "DeleteNamespace", &principal);
+
+        let result: Result<Response<DeleteNamespaceResponse>, Status> = async {
+            let access = self.resolve_namespace(&principal, &r.namespace).await?;
+            audit.namespace_id = access.namespace.id.clone();
+            if !access.is_owner(&principal) {
+                return Err(Error::permission_denied("Only the namespace owner can delete it").into());
+            }
+            let ns = access.namespace;
+            if ns.name == DEFAULT_NAMESPACE {
+                return Err(Error::failed_precondition("The default namespace cannot be deleted").into());
+            }
+
+            if !r.force {
+                let conversations = self.db.count_namespace_conversations(&ns.id)
+                    .await
+                    .map_err(Error::internal)?;
+                if ns.memory_count > 0 || conversations > 0 {
+                    return Err(Error::failed_precondition(format!(
+                        "Namespace '{}' is not empty ({} memories, {} conversations); pass force to delete",
+                        ns.name, ns.memory_count, conversations
+                    )).into());
+                }
+            }
+
+            let success = self.db.delete_namespace(&ns.id)
+                .await
+                .map_err(Error::internal)?;
+
+            tracing::info!("Deleted namespace {} ({})", ns.name, ns.id);
+            self.changes.publish(MemoryChange::AccessChanged { namespace_id: ns.id });
+            Ok(Response::new(DeleteNamespaceResponse { success, message: if success { "Deleted".into() } else { "Not found".into() } }))
+        }.await;
+
+        self.audit.record(audit, &result);
+        result
+    }
+
+    async fn grant_access(&self, req: Request<GrantAccessRequest>) -> Result<Response<GrantAccessResponse>, Status> {
+        let principal = Principal::from_request(&req)?;
+        let r = req.into_inner();
+        let mut audit = AuditEvent::new("memory", "GrantAccess", &principal).target(&r.memory_id);
+
+        let result: Result<Response<GrantAccessResponse>, Status> = async {
+            let grantee = acl::grantee_key(r.grantee.as_ref())?;
+            audit.add_target(&grantee);
+            let permission = Permission::try_from(r.permission)
+                .ok()
+                .filter(|p| *p != Permission::Unspecified)
+                .ok_or_else(|| Error::invalid_field("permission", "Permission must be READ, WRITE or ADMIN"))?;
+
+            let (access, memory_id) = self.resolve_grant_target(&principal, &r.namespace, &r.memory_id).await?;
+            audit.namespace_id = access.namespace.id.clone();
+            if access.namespace.settings.sharing != NamespaceSharing::Shareable as i32 {
+                return Err(Error::failed_precondition(format!(
+                    "Namespace '{}' is private; make it shareable before granting access",
+                    access.namespace.name
+                )).into());
+            }
+            if grantee == format!("user:{}", access.namespace.owner_id) {
+                return Err(Error::invalid_argument("The namespace owner already has full access").into());
+            }
+
+            let grant = GrantModel {
+                id: Uuid::new_v4().to_string(),
+                namespace_id: access.namespace.id.clone(),
+                memory_id,
+                grantee,
+                permission: permission as i32,
+                granted_by: principal.user_id.clone(),
+                created_at: chrono::Utc::now().timestamp(),
+            };
+            let stored = self.db.upsert_grant(&grant)
+                .await
+                .map_err(|e| Error::internal(format!("DB Error: {}", e)))?;
+
+            tracing::info!("Granted {} on namespace {} to {}", permission.as_str_name(), access.namespace.id, stored.grantee);
+            // Downgrades must reach active watchers too
+            self.changes.publish(MemoryChange::AccessChanged { namespace_id: access.namespace.id });
+            Ok(Response::new(GrantAccessResponse { grant: Some(acl::grant_to_proto(stored)) }))
+        }.await;
+
+        self.audit.record(audit, &result);
+        result
+    }
+
+    async fn revoke_access(&self, req: Request<RevokeAccessRequest>) -> Result<Response<RevokeAccessResponse>, Status> {
+        let principal = Principal::from_request(&req)?;
+        let r = req.into_inner();
+        let mut audit = AuditEvent::new("memory", "RevokeAccess", &principal).target(&r.memory_id);
+
+        let result: Result<Response<RevokeAccessResponse>, Status> = async {
+            let grantee = acl::grantee_key(r.grantee.as_ref())?;
+            audit.add_target(&grantee);
+            let (access, memory_id) = self.resolve_grant_target(&principal, &r.namespace, &r.memory_id).await?;
+            audit.namespace_id = access.namespace.id.clone();
+
+            let success = self.db.delete_grant(&access.namespace.id, memory_id.as_deref(), &grantee)
+                .await
+                .map_err(Error::internal)?;
+            if success {
+                tracing::info!("Revoked access to namespace {} from {}", access.namespace.id, grantee);
+                self.changes.publish(MemoryChange::AccessChanged { namespace_id: access.namespace.id });
+            }
+
+            Ok(Response::new(RevokeAccessResponse { success, message: if success { "Revoked".into() } else { "Not found".into() } }))
+        }.await;
+
+        self.audit.record(audit, &result);
+        result
+    }
+
+    async fn list_grants(&self, req: Request<ListGrantsRequest>) -> Result<Response<ListGrantsResponse>, Status> {
+        let principal = Principal::from_request(&req)?;
+        let r = req.into_inner();
+        let mut audit = AuditEvent::new("memory", "ListGrants", &principal).target(&r.memory_id);
+
+        let result: Result<Response<ListGrantsResponse>, Status> = async {
+            let (access, memory_id) = self.resolve_grant_target(&principal, &r.namespace, &r.memory_id).await?;
+            audit.namespace_id = access.namespace.id.clone();
+
+            let grants = self.db.list_grants(&access.namespace.id, memory_id.as_deref())
+                .await
+                .map_err(|e| Error::internal(format!("Database error: {}", e)))?;
+
+            Ok(Response::new(ListGrantsResponse { grants: grants.into_iter().map(acl::grant_to_proto).collect() }))
+        }.await;
+
+        self.audit.record(audit, &result);
+        result
+    }
+
+    async fn watch_memories(&self, req: Request<WatchMemoriesRequest>) -> Result<Response<Self::WatchMemoriesStream>, Status> {
+        let principal = Principal::from_request(&req)?;
+        let r = req.into_inner();
+        let mut audit = AuditEvent::new("memory", "WatchMemories", &principal);
+
+        let result: Result<Response<Self::WatchMemoriesStream>, Status> = async {
+            let access = self.resolve_namespace(&principal, &r.namespace).await?;
+            audit.namespace_id = access.namespace.id.clone();
+
+            tracing::info!("Watching namespace {} for {}", access.namespace.id, principal.user_id);
+            Ok(Response::new(self.changes.watch(self.db.clone(), principal, access)))
+        }.await;
+
+        self.audit.record(audit, &result);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn turn(role: &str, content: &str) -> NewConversationTurn {
+        NewConversationTurn { role: role.into(), content: content.into(), ..Default::default() }
+    }
+
+    #[test]
+    fn appended_turns_need_a_known_role_and_content() {
+        let turns = new_turns(vec![turn("user", "hi"), turn("assistant", "hello")], SecretPolicy::Redact).unwrap();
+        assert_eq!(turns.iter().map(|t| t.role.as_str()).collect::<Vec<_>>(), ["user", "assistant"]);
+
+        let error = Error::from_status(&new_turns(vec![turn("user", "hi"), turn("bot", "hello")], SecretPolicy::Redact).unwrap_err());
+        assert_eq!(error.code(), ErrorCode::InvalidArgument);
+        assert_eq!(error.field_violations()[0].field, "turns.role");
+        let error = Error::from_status(&new_turns(vec![turn("system", "  \n")], SecretPolicy::Redact).unwrap_err());
+        assert_eq!(error.field_violations()[0].field, "turns.content");
+    }
+
+    #[test]
+    fn turns_are_screened_for_credentials() {
+        let leaked = turn("user", "my key is sk-proj-4fT9xQ2mLw8ZkR7vN3pB ok");
+        let turns = new_turns(vec![turn("user", "hi"), leaked.clone()], SecretPolicy::Redact).unwrap();
+        assert_eq!(turns[1].content, "my key is [REDACTED:api_key] ok");
+        assert_eq!(turns[1].metadata[SECRETS_METADATA_KEY], r#"[{"kind":"api_key","start":10,"end":28}]"#);
+        assert!(!turns[0].metadata.contains_key(SECRETS_METADATA_KEY));
+
+        let error = Error::from_status(&new_turns(vec![turn("user", "hi"), leaked.clone()], SecretPolicy::Reject).unwrap_err());
+        assert_eq!(error.code(), ErrorCode::SecretDetected);
+
+        let mut encrypted = leaked;
+        encrypted.metadata.insert("encrypted".into(), "true".into());
+        let turns = new_turns(vec![encrypted.clone()], SecretPolicy::Reject).unwrap();
+        assert_eq!(turns[0].content, encrypted.content);
+        assert!(!turns[0].metadata.contains_key(SECRETS_METADATA_KEY));
+    }
+
+    #[test]
+    fn conversations_carry_their_namespace_and_turns() {
+        let conversation = ConversationModel {
+            id: "c1".into(),
+            title: "Trip".into(),
+            model: "gpt".into(),
+            metadata: HashMap::new(),
+            created_at: 10,
+            updated_at: 20,
+            turn_count: 1,
+        };
+        let turn = ConversationTurnModel {
+            id: "t1".into(),
+            conversation_id: "c1".into(),
+            sequence: 1,
+            role: "user".into(),
+            content: "hi".into(),
+            model: String::new(),
+            metadata: HashMap::new(),
+            created_at: 20,
+        };
+        let proto = conversation_to_proto(conversation, vec![turn], "work");
+        assert_eq!(proto.namespace, "work");
+        assert_eq!(proto.updated_at.unwrap().seconds, 20);
+        assert_eq!(proto.turns[0].sequence, 1);
+        assert_eq!(proto.turns[0].conversation_id, "c1");
+    }
+}
@@ -1,6 +1,16 @@
 pub mod health;
 pub mod vault;
 pub mod memory;
+pub mod namespace;
+pub mod acl;
+pub mod audit;
+pub mod watch;
+pub mod context;
+pub mod ranking;
+pub mod tags;
 
 // pub use health::HealthService;
 // pub use vault::VaultServiceImpl;
+pub mod listing;
+pub mod projection;
+pub mod secrets;
@@ -0,0 +1,206 @@
+use identra_proto::memory::{Namespace, NamespaceSettings, NamespaceSharing, Permission, SecretPolicy};
+use fastembed::EmbeddingModel;
+use identra_core::error::{Error, ErrorCode};
+use tonic::Status;
+
+/// Namespace used when a request leaves `namespace` empty
+pub const DEFAULT_NAMESPACE: &str = "default";
+
+/// Embedding model used when a namespace does not pick one
+pub const DEFAULT_EMBEDDING_MODEL: &str = "all-minilm-l6-v2";
+
+const MAX_NAME_LEN: usize = 64;
+
+/// Settings fields `UpdateNamespace` accepts in `update_mask`
+const SETTINGS_FIELDS: [&str; 4] = ["default_retention_days", "embedding_model", "sharing", "secret_policy"];
+
+#[derive(Debug, Clone)]
+pub struct NamespaceSettingsModel {
+    pub default_retention_days: i32,
+    pub embedding_model: String,
+    pub sharing: i32,
+    pub secret_policy: i32,
+}
+
+#[derive(Debug, Clone)]
+pub struct NamespaceModel {
+    pub id: String,
+    pub owner_id: String,
+    pub name: String,
+    pub settings: NamespaceSettingsModel,
+    pub created_at: i64,
+    pub updated_at: i64,
+    pub memory_count: i64,
+}
+
+impl NamespaceModel {
+    /// Expiry timestamp for a memory created at `now`, if the namespace has a retention period
+    pub fn expires_at(&self, now: i64) -> Option<i64> {
+        match self.settings.default_retention_days {
+            days if days > 0 => Some(now + days as i64 * 86_400),
+            _ => None,
+        }
+    }
+
+    pub fn secret_policy(&self) -> SecretPolicy {
+        SecretPolicy::try_from(self.settings.secret_policy).unwrap_or_default()
+    }
+
+    pub fn embedding_model(&self) -> &str {
+        if self.settings.embedding_model.is_empty() {
+            DEFAULT_EMBEDDING_MODEL
+        } else {
+            &self.settings.embedding_model
+        }
+    }
+}
+
+/// Length of every stored embedding; `memories.embedding` is `vector(384)`
+pub const EMBEDDING_DIMENSIONS: usize = 384;
+
+/// Map a namespace `embedding_model` setting to a local fastembed model.
+///
+/// Only `EMBEDDING_DIMENSIONS`-dimension models are accepted.
+pub fn parse_embedding_model(name: &str) -> Option<EmbeddingModel> {
+    match name {
+        "" | "all-minilm-l6-v2" => Some(EmbeddingModel::AllMiniLML6V2),
+        "all-minilm-l12-v2" => Some(EmbeddingModel::AllMiniLML12V2),
+        "bge-small-en-v1.5" => Some(EmbeddingModel::BGESmallENV15),
+        "paraphrase-multilingual-minilm-l12-v2" => Some(EmbeddingModel::ParaphraseMLMiniLML12V2),
+        "multilingual-e5-small" => Some(EmbeddingModel::MultilingualE5Small),
+        _ => None,
+    }
+}
+
+/// Resolve the namespace name a request refers to, defaulting to `DEFAULT_NAMESPACE`
+pub fn requested_name(namespace: &str) -> &str {
+    let name = namespace.trim();
+    if name.is_empty() { DEFAULT_NAMESPACE } else { name }
+}
+
+pub fn validate_name(name: &str) -> Result<(), Status> {
+    if name.is_empty() || name.len() > MAX_NAME_LEN {
+        return Err(Error::invalid_argument(format!("Namespace name must be 1-{} characters", MAX_NAME_LEN)).into());
+    }
+    if !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return Err(Error::invalid_argument("Namespace name may only contain letters, digits, '-' and '_'").into());
+    }
+    // Requests address shared namespaces by id, so names must not look like one
+    if uuid::Uuid::parse_str(name).is_ok() {
+        return Err(Error::invalid_argument("Namespace name cannot be a UUID").into());
+    }
+    Ok(())
+}
+
+pub fn settings_from_proto(settings: Option<NamespaceSettings>) -> Result<NamespaceSettingsModel, Status> {
+    let settings = settings.unwrap_or_default();
+    if settings.default_retention_days < 0 {
+        return Err(Error::invalid_field("settings.default_retention_days", "default_retention_days cannot be negative").into());
+    }
+    if parse_embedding_model(&settings.embedding_model).is_none() {
+        return Err(Error::new(ErrorCode::UnsupportedEmbeddingModel, format!("Unsupported embedding model '{}'", settings.embedding_model))
+            .with_field("settings.embedding_model", "Unsupported embedding model").into());
+    }
+    if NamespaceSharing::try_from(settings.sharing).is_err() {
+        return Err(Error::invalid_field("settings.sharing", "Unknown sharing mode").into());
+    }
+    if SecretPolicy::try_from(settings.secret_policy).is_err() {
+        return Err(Error::invalid_field("settings.secret_policy", "Unknown secret policy").into());
+    }
+
+    Ok(NamespaceSettingsModel {
+        default_retention_days: settings.default_retention_days,
+        embedding_model: settings.embedding_model,
+        sharing: settings.sharing,
+        secret_policy: settings.secret_policy,
+    })
+}
+
+/// Apply an `UpdateNamespace` settings change on top of `current`.
+///
+/// Only the fields named in `mask` change. With an empty mask, the fields `update` sets to a
+/// non-default value change, since proto3 cannot tell an unset field from a zero one.
+pub fn merge_settings(current: &NamespaceSettingsModel, update: NamespaceSettings, mask: &[String]) -> Result<NamespaceSettingsModel, Status> {
+    let fields: Vec<&str> = mask.iter().map(|path| path.trim().trim_start_matches("settings.")).collect();
+    if let Some(unknown) = fields.iter().find(|field| !SETTINGS_FIELDS.contains(field)) {
+        return Err(Error::invalid_field("update_mask", format!("Unknown settings field '{}'", unknown)).into());
+    }
+    let changes = |field: &str, set: bool| if fields.is_empty() { set } else { fields.contains(&field) };
+
+    let mut merged = NamespaceSettings {
+        default_retention_days: current.default_retention_days,
+        embedding_model: current.embedding_model.clone(),
+        sharing: current.sharing,
+        secret_policy: current.secret_policy,
+    };
+    if changes("default_retention_days", update.default_retention_days != 0) {
+        merged.default_retention_days = update.default_retention_days;
+    }
+    if changes("embedding_model", !update.embedding_model.is_empty()) {
+        merged.embedding_model = update.embedding_model;
+    }
+    if changes("sharing", update.sharing != 0) {
+        merged.sharing = update.sharing;
+    }
+    if changes("secret_policy", update.secret_policy != 0) {
+        merged.secret_policy = update.secret_policy;
+    }
+    settings_from_proto(Some(merged))
+}
+
+/// Convert a namespace for the response, with the caller's `permission` on it
+pub fn namespace_to_proto(n: NamespaceModel, permission: Permission) -> Namespace {
+    Namespace {
+        id: n.id,
+        name: n.name,
+        settings: Some(NamespaceSettings {
+            default_retention_days: n.settings.default_retention_days,
+            embedding_model: n.settings.embedding_model,
+            sharing: n.settings.sharing,
+            secret_policy: n.settings.secret_policy,
+        }),
+        created_at: Some(prost_types::Timestamp { seconds: n.created_at, nanos: 0 }),
+        updated_at: Some(prost_types::Timestamp { seconds: n.updated_at, nanos: 0 }),
+        memory_count: n.memory_count,
+        owner_id: n.owner_id,
+        permission: permission as i32,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn current() -> NamespaceSettingsModel {
+        NamespaceSettingsModel {
+            default_retention_days: 30,
+            embedding_model: "bge-small-en-v1.5".to_string(),
+            sharing: NamespaceSharing::Shareable as i32,
+            secret_policy: SecretPolicy::Reject as i32,
+        }
+    }
+
+    #[test]
+    fn partial_update_keeps_other_settings() {
+        let update = NamespaceSettings { sharing: NamespaceSharing::Private as i32, secret_policy: SecretPolicy::Flag as i32, ..Default::default() };
+        let merged = merge_settings(&current(), update, &[]).unwrap();
+        assert_eq!(merged.secret_policy, SecretPolicy::Flag as i32);
+        assert_eq!(merged.default_retention_days, 30);
+        assert_eq!(merged.embedding_model, "bge-small-en-v1.5");
+        // PRIVATE is the zero value, so without a mask it reads as unset
+        assert_eq!(merged.sharing, NamespaceSharing::Shareable as i32);
+    }
+
+    #[test]
+    fn masked_fields_can_be_reset_to_defaults() {
+        let mask = vec!["settings.sharing".to_string(), "default_retention_days".to_string()];
+        let merged = merge_settings(&current(), NamespaceSettings::default(), &mask).unwrap();
+        assert_eq!(merged.sharing, NamespaceSharing::Private as i32);
+        assert_eq!(merged.default_retention_days, 0);
+        assert_eq!(merged.secret_policy, SecretPolicy::Reject as i32);
+        assert_eq!(merged.embedding_model, "bge-small-en-v1.5");
+
+        let status = merge_settings(&current(), NamespaceSettings::default(), &["name".to_string()]).unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+    }
+}
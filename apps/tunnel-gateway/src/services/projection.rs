@@ -0,0 +1,111 @@
+//! Which columns of a memory the read RPCs fetch and return.
+
+use identra_proto::memory::{Memory, MemoryFields};
+use identra_core::error::Error;
+use tonic::Status;
+
+/// Longest snippet a caller may ask for, in characters
+pub const MAX_SNIPPET_LEN: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Projection {
+    pub content: bool,
+    pub embedding: bool,
+    /// Snippet length in characters; 0 for none
+    pub snippet: usize,
+}
+
+impl Default for Projection {
+    fn default() -> Self {
+        Self { content: true, embedding: false, snippet: 0 }
+    }
+}
+
+impl Projection {
+    pub fn from_proto(fields: Option<MemoryFields>) -> Result<Self, Status> {
+        let Some(fields) = fields else {
+            return Ok(Self::default());
+        };
+        let snippet = usize::try_from(fields.snippet_length)
+            .ok()
+            .filter(|length| *length <= MAX_SNIPPET_LEN)
+            .ok_or_else(|| Error::invalid_field("fields.snippet_length", format!("Must be between 0 and {}", MAX_SNIPPET_LEN)))?;
+        Ok(Self { content: !fields.omit_content, embedding: fields.include_embedding, snippet })
+    }
+
+    /// SELECT list over `memories` for `MemoryDatabase::map_rows`. Without content only the
+    /// snippet's worth is read past leading whitespace, one character over so `snippet` can
+    /// tell it was cut.
+    pub fn columns(&self) -> String {
+        let content = match (self.content, self.snippet) {
+            (true, _) => "content".to_string(),
+            (false, 0) => "'' AS content".to_string(),
+            (false, length) => format!(r"left(regexp_replace(content, '^\s+', ''), {}) AS content", length + 1),
+        };
+        let mut columns = format!("id, {}, metadata, tags, created_at, updated_at", content);
+        if self.embedding {
+            columns.push_str(", embedding::real[] AS embedding_values");
+        }
+        columns
+    }
+
+    /// Shape a memory read with `columns`, or in full, for the response
+    pub fn apply(&self, mut memory: Memory) -> Memory {
+        if self.snippet > 0 {
+            memory.snippet = snippet(&memory.content, self.snippet);
+        }
+        if !self.content {
+            memory.content.clear();
+        }
+        if !self.embedding {
+            memory.embedding.clear();
+        }
+        memory
+    }
+}
+
+/// Leading `length` characters of `content` on one line, cut back to a word boundary
+/// when one is near and marked with an ellipsis when anything was dropped
+fn snippet(content: &str, length: usize) -> String {
+    let flat: String = content.trim().chars().map(|c| if c.is_whitespace() { ' ' } else { c }).collect();
+    if flat.chars().count() <= length {
+        return flat;
+    }
+    let mut chars = flat.chars();
+    let cut: String = chars.by_ref().take(length.saturating_sub(1)).collect();
+    let whole_word = chars.next() == Some(' ');
+    let cut = match cut.rfind(' ') {
+        Some(space) if !whole_word && cut[..space].chars().count() >= length * 3 / 4 => &cut[..space],
+        _ => cut.as_str(),
+    }
+    .trim_end();
+    format!("{}…", cut)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn columns_follow_the_mask() {
+        assert_eq!(Projection::default().columns(), "id, content, metadata, tags, created_at, updated_at");
+        let listing = Projection::from_proto(Some(MemoryFields { omit_content: true, snippet_length: 40, ..Default::default() })).unwrap();
+        assert!(listing.columns().starts_with(r"id, left(regexp_replace(content, '^\s+', ''), 41) AS content,"));
+        let vectors = Projection { content: false, embedding: true, snippet: 0 };
+        assert_eq!(vectors.columns(), "id, '' AS content, metadata, tags, created_at, updated_at, embedding::real[] AS embedding_values");
+
+        assert!(Projection::from_proto(Some(MemoryFields { snippet_length: -1, ..Default::default() })).is_err());
+        assert!(Projection::from_proto(Some(MemoryFields { snippet_length: 1001, ..Default::default() })).is_err());
+    }
+
+    #[test]
+    fn snippets_cut_on_word_boundaries() {
+        assert_eq!(snippet("  short\nnote ", 20), "short note");
+        assert_eq!(snippet("the quick brown fox jumps", 20), "the quick brown fox…");
+        assert_eq!(snippet("supercalifragilistic", 10), "supercali…");
+
+        let memory = Memory { content: "ab cd".to_string(), embedding: vec![0.5], ..Default::default() };
+        let shaped = Projection { content: false, embedding: false, snippet: 4 }.apply(memory);
+        assert_eq!((shaped.content.as_str(), shaped.snippet.as_str(), shaped.embedding.len()), ("", "ab…", 0));
+    }
+}
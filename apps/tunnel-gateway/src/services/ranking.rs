@@ -0,0 +1,359 @@
+//! Re-ranking of vector search matches: cross-encoder scoring, time-decay weighting and
+//! maximal marginal relevance.
+
+use identra_proto::memory::{CrossEncoderRanking, RecencyField, SearchRanking};
+use crate::database::SimilarMemory;
+use fastembed::RerankerModel;
+use identra_core::error::Error;
+use tonic::Status;
+
+/// Most nearest neighbours a search re-ranks
+pub const MAX_CANDIDATES: i32 = 200;
+
+const DEFAULT_MMR_LAMBDA: f32 = 0.5;
+const DEFAULT_HALF_LIFE_DAYS: f32 = 30.0;
+const DEFAULT_RECENCY_WEIGHT: f32 = 0.3;
+
+/// Fewest matches a cross-encoder re-scores when the request leaves `top_k` unset
+const DEFAULT_RERANK_TOP_K: i32 = 20;
+/// Most matches a cross-encoder re-scores; each one is a model pass
+pub const MAX_RERANK_TOP_K: i32 = 100;
+pub const DEFAULT_RERANKER_MODEL: &str = "bge-reranker-base";
+
+pub struct Recency {
+    pub half_life_secs: f64,
+    pub weight: f32,
+    pub field: RecencyField,
+}
+
+pub struct Rerank {
+    pub query: String,
+    pub model: String,
+    top_k: i32,
+}
+
+impl Rerank {
+    /// How many of the nearest matches to re-score for `limit` results
+    pub fn depth(&self, limit: i32) -> usize {
+        let top_k = if self.top_k > 0 { self.top_k } else { limit.clamp(DEFAULT_RERANK_TOP_K, MAX_RERANK_TOP_K) };
+        top_k as usize
+    }
+}
+
+/// SearchMemories ranking options with defaults filled in
+#[derive(Default)]
+pub struct Ranking {
+    /// Relevance weight of maximal marginal relevance, when diversifying
+    pub mmr_lambda: Option<f32>,
+    pub recency: Option<Recency>,
+    pub rerank: Option<Rerank>,
+    candidates: i32,
+}
+
+impl Ranking {
+    pub fn from_proto(ranking: Option<SearchRanking>) -> Result<Self, Status> {
+        let Some(ranking) = ranking else {
+            return Ok(Self::default());
+        };
+        if !(0..=MAX_CANDIDATES).contains(&ranking.candidates) {
+            return Err(Error::invalid_field("ranking.candidates", format!("candidates must be 0-{}", MAX_CANDIDATES)).into());
+        }
+
+        let mmr_lambda = match ranking.mmr {
+            Some(mmr) if !(0.0..=1.0).contains(&mmr.lambda) => {
+                return Err(Error::invalid_field("ranking.mmr.lambda", "lambda must be between 0 and 1").into());
+            }
+            Some(mmr) => Some(if mmr.lambda > 0.0 { mmr.lambda } else { DEFAULT_MMR_LAMBDA }),
+            None => None,
+        };
+
+        let recency = match ranking.recency {
+            Some(recency) => {
+                if !recency.half_life_days.is_finite() || recency.half_life_days < 0.0 {
+                    return Err(Error::invalid_field("ranking.recency.half_life_days", "half_life_days cannot be negative").into());
+                }
+                if !(0.0..=1.0).contains(&recency.weight) {
+                    return Err(Error::invalid_field("ranking.recency.weight", "weight must be between 0 and 1").into());
+                }
+                let field = RecencyField::try_from(recency.field)
+                    .map_err(|_| Error::invalid_field("ranking.recency.field", "Unknown recency field"))?;
+                let half_life_days = if recency.half_life_days > 0.0 { recency.half_life_days } else { DEFAULT_HALF_LIFE_DAYS };
+                Some(Recency {
+                    half_life_secs: half_life_days as f64 * 86_400.0,
+                    weight: if recency.weight > 0.0 { recency.weight } else { DEFAULT_RECENCY_WEIGHT },
+                    field,
+                })
+            }
+            None => None,
+        };
+
+        let rerank = ranking.rerank.map(rerank_from_proto).transpose()?;
+
+        Ok(Self { mmr_lambda, recency, rerank, candidates: ranking.candidates })
+    }
+
+    /// Nearest neighbours to fetch for `limit` results: re-ranking needs more to choose from
+    pub fn candidates(&self, limit: i32) -> i32 {
+        let fetch = if self.mmr_lambda.is_none() && self.recency.is_none() {
+            limit
+        } else if self.candidates > 0 {
+            self.candidates.max(limit)
+        } else {
+            limit.saturating_mul(4).min(MAX_CANDIDATES).max(limit)
+        };
+        match &self.rerank {
+            Some(rerank) => fetch.max(rerank.depth(limit) as i32),
+            None => fetch,
+        }
+    }
+}
+
+fn rerank_from_proto(rerank: CrossEncoderRanking) -> Result<Rerank, Status> {
+    if rerank.query.trim().is_empty() {
+        return Err(Error::invalid_field("ranking.rerank.query", "Re-ranking needs the query text").into());
+    }
+    if !(0..=MAX_RERANK_TOP_K).contains(&rerank.top_k) {
+        return Err(Error::invalid_field("ranking.rerank.top_k", format!("top_k must be 0-{}", MAX_RERANK_TOP_K)).into());
+    }
+    let model = if rerank.model.is_empty() { DEFAULT_RERANKER_MODEL.to_string() } else { rerank.model.to_ascii_lowercase() };
+    if parse_reranker_model(&model).is_none() {
+        return Err(Error::invalid_field("ranking.rerank.model", format!("Unsupported re-ranking model '{}'", rerank.model)).into());
+    }
+    Ok(Rerank { query: rerank.query, model, top_k: rerank.top_k })
+}
+
+/// Map a `CrossEncoderRanking.model` name to a local fastembed re-ranker
+pub fn parse_reranker_model(name: &str) -> Option<RerankerModel> {
+    match name {
+        "bge-reranker-base" => Some(RerankerModel::BGERerankerBase),
+        "bge-reranker-v2-m3" => Some(RerankerModel::BGERerankerV2M3),
+        "jina-reranker-v1-turbo-en" => Some(RerankerModel::JINARerankerV1TurboEn),
+        "jina-reranker-v2-base-multilingual" => Some(RerankerModel::JINARerankerV2BaseMultiligual),
+        _ => None,
+    }
+}
+
+/// A match with its final score and the parts it was computed from
+pub struct Ranked {
+    pub found: SimilarMemory,
+    pub rerank_score: Option<f32>,
+    pub recency: f32,
+    pub relevance: f32,
+    pub redundancy: f32,
+    pub score: f32,
+}
+
+/// Order `found` by the ranking and keep the best `limit`. With `rerank_scores`, one per
+/// leading match in the same order, the cross-encoder score stands in for similarity;
+/// matches past the re-scored ones follow them, ranked without it.
+pub fn rank(mut found: Vec<SimilarMemory>, rerank_scores: Option<Vec<f32>>, limit: usize, ranking: &Ranking, now: i64) -> Vec<Ranked> {
+    if let Some(depth) = rerank_scores.as_ref().map(Vec::len).filter(|depth| *depth < found.len()) {
+        let rest = found.split_off(depth);
+        let mut ranked = rank(found, rerank_scores, limit, ranking, now);
+        ranked.extend(rank(rest, None, limit.saturating_sub(ranked.len()), ranking, now));
+        return ranked;
+    }
+
+    let mut rerank_scores = rerank_scores.map(Vec::into_iter);
+    let mut pool: Vec<Ranked> = found.into_iter()
+        .map(|found| {
+            let rerank_score = rerank_scores.as_mut().and_then(Iterator::next);
+            let base = rerank_score.unwrap_or(found.similarity);
+            let recency = ranking.recency.as_ref().map_or(1.0, |recency| decay(&found, recency, now));
+            let relevance = match &ranking.recency {
+                Some(r) => (1.0 - r.weight) * base + r.weight * recency,
+                None => base,
+            };
+            Ranked { found, rerank_score, recency, relevance, redundancy: 0.0, score: relevance }
+        })
+        .collect();
+
+    let Some(lambda) = ranking.mmr_lambda else {
+        pool.sort_by(|a, b| b.score.total_cmp(&a.score));
+        pool.truncate(limit);
+        return pool;
+    };
+
+    // Greedy MMR: each pick trades relevance against similarity to what was already picked
+    let mut picked: Vec<Ranked> = Vec::with_capacity(limit.min(pool.len()));
+    while picked.len() < limit && !pool.is_empty() {
+        for candidate in pool.iter_mut() {
+            candidate.redundancy = picked.iter()
+                .map(|p| cosine(&candidate.found.memory.embedding, &p.found.memory.embedding))
+                .fold(0.0, f32::max);
+            candidate.score = lambda * candidate.relevance - (1.0 - lambda) * candidate.redundancy;
+        }
+        let best = pool.iter()
+            .enumerate()
+            .max_by(|(_, a), (_, b)| a.score.total_cmp(&b.score))
+            .map(|(i, _)| i)
+            .unwrap_or_default();
+        picked.push(pool.swap_remove(best));
+    }
+    picked
+}
+
+/// 0.5 per half-life elapsed since the memory was created or last read
+fn decay(found: &SimilarMemory, recency: &Recency, now: i64) -> f32 {
+    let since = match recency.field {
+        RecencyField::CreatedAt => found.memory.created_at,
+        RecencyField::LastAccessedAt => found.last_accessed_at.unwrap_or(found.memory.created_at),
+    };
+    let age = (now - since).max(0) as f64;
+    0.5f64.powf(age / recency.half_life_secs) as f32
+}
+
+/// Cosine similarity; 0 for vectors of different length or without magnitude
+pub fn cosine(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norms = a.iter().map(|x| x * x).sum::<f32>().sqrt() * b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norms == 0.0 { 0.0 } else { dot / norms }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::memory::MemoryModel;
+    use identra_proto::memory::{MmrRanking, RecencyRanking};
+    use std::collections::HashMap;
+
+    const DAY: i64 = 86_400;
+
+    fn found(id: &str, embedding: Vec<f32>, similarity: f32, created_at: i64, last_accessed_at: Option<i64>) -> SimilarMemory {
+        SimilarMemory {
+            memory: MemoryModel {
+                id: id.to_string(),
+                content: id.to_string(),
+                metadata: HashMap::new(),
+                embedding,
+                tags: vec![],
+                created_at,
+                updated_at: created_at,
+            },
+            similarity,
+            last_accessed_at,
+        }
+    }
+
+    fn ids(ranked: &[Ranked]) -> Vec<&str> {
+        ranked.iter().map(|r| r.found.memory.id.as_str()).collect()
+    }
+
+    #[test]
+    fn plain_ranking_keeps_similarity_order() {
+        let ranking = Ranking::from_proto(None).unwrap();
+        assert_eq!(ranking.candidates(10), 10);
+
+        let ranked = rank(vec![found("b", vec![], 0.5, 0, None), found("a", vec![], 0.9, 0, None)], None, 1, &ranking, 0);
+        assert_eq!(ids(&ranked), ["a"]);
+        assert_eq!((ranked[0].score, ranked[0].recency, ranked[0].redundancy), (0.9, 1.0, 0.0));
+    }
+
+    #[test]
+    fn mmr_skips_near_duplicates() {
+        let ranking = Ranking::from_proto(Some(SearchRanking { mmr: Some(MmrRanking { lambda: 0.5 }), ..Default::default() })).unwrap();
+        assert_eq!(ranking.candidates(10), 40);
+
+        let ranked = rank(
+            vec![
+                found("turn-1", vec![1.0, 0.0], 0.90, 0, None),
+                found("turn-2", vec![0.99, 0.1], 0.89, 0, None),
+                found("other", vec![0.0, 1.0], 0.70, 0, None),
+            ],
+            None,
+            2,
+            &ranking,
+            0,
+        );
+        assert_eq!(ids(&ranked), ["turn-1", "other"]);
+        assert_eq!(ranked[1].redundancy, 0.0);
+        assert!((ranked[1].score - 0.35).abs() < 1e-6);
+    }
+
+    #[test]
+    fn recency_decays_by_half_life() {
+        let ranking = Ranking::from_proto(Some(SearchRanking {
+            recency: Some(RecencyRanking { half_life_days: 10.0, weight: 0.5, field: RecencyField::LastAccessedAt as i32 }),
+            ..Default::default()
+        }))
+        .unwrap();
+
+        let now = 100 * DAY;
+        let ranked = rank(
+            vec![
+                found("old", vec![], 0.8, 0, None),
+                found("read-recently", vec![], 0.7, 0, Some(now)),
+                found("ten-days-old", vec![], 0.7, now - 10 * DAY, None),
+            ],
+            None,
+            3,
+            &ranking,
+            now,
+        );
+        assert_eq!(ids(&ranked), ["read-recently", "ten-days-old", "old"]);
+        assert_eq!(ranked[0].recency, 1.0);
+        assert!((ranked[1].recency - 0.5).abs() < 1e-6);
+        assert!((ranked[1].relevance - 0.6).abs() < 1e-6);
+    }
+
+    #[test]
+    fn cross_encoder_scores_replace_similarity() {
+        let rerank = |top_k| CrossEncoderRanking { query: "favourite tea".to_string(), model: String::new(), top_k };
+        let ranking = Ranking::from_proto(Some(SearchRanking { rerank: Some(rerank(0)), ..Default::default() })).unwrap();
+        assert_eq!(ranking.rerank.as_ref().unwrap().model, DEFAULT_RERANKER_MODEL);
+        assert_eq!(ranking.rerank.as_ref().unwrap().depth(5), 20);
+        assert_eq!(ranking.candidates(5), 20);
+
+        let ranked = rank(
+            vec![found("mentions-tea", vec![], 0.9, 0, None), found("answers", vec![], 0.8, 0, None)],
+            Some(vec![0.2, 0.95]),
+            2,
+            &ranking,
+            0,
+        );
+        assert_eq!(ids(&ranked), ["answers", "mentions-tea"]);
+        assert_eq!((ranked[0].rerank_score, ranked[0].found.similarity, ranked[0].score), (Some(0.95), 0.8, 0.95));
+
+        // top_k below the limit re-scores the nearest matches and keeps the rest after them
+        let ranking = Ranking::from_proto(Some(SearchRanking { rerank: Some(rerank(2)), ..Default::default() })).unwrap();
+        let depth = ranking.rerank.as_ref().unwrap().depth(4);
+        assert_eq!((depth, ranking.candidates(4)), (2, 4));
+        let ranked = rank(
+            vec![
+                found("mentions-tea", vec![], 0.9, 0, None),
+                found("answers", vec![], 0.8, 0, None),
+                found("far", vec![], 0.6, 0, None),
+                found("close", vec![], 0.7, 0, None),
+            ],
+            Some(vec![0.2, 0.95]),
+            4,
+            &ranking,
+            0,
+        );
+        assert_eq!(ids(&ranked), ["answers", "mentions-tea", "close", "far"]);
+        assert_eq!((ranked[2].rerank_score, ranked[2].score), (None, 0.7));
+
+        let top_k = |top_k| Ranking::from_proto(Some(SearchRanking { rerank: Some(rerank(top_k)), ..Default::default() }));
+        assert!(top_k(MAX_RERANK_TOP_K + 1).is_err());
+        assert_eq!(top_k(50).unwrap().rerank.unwrap().depth(5), 50);
+    }
+
+    #[test]
+    fn rejects_out_of_range_options() {
+        let mmr = |lambda| Ranking::from_proto(Some(SearchRanking { mmr: Some(MmrRanking { lambda }), ..Default::default() }));
+        assert!(mmr(1.5).is_err());
+        assert_eq!(mmr(0.0).unwrap().mmr_lambda, Some(DEFAULT_MMR_LAMBDA));
+        assert!(Ranking::from_proto(Some(SearchRanking { candidates: 500, ..Default::default() })).is_err());
+
+        let rerank = |query: &str, model: &str| Ranking::from_proto(Some(SearchRanking {
+            rerank: Some(CrossEncoderRanking { query: query.to_string(), model: model.to_string(), top_k: 0 }),
+            ..Default::default()
+        }));
+        assert!(rerank(" ", "").is_err());
+        assert!(rerank("tea", "cohere-rerank").is_err());
+        assert!(rerank("tea", "BGE-Reranker-V2-M3").is_ok());
+    }
+}
@@ -0,0 +1,108 @@
+use identra_proto::memory::{MemoryStats, StatsBucket, TagCount};
+use identra_core::error::Error;
+
+/// Longest tag name, in characters
+pub const MAX_TAG_LEN: usize = 64;
+/// Most memories one RetagMemories call may touch
+pub const MAX_RETAG_MEMORIES: usize = 1000;
+pub const DEFAULT_TAG_LIMIT: i32 = 100;
+pub const MAX_TAG_LIMIT: i32 = 1000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StatsBucketModel {
+    pub key: String,
+    pub count: i64,
+    pub bytes: i64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MemoryStatsModel {
+    pub total_count: i64,
+    pub total_bytes: i64,
+    pub embedded_count: i64,
+    pub untagged_count: i64,
+    pub trashed_count: i64,
+    pub oldest: Option<i64>,
+    pub newest: Option<i64>,
+    pub by_tag: Vec<StatsBucketModel>,
+    pub by_type: Vec<StatsBucketModel>,
+    pub by_month: Vec<StatsBucketModel>,
+}
+
+fn bucket_to_proto(b: StatsBucketModel) -> StatsBucket {
+    StatsBucket { key: b.key, count: b.count, bytes: b.bytes }
+}
+
+pub fn stats_to_proto(s: MemoryStatsModel) -> MemoryStats {
+    let embedding_coverage = if s.total_count > 0 { s.embedded_count as f32 / s.total_count as f32 } else { 1.0 };
+    MemoryStats {
+        total_count: s.total_count,
+        total_bytes: s.total_bytes,
+        embedded_count: s.embedded_count,
+        embedding_coverage,
+        untagged_count: s.untagged_count,
+        trashed_count: s.trashed_count,
+        oldest: s.oldest.map(|seconds| prost_types::Timestamp { seconds, nanos: 0 }),
+        newest: s.newest.map(|seconds| prost_types::Timestamp { seconds, nanos: 0 }),
+        by_tag: s.by_tag.into_iter().map(bucket_to_proto).collect(),
+        by_type: s.by_type.into_iter().map(bucket_to_proto).collect(),
+        by_month: s.by_month.into_iter().map(bucket_to_proto).collect(),
+    }
+}
+
+pub fn tag_count_to_proto((tag, count): (String, i64)) -> TagCount {
+    TagCount { tag, count }
+}
+
+/// A tag as stored: trimmed, non-empty, at most `MAX_TAG_LEN` characters, no control characters
+pub fn tag_name(field: &str, tag: &str) -> Result<String, Error> {
+    let tag = tag.trim();
+    if tag.is_empty() {
+        return Err(Error::invalid_field(field, "Tag cannot be empty"));
+    }
+    if tag.chars().count() > MAX_TAG_LEN {
+        return Err(Error::invalid_field(field, format!("Tag cannot be longer than {} characters", MAX_TAG_LEN)));
+    }
+    if tag.chars().any(char::is_control) {
+        return Err(Error::invalid_field(field, "Tag cannot contain control characters"));
+    }
+    Ok(tag.to_string())
+}
+
+/// Validate a list of tags, dropping repeats but keeping the order they were given in
+pub fn tag_names(field: &str, tags: &[String]) -> Result<Vec<String>, Error> {
+    let mut names: Vec<String> = Vec::with_capacity(tags.len());
+    for tag in tags {
+        let name = tag_name(field, tag)?;
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    Ok(names)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tag_names_are_trimmed_and_bounded() {
+        assert_eq!(tag_name("tag", "  work ").unwrap(), "work");
+        assert!(tag_name("tag", " ").is_err());
+        assert!(tag_name("tag", "a\nb").is_err());
+        assert!(tag_name("tag", &"é".repeat(MAX_TAG_LEN)).is_ok());
+        assert!(tag_name("tag", &"é".repeat(MAX_TAG_LEN + 1)).is_err());
+
+        let tags = ["b".to_string(), "a ".to_string(), " b".to_string()];
+        assert_eq!(tag_names("tags", &tags).unwrap(), ["b", "a"]);
+    }
+
+    #[test]
+    fn empty_namespace_counts_as_fully_embedded() {
+        let stats = stats_to_proto(MemoryStatsModel::default());
+        assert_eq!((stats.embedding_coverage, stats.oldest), (1.0, None));
+
+        let stats = stats_to_proto(MemoryStatsModel { total_count: 4, embedded_count: 3, ..Default::default() });
+        assert_eq!(stats.embedding_coverage, 0.75);
+    }
+}
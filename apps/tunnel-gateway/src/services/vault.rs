@@ -6,148 +6,194 @@ use identra_proto::vault::{
     ListKeysRequest, ListKeysResponse,
     KeyExistsRequest, KeyExistsResponse,
 };
-use crate::ipc_client::VaultClient;
+use identra_client::ipc::VaultClient;
+use crate::services::acl::Principal;
+use crate::services::audit::{AuditEvent, AuditLog};
+use identra_core::error::{Error, ErrorCode};
 use tonic::{Request, Response, Status};
+use std::sync::Arc;
 
-pub struct VaultServiceImpl;
+pub struct VaultServiceImpl {
+    audit: AuditLog,
+    /// Local socket of the vault daemon
+    socket: String,
+}
 
 impl VaultServiceImpl {
-    pub fn new() -> Self {
-        Self
+    pub fn new(audit: AuditLog, socket: String) -> Self {
+        Self { audit, socket }
     }
     
-    pub fn into_server(self) -> VaultServiceServer<Self> {
-        VaultServiceServer::new(self)
+    pub fn into_server(self: Arc<Self>) -> VaultServiceServer<Self> {
+        VaultServiceServer::from_arc(self)
     }
 }
 
+fn vault_unavailable(error: impl std::fmt::Display) -> Error {
+    Error::new(ErrorCode::VaultUnavailable, "Vault daemon not available").with_internal(error)
+}
+
 #[tonic::async_trait]
 impl VaultService for VaultServiceImpl {
     async fn store_key(
         &self,
         request: Request<StoreKeyRequest>,
     ) -> Result<Response<StoreKeyResponse>, Status> {
+        let principal = Principal::from_request(&request)?;
         let req = request.into_inner();
-        
-        let mut client = VaultClient::connect()
-            .await
-            .map_err(|e| Status::unavailable(format!("Vault daemon not available: {}", e)))?;
-        
-        // Convert protobuf expires_at (Timestamp) to Unix timestamp
-        let expires_at = req.expires_at.map(|ts| ts.seconds);
-        
-        client.store_key(
-            req.key_id.clone(), 
-            req.key_data,
-            req.metadata,
-            expires_at,
-        )
-            .await
-            .map_err(|e| Status::internal(format!("Failed to store key: {}", e)))?;
-        
-        tracing::info!("Stored key: {}", req.key_id);
-        
-        Ok(Response::new(StoreKeyResponse {
-            success: true,
-            message: format!("Key '{}' stored successfully", req.key_id),
-        }))
+        let audit = AuditEvent::new("vault", "StoreKey", &principal).target(&req.key_id);
+
+        let result: Result<Response<StoreKeyResponse>, Status> = async {
+            let mut client = VaultClient::connect_to(&self.socket)
+                .await
+                .map_err(vault_unavailable)?;
+        
+            // Convert protobuf expires_at (Timestamp) to Unix timestamp
+            let expires_at = req.expires_at.map(|ts| ts.seconds);
+        
+            client.store_key(
+                req.key_id.clone(), 
+                req.key_data,
+                req.metadata,
+                expires_at,
+            )
+                .await
+                .map_err(|e| Error::internal(format!("Failed to store key: {}", e)))?;
+        
+            tracing::info!("Stored key: {}", req.key_id);
+        
+            Ok(Response::new(StoreKeyResponse {
+                success: true,
+                message: format!("Key '{}' stored successfully", req.key_id),
+            }))
+        }.await;
+
+        self.audit.record(audit, &result);
+        result
     }
     
     async fn retrieve_key(
         &self,
         request: Request<RetrieveKeyRequest>,
     ) -> Result<Response<RetrieveKeyResponse>, Status> {
+        let principal = Principal::from_request(&request)?;
         let req = request.into_inner();
-        
-        let mut client = VaultClient::connect()
-            .await
-            .map_err(|e| Status::unavailable(format!("Vault daemon not available: {}", e)))?;
-        
-        let (key_data, metadata, created_at, expires_at) = client.retrieve_key(req.key_id.clone())
-            .await
-            .map_err(|e| Status::not_found(format!("Key not found: {}", e)))?;
-        
-        tracing::info!("Retrieved key: {}", req.key_id);
-        
-        // Convert Unix timestamp to protobuf Timestamp
-        let created_at_ts = Some(prost_types::Timestamp {
-            seconds: created_at,
-            nanos: 0,
-        });
-        
-        Ok(Response::new(RetrieveKeyResponse {
-            key_data,
-            metadata,
-            created_at: created_at_ts,
-        }))
+        let audit = AuditEvent::new("vault", "RetrieveKey", &principal).target(&req.key_id);
+
+        let result: Result<Response<RetrieveKeyResponse>, Status> = async {
+            let mut client = VaultClient::connect_to(&self.socket)
+                .await
+                .map_err(vault_unavailable)?;
+        
+            let key = client.retrieve_key(req.key_id.clone())
+                .await
+                .map_err(|e| {
+                    Error::new(ErrorCode::KeyNotFound, "Key not found")
+                        .with_metadata("key_id", req.key_id.clone())
+                        .with_internal(e)
+                })?;
+        
+            tracing::info!("Retrieved key: {}", req.key_id);
+        
+            // Convert Unix timestamp to protobuf Timestamp
+            let created_at_ts = Some(prost_types::Timestamp {
+                seconds: key.created_at,
+                nanos: 0,
+            });
+        
+            Ok(Response::new(RetrieveKeyResponse {
+                key_data: key.key_data,
+                metadata: key.metadata,
+                created_at: created_at_ts,
+            }))
+        }.await;
+
+        self.audit.record(audit, &result);
+        result
     }
     
     async fn delete_key(
         &self,
         request: Request<DeleteKeyRequest>,
     ) -> Result<Response<DeleteKeyResponse>, Status> {
+        let principal = Principal::from_request(&request)?;
         let req = request.into_inner();
-        
-        let mut client = VaultClient::connect()
-            .await
-            .map_err(|e| Status::unavailable(format!("Vault daemon not available: {}", e)))?;
-        
-        client.delete_key(req.key_id.clone())
-            .await
-            .map_err(|e| Status::internal(format!("Failed to delete key: {}", e)))?;
-        
-        tracing::info!("Deleted key: {}", req.key_id);
-        
-        Ok(Response::new(DeleteKeyResponse {
-            success: true,
-            message: format!("Key '{}' deleted successfully", req.key_id),
-        }))
+        let audit = AuditEvent::new("vault", "DeleteKey", &principal).target(&req.key_id);
+
+        let result: Result<Response<DeleteKeyResponse>, Status> = async {
+            let mut client = VaultClient::connect_to(&self.socket)
+                .await
+                .map_err(vault_unavailable)?;
+        
+            client.delete_key(req.key_id.clone())
+                .await
+                .map_err(|e| Error::internal(format!("Failed to delete key: {}", e)))?;
+        
+            tracing::info!("Deleted key: {}", req.key_id);
+        
+            Ok(Response::new(DeleteKeyResponse {
+                success: true,
+                message: format!("Key '{}' deleted successfully", req.key_id),
+            }))
+        }.await;
+
+        self.audit.record(audit, &result);
+        result
     }
     
     async fn list_keys(
         &self,
-        _request: Request<ListKeysRequest>,
+        request: Request<ListKeysRequest>,
     ) -> Result<Response<ListKeysResponse>, Status> {
-        let mut client = VaultClient::connect()
-            .await
-            .map_err(|e| Status::unavailable(format!("Vault daemon not available: {}", e)))?;
-        
-        let key_ids = client.list_keys()
-            .await
-            .map_err(|e| {
-                tracing::warn!("list_keys not supported: {}", e);
-                // Windows Credential Manager doesn't support listing
-                Status::unimplemented("list_keys not supported by OS keychain")
-            })?;
-        
-        tracing::info!("Listed {} keys", key_ids.len());
-        
-        Ok(Response::new(ListKeysResponse {
-            key_ids,
-            next_page_token: String::new(),
-        }))
+        let principal = Principal::from_request(&request)?;
+        let audit = AuditEvent::new("vault", "ListKeys", &principal);
+
+        let result: Result<Response<ListKeysResponse>, Status> = async {
+            let mut client = VaultClient::connect_to(&self.socket)
+                .await
+                .map_err(vault_unavailable)?;
+        
+            let key_ids = client.list_keys()
+                .await
+                .map_err(|e| {
+                    tracing::warn!("list_keys not supported: {}", e);
+                    // Windows Credential Manager doesn't support listing
+                    Error::new(ErrorCode::Unimplemented, "list_keys not supported by OS keychain")
+                })?;
+        
+            tracing::info!("Listed {} keys", key_ids.len());
+        
+            Ok(Response::new(ListKeysResponse {
+                key_ids,
+                next_page_token: String::new(),
+            }))
+        }.await;
+
+        self.audit.record(audit, &result);
+        result
     }
     
     async fn key_exists(
         &self,
         request: Request<KeyExistsRequest>,
     ) -> Result<Response<KeyExistsResponse>, Status> {
+        let principal = Principal::from_request(&request)?;
         let req = request.into_inner();
+        let audit = AuditEvent::new("vault", "KeyExists", &principal).target(&req.key_id);
+
+        let result: Result<Response<KeyExistsResponse>, Status> = async {
+            let mut client = VaultClient::connect_to(&self.socket)
+                .await
+                .map_err(vault_unavailable)?;
         
-        let mut client = VaultClient::connect()
-            .await
-            .map_err(|e| Status::unavailable(format!("Vault daemon not available: {}", e)))?;
-        
-        let exists = client.key_exists(req.key_id.clone())
-            .await
-            .map_err(|e| Status::internal(format!("Failed to check key existence: {}", e)))?;
+            let exists = client.key_exists(req.key_id.clone())
+                .await
+                .map_err(|e| Error::internal(format!("Failed to check key existence: {}", e)))?;
         
-        Ok(Response::new(KeyExistsResponse { exists }))
-    }
-}
+            Ok(Response::new(KeyExistsResponse { exists }))
+        }.await;
 
-impl Default for VaultServiceImpl {
-    fn default() -> Self {
-        Self::new()
+        self.audit.record(audit, &result);
+        result
     }
 }
@@ -0,0 +1,176 @@
+use identra_proto::memory::{MemoryEvent, MemoryEventType, Permission};
+use std::collections::HashSet;
+use std::sync::Arc;
+use tokio::sync::{broadcast, mpsc};
+use tokio_stream::wrappers::ReceiverStream;
+use identra_core::error::Error;
+use tonic::Status;
+
+use crate::database::MemoryDatabase;
+use crate::services::acl::{NamespaceAccess, Principal};
+use crate::services::memory::{memory_to_proto, MemoryModel};
+
+const FEED_CAPACITY: usize = 1024;
+const WATCH_BUFFER: usize = 32;
+
+/// Something that happened in a namespace, fanned out to WatchMemories streams
+#[derive(Debug, Clone)]
+pub enum MemoryChange {
+    Stored { namespace_id: String, memory: MemoryModel },
+    /// Moved to the trash
+    Deleted { namespace_id: String, memory_id: String },
+    Restored { namespace_id: String, memory: MemoryModel },
+    /// Grants or sharing settings changed; watchers must re-check their access
+    AccessChanged { namespace_id: String },
+}
+
+impl MemoryChange {
+    fn namespace_id(&self) -> &str {
+        match self {
+            Self::Stored { namespace_id, .. }
+            | Self::Restored { namespace_id, .. }
+            | Self::Deleted { namespace_id, .. }
+            | Self::AccessChanged { namespace_id } => namespace_id,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ChangeFeed {
+    tx: broadcast::Sender<MemoryChange>,
+}
+
+impl Default for ChangeFeed {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ChangeFeed {
+    pub fn new() -> Self {
+        let (tx, _) = broadcast::channel(FEED_CAPACITY);
+        Self { tx }
+    }
+
+    pub fn publish(&self, change: MemoryChange) {
+        // Sending only fails when nobody is watching
+        let _ = self.tx.send(change);
+    }
+
+    /// Stream changes in `access.namespace` that the principal may see. Access is re-checked
+    /// whenever it changes, and the stream ends with PERMISSION_DENIED once it is gone.
+    pub fn watch(
+        &self,
+        db: Arc<MemoryDatabase>,
+        principal: Principal,
+        access: NamespaceAccess,
+    ) -> ReceiverStream<Result<MemoryEvent, Status>> {
+        let mut changes = self.tx.subscribe();
+        let (tx, rx) = mpsc::channel(WATCH_BUFFER);
+
+        tokio::spawn(async move {
+            let namespace_id = access.namespace.id.clone();
+            let mut access = access;
+            let mut granted = match granted_memories(&db, &access).await {
+                Ok(granted) => granted,
+                Err(e) => {
+                    let _ = tx.send(Err(Error::internal(e).into())).await;
+                    return;
+                }
+            };
+
+            loop {
+                let change = tokio::select! {
+                    _ = tx.closed() => break,
+                    change = changes.recv() => change,
+                };
+
+                let change = match change {
+                    Ok(change) if change.namespace_id() != namespace_id => continue,
+                    Ok(change) => change,
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        // A missed change may have been a revocation
+                        tracing::warn!("Memory watcher lagged by {} changes", skipped);
+                        MemoryChange::AccessChanged { namespace_id: namespace_id.clone() }
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+
+                let event = match change {
+                    MemoryChange::AccessChanged { .. } => {
+                        match reload_access(&db, &principal, &namespace_id).await {
+                            Ok(Some((reloaded, ids))) => {
+                                access = reloaded;
+                                granted = ids;
+                            }
+                            Ok(None) => {
+                                let _ = tx.send(Err(Error::permission_denied("Access to namespace revoked").into())).await;
+                                break;
+                            }
+                            Err(e) => {
+                                let _ = tx.send(Err(Error::internal(e).into())).await;
+                                break;
+                            }
+                        }
+                        continue;
+                    }
+                    MemoryChange::Stored { memory, .. } => {
+                        if !visible(&access, &granted, &memory.id) { continue; }
+                        MemoryEvent {
+                            r#type: MemoryEventType::Created as i32,
+                            memory_id: memory.id.clone(),
+                            occurred_at: Some(prost_types::Timestamp { seconds: memory.created_at, nanos: 0 }),
+                            memory: Some(memory_to_proto(memory, &access.namespace.name)),
+                        }
+                    }
+                    MemoryChange::Restored { memory, .. } => {
+                        if !visible(&access, &granted, &memory.id) { continue; }
+                        MemoryEvent {
+                            r#type: MemoryEventType::Restored as i32,
+                            memory_id: memory.id.clone(),
+                            occurred_at: Some(prost_types::Timestamp { seconds: chrono::Utc::now().timestamp(), nanos: 0 }),
+                            memory: Some(memory_to_proto(memory, &access.namespace.name)),
+                        }
+                    }
+                    MemoryChange::Deleted { memory_id, .. } => {
+                        if !visible(&access, &granted, &memory_id) { continue; }
+                        MemoryEvent {
+                            r#type: MemoryEventType::Deleted as i32,
+                            memory_id,
+                            memory: None,
+                            occurred_at: Some(prost_types::Timestamp { seconds: chrono::Utc::now().timestamp(), nanos: 0 }),
+                        }
+                    }
+                };
+
+                if tx.send(Ok(event)).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        ReceiverStream::new(rx)
+    }
+}
+
+fn visible(access: &NamespaceAccess, granted: &HashSet<String>, memory_id: &str) -> bool {
+    access.permission >= Permission::Read || granted.contains(memory_id)
+}
+
+async fn granted_memories(db: &MemoryDatabase, access: &NamespaceAccess) -> Result<HashSet<String>, sqlx::Error> {
+    match access.memory_filter() {
+        None => Ok(HashSet::new()),
+        Some(grantees) => Ok(db.granted_memory_ids(&access.namespace.id, grantees).await?.into_iter().collect()),
+    }
+}
+
+async fn reload_access(
+    db: &MemoryDatabase,
+    principal: &Principal,
+    namespace_id: &str,
+) -> Result<Option<(NamespaceAccess, HashSet<String>)>, sqlx::Error> {
+    let Some(namespace) = db.get_namespace_by_id(namespace_id).await? else { return Ok(None) };
+    let Some(access) = NamespaceAccess::resolve(db, principal, namespace).await? else { return Ok(None) };
+    let granted = granted_memories(db, &access).await?;
+    Ok(Some((access, granted)))
+}
@@ -0,0 +1,278 @@
+//! Prometheus metrics, served on `gateway.metrics_addr` rather than the public listener,
+//! and the server span every request runs in.
+//!
+//! RPCs are counted per service, method and status: the gRPC code name for gRPC and
+//! gRPC-Web calls, the HTTP status for REST routes. gRPC paths that name no registered
+//! method share the `unknown` service and method, so callers cannot mint label values.
+//! Latency is measured up to the response headers, so a streaming RPC records the time to
+//! its first message.
+
+use std::future::Future;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use identra_core::trace;
+use metrics::{counter, gauge, histogram};
+use metrics_exporter_prometheus::{BuildError, Matcher, PrometheusBuilder};
+use tonic::Code;
+use tower::{Layer, Service};
+use tower_http::trace::MakeSpan;
+
+use crate::database::MemoryDatabase;
+use crate::rest;
+use crate::rest::schema::ProtoSchema;
+use crate::services::audit::code_name;
+
+pub const RPC_REQUESTS: &str = "identra_rpc_requests_total";
+pub const RPC_DURATION: &str = "identra_rpc_duration_seconds";
+pub const EMBEDDING_BATCH_SIZE: &str = "identra_embedding_batch_size";
+pub const EMBEDDING_DURATION: &str = "identra_embedding_duration_seconds";
+pub const RERANK_DURATION: &str = "identra_rerank_duration_seconds";
+pub const SECRETS_DETECTED: &str = "identra_secrets_detected_total";
+pub const AUDIT_ENTRIES_DROPPED: &str = "identra_audit_entries_dropped_total";
+pub const VECTOR_SEARCH_DURATION: &str = "identra_vector_search_duration_seconds";
+pub const DB_POOL_CONNECTIONS: &str = "identra_db_pool_connections";
+pub const DB_POOL_IDLE: &str = "identra_db_pool_idle_connections";
+pub const DB_POOL_MAX: &str = "identra_db_pool_max_connections";
+
+/// Latency buckets shared by every `*_seconds` histogram, 1ms to 30s
+const SECONDS_BUCKETS: &[f64] = &[0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0];
+const BATCH_BUCKETS: &[f64] = &[1.0, 2.0, 4.0, 8.0, 16.0, 32.0, 64.0, 128.0, 256.0];
+
+/// Service and method label of gRPC calls to methods that are not registered
+const UNKNOWN: &str = "unknown";
+
+/// How often database pool usage is sampled
+const POOL_SAMPLE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Install the global recorder and serve `GET /metrics` on `addr`
+pub fn install(addr: SocketAddr) -> Result<(), BuildError> {
+    PrometheusBuilder::new()
+        .with_http_listener(addr)
+        .set_buckets_for_metric(Matcher::Suffix("_seconds".to_string()), SECONDS_BUCKETS)?
+        .set_buckets_for_metric(Matcher::Full(EMBEDDING_BATCH_SIZE.to_string()), BATCH_BUCKETS)?
+        .install()
+}
+
+/// Publish connection pool usage every few seconds
+pub fn watch_pool(db: Arc<MemoryDatabase>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(POOL_SAMPLE_INTERVAL);
+        loop {
+            interval.tick().await;
+            let stats = db.pool_stats();
+            gauge!(DB_POOL_CONNECTIONS).set(stats.size as f64);
+            gauge!(DB_POOL_IDLE).set(stats.idle as f64);
+            gauge!(DB_POOL_MAX).set(stats.max as f64);
+        }
+    });
+}
+
+/// Record one embedding call over `batch` documents
+pub fn record_embedding(model: &str, batch: usize, elapsed: Duration) {
+    histogram!(EMBEDDING_BATCH_SIZE, "model" => model.to_string()).record(batch as f64);
+    histogram!(EMBEDDING_DURATION, "model" => model.to_string()).record(elapsed.as_secs_f64());
+}
+
+/// Record one cross-encoder pass over a batch of documents
+pub fn record_rerank(model: &str, elapsed: Duration) {
+    histogram!(RERANK_DURATION, "model" => model.to_string()).record(elapsed.as_secs_f64());
+}
+
+/// Count a suspected credential found in stored content and what the namespace did with it
+pub fn record_secret(kind: &'static str, policy: &'static str) {
+    counter!(SECRETS_DETECTED, "kind" => kind, "policy" => policy).increment(1);
+}
+
+/// Count a lost audit entry: the writer's queue was full, the writer had stopped, or the
+/// write failed for good
+pub fn record_audit_dropped(reason: &'static str) {
+    counter!(AUDIT_ENTRIES_DROPPED, "reason" => reason).increment(1);
+}
+
+/// The service and method a request is counted under, and whether it is a REST call.
+/// gRPC paths are checked against `schema`; anything that is neither a REST route nor
+/// shaped like a gRPC path (health probes, other paths) is not recorded.
+fn rpc_for<B>(req: &http::Request<B>, schema: &ProtoSchema) -> Option<(&'static str, String, String)> {
+    if let Some(route) = rest::route_for(req.method(), req.uri().path()) {
+        return Some(("rest", route.service.to_string(), route.rpc.to_string()));
+    }
+    let (service, method) = req.uri().path().strip_prefix('/')?.split_once('/')?;
+    if !service.contains('.') {
+        return None;
+    }
+    if schema.method(service, method).is_none() {
+        return Some(("grpc", UNKNOWN.to_string(), UNKNOWN.to_string()));
+    }
+    Some(("grpc", service.to_string(), method.to_string()))
+}
+
+/// Status label of a response. Failed unary gRPC calls carry `grpc-status` in the
+/// headers; otherwise a gRPC call is OK until its trailers say differently.
+fn status_label<B>(protocol: &str, response: &http::Response<B>) -> String {
+    if protocol == "rest" {
+        return response.status().as_u16().to_string();
+    }
+    let code = response.headers().get("grpc-status")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<i32>().ok())
+        .map(Code::from_i32)
+        .unwrap_or(if response.status().is_success() { Code::Ok } else { Code::Unknown });
+    code_name(code).to_string()
+}
+
+/// Server span for each request, continuing the caller's trace from its `traceparent`
+#[derive(Clone, Copy, Default)]
+pub struct RpcSpan;
+
+impl<B> MakeSpan<B> for RpcSpan {
+    fn make_span(&mut self, req: &http::Request<B>) -> tracing::Span {
+        let path = req.uri().path();
+        let span = tracing::info_span!("request", otel.name = path, otel.kind = "server", http.method = %req.method());
+        if let Some(parent) = req.headers().get(trace::TRACEPARENT).and_then(|value| value.to_str().ok()) {
+            trace::set_parent(&span, parent);
+        }
+        span
+    }
+}
+
+/// Counts and times every RPC; sits outermost so rejected requests are counted too
+#[derive(Clone)]
+pub struct MetricsLayer {
+    schema: Arc<ProtoSchema>,
+}
+
+impl MetricsLayer {
+    /// `schema` lists the gRPC methods counted under their own names
+    pub fn new(schema: Arc<ProtoSchema>) -> Self {
+        Self { schema }
+    }
+}
+
+impl<S> Layer<S> for MetricsLayer {
+    type Service = MetricsService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        MetricsService { inner, schema: self.schema.clone() }
+    }
+}
+
+#[derive(Clone)]
+pub struct MetricsService<S> {
+    inner: S,
+    schema: Arc<ProtoSchema>,
+}
+
+impl<S, B, ResBody> Service<http::Request<B>> for MetricsService<S>
+where
+    S: Service<http::Request<B>, Response = http::Response<ResBody>> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+    B: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<B>) -> Self::Future {
+        let rpc = rpc_for(&req, &self.schema);
+        let started = Instant::now();
+
+        // Take the service that was driven to readiness, leave a fresh clone behind
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        Box::pin(async move {
+            let result = inner.call(req).await;
+            if let (Some((protocol, service, method)), Ok(response)) = (rpc, &result) {
+                let labels = [
+                    ("protocol", protocol.to_string()),
+                    ("service", service),
+                    ("method", method),
+                    ("code", status_label(protocol, response)),
+                ];
+                counter!(RPC_REQUESTS, &labels).increment(1);
+                histogram!(RPC_DURATION, &labels).record(started.elapsed().as_secs_f64());
+            }
+            result
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use metrics_exporter_prometheus::PrometheusBuilder;
+    use std::convert::Infallible;
+
+    fn request(method: http::Method, path: &str) -> http::Request<()> {
+        http::Request::builder().method(method).uri(path).body(()).unwrap()
+    }
+
+    #[test]
+    fn labels_grpc_and_rest_calls() {
+        let schema = ProtoSchema::load().unwrap();
+        let grpc = request(http::Method::POST, "/identra.memory.v1.MemoryService/GetMemory");
+        assert_eq!(
+            rpc_for(&grpc, &schema),
+            Some(("grpc", "identra.memory.v1.MemoryService".to_string(), "GetMemory".to_string()))
+        );
+        let rest = request(http::Method::GET, "/v1/memories/1234");
+        assert_eq!(rpc_for(&rest, &schema).map(|(protocol, _, method)| (protocol, method)), Some(("rest", "GetMemory".to_string())));
+
+        assert_eq!(rpc_for(&request(http::Method::GET, "/healthz"), &schema), None);
+        assert_eq!(rpc_for(&request(http::Method::GET, "/wp-admin/setup.php"), &schema), None);
+    }
+
+    #[test]
+    fn unregistered_grpc_paths_are_unknown() {
+        let schema = ProtoSchema::load().unwrap();
+        let unknown = Some(("grpc", UNKNOWN.to_string(), UNKNOWN.to_string()));
+        for path in [
+            "/identra.memory.v1.MemoryService/NoSuchMethod",
+            "/evil.Service/GetMemory",
+            "/identra.memory.v1.MemoryService/GetMemory/extra",
+            "/identra.memory.v1.MemoryService/",
+        ] {
+            assert_eq!(rpc_for(&request(http::Method::POST, path), &schema), unknown, "{}", path);
+        }
+    }
+
+    #[test]
+    fn status_comes_from_grpc_status_header() {
+        let failed = http::Response::builder().header("grpc-status", "5").body(()).unwrap();
+        assert_eq!(status_label("grpc", &failed), "NOT_FOUND");
+        let ok = http::Response::builder().body(()).unwrap();
+        assert_eq!(status_label("grpc", &ok), "OK");
+        let rest = http::Response::builder().status(429).body(()).unwrap();
+        assert_eq!(status_label("rest", &rest), "429");
+    }
+
+    #[test]
+    fn layer_records_requests() {
+        let recorder = PrometheusBuilder::new().build_recorder();
+        let handle = recorder.handle();
+        let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+
+        metrics::with_local_recorder(&recorder, || {
+            let inner = tower::service_fn(|_req: http::Request<()>| async {
+                Ok::<_, Infallible>(http::Response::builder().header("grpc-status", "16").body(()).unwrap())
+            });
+            let mut service = MetricsLayer::new(Arc::new(ProtoSchema::load().unwrap())).layer(inner);
+            let req = request(http::Method::POST, "/identra.vault.v1.VaultService/StoreKey");
+            runtime.block_on(service.call(req)).unwrap();
+        });
+
+        let rendered = handle.render();
+        let line = rendered.lines()
+            .find(|line| line.starts_with(RPC_REQUESTS))
+            .expect("request counter missing");
+        assert!(line.contains("method=\"StoreKey\""), "{}", line);
+        assert!(line.contains("code=\"UNAUTHENTICATED\""), "{}", line);
+        assert!(line.ends_with(" 1"), "{}", line);
+    }
+}
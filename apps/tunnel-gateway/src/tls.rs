@@ -0,0 +1,517 @@
+//! TLS termination for the gateway listener: certificates are reloaded from disk when
+//! they change, and client certificates can be required or mapped to service identities.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, SystemTime};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio_rustls::rustls::crypto::{ring, CryptoProvider};
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use tokio_rustls::rustls::server::{ClientHello, ResolvesServerCert, WebPkiClientVerifier};
+use tokio_rustls::rustls::sign::CertifiedKey;
+use tokio_rustls::rustls::{RootCertStore, ServerConfig};
+use tokio_rustls::server::TlsStream;
+use tokio_rustls::TlsAcceptor;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::transport::server::{TcpConnectInfo, TlsConnectInfo};
+use identra_core::config::ListenerTlsConfig;
+
+use crate::auth::AuthClaims;
+
+/// How often certificate files are checked for changes
+const RELOAD_INTERVAL: Duration = Duration::from_secs(10);
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+const ACCEPT_BUFFER: usize = 64;
+
+/// Listener TLS configuration, from `gateway.tls`
+#[derive(Debug, Clone)]
+pub struct TlsSettings {
+    pub cert_path: PathBuf,
+    pub key_path: PathBuf,
+    /// CA bundle that client certificates are verified against; enables mTLS
+    pub client_ca_path: Option<PathBuf>,
+    /// Reject connections without a valid client certificate
+    pub require_client_cert: bool,
+}
+
+impl TlsSettings {
+    /// `None` when no certificate is configured and the gateway should serve plaintext.
+    /// The config is validated at startup, so a certificate always comes with a key.
+    pub fn from_config(config: &ListenerTlsConfig) -> Option<Self> {
+        let (cert_path, key_path) = (config.cert.clone()?, config.key.clone()?);
+        Some(Self {
+            cert_path,
+            key_path,
+            client_ca_path: config.client_ca.clone(),
+            require_client_cert: config.require_client_cert,
+        })
+    }
+}
+
+fn provider() -> Arc<CryptoProvider> {
+    Arc::new(ring::default_provider())
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn read_certs(path: &Path) -> io::Result<Vec<CertificateDer<'static>>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let certs = rustls_pemfile::certs(&mut reader).collect::<Result<Vec<_>, _>>()?;
+    if certs.is_empty() {
+        return Err(invalid(format!("No certificates in {}", path.display())));
+    }
+    Ok(certs)
+}
+
+fn read_key(path: &Path) -> io::Result<PrivateKeyDer<'static>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    rustls_pemfile::private_key(&mut reader)?
+        .ok_or_else(|| invalid(format!("No private key in {}", path.display())))
+}
+
+fn modified(path: &Path) -> io::Result<SystemTime> {
+    std::fs::metadata(path)?.modified()
+}
+
+/// Serves the certificate currently on disk, swapping it in when the files change
+#[derive(Debug)]
+pub struct ReloadingCert {
+    cert_path: PathBuf,
+    key_path: PathBuf,
+    provider: Arc<CryptoProvider>,
+    current: RwLock<Arc<CertifiedKey>>,
+    loaded_versions: Mutex<(SystemTime, SystemTime)>,
+}
+
+impl ReloadingCert {
+    pub fn load(cert_path: &Path, key_path: &Path) -> io::Result<Self> {
+        let provider = provider();
+        let versions = (modified(cert_path)?, modified(key_path)?);
+        let key = Self::certified_key(&provider, cert_path, key_path)?;
+        Ok(Self {
+            cert_path: cert_path.to_path_buf(),
+            key_path: key_path.to_path_buf(),
+            provider,
+            current: RwLock::new(Arc::new(key)),
+            loaded_versions: Mutex::new(versions),
+        })
+    }
+
+    fn certified_key(provider: &CryptoProvider, cert_path: &Path, key_path: &Path) -> io::Result<CertifiedKey> {
+        let certs = read_certs(cert_path)?;
+        let key = provider.key_provider
+            .load_private_key(read_key(key_path)?)
+            .map_err(|e| invalid(format!("Unusable private key in {}: {}", key_path.display(), e)))?;
+        let certified = CertifiedKey::new(certs, key);
+        certified.keys_match()
+            .map_err(|e| invalid(format!("{} does not match {}: {}", key_path.display(), cert_path.display(), e)))?;
+        Ok(certified)
+    }
+
+    /// Reload when either file changed since the last load. A broken pair (e.g. the
+    /// certificate replaced before the key) keeps the old one in use and is retried.
+    pub fn reload_if_changed(&self) -> io::Result<bool> {
+        let versions = (modified(&self.cert_path)?, modified(&self.key_path)?);
+        let mut loaded = self.loaded_versions.lock().unwrap_or_else(|e| e.into_inner());
+        if *loaded == versions {
+            return Ok(false);
+        }
+        let key = Self::certified_key(&self.provider, &self.cert_path, &self.key_path)?;
+        *self.current.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(key);
+        *loaded = versions;
+        Ok(true)
+    }
+
+    /// Poll the files in the background for the lifetime of the process
+    pub fn watch(self: &Arc<Self>) {
+        let cert = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(RELOAD_INTERVAL);
+            loop {
+                interval.tick().await;
+                match cert.reload_if_changed() {
+                    Ok(false) => {}
+                    Ok(true) => tracing::info!("Reloaded TLS certificate from {}", cert.cert_path.display()),
+                    Err(e) => tracing::warn!("TLS certificate reload failed, keeping the current one: {}", e),
+                }
+            }
+        });
+    }
+}
+
+impl ResolvesServerCert for ReloadingCert {
+    fn resolve(&self, _client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        Some(self.current.read().unwrap_or_else(|e| e.into_inner()).clone())
+    }
+}
+
+/// rustls configuration for the listener. ALPN offers HTTP/1.1 as well, for gRPC-Web and REST.
+pub fn server_config(settings: &TlsSettings, cert: Arc<ReloadingCert>) -> Result<ServerConfig, Box<dyn std::error::Error>> {
+    let provider = provider();
+    let builder = ServerConfig::builder_with_provider(provider.clone()).with_safe_default_protocol_versions()?;
+
+    let builder = match &settings.client_ca_path {
+        Some(ca_path) => {
+            let mut roots = RootCertStore::empty();
+            for ca in read_certs(ca_path)? {
+                roots.add(ca)?;
+            }
+            let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider);
+            let verifier = if settings.require_client_cert { verifier } else { verifier.allow_unauthenticated() };
+            builder.with_client_cert_verifier(verifier.build()?)
+        }
+        None => builder.with_no_client_auth(),
+    };
+
+    let mut config = builder.with_cert_resolver(cert);
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    Ok(config)
+}
+
+/// Accept TCP connections and complete TLS handshakes off the accept loop, yielding
+/// established streams for `Server::serve_with_incoming`
+pub fn incoming(listener: TcpListener, acceptor: TlsAcceptor) -> ReceiverStream<io::Result<TlsStream<TcpStream>>> {
+    let (tx, rx) = mpsc::channel(ACCEPT_BUFFER);
+    tokio::spawn(async move {
+        loop {
+            let (stream, peer) = match listener.accept().await {
+                Ok(accepted) => accepted,
+                Err(e) => {
+                    tracing::warn!("Accept failed: {}", e);
+                    continue;
+                }
+            };
+            let _ = stream.set_nodelay(true);
+            let acceptor = acceptor.clone();
+            let accepted = tx.clone();
+            tokio::spawn(async move {
+                match tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+                    Ok(Ok(tls)) => { let _ = accepted.send(Ok(tls)).await; }
+                    Ok(Err(e)) => tracing::debug!("TLS handshake with {} failed: {}", peer, e),
+                    Err(_) => tracing::debug!("TLS handshake with {} timed out", peer),
+                }
+            });
+            if tx.is_closed() {
+                break;
+            }
+        }
+    });
+    ReceiverStream::new(rx)
+}
+
+/// Peer address of a request, over plaintext or TLS
+pub fn remote_addr(extensions: &http::Extensions) -> Option<SocketAddr> {
+    extensions.get::<TcpConnectInfo>()
+        .and_then(|info| info.remote_addr())
+        .or_else(|| extensions.get::<TlsConnectInfo<TcpConnectInfo>>().and_then(|info| info.get_ref().remote_addr()))
+}
+
+/// Maps client certificate subject common names to service identities, from
+/// `gateway.mtls_identities` entries like `brain-service=brain,ghost-desktop=desktop`
+#[derive(Debug, Clone, Default)]
+pub struct ServiceIdentities {
+    by_common_name: HashMap<String, String>,
+}
+
+impl ServiceIdentities {
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut by_common_name = HashMap::new();
+        for entry in spec.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let (common_name, identity) = entry.split_once('=')
+                .ok_or_else(|| format!("Invalid service identity '{}', expected common_name=identity", entry))?;
+            let (common_name, identity) = (common_name.trim(), identity.trim());
+            if common_name.is_empty() || identity.is_empty() {
+                return Err(format!("Invalid service identity '{}'", entry));
+            }
+            by_common_name.insert(common_name.to_string(), identity.to_string());
+        }
+        Ok(Self { by_common_name })
+    }
+
+    /// Claims for the verified client certificate of a request, if its subject is mapped
+    pub fn claims_for(&self, extensions: &http::Extensions) -> Option<AuthClaims> {
+        if self.by_common_name.is_empty() {
+            return None;
+        }
+        let certs = extensions.get::<TlsConnectInfo<TcpConnectInfo>>()?.peer_certs()?;
+        let identity = self.by_common_name.get(&common_name(certs.first()?)?)?;
+        Some(AuthClaims {
+            sub: format!("service:{}", identity),
+            email: String::new(),
+            role: "service".to_string(),
+            groups: Vec::new(),
+        })
+    }
+}
+
+fn common_name(cert: &CertificateDer<'_>) -> Option<String> {
+    let (_, cert) = x509_parser::parse_x509_certificate(cert.as_ref()).ok()?;
+    let name = cert.subject().iter_common_name().next()?.as_str().ok()?.to_string();
+    Some(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rcgen::{BasicConstraints, CertificateParams, DnType, IsCa, KeyPair};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio_rustls::rustls::ClientConfig;
+    use tokio_rustls::TlsConnector;
+    use tokio_stream::StreamExt;
+    use tonic::transport::server::Connected;
+
+    struct TestCa {
+        cert: rcgen::Certificate,
+        key: KeyPair,
+    }
+
+    impl TestCa {
+        fn new() -> Self {
+            let mut params = CertificateParams::new(Vec::<String>::new()).unwrap();
+            params.distinguished_name.push(DnType::CommonName, "Identra Test CA");
+            params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+            let key = KeyPair::generate().unwrap();
+            Self { cert: params.self_signed(&key).unwrap(), key }
+        }
+
+        /// PEM certificate and key signed by this CA
+        fn issue(&self, common_name: &str, sans: &[&str]) -> (String, String) {
+            let mut params = CertificateParams::new(sans.iter().map(|s| s.to_string()).collect::<Vec<_>>()).unwrap();
+            params.distinguished_name.push(DnType::CommonName, common_name);
+            let key = KeyPair::generate().unwrap();
+            let cert = params.signed_by(&key, &self.cert, &self.key).unwrap();
+            (cert.pem(), key.serialize_pem())
+        }
+
+        fn roots(&self) -> RootCertStore {
+            let mut roots = RootCertStore::empty();
+            roots.add(self.cert.der().clone()).unwrap();
+            roots
+        }
+    }
+
+    fn write(dir: &Path, name: &str, contents: &str) -> PathBuf {
+        let path = dir.join(name);
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    fn temp_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("identra-tls-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    struct TestServer {
+        addr: SocketAddr,
+        cert: Arc<ReloadingCert>,
+        /// What tonic would attach to requests on each accepted connection
+        peers: mpsc::UnboundedReceiver<TlsConnectInfo<TcpConnectInfo>>,
+    }
+
+    async fn start_server(ca: &TestCa, dir: &Path, client_ca: Option<PathBuf>, require_client_cert: bool) -> TestServer {
+        let (cert_pem, key_pem) = ca.issue("gateway", &["localhost"]);
+        let settings = TlsSettings {
+            cert_path: write(dir, "server.pem", &cert_pem),
+            key_path: write(dir, "server.key", &key_pem),
+            client_ca_path: client_ca,
+            require_client_cert,
+        };
+        let cert = Arc::new(ReloadingCert::load(&settings.cert_path, &settings.key_path).unwrap());
+        let acceptor = TlsAcceptor::from(Arc::new(server_config(&settings, cert.clone()).unwrap()));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let (peer_tx, peers) = mpsc::unbounded_channel();
+        let mut streams = incoming(listener, acceptor);
+        tokio::spawn(async move {
+            while let Some(Ok(mut stream)) = streams.next().await {
+                let _ = peer_tx.send(stream.connect_info());
+                let _ = stream.write_all(b"ok").await;
+                let _ = stream.shutdown().await;
+            }
+        });
+        TestServer { addr, cert, peers }
+    }
+
+    async fn connect(addr: SocketAddr, config: ClientConfig) -> io::Result<(Vec<u8>, Vec<u8>)> {
+        let connector = TlsConnector::from(Arc::new(config));
+        let tcp = TcpStream::connect(addr).await?;
+        let mut tls = connector.connect("localhost".try_into().unwrap(), tcp).await?;
+        let mut body = Vec::new();
+        tls.read_to_end(&mut body).await?;
+        let served = tls.get_ref().1.peer_certificates().unwrap()[0].to_vec();
+        Ok((body, served))
+    }
+
+    fn client_config(ca: &TestCa, client_cert: Option<(&str, &str)>) -> ClientConfig {
+        let builder = ClientConfig::builder_with_provider(provider())
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_root_certificates(ca.roots());
+        match client_cert {
+            Some((cert_pem, key_pem)) => {
+                let certs = rustls_pemfile::certs(&mut cert_pem.as_bytes()).collect::<Result<Vec<_>, _>>().unwrap();
+                let key = rustls_pemfile::private_key(&mut key_pem.as_bytes()).unwrap().unwrap();
+                builder.with_client_auth_cert(certs, key).unwrap()
+            }
+            None => builder.with_no_client_auth(),
+        }
+    }
+
+    #[tokio::test]
+    async fn serves_and_hot_reloads_certificate() {
+        let ca = TestCa::new();
+        let dir = temp_dir();
+        let server = start_server(&ca, &dir, None, false).await;
+
+        let (body, first) = connect(server.addr, client_config(&ca, None)).await.unwrap();
+        assert_eq!(body, b"ok");
+
+        // Replace the pair on disk; mtimes may not tick between writes, so push them forward
+        let (cert_pem, key_pem) = ca.issue("gateway-rotated", &["localhost"]);
+        let later = SystemTime::now() + Duration::from_secs(5);
+        for (name, contents) in [("server.pem", &cert_pem), ("server.key", &key_pem)] {
+            let file = File::options().write(true).open(write(&dir, name, contents)).unwrap();
+            file.set_modified(later).unwrap();
+        }
+        assert!(server.cert.reload_if_changed().unwrap());
+        assert!(!server.cert.reload_if_changed().unwrap());
+
+        let (_, second) = connect(server.addr, client_config(&ca, None)).await.unwrap();
+        assert_ne!(first, second);
+        assert_eq!(common_name(&CertificateDer::from(second)).as_deref(), Some("gateway-rotated"));
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[tokio::test]
+    async fn mismatched_reload_keeps_current_certificate() {
+        let ca = TestCa::new();
+        let dir = temp_dir();
+        let server = start_server(&ca, &dir, None, false).await;
+
+        let (_, other_key) = ca.issue("other", &["localhost"]);
+        let file = File::options().write(true).open(write(&dir, "server.key", &other_key)).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(5)).unwrap();
+        assert!(server.cert.reload_if_changed().is_err());
+
+        let (body, _) = connect(server.addr, client_config(&ca, None)).await.unwrap();
+        assert_eq!(body, b"ok");
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[tokio::test]
+    async fn requires_client_certificate_from_trusted_ca() {
+        let ca = TestCa::new();
+        let dir = temp_dir();
+        let client_ca = write(&dir, "clients.pem", &ca.cert.pem());
+        let mut server = start_server(&ca, &dir, Some(client_ca), true).await;
+
+        let identities = ServiceIdentities::parse("brain-service=brain").unwrap();
+        let (cert_pem, key_pem) = ca.issue("brain-service", &[]);
+        let (body, _) = connect(server.addr, client_config(&ca, Some((&cert_pem, &key_pem)))).await.unwrap();
+        assert_eq!(body, b"ok");
+        let mut extensions = http::Extensions::new();
+        extensions.insert(server.peers.recv().await.unwrap());
+        let claims = identities.claims_for(&extensions).unwrap();
+        assert_eq!(claims.sub, "service:brain");
+        assert_eq!(claims.role, "service");
+
+        // Trusted but unmapped subjects get no service identity
+        let (cert_pem, key_pem) = ca.issue("someone-else", &[]);
+        connect(server.addr, client_config(&ca, Some((&cert_pem, &key_pem)))).await.unwrap();
+        let mut extensions = http::Extensions::new();
+        extensions.insert(server.peers.recv().await.unwrap());
+        assert!(identities.claims_for(&extensions).is_none());
+
+        // No certificate, or one from an unknown CA, never gets a connection
+        assert!(connect(server.addr, client_config(&ca, None)).await.is_err());
+        let stranger = TestCa::new();
+        let (cert_pem, key_pem) = stranger.issue("brain-service", &[]);
+        assert!(connect(server.addr, client_config(&ca, Some((&cert_pem, &key_pem)))).await.is_err());
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[tokio::test]
+    async fn optional_client_certificate() {
+        let ca = TestCa::new();
+        let dir = temp_dir();
+        let client_ca = write(&dir, "clients.pem", &ca.cert.pem());
+        let mut server = start_server(&ca, &dir, Some(client_ca), false).await;
+
+        assert!(connect(server.addr, client_config(&ca, None)).await.is_ok());
+        assert!(server.peers.recv().await.unwrap().peer_certs().is_none());
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[tokio::test]
+    async fn grpc_over_mutual_tls() {
+        use crate::services::health::{HealthChecker, HealthService};
+        use identra_proto::health::health_client::HealthClient;
+        use identra_proto::health::health_server::HealthServer;
+        use identra_proto::health::HealthCheckRequest;
+        use tonic::transport::{Certificate, Channel, ClientTlsConfig, Identity, Server};
+
+        let ca = TestCa::new();
+        let dir = temp_dir();
+        let (cert_pem, key_pem) = ca.issue("gateway", &["localhost"]);
+        let settings = TlsSettings {
+            cert_path: write(&dir, "server.pem", &cert_pem),
+            key_path: write(&dir, "server.key", &key_pem),
+            client_ca_path: Some(write(&dir, "clients.pem", &ca.cert.pem())),
+            require_client_cert: true,
+        };
+        let cert = Arc::new(ReloadingCert::load(&settings.cert_path, &settings.key_path).unwrap());
+        let acceptor = TlsAcceptor::from(Arc::new(server_config(&settings, cert).unwrap()));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        // Report the client certificate subject each call arrives with
+        let (peer_tx, mut peers) = mpsc::unbounded_channel();
+        let checker = Arc::new(HealthChecker::new(Duration::from_secs(60)));
+        checker.check_now().await;
+        let health = HealthServer::with_interceptor(HealthService::new(checker), move |req: tonic::Request<()>| {
+            let subject = req.peer_certs().and_then(|certs| certs.first().and_then(common_name));
+            let _ = peer_tx.send(subject);
+            Ok(req)
+        });
+        tokio::spawn(Server::builder().add_service(health).serve_with_incoming(incoming(listener, acceptor)));
+
+        let (client_cert, client_key) = ca.issue("ghost-desktop", &[]);
+        let tls = ClientTlsConfig::new()
+            .ca_certificate(Certificate::from_pem(ca.cert.pem()))
+            .identity(Identity::from_pem(client_cert, client_key))
+            .domain_name("localhost");
+        let channel = Channel::from_shared(format!("https://{}", addr)).unwrap()
+            .tls_config(tls)
+            .unwrap()
+            .connect()
+            .await
+            .unwrap();
+        let response = HealthClient::new(channel).check(HealthCheckRequest { service: String::new() }).await.unwrap();
+        assert_eq!(response.into_inner().message, "Gateway is healthy");
+        assert_eq!(peers.recv().await.unwrap().as_deref(), Some("ghost-desktop"));
+
+        // Without a client certificate the connection is refused
+        let tls = ClientTlsConfig::new().ca_certificate(Certificate::from_pem(ca.cert.pem())).domain_name("localhost");
+        let channel = Channel::from_shared(format!("https://{}", addr)).unwrap().tls_config(tls).unwrap().connect_lazy();
+        assert!(HealthClient::new(channel).check(HealthCheckRequest { service: String::new() }).await.is_err());
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn parses_service_identities() {
+        let identities = ServiceIdentities::parse("brain-service=brain, ghost-desktop = desktop").unwrap();
+        assert_eq!(identities.by_common_name["ghost-desktop"], "desktop");
+        assert!(ServiceIdentities::parse("brain-service").is_err());
+        assert!(ServiceIdentities::parse("=brain").is_err());
+        assert!(ServiceIdentities::parse("").unwrap().claims_for(&http::Extensions::new()).is_none());
+    }
+}
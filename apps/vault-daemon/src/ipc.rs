@@ -2,19 +2,26 @@ use crate::error::{Result, VaultError};
 use crate::keychain::{KeyStorage, create_key_storage};
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::time::{Duration, Instant};
+use metrics::{counter, gauge, histogram};
+use tokio::sync::{watch, RwLock};
+use tokio::task::JoinSet;
 use interprocess::local_socket::{
     tokio::prelude::*,
-    GenericNamespaced, ListenerOptions, ToNsName,
+    GenericNamespaced, ListenerOptions, Name, ToNsName,
 };
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tracing::Instrument;
+use identra_core::config::DEFAULT_VAULT_SOCKET;
+use identra_core::trace;
 
-/// IPC pipe name
-#[cfg(windows)]
-const PIPE_NAME: &str = "@identra-vault";
+pub const REQUESTS: &str = "identra_vault_requests_total";
+pub const REQUEST_ERRORS: &str = "identra_vault_request_errors_total";
+pub const REQUEST_DURATION: &str = "identra_vault_request_duration_seconds";
+pub const ACTIVE_CONNECTIONS: &str = "identra_vault_active_connections";
 
-#[cfg(unix)]
-const PIPE_NAME: &str = "/tmp/identra-vault.sock";
+/// How long open connections get to finish their current request after shutdown starts
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(10);
 
 /// IPC message types
 #[derive(Debug, Serialize, Deserialize)]
@@ -30,7 +37,42 @@ pub enum VaultRequest {
     KeyExists { key_id: String },
     ListKeys,
     Ping,
-    Shutdown,
+    /// Stop the daemon; `token` must match `vault.shutdown_token`
+    Shutdown { token: String },
+}
+
+impl VaultRequest {
+    /// Variant name, used as the metrics label
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::StoreKey { .. } => "StoreKey",
+            Self::RetrieveKey { .. } => "RetrieveKey",
+            Self::DeleteKey { .. } => "DeleteKey",
+            Self::KeyExists { .. } => "KeyExists",
+            Self::ListKeys => "ListKeys",
+            Self::Ping => "Ping",
+            Self::Shutdown { .. } => "Shutdown",
+        }
+    }
+}
+
+/// A request with the trace it belongs to, as sent by clients
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VaultEnvelope {
+    /// W3C trace context of the caller's span; the request span becomes its child
+    #[serde(default)]
+    pub traceparent: Option<String>,
+    pub request: VaultRequest,
+}
+
+impl VaultEnvelope {
+    /// Parse one line from a client. Bare requests, as sent by clients that
+    /// predate the envelope, are accepted without a trace context.
+    pub fn parse(line: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(line).or_else(|_| {
+            serde_json::from_str(line).map(|request| Self { traceparent: None, request })
+        })
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -53,6 +95,12 @@ pub enum VaultResponse {
 pub struct VaultServer {
     keychain: Arc<Box<dyn KeyStorage>>,
     state: Arc<RwLock<VaultState>>,
+    /// IPC socket (named pipe on Windows) to listen on
+    socket: String,
+    /// Token that authorizes a `Shutdown` request, if remote shutdown is allowed
+    shutdown_token: Option<Arc<str>>,
+    /// Set once shutdown starts; the accept loop and idle connections watch it
+    shutdown: watch::Sender<bool>,
 }
 
 struct VaultState {
@@ -62,6 +110,11 @@ struct VaultState {
 
 impl VaultServer {
     pub fn new() -> Self {
+        Self::with_socket(DEFAULT_VAULT_SOCKET)
+    }
+
+    /// Listen on `socket` instead of the default, see `vault.socket`
+    pub fn with_socket(socket: impl Into<String>) -> Self {
         let keychain = create_key_storage();
         
         Self {
@@ -70,15 +123,29 @@ impl VaultServer {
                 initialized: false,
                 active_connections: 0,
             })),
+            socket: socket.into(),
+            shutdown_token: None,
+            shutdown: watch::Sender::new(false),
         }
     }
+
+    /// Accept `Shutdown` requests carrying `token`; without one they are refused
+    pub fn with_shutdown_token(mut self, token: Option<String>) -> Self {
+        self.shutdown_token = token.map(Arc::from);
+        self
+    }
+
+    /// Stop accepting connections and let open ones finish their current request.
+    /// `start` returns once they have drained.
+    pub fn shutdown(&self) {
+        self.shutdown.send_replace(true);
+    }
     
     pub async fn start(&self) -> Result<()> {
-        println!("🔌 Starting IPC server on: {}", PIPE_NAME);
+        println!("🔌 Starting IPC server on: {}", self.socket);
         
         // Create listener
-        let name = PIPE_NAME.to_ns_name::<GenericNamespaced>()
-            .map_err(|e| VaultError::Ipc(format!("Invalid pipe name: {}", e)))?;
+        let name = socket_name(&self.socket)?;
         
         let listener = ListenerOptions::new()
             .name(name)
@@ -92,9 +159,15 @@ impl VaultServer {
         
         println!("✅ IPC server ready, waiting for connections...");
         
-        // Accept connections in a loop
+        // Accept connections until shutdown starts
+        let mut stopping = self.shutdown.subscribe();
+        let mut connections = JoinSet::new();
         loop {
-            match listener.accept().await {
+            let accepted = tokio::select! {
+                accepted = listener.accept() => accepted,
+                _ = stopping.wait_for(|stop| *stop) => break,
+            };
+            match accepted {
                 Ok(stream) => {
                     println!("📥 New IPC connection accepted");
                     
@@ -102,14 +175,19 @@ impl VaultServer {
                     {
                         let mut state = self.state.write().await;
                         state.active_connections += 1;
+                        gauge!(ACTIVE_CONNECTIONS).set(state.active_connections as f64);
                     }
                     
                     // Handle connection in a separate task
                     let keychain = Arc::clone(&self.keychain);
                     let state = Arc::clone(&self.state);
+                    let control = Control {
+                        shutdown: self.shutdown.clone(),
+                        token: self.shutdown_token.clone(),
+                    };
                     
-                    tokio::spawn(async move {
-                        if let Err(e) = Self::handle_connection(stream, keychain, state.clone()).await {
+                    connections.spawn(async move {
+                        if let Err(e) = Self::handle_connection(stream, keychain, state, control).await {
                             eprintln!("❌ Connection error: {}", e);
                         }
                     });
@@ -119,7 +197,22 @@ impl VaultServer {
                     break;
                 }
             }
+            // Reap finished connections so the set doesn't grow unbounded
+            while connections.try_join_next().is_some() {}
+        }
+        
+        // Stop accepting, then give open connections a deadline to finish
+        drop(listener);
+        self.shutdown();
+        println!("🛑 Draining {} IPC connection(s)...", connections.len());
+        let drained = tokio::time::timeout(DRAIN_TIMEOUT, async {
+            while connections.join_next().await.is_some() {}
+        }).await;
+        if drained.is_err() {
+            eprintln!("⚠️ {} IPC connection(s) did not finish in time, closing them", connections.len());
+            connections.shutdown().await;
         }
+        remove_socket_file(&self.socket);
         
         Ok(())
     }
@@ -128,15 +221,25 @@ impl VaultServer {
         stream: interprocess::local_socket::tokio::Stream,
         keychain: Arc<Box<dyn KeyStorage>>,
         state: Arc<RwLock<VaultState>>,
+        control: Control,
     ) -> Result<()> {
         let (reader, mut writer) = tokio::io::split(stream);
         let mut buf_reader = BufReader::new(reader);
         let mut line = String::new();
+        let mut stopping = control.shutdown.subscribe();
         
         loop {
             line.clear();
             
-            match buf_reader.read_line(&mut line).await {
+            // An idle connection closes on shutdown; a request being read or handled finishes first
+            let read = tokio::select! {
+                read = buf_reader.read_line(&mut line) => read,
+                _ = stopping.wait_for(|stop| *stop) => {
+                    println!("📤 Closing idle connection for shutdown");
+                    break;
+                }
+            };
+            match read {
                 Ok(0) => {
                     // Connection closed
                     println!("📤 Client disconnected");
@@ -144,9 +247,11 @@ impl VaultServer {
                 }
                 Ok(_) => {
                     // Parse request
-                    let request: VaultRequest = match serde_json::from_str(&line) {
-                        Ok(req) => req,
+                    let envelope = match VaultEnvelope::parse(&line) {
+                        Ok(envelope) => envelope,
                         Err(e) => {
+                            counter!(REQUESTS, "request" => "Invalid").increment(1);
+                            counter!(REQUEST_ERRORS, "request" => "Invalid").increment(1);
                             let error_response = VaultResponse::Error(
                                 format!("Invalid request format: {}", e)
                             );
@@ -161,8 +266,19 @@ impl VaultServer {
                         }
                     };
                     
-                    // Handle request
-                    let response = Self::handle_request(request, &keychain).await;
+                    // Handle request in a span joined to the caller's trace
+                    let name = envelope.request.name();
+                    let span = tracing::info_span!("vault.request", otel.kind = "server", request = name);
+                    if let Some(parent) = &envelope.traceparent {
+                        trace::set_parent(&span, parent);
+                    }
+                    let started = Instant::now();
+                    let response = Self::handle_request(envelope.request, &keychain, &control).instrument(span).await;
+                    counter!(REQUESTS, "request" => name).increment(1);
+                    histogram!(REQUEST_DURATION, "request" => name).record(started.elapsed().as_secs_f64());
+                    if matches!(response, VaultResponse::Error(_)) {
+                        counter!(REQUEST_ERRORS, "request" => name).increment(1);
+                    }
                     
                     // Send response
                     let response_json = serde_json::to_string(&response)
@@ -175,7 +291,7 @@ impl VaultServer {
                     writer.flush().await
                         .map_err(|e| VaultError::Io(e))?;
                     
-                    // Check for shutdown
+                    // The whole server is stopping; this connection is done
                     if matches!(response, VaultResponse::ShuttingDown) {
                         break;
                     }
@@ -191,6 +307,7 @@ impl VaultServer {
         {
             let mut state_guard = state.write().await;
             state_guard.active_connections = state_guard.active_connections.saturating_sub(1);
+            gauge!(ACTIVE_CONNECTIONS).set(state_guard.active_connections as f64);
         }
         
         Ok(())
@@ -199,6 +316,7 @@ impl VaultServer {
     async fn handle_request(
         request: VaultRequest,
         keychain: &Arc<Box<dyn KeyStorage>>,
+        control: &Control,
     ) -> VaultResponse {
         match request {
             VaultRequest::Ping => {
@@ -260,9 +378,19 @@ impl VaultServer {
                     Err(e) => VaultResponse::Error(format!("Failed to list keys: {}", e)),
                 }
             }
-            VaultRequest::Shutdown => {
-                println!("🛑 Shutdown requested");
-                VaultResponse::ShuttingDown
+            VaultRequest::Shutdown { token } => {
+                match &control.token {
+                    Some(expected) if constant_time_eq(expected.as_bytes(), token.as_bytes()) => {
+                        println!("🛑 Shutdown requested");
+                        control.shutdown.send_replace(true);
+                        VaultResponse::ShuttingDown
+                    }
+                    Some(_) => {
+                        eprintln!("⚠️ Shutdown refused: invalid token");
+                        VaultResponse::Error("Invalid shutdown token".to_string())
+                    }
+                    None => VaultResponse::Error("Remote shutdown is disabled".to_string()),
+                }
             }
         }
     }
@@ -272,8 +400,91 @@ impl VaultServer {
     }
 }
 
+/// What a connection needs to act on `Shutdown` requests
+struct Control {
+    shutdown: watch::Sender<bool>,
+    token: Option<Arc<str>>,
+}
+
+/// On Unix an absolute path is a socket file; anything else is a namespaced name
+fn socket_name(socket: &str) -> Result<Name<'_>> {
+    #[cfg(unix)]
+    if socket.starts_with('/') {
+        use interprocess::local_socket::{GenericFilePath, ToFsName};
+        return socket.to_fs_name::<GenericFilePath>()
+            .map_err(|e| VaultError::Ipc(format!("Invalid socket path: {}", e)));
+    }
+    socket.to_ns_name::<GenericNamespaced>()
+        .map_err(|e| VaultError::Ipc(format!("Invalid pipe name: {}", e)))
+}
+
+/// Remove the socket file left behind by a file-path listener
+fn remove_socket_file(socket: &str) {
+    if cfg!(unix) && socket.starts_with('/') {
+        if let Err(e) = std::fs::remove_file(socket) {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                eprintln!("⚠️ Failed to remove socket file {}: {}", socket, e);
+            }
+        }
+    }
+}
+
+/// Compare secrets without leaking the position of the first mismatch
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
 impl Default for VaultServer {
     fn default() -> Self {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_envelopes_and_bare_requests() {
+        let traceparent = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+        let line = format!(r#"{{"traceparent":"{}","request":{{"KeyExists":{{"key_id":"k1"}}}}}}"#, traceparent);
+        let envelope = VaultEnvelope::parse(&line).unwrap();
+        assert_eq!(envelope.traceparent.as_deref(), Some(traceparent));
+        assert!(matches!(envelope.request, VaultRequest::KeyExists { key_id } if key_id == "k1"));
+
+        let bare = VaultEnvelope::parse(r#""Ping""#).unwrap();
+        assert!(bare.traceparent.is_none());
+        assert!(matches!(bare.request, VaultRequest::Ping));
+
+        assert!(VaultEnvelope::parse(r#"{"request":"Reboot"}"#).is_err());
+    }
+
+    #[tokio::test]
+    async fn shutdown_requires_matching_token() {
+        let keychain: Arc<Box<dyn KeyStorage>> = Arc::new(create_key_storage());
+        let shutdown = |token: &str, configured: Option<&str>| {
+            let control = Control {
+                shutdown: watch::Sender::new(false),
+                token: configured.map(Arc::from),
+            };
+            let request = VaultRequest::Shutdown { token: token.to_string() };
+            let keychain = Arc::clone(&keychain);
+            async move {
+                let response = VaultServer::handle_request(request, &keychain, &control).await;
+                (response, *control.shutdown.borrow())
+            }
+        };
+
+        let (response, stopped) = shutdown("s3cret", Some("s3cret")).await;
+        assert!(matches!(response, VaultResponse::ShuttingDown));
+        assert!(stopped);
+
+        let (response, stopped) = shutdown("guess", Some("s3cret")).await;
+        assert!(matches!(response, VaultResponse::Error(_)));
+        assert!(!stopped);
+
+        let (response, stopped) = shutdown("", None).await;
+        assert!(matches!(response, VaultResponse::Error(_)));
+        assert!(!stopped);
+    }
+}
@@ -1,26 +1,51 @@
 use anyhow::Result;
+use identra_core::config::{self, Service};
+use identra_core::{shutdown, trace};
+use std::sync::Arc;
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder};
+
+/// Latency buckets for request durations, 0.5ms to 5s
+const SECONDS_BUCKETS: &[f64] = &[0.0005, 0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0, 5.0];
 use vault_daemon::VaultServer;
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    // Defaults, identra.toml, environment and flags; exits early on --print-config
+    let config = config::init(Service::VaultDaemon)?;
+
+    // Request spans join the caller's trace and go to telemetry.otlp_endpoint when set
+    let _trace = trace::init("vault-daemon", &config.telemetry)?;
+
     println!("🔐 Identra Vault Daemon starting...");
     println!("📍 Local secure storage initialized");
     println!("🔑 OS Keychain integration active");
+
+    // Prometheus scrape endpoint, only when vault.metrics_addr is set
+    if let Some(addr) = config.vault.metrics_addr {
+        PrometheusBuilder::new()
+            .with_http_listener(addr)
+            .set_buckets_for_metric(Matcher::Suffix("_seconds".to_string()), SECONDS_BUCKETS)?
+            .install()?;
+        println!("📈 Serving metrics on http://{}/metrics", addr);
+    }
     
     // Initialize IPC server
-    let server = VaultServer::new();
+    let server = Arc::new(
+        VaultServer::with_socket(config.vault.socket)
+            .with_shutdown_token(config.vault.shutdown_token.map(|token| token.expose().to_string())),
+    );
+    
+    // SIGTERM/SIGINT stop accepting and drain open connections, as does an authenticated Shutdown request
+    let signalled = Arc::clone(&server);
+    tokio::spawn(async move {
+        shutdown::signal().await;
+        println!("\n🛑 Shutdown signal received");
+        signalled.shutdown();
+    });
     
-    // Start listening for IPC connections
-    // This will block until shutdown signal
-    tokio::select! {
-        result = server.start() => {
-            if let Err(e) = result {
-                eprintln!("❌ Server error: {}", e);
-            }
-        }
-        _ = tokio::signal::ctrl_c() => {
-            println!("\n🛑 Shutdown signal received");
-        }
+    // Blocks until shutdown has drained the open connections
+    if let Err(e) = server.start().await {
+        eprintln!("❌ Server error: {}", e);
     }
     
     println!("🛑 Shutting down Vault Daemon...");
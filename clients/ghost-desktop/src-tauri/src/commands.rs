@@ -7,20 +7,8 @@ use aes_gcm::{Aes256Gcm, Key}; // Removed unused KeyInit
 use fastembed::{TextEmbedding, InitOptions, EmbeddingModel};
 use std::sync::Mutex;
 use std::collections::HashMap;
-
-// --- Helper Functions ---
-
-fn get_env_or(key: &str, default: &str) -> String {
-    std::env::var(key).unwrap_or_else(|_| default.to_string())
-}
-
-#[allow(dead_code)]
-fn get_context_limit() -> usize {
-    std::env::var("CHAT_CONTEXT_LIMIT")
-        .ok()
-        .and_then(|s| s.parse().ok())
-        .unwrap_or(10)
-}
+use identra_client::{Client, Conversation, Memories, MemoryFilter, MemoryStats, NewMemory, NewTurn, TagCount, TimeRange, Turn};
+use identra_core::config::{Config, ModelProviderConfig};
 
 // --- Data Models ---
 
@@ -36,6 +24,8 @@ pub struct ChatResponse {
     pub message: String,
     pub model: String,
     pub conversation_id: Option<String>,
+    /// Set when the answer could not be saved to the conversation
+    pub warning: Option<String>,
 }
 
 #[derive(serde::Serialize)]
@@ -46,6 +36,15 @@ pub struct SystemStatusResponse {
     pub security_level: String,
 }
 
+#[derive(serde::Serialize)]
+pub struct ConversationSummary {
+    pub id: String,
+    pub title: String,
+    pub model: String,
+    pub turn_count: u32,
+    pub timestamp: i64,
+}
+
 #[derive(serde::Serialize)]
 pub struct ConversationItem {
     pub id: String,
@@ -145,7 +144,8 @@ pub async fn initialize_session(state: State<'_, NexusState>) -> Result<String,
 }
 
 #[tauri::command]
-pub async fn vault_memory(state: State<'_, NexusState>, content: String) -> Result<String, String> {
+#[tracing::instrument(skip_all)]
+pub async fn vault_memory(state: State<'_, NexusState>, client: State<'_, Client>, content: String) -> Result<String, String> {
     if content.trim().is_empty() { return Err("Payload empty.".to_string()); }
 
     let session_key = {
@@ -161,17 +161,12 @@ pub async fn vault_memory(state: State<'_, NexusState>, content: String) -> Resu
         .map_err(|e| format!("Crypto Error: {}", e))?;
 
     // Store in DB
-    let mut client = crate::grpc_client::GrpcClient::connect()
-        .await
-        .map_err(|e| format!("Failed to connect to gateway: {}", e))?;
-    
-    let metadata = std::collections::HashMap::from([
-        ("encrypted".to_string(), "true".to_string()),
-        ("timestamp".to_string(), chrono::Utc::now().to_rfc3339()),
-    ]);
+    let memory = NewMemory::new(encrypted_blob)
+        .metadata("encrypted", "true")
+        .metadata("timestamp", chrono::Utc::now().to_rfc3339());
     
-    let memory_id = client
-        .store_memory(encrypted_blob.clone(), metadata, vec![])
+    let memory_id = client.memories()
+        .store(memory)
         .await
         .map_err(|e| format!("Failed to store memory: {}", e))?;
 
@@ -200,107 +195,202 @@ pub async fn decrypt_memory(state: State<'_, NexusState>, encrypted_val: String)
 }
 
 #[tauri::command]
+#[tracing::instrument(skip_all)]
 pub async fn chat_with_ai(
     state: State<'_, NexusState>,
+    config: State<'_, Config>,
+    client: State<'_, Client>,
     message: String,
     model: String,
-    conversation_history: Vec<ChatMessage>,
+    conversation_id: Option<String>,
 ) -> Result<ChatResponse, String> {
     if message.trim().is_empty() {
         return Err("Message cannot be empty".to_string());
     }
 
-    // Get API key from environment variable based on model
-    let api_key = match model.as_str() {
-        "claude" => get_env_or("ANTHROPIC_API_KEY", "demo-key"),
-        "gpt" => get_env_or("OPENAI_API_KEY", "demo-key"),
-        "gemini" => get_env_or("GOOGLE_API_KEY", "demo-key"),
+    // API key, endpoint and model name come from the desktop.<provider> config section
+    let provider = match model.as_str() {
+        "claude" => &config.desktop.anthropic,
+        "gpt" => &config.desktop.openai,
+        "gemini" => &config.desktop.gemini,
         _ => return Err("Unsupported model".to_string()),
     };
 
-    // For demo purposes, if no API key is set, return a mock response
-    if api_key == "demo-key" {
-        println!("[CHAT] Demo mode: No API key found for model: {}", model);
-        let response_text = format!(
-            "[Demo Response from {}] I received your message: '{}'. To enable real AI responses, please set the appropriate API key environment variable (ANTHROPIC_API_KEY, OPENAI_API_KEY, or GOOGLE_API_KEY).",
-            model, message
-        );
-        
-        // Store both user message and response
-        let _ = store_chat_interaction(&state, &message, &response_text, &model).await;
-        
-        return Ok(ChatResponse {
-            message: response_text,
-            model: model.clone(),
-            conversation_id: None,
-        });
-    }
+    // Chat works with a locked vault too; the turns just aren't saved
+    let session_key = state.session_key.lock().map_err(|_| "Key poisoned")?.clone();
+    let memories = client.memories();
+
+    // Prior turns are loaded from the gateway rather than supplied by the frontend
+    let conversation_history = match (&conversation_id, &session_key) {
+        (Some(id), Some(session_key)) => match load_history(&memories, id, config.desktop.chat_context_limit, session_key).await {
+            Ok(history) => history,
+            Err(e) => {
+                tracing::warn!("Answering without conversation history: {}", e);
+                Vec::new()
+            }
+        },
+        _ => Vec::new(),
+    };
 
-    // Call the appropriate LLM API
-    let response_text = match model.as_str() {
-        "claude" => call_claude_api(&api_key, &message, &conversation_history).await?,
-        "gpt" => call_openai_api(&api_key, &message, &conversation_history).await?,
-        "gemini" => call_gemini_api(&api_key, &message, &conversation_history).await?,
-        _ => return Err("Unsupported model".to_string()),
+    let response_text = match &provider.api_key {
+        // Call the appropriate LLM API
+        Some(api_key) => match model.as_str() {
+            "claude" => call_claude_api(provider, api_key.expose(), &message, &conversation_history).await?,
+            "gpt" => call_openai_api(provider, api_key.expose(), &message, &conversation_history).await?,
+            "gemini" => call_gemini_api(provider, api_key.expose(), &message, &conversation_history).await?,
+            _ => return Err("Unsupported model".to_string()),
+        },
+        None => {
+            // For demo purposes, if no API key is set, return a mock response
+            println!("[CHAT] Demo mode: No API key found for model: {}", model);
+            format!(
+                "[Demo Response from {}] I received your message: '{}'. To enable real AI responses, please set the appropriate API key (ANTHROPIC_API_KEY, OPENAI_API_KEY or GOOGLE_API_KEY, or desktop.<provider>.api_key in identra.toml).",
+                model, message
+            )
+        }
     };
 
-    // Store the conversation in encrypted vault
-    let _ = store_chat_interaction(&state, &message, &response_text, &model).await;
+    // Saving is best effort: the answer is returned even when it could not be stored
+    let saved = match &session_key {
+        Some(session_key) => save_turns(&memories, conversation_id.clone(), &message, &response_text, &model, session_key).await,
+        None => Err("VAULT_LOCKED: conversation not saved".to_string()),
+    };
+    let (conversation_id, warning) = match saved {
+        Ok(id) => (Some(id), None),
+        Err(e) => {
+            tracing::warn!("Chat turns not saved: {}", e);
+            (conversation_id, Some(e))
+        }
+    };
 
     Ok(ChatResponse {
         message: response_text,
         model: model.clone(),
-        conversation_id: None,
+        conversation_id,
+        warning,
     })
 }
 
-async fn store_chat_interaction(
-    state: &State<'_, NexusState>,
-    user_message: &str,
-    ai_response: &str,
+async fn load_history(memories: &Memories, conversation_id: &str, limit: u32, session_key: &Key<Aes256Gcm>) -> Result<Vec<ChatMessage>, String> {
+    let conversation = memories.conversation(conversation_id, limit)
+        .await
+        .map_err(|e| format!("Failed to load conversation: {}", e))?;
+    conversation.turns.iter().map(|turn| decrypt_turn(turn, session_key)).collect()
+}
+
+/// Append both turns, in order, to the encrypted conversation, starting one titled after
+/// the message when there is none yet. Returns the conversation id.
+async fn save_turns(
+    memories: &Memories,
+    conversation_id: Option<String>,
+    message: &str,
+    response_text: &str,
     model: &str,
-) -> Result<(), String> {
-    let session_key = {
-        let key_guard = state.session_key.lock().map_err(|_| "Key poisoned")?;
-        match key_guard.as_ref() {
-            Some(k) => k.clone(),
-            None => return Ok(()), // Skip storage if vault is locked
+    session_key: &Key<Aes256Gcm>,
+) -> Result<String, String> {
+    let turns = vec![
+        encrypt_turn(NewTurn::user(message), session_key)?,
+        encrypt_turn(NewTurn::assistant(response_text, model), session_key)?,
+    ];
+    let conversation_id = match conversation_id {
+        Some(id) => id,
+        None => {
+            let title: String = message.chars().take(60).collect();
+            let encrypted_title = MemoryVault::lock(&title, session_key)
+                .map_err(|e| format!("Encryption error: {}", e))?;
+            let metadata = HashMap::from([("encrypted".to_string(), "true".to_string())]);
+
+            memories.create_conversation(&encrypted_title, model, metadata)
+                .await
+                .map_err(|e| format!("Failed to create conversation: {}", e))?
+                .id
         }
     };
+    memories.append_turns(&conversation_id, turns)
+        .await
+        .map_err(|e| format!("Failed to store conversation: {}", e))?;
+    Ok(conversation_id)
+}
 
-    // Create conversation record
-    let conversation = serde_json::json!({
-        "user": user_message,
-        "assistant": ai_response,
-        "model": model,
-        "timestamp": chrono::Utc::now().to_rfc3339(),
-    });
+fn encrypt_turn(mut turn: NewTurn, session_key: &Key<Aes256Gcm>) -> Result<NewTurn, String> {
+    turn.content = MemoryVault::lock(&turn.content, session_key)
+        .map_err(|e| format!("Encryption error: {}", e))?;
+    Ok(turn.metadata("encrypted", "true"))
+}
 
-    let conversation_str = serde_json::to_string(&conversation)
-        .map_err(|e| format!("JSON error: {}", e))?;
+fn decrypt_turn(turn: &Turn, session_key: &Key<Aes256Gcm>) -> Result<ChatMessage, String> {
+    let content = if turn.metadata.get("encrypted").map(String::as_str) == Some("true") {
+        MemoryVault::open(&turn.content, session_key)
+            .map_err(|e| format!("Decryption Failed: {}", e))?
+    } else {
+        turn.content.clone()
+    };
 
-    // Encrypt and store
-    let encrypted_blob = MemoryVault::lock(&conversation_str, &session_key)
-        .map_err(|e| format!("Encryption error: {}", e))?;
+    Ok(ChatMessage {
+        role: turn.role.to_string(),
+        content,
+        timestamp: turn.created_at.timestamp(),
+    })
+}
 
-    let mut client = crate::grpc_client::GrpcClient::connect()
+fn decrypt_title(conversation: &Conversation, session_key: &Key<Aes256Gcm>) -> String {
+    if conversation.metadata.get("encrypted").map(String::as_str) == Some("true") {
+        MemoryVault::open(&conversation.title, session_key).unwrap_or_default()
+    } else {
+        conversation.title.clone()
+    }
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+pub async fn list_conversations(
+    state: State<'_, NexusState>,
+    client: State<'_, Client>,
+    limit: u32,
+) -> Result<Vec<ConversationSummary>, String> {
+    let session_key = {
+        let key_guard = state.session_key.lock().map_err(|_| "Key poisoned")?;
+        key_guard.as_ref().cloned().ok_or("VAULT_LOCKED")?
+    };
+
+    let conversations = client.memories().conversations(limit)
         .await
-        .map_err(|e| format!("Failed to connect: {}", e))?;
+        .map_err(|e| format!("Failed to list conversations: {}", e))?;
+
+    let items = conversations.iter().map(|c| ConversationSummary {
+        id: c.id.clone(),
+        title: decrypt_title(c, &session_key),
+        model: c.model.clone(),
+        turn_count: c.turn_count,
+        timestamp: c.updated_at.timestamp(),
+    }).collect();
+
+    Ok(items)
+}
 
-    let metadata = HashMap::from([
-        ("type".to_string(), "conversation".to_string()),
-        ("model".to_string(), model.to_string()),
-        ("timestamp".to_string(), chrono::Utc::now().to_rfc3339()),
-    ]);
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+pub async fn load_conversation(
+    state: State<'_, NexusState>,
+    client: State<'_, Client>,
+    conversation_id: String,
+) -> Result<Vec<ChatMessage>, String> {
+    let session_key = {
+        let key_guard = state.session_key.lock().map_err(|_| "Key poisoned")?;
+        key_guard.as_ref().cloned().ok_or("VAULT_LOCKED")?
+    };
 
-    let _ = client.store_memory(encrypted_blob, metadata, vec!["chat".to_string()])
+    let conversation = client.memories().conversation(&conversation_id, 0)
         .await
-        .map_err(|e| format!("Storage error: {}", e))?;
+        .map_err(|e| format!("Failed to load conversation: {}", e))?;
 
-    Ok(())
+    conversation.turns.iter()
+        .map(|turn| decrypt_turn(turn, &session_key))
+        .collect()
 }
 
 async fn call_claude_api(
+    provider: &ModelProviderConfig,
     api_key: &str,
     message: &str,
     history: &[ChatMessage],
@@ -319,20 +409,16 @@ async fn call_claude_api(
         "content": message,
     }));
 
-    let model = get_env_or("ANTHROPIC_MODEL", "claude-3-5-sonnet-20241022");
-    let max_tokens: i32 = get_env_or("ANTHROPIC_MAX_TOKENS", "1024").parse().unwrap_or(1024);
-    
     let body = serde_json::json!({
-        "model": model,
-        "max_tokens": max_tokens,
+        "model": provider.model,
+        "max_tokens": provider.max_tokens,
         "messages": messages,
     });
 
-    let api_url = get_env_or("ANTHROPIC_API_URL", "https://api.anthropic.com/v1/messages");
-    let api_version = get_env_or("ANTHROPIC_API_VERSION", "2023-06-01");
+    let api_version = provider.api_version.as_deref().unwrap_or("2023-06-01");
     
     let response = client
-        .post(&api_url)
+        .post(&provider.api_url)
         .header("x-api-key", api_key)
         .header("anthropic-version", api_version)
         .header("content-type", "application/json")
@@ -353,6 +439,7 @@ async fn call_claude_api(
 }
 
 async fn call_openai_api(
+    provider: &ModelProviderConfig,
     api_key: &str,
     message: &str,
     history: &[ChatMessage],
@@ -371,19 +458,14 @@ async fn call_openai_api(
         "content": message,
     }));
 
-    let model = get_env_or("OPENAI_MODEL", "gpt-4o");
-    let max_tokens: i32 = get_env_or("OPENAI_MAX_TOKENS", "1024").parse().unwrap_or(1024);
-    
     let body = serde_json::json!({
-        "model": model,
+        "model": provider.model,
         "messages": messages,
-        "max_tokens": max_tokens,
+        "max_tokens": provider.max_tokens,
     });
-
-    let api_url = get_env_or("OPENAI_API_URL", "https://api.openai.com/v1/chat/completions");
     
     let response = client
-        .post(&api_url)
+        .post(&provider.api_url)
         .header("Authorization", format!("Bearer {}", api_key))
         .header("content-type", "application/json")
         .json(&body)
@@ -403,6 +485,7 @@ async fn call_openai_api(
 }
 
 async fn call_gemini_api(
+    provider: &ModelProviderConfig,
     api_key: &str,
     message: &str,
     history: &[ChatMessage],
@@ -422,18 +505,14 @@ async fn call_gemini_api(
         "parts": [{ "text": message }],
     }));
 
-    let max_tokens: i32 = get_env_or("GEMINI_MAX_TOKENS", "1024").parse().unwrap_or(1024);
-    
     let body = serde_json::json!({
         "contents": contents,
         "generationConfig": {
-            "maxOutputTokens": max_tokens,
+            "maxOutputTokens": provider.max_tokens,
         },
     });
 
-    let base_url = get_env_or("GEMINI_API_URL", "https://generativelanguage.googleapis.com/v1beta/models");
-    let model = get_env_or("GEMINI_MODEL", "gemini-1.5-pro");
-    let url = format!("{}/{}:generateContent?key={}", base_url, model, api_key);
+    let url = format!("{}/{}:generateContent?key={}", provider.api_url, provider.model, api_key);
 
     let response = client
         .post(&url)
@@ -455,19 +534,16 @@ async fn call_gemini_api(
 }
 
 #[tauri::command]
-pub async fn query_history(limit: i32) -> Result<Vec<ConversationItem>, String> {
-    let mut client = crate::grpc_client::GrpcClient::connect()
-        .await
-        .map_err(|e| format!("Failed to connect: {}", e))?;
-    
+#[tracing::instrument(skip_all)]
+pub async fn query_history(client: State<'_, Client>, limit: u32) -> Result<Vec<ConversationItem>, String> {
     // Legacy query: empty string matches everything via ILIKE %%
-    let memories = client
-        .query_memories("".to_string(), limit)
+    let memories = client.memories()
+        .query("", limit)
         .await
         .map_err(|e| format!("Failed to query: {}", e))?;
     
     let items: Vec<ConversationItem> = memories.into_iter()
-        .map(|(id, content, timestamp)| ConversationItem { id, content, timestamp })
+        .map(|m| ConversationItem { timestamp: m.created_at.timestamp(), id: m.id, content: m.content })
         .collect();
     
     Ok(items)
@@ -476,26 +552,21 @@ pub async fn query_history(limit: i32) -> Result<Vec<ConversationItem>, String>
 // --- Auth Commands ---
 
 #[tauri::command]
-pub async fn login_user(username: String, password: String) -> Result<String, String> {
-    let mut client = crate::grpc_client::GrpcClient::connect()
-        .await
-        .map_err(|e| format!("Connection failed: {}", e))?;
-
-    let token = client.login(username, password)
+#[tracing::instrument(skip_all)]
+pub async fn login_user(client: State<'_, Client>, username: String, password: String) -> Result<String, String> {
+    // Later gateway calls carry the session and refresh it as it nears expiry
+    let session = client.auth().login(&username, &password)
         .await
         .map_err(|e| e.to_string())?;
 
     println!("[AUTH] Login successful");
-    Ok(token)
+    Ok(session.access_token)
 }
 
 #[tauri::command]
-pub async fn register_user(username: String, email: String, password: String) -> Result<String, String> {
-    let mut client = crate::grpc_client::GrpcClient::connect()
-        .await
-        .map_err(|e| format!("Connection failed: {}", e))?;
-
-    let user_id = client.register(username, email, password)
+#[tracing::instrument(skip_all)]
+pub async fn register_user(client: State<'_, Client>, username: String, email: String, password: String) -> Result<String, String> {
+    let user_id = client.auth().register(&username, &email, &password)
         .await
         .map_err(|e| e.to_string())?;
 
@@ -506,8 +577,10 @@ pub async fn register_user(username: String, email: String, password: String) ->
 // --- Search & History Commands ---
 
 #[tauri::command]
+#[tracing::instrument(skip_all)]
 pub async fn semantic_search(
     ai_state: State<'_, AIState>,
+    client: State<'_, Client>,
     query: String
 ) -> Result<Vec<ConversationItem>, String> {
     // 1. Generate Vector Locally
@@ -520,20 +593,16 @@ pub async fn semantic_search(
     };
 
     // 2. Send to Backend
-    let mut client = crate::grpc_client::GrpcClient::connect()
-        .await
-        .map_err(|e| format!("Connection failed: {}", e))?;
-
-    let results = client.search_memories(embedding, 5, 0.5)
+    let results = client.memories().search(embedding, 5, 0.5)
         .await
         .map_err(|e| format!("Search failed: {}", e))?;
 
     // 3. Format
-    let items = results.into_iter().map(|(id, content, score)| {
+    let items = results.into_iter().map(|found| {
         ConversationItem {
-            id,
-            content: format!("(Match: {:.0}%) {}", score * 100.0, content),
-            timestamp: 0, 
+            content: format!("(Match: {:.0}%) {}", found.score * 100.0, found.memory.content),
+            timestamp: found.memory.created_at.timestamp(),
+            id: found.memory.id,
         }
     }).collect();
 
@@ -541,22 +610,42 @@ pub async fn semantic_search(
 }
 
 #[tauri::command]
-pub async fn fetch_history() -> Result<Vec<ConversationItem>, String> {
-    let mut client = crate::grpc_client::GrpcClient::connect()
-        .await
-        .map_err(|e| format!("Connection failed: {}", e))?;
-
-    let memories = client.get_recent_memories(50)
+#[tracing::instrument(skip_all)]
+pub async fn fetch_history(client: State<'_, Client>, since_days: Option<u32>) -> Result<Vec<ConversationItem>, String> {
+    // "Last week" style views: only memories created in the last `since_days` days
+    let filter = MemoryFilter {
+        created: since_days.map(|days| TimeRange::last(std::time::Duration::from_secs(u64::from(days) * 86_400))),
+        ..Default::default()
+    };
+    let memories = client.memories().filter(filter).recent(50)
         .await
         .map_err(|e| e.to_string())?;
 
-    let items = memories.into_iter().map(|(id, content, timestamp)| {
+    let items = memories.into_iter().map(|memory| {
         ConversationItem {
-            id,
-            content,
-            timestamp,
+            timestamp: memory.created_at.timestamp(),
+            id: memory.id,
+            content: memory.content,
         }
     }).collect();
 
     Ok(items)
-}
\ No newline at end of file
+}
+
+// --- Dashboard Commands ---
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+pub async fn get_memory_stats(client: State<'_, Client>) -> Result<MemoryStats, String> {
+    client.memories().stats()
+        .await
+        .map_err(|e| format!("Failed to load stats: {}", e))
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all)]
+pub async fn list_tags(client: State<'_, Client>, prefix: Option<String>, limit: Option<u32>) -> Result<Vec<TagCount>, String> {
+    client.memories().tags(prefix.as_deref().unwrap_or_default(), limit.unwrap_or(100))
+        .await
+        .map_err(|e| format!("Failed to list tags: {}", e))
+}
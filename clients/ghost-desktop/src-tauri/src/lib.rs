@@ -1,12 +1,33 @@
 pub mod commands;
-pub mod grpc_client;
-pub mod ipc_client;
 pub mod state;
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     // Load environment variables from .env file
     let _ = dotenvy::dotenv();
+
+    // Defaults, identra.toml, environment and flags; exits early on --print-config
+    let config = identra_core::config::init(identra_core::config::Service::Desktop)
+        .unwrap_or_else(|e| {
+            eprintln!("❌ {}", e);
+            std::process::exit(1);
+        });
+
+    // Spans around gateway calls; exported when telemetry.otlp_endpoint is set
+    let _trace = tauri::async_runtime::block_on(async {
+        identra_core::trace::init("ghost-desktop", &config.telemetry)
+    })
+    .inspect_err(|e| eprintln!("⚠️ Tracing disabled: {}", e))
+    .ok();
+
+    // One pooled gateway client for every command; connects on first use
+    let client = tauri::async_runtime::block_on(async {
+        identra_client::Client::from_config(&config.desktop)
+    })
+    .unwrap_or_else(|e| {
+        eprintln!("❌ {}", e);
+        std::process::exit(1);
+    });
     
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
@@ -14,6 +35,8 @@ pub fn run() {
         // Initialize AI & State Management
         .manage(commands::AIState::new())
         .manage(state::NexusState::new())
+        .manage(config)
+        .manage(client)
         // Register Commands
         .invoke_handler(tauri::generate_handler![
             // --- System ---
@@ -33,6 +56,12 @@ pub fn run() {
             commands::semantic_search,  // Vector Search
             commands::fetch_history,    // Recent History
             commands::chat_with_ai,     // AI Chat (NEW)
+            commands::list_conversations, // Conversation List
+            commands::load_conversation,  // Conversation Turns
+
+            // --- Dashboard ---
+            commands::get_memory_stats,
+            commands::list_tags,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
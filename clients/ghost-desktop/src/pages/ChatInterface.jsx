@@ -49,6 +49,7 @@ export default function ChatInterface() {
   const [selectedModel, setSelectedModel] = useState("claude"); // claude, gemini, gpt
   const [sessionInitialized, setSessionInitialized] = useState(false);
   const [conversationHistory, setConversationHistory] = useState([]);
+  const [conversationId, setConversationId] = useState(null);
   const [profileOpen, setProfileOpen] = useState(false);
   const [settingsOpen, setSettingsOpen] = useState(false);
   const [themeOpen, setThemeOpen] = useState(false);
@@ -106,7 +107,7 @@ export default function ChatInterface() {
   useEffect(() => {
     // Load conversation history after session initialized
     if (sessionInitialized) {
-      invoke("query_history", { limit: 50 })
+      invoke("list_conversations", { limit: 50 })
         .then(history => {
           setConversationHistory(history);
           console.log("📜 Loaded", history.length, "conversations from database");
@@ -161,33 +162,31 @@ export default function ChatInterface() {
     setIsProcessing(true);
 
     try {
-      // Prepare conversation history for API
+      // Prior turns are loaded by the backend from the stored conversation
       // Note: Context limit is configured in .env (CHAT_CONTEXT_LIMIT, default: 10)
-      const historyForAPI = messages.slice(-10).map(msg => ({
-        role: msg.role,
-        content: msg.content,
-        timestamp: Math.floor(msg.timestamp.getTime() / 1000)
-      }));
-
-      // Call the new chat_with_ai command
       const response = await invoke("chat_with_ai", {
         message: input,
         model: selectedModel,
-        conversationHistory: historyForAPI
+        conversationId
       });
+      setConversationId(response.conversation_id);
       
       const assistantMessage = {
         id: Date.now() + 1,
         role: "assistant",
         content: response.message,
         timestamp: new Date(),
-        model: selectedModel
+        model: selectedModel,
+        warning: response.warning
       };
+      if (response.warning) {
+        console.warn("Chat not saved:", response.warning);
+      }
 
       setMessages(prev => [...prev, assistantMessage]);
       
       // Refresh history to show the new conversation
-      invoke("query_history", { limit: 50 })
+      invoke("list_conversations", { limit: 50 })
         .then(history => {
           setConversationHistory(history);
         })
@@ -231,62 +230,28 @@ export default function ChatInterface() {
   const handleLoadConversation = async (item) => {
     try {
       console.log("🔄 Loading conversation:", item.id);
-      console.log("📦 Encrypted content:", item.content.substring(0, 50));
-      
-      // Decrypt the encrypted content
-      const decryptedContent = await invoke("decrypt_memory", { encryptedVal: item.content });
-      
-      console.log("🔓 Decrypted content:", decryptedContent);
-      
-      // Parse the conversation JSON
-      try {
-        const conversation = JSON.parse(decryptedContent);
-        
-        // Load both user message and AI response
-        const loadedMessages = [];
-        
-        if (conversation.user) {
-          loadedMessages.push({
-            id: `${item.id}-user`,
-            role: "user",
-            content: conversation.user,
-            timestamp: new Date(conversation.timestamp || item.timestamp * 1000)
-          });
-        }
-        
-        if (conversation.assistant) {
-          loadedMessages.push({
-            id: `${item.id}-assistant`,
-            role: "assistant",
-            content: conversation.assistant,
-            timestamp: new Date(conversation.timestamp || item.timestamp * 1000),
-            model: conversation.model || selectedModel
-          });
-        }
-        
-        setMessages(loadedMessages);
-        
-        console.log("✅ Loaded conversation with", loadedMessages.length, "messages");
-      } catch (error_) {
-        console.warn("Not JSON format, treating as legacy:", error_.message);
-        // If it's not JSON, treat it as a single user message (legacy format)
-        const loadedMessage = {
-          id: item.id,
-          role: "user",
-          content: decryptedContent,
-          timestamp: new Date(item.timestamp * 1000)
-        };
-        
-        setMessages([loadedMessage]);
-        console.log("✅ Loaded legacy conversation:", item.id);
-      }
-      
+
+      // Turns are decrypted by the backend with the session key
+      const turns = await invoke("load_conversation", { conversationId: item.id });
+
+      const loadedMessages = turns.map((turn, index) => ({
+        id: `${item.id}-${index}`,
+        role: turn.role,
+        content: turn.content,
+        timestamp: new Date(turn.timestamp * 1000),
+        model: turn.role === "assistant" ? (item.model || selectedModel) : undefined
+      }));
+
+      setMessages(loadedMessages);
+      setConversationId(item.id);
+      console.log("✅ Loaded conversation with", loadedMessages.length, "messages");
+
       // Scroll to view
       setTimeout(() => {
         messagesEndRef.current?.scrollIntoView({ behavior: "smooth" });
       }, 100);
     } catch (err) {
-      console.error("❌ Failed to decrypt conversation:", err);
+      console.error("❌ Failed to load conversation:", err);
       // Show error in UI
       setMessages([{
         id: Date.now(),
@@ -303,7 +268,7 @@ export default function ChatInterface() {
     const q = historySearch.trim().toLowerCase();
     if (!q) return conversationHistory;
     return conversationHistory.filter((item) => {
-      const hay = `${item.id} ${item.model || ""} ${item.timestamp || ""}`.toLowerCase();
+      const hay = `${item.id} ${item.title || ""} ${item.model || ""} ${item.timestamp || ""}`.toLowerCase();
       return hay.includes(q);
     });
   }, [conversationHistory, historySearch]);
@@ -596,6 +561,11 @@ export default function ChatInterface() {
                           {models.find((m) => m.id === msg.model)?.name}
                         </span>
                       )}
+                      {msg.warning && (
+                        <span className="text-[9px] text-identra-text-muted" title={msg.warning}>
+                          not saved
+                        </span>
+                      )}
                       <span className="text-[9px] text-identra-text-muted">
                         {msg.timestamp.toLocaleTimeString([], {
                           hour: "2-digit",
@@ -778,7 +748,7 @@ export default function ChatInterface() {
                       timeStr = `${Math.floor(timeAgo / 1440)}d ago`;
                     }
 
-                    const title = `Conversation ${String(item.id).slice(0, 6)}`;
+                    const title = item.title || `Conversation ${String(item.id).slice(0, 6)}`;
 
                     return (
                       <button
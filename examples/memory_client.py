@@ -23,15 +23,36 @@ except ImportError:
 class MemoryClient:
     """Python client for Identra Memory Service"""
     
-    def __init__(self, host='[::1]', port=50051):
+    def __init__(self, host='[::1]', port=50051, ca_cert=None, client_cert=None, client_key=None):
         """
         Initialize connection to tunnel-gateway
         
         Args:
             host: Gateway host (default: [::1] for IPv6 localhost)
             port: Gateway port (default: 50051)
+            ca_cert: PEM CA bundle path; connects over TLS when set (or GATEWAY_CA_CERT)
+            client_cert: PEM client certificate path for mTLS (or GATEWAY_CLIENT_CERT)
+            client_key: PEM client key path for mTLS (or GATEWAY_CLIENT_KEY)
         """
-        self.channel = grpc.insecure_channel(f'{host}:{port}')
+        ca_cert = ca_cert or os.environ.get('GATEWAY_CA_CERT')
+        client_cert = client_cert or os.environ.get('GATEWAY_CLIENT_CERT')
+        client_key = client_key or os.environ.get('GATEWAY_CLIENT_KEY')
+
+        if ca_cert or client_cert:
+            def read(path):
+                with open(path, 'rb') as f:
+                    return f.read()
+            credentials = grpc.ssl_channel_credentials(
+                root_certificates=read(ca_cert) if ca_cert else None,
+                private_key=read(client_key) if client_key else None,
+                certificate_chain=read(client_cert) if client_cert else None,
+            )
+            # Verify the certificate against another name than the host, e.g. "localhost"
+            domain = os.environ.get('GATEWAY_TLS_DOMAIN')
+            options = [('grpc.ssl_target_name_override', domain)] if domain else None
+            self.channel = grpc.secure_channel(f'{host}:{port}', credentials, options=options)
+        else:
+            self.channel = grpc.insecure_channel(f'{host}:{port}')
         self.stub = memory_pb2_grpc.MemoryServiceStub(self.channel)
     
     def store_conversation(
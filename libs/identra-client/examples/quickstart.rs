@@ -0,0 +1,36 @@
+//! Log in, store a memory and read it back.
+//!
+//! IDENTRA_USER=ada IDENTRA_PASSWORD=... cargo run -p identra-client --example quickstart
+
+use identra_client::{Client, ErrorCode, NewMemory};
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let address = std::env::var("IDENTRA_GATEWAY").unwrap_or_else(|_| "http://[::1]:50051".to_string());
+    let client = Client::builder(address).build()?;
+
+    let health = client.health().check().await?;
+    println!("Gateway {:?}, up {}s", health.status, health.uptime.as_secs());
+
+    let username = std::env::var("IDENTRA_USER")?;
+    let password = std::env::var("IDENTRA_PASSWORD")?;
+    let session = client.auth().login(&username, &password).await?;
+    println!("Logged in until {}", session.expires_at);
+
+    let memories = client.memories();
+    let id = memories
+        .store(NewMemory::new("Prefers tea, no sugar").tag("preferences").metadata("source", "quickstart"))
+        .await?;
+    println!("Stored {}", id);
+
+    for memory in memories.query("tea", 5).await? {
+        println!("{}  {}  {:?}", memory.created_at.format("%F %R"), memory.content, memory.tags);
+    }
+
+    memories.delete(&id).await?;
+    match memories.get(&id).await {
+        Err(e) if e.code() == Some(ErrorCode::MemoryNotFound) => println!("Deleted {}", id),
+        other => println!("Unexpected: {:?}", other.map(|m| m.id)),
+    }
+    Ok(())
+}
@@ -1,4 +1,4 @@
-use tunnel_gateway::ipc_client::VaultClient;
+use identra_client::ipc::VaultClient;
 use std::collections::HashMap;
 
 #[tokio::main]
@@ -38,15 +38,14 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     
     // Test 5: Retrieve Key
     println!("🔓 Test 5: Retrieving key...");
-    let (retrieved_data, retrieved_metadata, created_at, expires_at) = 
-        client.retrieve_key(key_id.clone()).await
-            .map_err(|e| format!("Retrieve failed: {}", e))?;
+    let retrieved = client.retrieve_key(key_id.clone()).await
+        .map_err(|e| format!("Retrieve failed: {}", e))?;
     
     println!("✅ Key retrieved:");
-    println!("   Data matches: {}", retrieved_data == key_data);
-    println!("   Metadata: {:?}", retrieved_metadata);
-    println!("   Created at: {}", created_at);
-    println!("   Expires at: {:?}\n", expires_at);
+    println!("   Data matches: {}", retrieved.key_data == key_data);
+    println!("   Metadata: {:?}", retrieved.metadata);
+    println!("   Created at: {}", retrieved.created_at);
+    println!("   Expires at: {:?}\n", retrieved.expires_at);
     
     // Test 6: List Keys
     println!("📋 Test 6: Listing all keys...");
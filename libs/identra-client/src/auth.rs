@@ -0,0 +1,72 @@
+use crate::error::{ClientError, Result};
+use crate::types::Identity;
+use crate::{Client, Session};
+use chrono::DateTime;
+use identra_proto::auth::{auth_service_client::AuthServiceClient, LoginRequest, RegisterRequest, VerifyTokenRequest};
+
+/// Accounts and the client's login session
+#[derive(Clone)]
+pub struct Auth {
+    client: Client,
+}
+
+impl Auth {
+    pub(crate) fn new(client: Client) -> Self {
+        Self { client }
+    }
+
+    /// Create an account and return its user id. Does not log in.
+    pub async fn register(&self, username: &str, email: &str, password: &str) -> Result<String> {
+        let request = RegisterRequest {
+            username: username.to_string(),
+            email: email.to_string(),
+            password: password.to_string(),
+        };
+        let response = self.client.call(|channel| {
+            let request = request.clone();
+            async move { AuthServiceClient::new(channel).register(request).await }
+        }).await?;
+        Ok(response.user_id)
+    }
+
+    /// Log in; later calls on this client and its clones carry the session
+    pub async fn login(&self, username: &str, password: &str) -> Result<Session> {
+        let request = LoginRequest { username: username.to_string(), password: password.to_string() };
+        let response = self.client.call(|channel| {
+            let request = request.clone();
+            async move { AuthServiceClient::new(channel).login(request).await }
+        }).await?;
+        let session = Session::new(response.access_token, response.refresh_token, response.expires_in);
+        self.client.set_session(Some(session.clone()));
+        Ok(session)
+    }
+
+    /// Replace the access token now rather than when it nears expiry
+    pub async fn refresh(&self) -> Result<Session> {
+        let session = self.client.session().ok_or(ClientError::NotLoggedIn)?;
+        self.client.refresh_session(&session.access_token).await?;
+        self.client.session().ok_or(ClientError::NotLoggedIn)
+    }
+
+    /// The user behind `token`, or `None` when it is invalid or expired
+    pub async fn verify(&self, token: &str) -> Result<Option<Identity>> {
+        let request = VerifyTokenRequest { token: token.to_string() };
+        let response = self.client.call_idempotent(|channel| {
+            let request = request.clone();
+            async move { AuthServiceClient::new(channel).verify_token(request).await }
+        }).await?;
+        if !response.valid {
+            return Ok(None);
+        }
+        Ok(Some(Identity {
+            user_id: response.user_id,
+            username: response.username,
+            expires_at: DateTime::from_timestamp(response.expires_at, 0).unwrap_or_default(),
+        }))
+    }
+
+    /// Drop the session. Tokens stay valid at the gateway until they expire.
+    pub fn logout(&self) {
+        self.client.set_session(None);
+    }
+}
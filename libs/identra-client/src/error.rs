@@ -0,0 +1,37 @@
+use identra_core::error::{Error, ErrorCode};
+use tonic::Status;
+
+pub type Result<T> = std::result::Result<T, ClientError>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ClientError {
+    /// The gateway answered with an error; match on [`Error::code`]
+    #[error(transparent)]
+    Api(#[from] Error),
+    #[error("Invalid gateway address: {0}")]
+    InvalidAddress(String),
+    #[error("TLS configuration error: {0}")]
+    Tls(String),
+    /// The call needs a session and none is set, or it cannot be refreshed
+    #[error("Not logged in")]
+    NotLoggedIn,
+    /// The gateway sent a response this client cannot represent
+    #[error("Unexpected gateway response: {0}")]
+    InvalidResponse(String),
+}
+
+impl ClientError {
+    /// Stable code of an API error, `None` for local failures
+    pub fn code(&self) -> Option<ErrorCode> {
+        match self {
+            Self::Api(error) => Some(error.code()),
+            _ => None,
+        }
+    }
+}
+
+impl From<Status> for ClientError {
+    fn from(status: Status) -> Self {
+        Self::Api(Error::from_status(&status))
+    }
+}
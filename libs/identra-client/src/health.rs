@@ -0,0 +1,31 @@
+use crate::error::Result;
+use crate::types::HealthReport;
+use crate::Client;
+use identra_proto::health::{health_client::HealthClient, HealthCheckRequest};
+
+/// The gateway's Health service
+#[derive(Clone)]
+pub struct Health {
+    client: Client,
+}
+
+impl Health {
+    pub(crate) fn new(client: Client) -> Self {
+        Self { client }
+    }
+
+    /// Overall gateway health
+    pub async fn check(&self) -> Result<HealthReport> {
+        self.check_service("").await
+    }
+
+    /// Health of one service, e.g. `identra.memory.v1.MemoryService`
+    pub async fn check_service(&self, service: &str) -> Result<HealthReport> {
+        let request = HealthCheckRequest { service: service.to_string() };
+        let response = self.client.call_idempotent(|channel| {
+            let request = request.clone();
+            async move { HealthClient::new(channel).check(request).await }
+        }).await?;
+        Ok(response.into())
+    }
+}
@@ -1,3 +1,6 @@
+//! Client for the vault daemon's local socket: line-delimited JSON requests, each
+//! carrying the caller's trace context.
+
 use interprocess::local_socket::{
     tokio::{prelude::*, Stream},
     GenericNamespaced,
@@ -5,13 +8,16 @@ use interprocess::local_socket::{
 use serde::{Deserialize, Serialize};
 use std::error::Error;
 use std::fmt;
+use std::time::Instant;
+use metrics::{counter, histogram};
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tracing::Instrument;
+use identra_core::config::DEFAULT_VAULT_SOCKET;
+use identra_core::trace;
 
-#[cfg(windows)]
-const IPC_PIPE_NAME: &str = "@identra-vault";
-
-#[cfg(unix)]
-const IPC_PIPE_NAME: &str = "/tmp/identra-vault.sock";
+pub const VAULT_IPC_REQUESTS: &str = "identra_vault_ipc_requests_total";
+pub const VAULT_IPC_ERRORS: &str = "identra_vault_ipc_errors_total";
+pub const VAULT_IPC_DURATION: &str = "identra_vault_ipc_duration_seconds";
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum VaultRequest {
@@ -28,6 +34,29 @@ pub enum VaultRequest {
     Ping,
 }
 
+impl VaultRequest {
+    /// Variant name, used as the metrics label
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::StoreKey { .. } => "StoreKey",
+            Self::RetrieveKey { .. } => "RetrieveKey",
+            Self::DeleteKey { .. } => "DeleteKey",
+            Self::KeyExists { .. } => "KeyExists",
+            Self::ListKeys => "ListKeys",
+            Self::Ping => "Ping",
+        }
+    }
+}
+
+/// A request as sent over the socket, with the trace it belongs to
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VaultEnvelope {
+    /// W3C trace context of the calling span, so daemon spans join the caller's trace
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub traceparent: Option<String>,
+    pub request: VaultRequest,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum VaultResponse {
     Success,
@@ -64,6 +93,16 @@ impl fmt::Display for VaultClientError {
 
 impl Error for VaultClientError {}
 
+/// A key as returned by the vault daemon
+#[derive(Debug, Clone)]
+pub struct KeyRecord {
+    pub key_data: Vec<u8>,
+    pub metadata: std::collections::HashMap<String, String>,
+    /// Unix seconds
+    pub created_at: i64,
+    pub expires_at: Option<i64>,
+}
+
 pub struct VaultClient {
     reader: BufReader<tokio::io::ReadHalf<Stream>>,
     writer: tokio::io::WriteHalf<Stream>,
@@ -71,12 +110,29 @@ pub struct VaultClient {
 
 impl VaultClient {
     pub async fn connect() -> Result<Self, VaultClientError> {
-        let name = IPC_PIPE_NAME.to_ns_name::<GenericNamespaced>()
-            .map_err(|e| VaultClientError::ConnectionFailed(e.to_string()))?;
+        Self::connect_to(DEFAULT_VAULT_SOCKET).await
+    }
+
+    /// Connect to a vault daemon listening on `socket`, see `vault.socket`
+    pub async fn connect_to(socket: &str) -> Result<Self, VaultClientError> {
+        // On Unix an absolute path is a socket file, as on the daemon side
+        #[cfg(unix)]
+        let name = if socket.starts_with('/') {
+            use interprocess::local_socket::{GenericFilePath, ToFsName};
+            socket.to_fs_name::<GenericFilePath>()
+        } else {
+            socket.to_ns_name::<GenericNamespaced>()
+        };
+        #[cfg(not(unix))]
+        let name = socket.to_ns_name::<GenericNamespaced>();
+        let name = name.map_err(|e| VaultClientError::ConnectionFailed(e.to_string()))?;
         
         let stream = Stream::connect(name)
             .await
-            .map_err(|e| VaultClientError::ConnectionFailed(e.to_string()))?;
+            .map_err(|e| {
+                counter!(VAULT_IPC_ERRORS, "request" => "Connect").increment(1);
+                VaultClientError::ConnectionFailed(e.to_string())
+            })?;
 
         let (reader, writer) = tokio::io::split(stream);
         let reader = BufReader::new(reader);
@@ -85,8 +141,27 @@ impl VaultClient {
     }
 
     pub async fn send_request(&mut self, request: VaultRequest) -> Result<VaultResponse, VaultClientError> {
+        let name = request.name();
+        let started = Instant::now();
+        let span = tracing::info_span!("vault.ipc", otel.kind = "client", request = name);
+        let result = async {
+            let envelope = VaultEnvelope { traceparent: trace::traceparent(), request };
+            self.exchange(&envelope).await
+        }
+        .instrument(span)
+        .await;
+
+        counter!(VAULT_IPC_REQUESTS, "request" => name).increment(1);
+        histogram!(VAULT_IPC_DURATION, "request" => name).record(started.elapsed().as_secs_f64());
+        if matches!(result, Err(_) | Ok(VaultResponse::Error(_))) {
+            counter!(VAULT_IPC_ERRORS, "request" => name).increment(1);
+        }
+        result
+    }
+
+    async fn exchange(&mut self, envelope: &VaultEnvelope) -> Result<VaultResponse, VaultClientError> {
         // Serialize request to JSON
-        let request_json = serde_json::to_string(&request)
+        let request_json = serde_json::to_string(envelope)
             .map_err(|e| VaultClientError::SerializationError(e.to_string()))?;
         
         // Send line-delimited JSON (matches vault-daemon protocol)
@@ -130,11 +205,11 @@ impl VaultClient {
         }
     }
 
-    pub async fn retrieve_key(&mut self, key_id: String) -> Result<(Vec<u8>, std::collections::HashMap<String, String>, i64, Option<i64>), VaultClientError> {
+    pub async fn retrieve_key(&mut self, key_id: String) -> Result<KeyRecord, VaultClientError> {
         let response = self.send_request(VaultRequest::RetrieveKey { key_id }).await?;
         match response {
             VaultResponse::KeyData { key_data, metadata, created_at, expires_at } => {
-                Ok((key_data, metadata, created_at, expires_at))
+                Ok(KeyRecord { key_data, metadata, created_at, expires_at })
             }
             VaultResponse::Error(message) => Err(VaultClientError::ReceiveFailed(message)),
             _ => Err(VaultClientError::ReceiveFailed("Unexpected response type".to_string())),
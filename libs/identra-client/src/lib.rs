@@ -0,0 +1,452 @@
+//! Typed async client for the Identra gateway, shared by the desktop app, the CLI
+//! and the examples.
+//!
+//! A [`Client`] owns a small pool of HTTP/2 connections and the login session. Clones
+//! are cheap and share both, so one client serves a whole process. Every call carries
+//! the session's bearer token and the caller's trace context; an access token close
+//! to expiry is refreshed first, and a call rejected for an expired token is refreshed
+//! and replayed once. Idempotent calls are retried on transient failures following
+//! the client's [`RetryPolicy`].
+//!
+//! ```no_run
+//! # async fn run() -> identra_client::Result<()> {
+//! use identra_client::{Client, NewMemory};
+//!
+//! let client = Client::builder("http://[::1]:50051").build()?;
+//! client.auth().login("ada", "correct horse").await?;
+//! let id = client.memories().store(NewMemory::new("Tea, no sugar").tag("preferences")).await?;
+//! let memory = client.memories().get(&id).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod auth;
+pub mod error;
+pub mod health;
+pub mod ipc;
+pub mod memory;
+pub mod namespace;
+mod retry;
+mod session;
+mod transport;
+pub mod types;
+pub mod vault;
+
+pub use auth::Auth;
+pub use error::{ClientError, Result};
+pub use health::Health;
+pub use identra_core::error::{Error, ErrorCode};
+pub use memory::Memories;
+pub use namespace::Namespaces;
+pub use retry::RetryPolicy;
+pub use session::Session;
+pub use transport::GatewayTls;
+pub use types::*;
+pub use vault::Vault;
+
+use identra_core::config::DesktopConfig;
+use identra_proto::auth::{auth_service_client::AuthServiceClient, RefreshTokenRequest};
+use session::SessionStore;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tonic::Status;
+use transport::{AuthedChannel, SessionInterceptor};
+
+/// Connections kept open to the gateway
+pub const DEFAULT_POOL_SIZE: usize = 4;
+
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Clone)]
+pub struct Client {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    channel: AuthedChannel,
+    session: SessionStore,
+    retry: RetryPolicy,
+}
+
+pub struct ClientBuilder {
+    address: String,
+    tls: Option<GatewayTls>,
+    pool_size: usize,
+    connect_timeout: Duration,
+    request_timeout: Option<Duration>,
+    retry: RetryPolicy,
+    session: Option<Session>,
+}
+
+impl ClientBuilder {
+    /// TLS settings; `https://` addresses use the system roots without them
+    pub fn tls(mut self, tls: GatewayTls) -> Self {
+        self.tls = Some(tls);
+        self
+    }
+
+    pub fn pool_size(mut self, connections: usize) -> Self {
+        self.pool_size = connections;
+        self
+    }
+
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = timeout;
+        self
+    }
+
+    /// Deadline of each attempt of a unary call; unset by default
+    pub fn request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = Some(timeout);
+        self
+    }
+
+    pub fn retry(mut self, policy: RetryPolicy) -> Self {
+        self.retry = policy;
+        self
+    }
+
+    /// Start with a session from an earlier login
+    pub fn session(mut self, session: Session) -> Self {
+        self.session = Some(session);
+        self
+    }
+
+    /// Connections open on first use, so this only fails on bad settings.
+    /// Must be called within a Tokio runtime.
+    pub fn build(self) -> Result<Client> {
+        let endpoint = transport::endpoint(
+            &self.address,
+            self.tls.as_ref(),
+            self.connect_timeout,
+            self.request_timeout,
+        )?;
+        let session = SessionStore::default();
+        session.set(self.session);
+        let channel = AuthedChannel::new(
+            transport::pool(endpoint, self.pool_size),
+            SessionInterceptor { session: session.clone() },
+        );
+        Ok(Client { inner: Arc::new(Inner { channel, session, retry: self.retry }) })
+    }
+}
+
+impl Client {
+    pub fn builder(address: impl Into<String>) -> ClientBuilder {
+        ClientBuilder {
+            address: address.into(),
+            tls: None,
+            pool_size: DEFAULT_POOL_SIZE,
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            request_timeout: None,
+            retry: RetryPolicy::default(),
+            session: None,
+        }
+    }
+
+    /// Client for `desktop.gateway_address` with the TLS files in `desktop.tls`
+    pub fn from_config(config: &DesktopConfig) -> Result<Self> {
+        let mut builder = Self::builder(config.gateway_address.clone());
+        if let Some(tls) = GatewayTls::from_config(&config.tls).map_err(|e| ClientError::Tls(e.to_string()))? {
+            builder = builder.tls(tls);
+        }
+        builder.build()
+    }
+
+    /// Memories and conversations in the caller's default namespace
+    pub fn memories(&self) -> Memories {
+        Memories::new(self.clone(), String::new())
+    }
+
+    /// Memories and conversations in a named namespace
+    pub fn namespace(&self, name: impl Into<String>) -> Memories {
+        Memories::new(self.clone(), name.into())
+    }
+
+    pub fn namespaces(&self) -> Namespaces {
+        Namespaces::new(self.clone())
+    }
+
+    pub fn auth(&self) -> Auth {
+        Auth::new(self.clone())
+    }
+
+    pub fn vault(&self) -> Vault {
+        Vault::new(self.clone())
+    }
+
+    pub fn health(&self) -> Health {
+        Health::new(self.clone())
+    }
+
+    /// The current session, including tokens refreshed since login
+    pub fn session(&self) -> Option<Session> {
+        self.inner.session.get()
+    }
+
+    pub fn set_session(&self, session: Option<Session>) {
+        self.inner.session.set(session);
+    }
+
+    /// Make a call that changes state; it is not retried after transient failures
+    pub(crate) async fn call<T, F, Fut>(&self, call: F) -> Result<T>
+    where
+        F: FnMut(AuthedChannel) -> Fut,
+        Fut: Future<Output = std::result::Result<tonic::Response<T>, Status>>,
+    {
+        self.invoke(false, call).await
+    }
+
+    /// Make a call that is safe to repeat, retrying transient failures
+    pub(crate) async fn call_idempotent<T, F, Fut>(&self, call: F) -> Result<T>
+    where
+        F: FnMut(AuthedChannel) -> Fut,
+        Fut: Future<Output = std::result::Result<tonic::Response<T>, Status>>,
+    {
+        self.invoke(true, call).await
+    }
+
+    async fn invoke<T, F, Fut>(&self, idempotent: bool, mut call: F) -> Result<T>
+    where
+        F: FnMut(AuthedChannel) -> Fut,
+        Fut: Future<Output = std::result::Result<tonic::Response<T>, Status>>,
+    {
+        self.refresh_if_expiring().await?;
+
+        let mut attempt = 0;
+        let mut refreshed = false;
+        loop {
+            let token = self.inner.session.access_token();
+            let error = match call(self.inner.channel.clone()).await {
+                Ok(response) => return Ok(response.into_inner()),
+                Err(status) => Error::from_status(&status),
+            };
+
+            // The gateway rejects stale tokens before running the call, so replaying is safe
+            let rejected = matches!(error.code(), ErrorCode::InvalidToken | ErrorCode::Unauthenticated);
+            let can_refresh = self.session().is_some_and(|session| session.can_refresh());
+            if let Some(token) = token.filter(|_| rejected && can_refresh && !refreshed) {
+                refreshed = true;
+                self.refresh_session(&token).await?;
+                continue;
+            }
+
+            if idempotent {
+                if let Some(delay) = self.inner.retry.backoff(attempt, &error) {
+                    tracing::debug!(code = %error.code(), attempt, ?delay, "Retrying gateway call");
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                    continue;
+                }
+            }
+            return Err(error.into());
+        }
+    }
+
+    /// Refresh ahead of a call when the access token is about to expire. A failed
+    /// refresh only fails the call once the token has actually expired.
+    async fn refresh_if_expiring(&self) -> Result<()> {
+        let Some(session) = self.session() else {
+            return Ok(());
+        };
+        if !session.needs_refresh() || !session.can_refresh() {
+            return Ok(());
+        }
+        match self.refresh_session(&session.access_token).await {
+            Err(e) if !session.is_expired() => {
+                tracing::debug!("Token refresh failed, using the current token: {}", e);
+                Ok(())
+            }
+            result => result,
+        }
+    }
+
+    /// Exchange the refresh token for a new access token, unless another call already
+    /// replaced `stale`. A rejected refresh token ends the session.
+    pub(crate) async fn refresh_session(&self, stale: &str) -> Result<()> {
+        let _refreshing = self.inner.session.refreshing.lock().await;
+        let session = self.session().ok_or(ClientError::NotLoggedIn)?;
+        if session.access_token != stale {
+            return Ok(());
+        }
+        if !session.can_refresh() {
+            return Err(ClientError::NotLoggedIn);
+        }
+
+        let request = RefreshTokenRequest { refresh_token: session.refresh_token.clone() };
+        let response = match AuthServiceClient::new(self.inner.channel.clone()).refresh_token(request).await {
+            Ok(response) => response.into_inner(),
+            Err(status) => {
+                let error = Error::from_status(&status);
+                if matches!(error.code(), ErrorCode::InvalidToken | ErrorCode::Unauthenticated) {
+                    self.set_session(None);
+                }
+                return Err(error.into());
+            }
+        };
+        self.set_session(Some(Session::new(response.access_token, session.refresh_token, response.expires_in)));
+        tracing::debug!("Refreshed gateway access token");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use identra_proto::auth::auth_service_server::{AuthService, AuthServiceServer};
+    use identra_proto::auth::{
+        LoginRequest, LoginResponse, RefreshTokenResponse, RegisterRequest, RegisterResponse, VerifyTokenRequest,
+        VerifyTokenResponse,
+    };
+    use identra_proto::health::health_server::{Health as HealthService, HealthServer};
+    use identra_proto::health::{HealthCheckRequest, HealthCheckResponse};
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Mutex;
+    use tokio_stream::wrappers::TcpListenerStream;
+    use tonic::{Request, Response};
+
+    /// Gateway stand-in: health checks fail `failures` times, then need the token "fresh"
+    #[derive(Default)]
+    struct Calls {
+        failures: AtomicU32,
+        count: AtomicU32,
+        tokens: Mutex<Vec<String>>,
+    }
+
+    #[derive(Clone, Default)]
+    struct Gateway(Arc<Calls>);
+
+    #[tonic::async_trait]
+    impl HealthService for Gateway {
+        async fn check(&self, request: Request<HealthCheckRequest>) -> std::result::Result<Response<HealthCheckResponse>, Status> {
+            self.0.count.fetch_add(1, Ordering::SeqCst);
+            let token = request.metadata().get("authorization").and_then(|v| v.to_str().ok()).unwrap_or_default();
+            self.0.tokens.lock().unwrap().push(token.to_string());
+            if self.0.failures.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1)).is_ok() {
+                return Err(Error::new(ErrorCode::Unavailable, "warming up").into());
+            }
+            if token != "Bearer fresh" {
+                return Err(Error::new(ErrorCode::InvalidToken, "Token expired").into());
+            }
+            Ok(Response::new(HealthCheckResponse { status: 1, message: String::new(), uptime_seconds: 7 }))
+        }
+
+        type WatchStream = tokio_stream::Empty<std::result::Result<HealthCheckResponse, Status>>;
+
+        async fn watch(&self, _: Request<HealthCheckRequest>) -> std::result::Result<Response<Self::WatchStream>, Status> {
+            Err(Status::unimplemented("watch"))
+        }
+    }
+
+    #[tonic::async_trait]
+    impl AuthService for Gateway {
+        async fn register(&self, _: Request<RegisterRequest>) -> std::result::Result<Response<RegisterResponse>, Status> {
+            self.0.count.fetch_add(1, Ordering::SeqCst);
+            Err(Error::new(ErrorCode::AuthUnavailable, "Auth provider unavailable").into())
+        }
+
+        async fn login(&self, _: Request<LoginRequest>) -> std::result::Result<Response<LoginResponse>, Status> {
+            Err(Status::unimplemented("login"))
+        }
+
+        async fn verify_token(&self, _: Request<VerifyTokenRequest>) -> std::result::Result<Response<VerifyTokenResponse>, Status> {
+            Err(Status::unimplemented("verify_token"))
+        }
+
+        async fn refresh_token(
+            &self,
+            request: Request<RefreshTokenRequest>,
+        ) -> std::result::Result<Response<RefreshTokenResponse>, Status> {
+            if request.into_inner().refresh_token != "refresh" {
+                return Err(Error::new(ErrorCode::InvalidToken, "Invalid refresh token").into());
+            }
+            Ok(Response::new(RefreshTokenResponse { success: true, access_token: "fresh".to_string(), expires_in: 3600 }))
+        }
+    }
+
+    async fn serve(gateway: Gateway) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(
+            tonic::transport::Server::builder()
+                .add_service(HealthServer::new(gateway.clone()))
+                .add_service(AuthServiceServer::new(gateway))
+                .serve_with_incoming(TcpListenerStream::new(listener)),
+        );
+        address
+    }
+
+    fn fast_retries() -> RetryPolicy {
+        RetryPolicy { initial_backoff: Duration::from_millis(1), ..RetryPolicy::default() }
+    }
+
+    #[tokio::test]
+    async fn refreshes_rejected_token_and_replays_call() {
+        let gateway = Gateway::default();
+        let client = Client::builder(serve(gateway.clone()).await)
+            .session(Session::new("stale".to_string(), "refresh".to_string(), 3600))
+            .build()
+            .unwrap();
+
+        let report = client.health().check().await.unwrap();
+        assert!(report.is_serving());
+        assert_eq!(report.uptime, Duration::from_secs(7));
+        assert_eq!(*gateway.0.tokens.lock().unwrap(), ["Bearer stale", "Bearer fresh"]);
+        assert_eq!(client.session().unwrap().access_token, "fresh");
+    }
+
+    #[tokio::test]
+    async fn refreshes_expiring_token_before_calling() {
+        let gateway = Gateway::default();
+        let client = Client::builder(serve(gateway.clone()).await)
+            .session(Session::new("stale".to_string(), "refresh".to_string(), 5))
+            .build()
+            .unwrap();
+
+        client.health().check().await.unwrap();
+        assert_eq!(*gateway.0.tokens.lock().unwrap(), ["Bearer fresh"]);
+    }
+
+    #[tokio::test]
+    async fn rejected_refresh_token_ends_session() {
+        let gateway = Gateway::default();
+        let client = Client::builder(serve(gateway.clone()).await)
+            .session(Session::new("stale".to_string(), "revoked".to_string(), 3600))
+            .build()
+            .unwrap();
+
+        let error = client.health().check().await.unwrap_err();
+        assert_eq!(error.code(), Some(ErrorCode::InvalidToken));
+        assert!(client.session().is_none());
+    }
+
+    #[tokio::test]
+    async fn retries_only_idempotent_calls() {
+        let gateway = Gateway::default();
+        gateway.0.failures.store(2, Ordering::SeqCst);
+        let client = Client::builder(serve(gateway.clone()).await)
+            .session(Session::new("fresh".to_string(), String::new(), 3600))
+            .retry(fast_retries())
+            .build()
+            .unwrap();
+
+        client.health().check().await.unwrap();
+        assert_eq!(gateway.0.count.swap(0, Ordering::SeqCst), 3);
+
+        let error = client.auth().register("ada", "ada@example.com", "pw").await.unwrap_err();
+        assert_eq!(error.code(), Some(ErrorCode::AuthUnavailable));
+        assert_eq!(gateway.0.count.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn unreachable_gateway_is_unavailable() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        drop(listener);
+
+        let client = Client::builder(address).retry(fast_retries()).build().unwrap();
+        let error = client.health().check().await.unwrap_err();
+        assert_eq!(error.code(), Some(ErrorCode::Unavailable));
+    }
+}
@@ -0,0 +1,412 @@
+use crate::error::{ClientError, Result};
+use crate::types::{
+    required, Context, ContextPolicy, Conversation, Grant, Grantee, Memory, MemoryEvent, MemoryFields, MemoryFilter, MemoryStats, NewMemory,
+    NewTurn, Permission, ScoredMemory, SearchRanking, TagCount, TrashedMemory, Turn,
+};
+use crate::Client;
+use identra_proto::memory::{
+    memory_service_client::MemoryServiceClient, AppendConversationTurnsRequest, BuildContextRequest,
+    CreateConversationRequest, DeleteConversationRequest, DeleteMemoryRequest, GetConversationRequest,
+    GetMemoryRequest, GetMemoryStatsRequest, GetRecentMemoriesRequest, GrantAccessRequest, ListConversationsRequest,
+    ListGrantsRequest, ListTagsRequest, ListTrashRequest, MergeTagsRequest, PurgeMemoryRequest, QueryMemoriesRequest, QueryMemoriesResponse,
+    RenameTagRequest, RestoreMemoryRequest, RetagMemoriesRequest, RevokeAccessRequest, SearchMemoriesRequest,
+    StoreMemoryRequest, WatchMemoriesRequest,
+};
+use std::collections::HashMap;
+use tokio_stream::{Stream, StreamExt};
+
+/// Memories fetched per call by `query_all`
+const QUERY_PAGE_SIZE: u32 = 500;
+
+/// Wire form of a result limit
+pub(crate) fn limit(value: u32) -> i32 {
+    value.min(i32::MAX as u32) as i32
+}
+
+/// Memories, conversations and grants of one namespace
+#[derive(Clone)]
+pub struct Memories {
+    client: Client,
+    namespace: String,
+    filter: MemoryFilter,
+    fields: MemoryFields,
+}
+
+impl Memories {
+    pub(crate) fn new(client: Client, namespace: String) -> Self {
+        Self { client, namespace, filter: MemoryFilter::default(), fields: MemoryFields::default() }
+    }
+
+    /// Empty for the caller's default namespace
+    pub fn namespace(&self) -> &str {
+        &self.namespace
+    }
+
+    /// A handle whose `query`, `recent` and `search_ranked` calls only return memories
+    /// within the filter's time ranges, in its order
+    pub fn filter(&self, filter: MemoryFilter) -> Self {
+        Self { filter, ..self.clone() }
+    }
+
+    /// A handle whose `get`, `query`, `recent` and `search_ranked` calls return these fields
+    pub fn fields(&self, fields: MemoryFields) -> Self {
+        Self { fields, ..self.clone() }
+    }
+
+    /// Store a memory and return its id. Transient failures are retried under one
+    /// idempotency key, so a retry after a lost response does not store a copy.
+    pub async fn store(&self, memory: NewMemory) -> Result<String> {
+        let request = StoreMemoryRequest {
+            content: memory.content,
+            metadata: memory.metadata,
+            tags: memory.tags,
+            namespace: self.namespace.clone(),
+            idempotency_key: memory.idempotency_key.unwrap_or_else(|| format!("{:032x}", fastrand::u128(..))),
+        };
+        let response = self.client.call_idempotent(|channel| {
+            let request = request.clone();
+            async move { MemoryServiceClient::new(channel).store_memory(request).await }
+        }).await?;
+        Ok(response.memory_id)
+    }
+
+    pub async fn get(&self, id: &str) -> Result<Memory> {
+        let request = GetMemoryRequest {
+            memory_id: id.to_string(),
+            namespace: self.namespace.clone(),
+            fields: Some(self.fields.into()),
+        };
+        let response = self.client.call_idempotent(|channel| {
+            let request = request.clone();
+            async move { MemoryServiceClient::new(channel).get_memory(request).await }
+        }).await?;
+        Ok(required(response.memory, "memory")?.into())
+    }
+
+    /// Move a memory to the trash
+    pub async fn delete(&self, id: &str) -> Result<()> {
+        let request = DeleteMemoryRequest { memory_id: id.to_string(), namespace: self.namespace.clone() };
+        self.client.call(|channel| {
+            let request = request.clone();
+            async move { MemoryServiceClient::new(channel).delete_memory(request).await }
+        }).await?;
+        Ok(())
+    }
+
+    /// Memories whose content contains `text`, newest first unless the filter sorts otherwise
+    pub async fn query(&self, text: &str, max: u32) -> Result<Vec<Memory>> {
+        let page = self.query_page(text, HashMap::new(), max, String::new()).await?;
+        Ok(page.memories.into_iter().map(Memory::from).collect())
+    }
+
+    /// Every memory whose content contains `text` and whose metadata has each entry of
+    /// `metadata`, following the pages
+    pub async fn query_all(&self, text: &str, metadata: HashMap<String, String>) -> Result<Vec<Memory>> {
+        let mut memories = Vec::new();
+        let mut page_token = String::new();
+        loop {
+            let page = self.query_page(text, metadata.clone(), QUERY_PAGE_SIZE, page_token).await?;
+            memories.extend(page.memories.into_iter().map(Memory::from));
+            if page.next_page_token.is_empty() {
+                return Ok(memories);
+            }
+            page_token = page.next_page_token;
+        }
+    }
+
+    async fn query_page(&self, text: &str, metadata: HashMap<String, String>, max: u32, page_token: String) -> Result<QueryMemoriesResponse> {
+        let request = QueryMemoriesRequest {
+            query: text.to_string(),
+            limit: limit(max),
+            filters: metadata,
+            namespace: self.namespace.clone(),
+            created: self.filter.created.map(Into::into),
+            updated: self.filter.updated.map(Into::into),
+            sort: self.filter.sort.map(Into::into),
+            fields: Some(self.fields.into()),
+            page_token,
+        };
+        self.client.call_idempotent(|channel| {
+            let request = request.clone();
+            async move { MemoryServiceClient::new(channel).query_memories(request).await }
+        }).await
+    }
+
+    /// Memories closest to `embedding`, best match first
+    pub async fn search(&self, embedding: Vec<f32>, max: u32, threshold: f32) -> Result<Vec<ScoredMemory>> {
+        self.search_ranked(embedding, max, threshold, SearchRanking::default()).await
+    }
+
+    /// Memories closest to `embedding`, re-ranked for diversity, recency or by a cross-encoder, best score first
+    pub async fn search_ranked(
+        &self,
+        embedding: Vec<f32>,
+        max: u32,
+        threshold: f32,
+        ranking: SearchRanking,
+    ) -> Result<Vec<ScoredMemory>> {
+        let request = SearchMemoriesRequest {
+            query_embedding: embedding,
+            limit: limit(max),
+            similarity_threshold: threshold,
+            filters: HashMap::new(),
+            namespace: self.namespace.clone(),
+            ranking: Some(ranking.into()),
+            created: self.filter.created.map(Into::into),
+            updated: self.filter.updated.map(Into::into),
+            sort: self.filter.sort.map(Into::into),
+            fields: Some(self.fields.into()),
+        };
+        let response = self.client.call_idempotent(|channel| {
+            let request = request.clone();
+            async move { MemoryServiceClient::new(channel).search_memories(request).await }
+        }).await?;
+        response.matches.into_iter().map(ScoredMemory::try_from).collect()
+    }
+
+    /// The memories most relevant to `query`, de-duplicated and laid out as prompt text
+    /// of at most `token_budget` tokens, with citations back to the memories
+    pub async fn context(&self, query: &str, token_budget: u32, policy: ContextPolicy) -> Result<Context> {
+        let request = BuildContextRequest {
+            query: query.to_string(),
+            token_budget: limit(token_budget),
+            policy: Some(policy.into()),
+            namespace: self.namespace.clone(),
+        };
+        let response = self.client.call_idempotent(|channel| {
+            let request = request.clone();
+            async move { MemoryServiceClient::new(channel).build_context(request).await }
+        }).await?;
+        Context::try_from(response)
+    }
+
+    pub async fn recent(&self, max: u32) -> Result<Vec<Memory>> {
+        let request = GetRecentMemoriesRequest {
+            limit: limit(max),
+            namespace: self.namespace.clone(),
+            created: self.filter.created.map(Into::into),
+            updated: self.filter.updated.map(Into::into),
+            sort: self.filter.sort.map(Into::into),
+            fields: Some(self.fields.into()),
+        };
+        let response = self.client.call_idempotent(|channel| {
+            let request = request.clone();
+            async move { MemoryServiceClient::new(channel).get_recent_memories(request).await }
+        }).await?;
+        Ok(response.memories.into_iter().map(Memory::from).collect())
+    }
+
+    /// Counts by tag, type and month, sizes and embedding coverage of the namespace
+    pub async fn stats(&self) -> Result<MemoryStats> {
+        let request = GetMemoryStatsRequest { namespace: self.namespace.clone() };
+        let response = self.client.call_idempotent(|channel| {
+            let request = request.clone();
+            async move { MemoryServiceClient::new(channel).get_memory_stats(request).await }
+        }).await?;
+        Ok(required(response.stats, "stats")?.into())
+    }
+
+    /// Tags starting with `prefix`, most used first
+    pub async fn tags(&self, prefix: &str, max: u32) -> Result<Vec<TagCount>> {
+        let request = ListTagsRequest { namespace: self.namespace.clone(), prefix: prefix.to_string(), limit: limit(max) };
+        let response = self.client.call_idempotent(|channel| {
+            let request = request.clone();
+            async move { MemoryServiceClient::new(channel).list_tags(request).await }
+        }).await?;
+        Ok(response.tags.into_iter().map(TagCount::from).collect())
+    }
+
+    /// Rename a tag on every memory carrying it; fails if `new_name` is already in use.
+    /// Returns the number of memories changed.
+    pub async fn rename_tag(&self, tag: &str, new_name: &str) -> Result<u64> {
+        let request = RenameTagRequest {
+            namespace: self.namespace.clone(),
+            tag: tag.to_string(),
+            new_name: new_name.to_string(),
+        };
+        let response = self.client.call(|channel| {
+            let request = request.clone();
+            async move { MemoryServiceClient::new(channel).rename_tag(request).await }
+        }).await?;
+        Ok(response.memories_updated.max(0) as u64)
+    }
+
+    /// Replace each of `tags` with `into`, which may already be in use
+    pub async fn merge_tags(&self, tags: &[&str], into: &str) -> Result<u64> {
+        let request = MergeTagsRequest {
+            namespace: self.namespace.clone(),
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            into: into.to_string(),
+        };
+        let response = self.client.call_idempotent(|channel| {
+            let request = request.clone();
+            async move { MemoryServiceClient::new(channel).merge_tags(request).await }
+        }).await?;
+        Ok(response.memories_updated.max(0) as u64)
+    }
+
+    /// Add and remove tags on many memories; removal wins for a tag in both
+    pub async fn retag(&self, ids: &[&str], add: &[&str], remove: &[&str]) -> Result<u64> {
+        let request = RetagMemoriesRequest {
+            namespace: self.namespace.clone(),
+            memory_ids: ids.iter().map(|id| id.to_string()).collect(),
+            add_tags: add.iter().map(|tag| tag.to_string()).collect(),
+            remove_tags: remove.iter().map(|tag| tag.to_string()).collect(),
+        };
+        let response = self.client.call_idempotent(|channel| {
+            let request = request.clone();
+            async move { MemoryServiceClient::new(channel).retag_memories(request).await }
+        }).await?;
+        Ok(response.memories_updated.max(0) as u64)
+    }
+
+    pub async fn trash(&self, max: u32) -> Result<Vec<TrashedMemory>> {
+        let request = ListTrashRequest { namespace: self.namespace.clone(), limit: limit(max) };
+        let response = self.client.call_idempotent(|channel| {
+            let request = request.clone();
+            async move { MemoryServiceClient::new(channel).list_trash(request).await }
+        }).await?;
+        response.memories.into_iter().map(TrashedMemory::try_from).collect()
+    }
+
+    pub async fn restore(&self, id: &str) -> Result<Memory> {
+        let request = RestoreMemoryRequest { memory_id: id.to_string(), namespace: self.namespace.clone() };
+        let response = self.client.call(|channel| {
+            let request = request.clone();
+            async move { MemoryServiceClient::new(channel).restore_memory(request).await }
+        }).await?;
+        Ok(required(response.memory, "memory")?.into())
+    }
+
+    /// Delete a trashed memory for good
+    pub async fn purge(&self, id: &str) -> Result<()> {
+        let request = PurgeMemoryRequest { memory_id: id.to_string(), namespace: self.namespace.clone() };
+        self.client.call(|channel| {
+            let request = request.clone();
+            async move { MemoryServiceClient::new(channel).purge_memory(request).await }
+        }).await?;
+        Ok(())
+    }
+
+    /// Changes to the namespace as they happen. The stream ends when the gateway
+    /// closes it; callers that need every event should watch again.
+    pub async fn watch(&self) -> Result<impl Stream<Item = Result<MemoryEvent>> + Send + 'static> {
+        let request = WatchMemoriesRequest { namespace: self.namespace.clone() };
+        let events = self.client.call_idempotent(|channel| {
+            let request = request.clone();
+            async move { MemoryServiceClient::new(channel).watch_memories(request).await }
+        }).await?;
+        Ok(events.map(|event| event.map_err(ClientError::from).and_then(MemoryEvent::try_from)))
+    }
+
+    // --- CONVERSATIONS ---
+
+    pub async fn create_conversation(
+        &self,
+        title: &str,
+        model: &str,
+        metadata: HashMap<String, String>,
+    ) -> Result<Conversation> {
+        let request = CreateConversationRequest {
+            title: title.to_string(),
+            model: model.to_string(),
+            metadata,
+            namespace: self.namespace.clone(),
+        };
+        let response = self.client.call(|channel| {
+            let request = request.clone();
+            async move { MemoryServiceClient::new(channel).create_conversation(request).await }
+        }).await?;
+        required(response.conversation, "conversation")?.try_into()
+    }
+
+    /// A conversation with its last `turn_limit` turns (0 = all), oldest first
+    pub async fn conversation(&self, id: &str, turn_limit: u32) -> Result<Conversation> {
+        let request = GetConversationRequest {
+            conversation_id: id.to_string(),
+            turn_limit: limit(turn_limit),
+            namespace: self.namespace.clone(),
+        };
+        let response = self.client.call_idempotent(|channel| {
+            let request = request.clone();
+            async move { MemoryServiceClient::new(channel).get_conversation(request).await }
+        }).await?;
+        required(response.conversation, "conversation")?.try_into()
+    }
+
+    /// Conversations without their turns, most recently updated first
+    pub async fn conversations(&self, max: u32) -> Result<Vec<Conversation>> {
+        let request = ListConversationsRequest { limit: limit(max), namespace: self.namespace.clone() };
+        let response = self.client.call_idempotent(|channel| {
+            let request = request.clone();
+            async move { MemoryServiceClient::new(channel).list_conversations(request).await }
+        }).await?;
+        response.conversations.into_iter().map(Conversation::try_from).collect()
+    }
+
+    /// Append turns in order, atomically, and return them as stored
+    pub async fn append_turns(&self, conversation_id: &str, turns: Vec<NewTurn>) -> Result<Vec<Turn>> {
+        let request = AppendConversationTurnsRequest {
+            conversation_id: conversation_id.to_string(),
+            turns: turns.into_iter().map(Into::into).collect(),
+            namespace: self.namespace.clone(),
+        };
+        let response = self.client.call(|channel| {
+            let request = request.clone();
+            async move { MemoryServiceClient::new(channel).append_conversation_turns(request).await }
+        }).await?;
+        response.turns.into_iter().map(Turn::try_from).collect()
+    }
+
+    pub async fn delete_conversation(&self, id: &str) -> Result<()> {
+        let request = DeleteConversationRequest { conversation_id: id.to_string(), namespace: self.namespace.clone() };
+        self.client.call(|channel| {
+            let request = request.clone();
+            async move { MemoryServiceClient::new(channel).delete_conversation(request).await }
+        }).await?;
+        Ok(())
+    }
+
+    // --- GRANTS ---
+
+    /// Give `grantee` access to one memory, or to the whole namespace when `memory_id` is `None`
+    pub async fn grant(&self, memory_id: Option<&str>, grantee: Grantee, permission: Permission) -> Result<Grant> {
+        let request = GrantAccessRequest {
+            namespace: self.namespace.clone(),
+            memory_id: memory_id.unwrap_or_default().to_string(),
+            grantee: Some(grantee.into()),
+            permission: identra_proto::memory::Permission::from(permission).into(),
+        };
+        let response = self.client.call(|channel| {
+            let request = request.clone();
+            async move { MemoryServiceClient::new(channel).grant_access(request).await }
+        }).await?;
+        required(response.grant, "grant")?.try_into()
+    }
+
+    pub async fn revoke(&self, memory_id: Option<&str>, grantee: Grantee) -> Result<()> {
+        let request = RevokeAccessRequest {
+            namespace: self.namespace.clone(),
+            memory_id: memory_id.unwrap_or_default().to_string(),
+            grantee: Some(grantee.into()),
+        };
+        self.client.call(|channel| {
+            let request = request.clone();
+            async move { MemoryServiceClient::new(channel).revoke_access(request).await }
+        }).await?;
+        Ok(())
+    }
+
+    /// Grants on one memory, or on the namespace when `memory_id` is `None`
+    pub async fn grants(&self, memory_id: Option<&str>) -> Result<Vec<Grant>> {
+        let request = ListGrantsRequest {
+            namespace: self.namespace.clone(),
+            memory_id: memory_id.unwrap_or_default().to_string(),
+        };
+        let response = self.client.call_idempotent(|channel| {
+            let request = request.clone();
+            async move { MemoryServiceClient::new(channel).list_grants(request).await }
+        }).await?;
+        response.grants.into_iter().map(Grant::try_from).collect()
+    }
+}
@@ -0,0 +1,69 @@
+use crate::error::Result;
+use crate::types::{required, Namespace, NamespaceSettings};
+use crate::Client;
+use identra_proto::memory::{
+    memory_service_client::MemoryServiceClient, CreateNamespaceRequest, DeleteNamespaceRequest,
+    ListNamespacesRequest, UpdateNamespaceRequest,
+};
+
+/// Creating and managing namespaces; see [`Client::namespace`] for their contents
+#[derive(Clone)]
+pub struct Namespaces {
+    client: Client,
+}
+
+impl Namespaces {
+    pub(crate) fn new(client: Client) -> Self {
+        Self { client }
+    }
+
+    /// Owned namespaces followed by those shared with the caller
+    pub async fn list(&self) -> Result<Vec<Namespace>> {
+        let response = self.client.call_idempotent(|channel| async move {
+            MemoryServiceClient::new(channel).list_namespaces(ListNamespacesRequest {}).await
+        }).await?;
+        response.namespaces.into_iter().map(Namespace::try_from).collect()
+    }
+
+    pub async fn create(&self, name: &str, settings: NamespaceSettings) -> Result<Namespace> {
+        let request = CreateNamespaceRequest { name: name.to_string(), settings: Some(settings.into()) };
+        let response = self.client.call(|channel| {
+            let request = request.clone();
+            async move { MemoryServiceClient::new(channel).create_namespace(request).await }
+        }).await?;
+        required(response.namespace, "namespace")?.try_into()
+    }
+
+    /// Rename a namespace and/or replace its settings; `None` keeps the current value
+    pub async fn update(
+        &self,
+        name: &str,
+        new_name: Option<&str>,
+        settings: Option<NamespaceSettings>,
+    ) -> Result<Namespace> {
+        let request = UpdateNamespaceRequest {
+            namespace: name.to_string(),
+            new_name: new_name.unwrap_or_default().to_string(),
+            update_mask: match settings {
+                Some(_) => ["default_retention_days", "embedding_model", "sharing", "secret_policy"].map(String::from).to_vec(),
+                None => Vec::new(),
+            },
+            settings: settings.map(Into::into),
+        };
+        let response = self.client.call(|channel| {
+            let request = request.clone();
+            async move { MemoryServiceClient::new(channel).update_namespace(request).await }
+        }).await?;
+        required(response.namespace, "namespace")?.try_into()
+    }
+
+    /// `force` is required to delete a namespace that still holds memories or conversations
+    pub async fn delete(&self, name: &str, force: bool) -> Result<()> {
+        let request = DeleteNamespaceRequest { namespace: name.to_string(), force };
+        self.client.call(|channel| {
+            let request = request.clone();
+            async move { MemoryServiceClient::new(channel).delete_namespace(request).await }
+        }).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,92 @@
+use identra_core::error::{Error, ErrorCode};
+use std::time::Duration;
+
+/// Retries of idempotent calls (reads, lookups, health checks) after transient
+/// failures, with exponential backoff and jitter. Calls that change state are never
+/// retried, since the first attempt may have been applied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Retries after the first attempt
+    pub max_retries: u32,
+    pub initial_backoff: Duration,
+    /// Upper bound of the backoff. A server-requested delay longer than this fails
+    /// the call instead of blocking the caller.
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(5),
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn none() -> Self {
+        Self { max_retries: 0, ..Self::default() }
+    }
+
+    /// Delay before retry `attempt` (0-based) of a call that failed with `error`,
+    /// or `None` to give up
+    pub(crate) fn backoff(&self, attempt: u32, error: &Error) -> Option<Duration> {
+        if attempt >= self.max_retries || !is_transient(error.code()) {
+            return None;
+        }
+        let ceiling = self
+            .initial_backoff
+            .saturating_mul(1 << attempt.min(16))
+            .min(self.max_backoff);
+        // Half fixed, half random, so clients failed by the same outage spread out
+        let delay = ceiling / 2 + ceiling.mul_f64(fastrand::f64() / 2.0);
+        match error.retry_after() {
+            Some(after) if after > self.max_backoff => None,
+            Some(after) => Some(after.max(delay)),
+            None => Some(delay),
+        }
+    }
+}
+
+/// Failures that a later attempt of the same call may not hit
+fn is_transient(code: ErrorCode) -> bool {
+    matches!(
+        code,
+        ErrorCode::Unavailable | ErrorCode::AuthUnavailable | ErrorCode::VaultUnavailable | ErrorCode::RateLimited
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backs_off_exponentially_for_transient_errors() {
+        let policy = RetryPolicy::default();
+        let unavailable = Error::new(ErrorCode::Unavailable, "down");
+
+        let first = policy.backoff(0, &unavailable).unwrap();
+        assert!(first >= Duration::from_millis(50) && first <= Duration::from_millis(100));
+        let third = policy.backoff(2, &unavailable).unwrap();
+        assert!(third >= Duration::from_millis(200) && third <= Duration::from_millis(400));
+        assert_eq!(policy.backoff(3, &unavailable), None);
+
+        let capped = RetryPolicy { max_retries: 30, ..policy }.backoff(29, &unavailable).unwrap();
+        assert!(capped <= policy.max_backoff);
+
+        assert_eq!(policy.backoff(0, &Error::new(ErrorCode::NotFound, "gone")), None);
+        assert_eq!(policy.backoff(0, &Error::new(ErrorCode::QuotaExceeded, "full")), None);
+        assert_eq!(RetryPolicy::none().backoff(0, &unavailable), None);
+    }
+
+    #[test]
+    fn honours_server_retry_delay() {
+        let policy = RetryPolicy::default();
+        let limited = Error::new(ErrorCode::RateLimited, "slow down").with_retry_after(Duration::from_secs(2));
+        assert_eq!(policy.backoff(0, &limited), Some(Duration::from_secs(2)));
+
+        let too_long = Error::new(ErrorCode::RateLimited, "slow down").with_retry_after(Duration::from_secs(60));
+        assert_eq!(policy.backoff(0, &too_long), None);
+    }
+}
@@ -0,0 +1,75 @@
+//! Bearer-token session shared by every handle of a [`Client`](crate::Client).
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::{Arc, RwLock};
+
+/// Access tokens expiring within this window are refreshed before the next call
+pub(crate) const REFRESH_MARGIN: chrono::TimeDelta = chrono::TimeDelta::seconds(30);
+
+/// Tokens from a login. Serializable so callers can cache it between runs.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Session {
+    pub access_token: String,
+    /// Empty when the gateway issued none; the session then ends at `expires_at`
+    pub refresh_token: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl Session {
+    pub fn new(access_token: String, refresh_token: String, expires_in_secs: i64) -> Self {
+        Self {
+            access_token,
+            refresh_token,
+            expires_at: Utc::now() + chrono::TimeDelta::seconds(expires_in_secs.max(0)),
+        }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires_at <= Utc::now()
+    }
+
+    pub fn can_refresh(&self) -> bool {
+        !self.refresh_token.is_empty()
+    }
+
+    pub(crate) fn needs_refresh(&self) -> bool {
+        self.expires_at - REFRESH_MARGIN <= Utc::now()
+    }
+}
+
+impl fmt::Debug for Session {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Session")
+            .field("access_token", &"<redacted>")
+            .field("refresh_token", &"<redacted>")
+            .field("expires_at", &self.expires_at)
+            .finish()
+    }
+}
+
+#[derive(Clone, Default)]
+pub(crate) struct SessionStore {
+    current: Arc<RwLock<Option<Session>>>,
+    /// Held while refreshing, so concurrent calls share one refresh
+    pub(crate) refreshing: Arc<tokio::sync::Mutex<()>>,
+}
+
+impl SessionStore {
+    pub(crate) fn get(&self) -> Option<Session> {
+        self.current.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    pub(crate) fn set(&self, session: Option<Session>) {
+        *self.current.write().unwrap_or_else(|e| e.into_inner()) = session;
+    }
+
+    pub(crate) fn access_token(&self) -> Option<String> {
+        self.current
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .as_ref()
+            .map(|session| session.access_token.clone())
+    }
+}
@@ -0,0 +1,120 @@
+use crate::error::{ClientError, Result};
+use crate::session::SessionStore;
+use identra_core::config::ClientTlsConfig as GatewayTlsConfig;
+use identra_core::trace;
+use std::time::Duration;
+use tonic::metadata::MetadataValue;
+use tonic::service::interceptor::InterceptedService;
+use tonic::service::Interceptor;
+use tonic::transport::{Certificate, Channel, ClientTlsConfig, Endpoint, Identity};
+use tower::discover::Change;
+
+/// Pooled channel that sends the session token and trace context with every call
+pub(crate) type AuthedChannel = InterceptedService<Channel, SessionInterceptor>;
+
+/// TLS settings for the gateway connection
+#[derive(Debug, Clone, Default)]
+pub struct GatewayTls {
+    /// PEM CA bundle to verify the gateway with, instead of the system roots
+    pub ca_cert: Option<Vec<u8>>,
+    /// PEM client certificate and key, for gateways that require mTLS
+    pub client_identity: Option<(Vec<u8>, Vec<u8>)>,
+    /// Name to verify the gateway certificate against, when it differs from the address host
+    pub domain: Option<String>,
+}
+
+impl GatewayTls {
+    /// Read the files named in `desktop.tls`.
+    /// Returns `None` when nothing is set, leaving TLS to the address scheme.
+    pub fn from_config(config: &GatewayTlsConfig) -> std::io::Result<Option<Self>> {
+        let read = |path: &Option<std::path::PathBuf>| path.as_ref().map(std::fs::read).transpose();
+        let ca_cert = read(&config.ca_cert)?;
+        let client_identity = match (read(&config.client_cert)?, read(&config.client_key)?) {
+            (Some(cert), Some(key)) => Some((cert, key)),
+            (None, None) => None,
+            _ => return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "desktop.tls.client_cert and desktop.tls.client_key must be set together",
+            )),
+        };
+        let domain = config.domain.clone();
+
+        if ca_cert.is_none() && client_identity.is_none() && domain.is_none() {
+            return Ok(None);
+        }
+        Ok(Some(Self { ca_cert, client_identity, domain }))
+    }
+
+    fn client_config(&self) -> ClientTlsConfig {
+        let mut config = ClientTlsConfig::new();
+        config = match &self.ca_cert {
+            Some(ca) => config.ca_certificate(Certificate::from_pem(ca)),
+            None => config.with_native_roots(),
+        };
+        if let Some((cert, key)) = &self.client_identity {
+            config = config.identity(Identity::from_pem(cert, key));
+        }
+        if let Some(domain) = &self.domain {
+            config = config.domain_name(domain.clone());
+        }
+        config
+    }
+}
+
+/// Endpoint for a gateway address; `https://` addresses use the system roots unless
+/// `tls` overrides them, and `tls` also turns on TLS for `http://` addresses
+pub(crate) fn endpoint(
+    address: &str,
+    tls: Option<&GatewayTls>,
+    connect_timeout: Duration,
+    request_timeout: Option<Duration>,
+) -> Result<Endpoint> {
+    let mut endpoint = Channel::from_shared(address.to_string())
+        .map_err(|e| ClientError::InvalidAddress(format!("{}: {}", address, e)))?
+        .connect_timeout(connect_timeout)
+        .tcp_nodelay(true);
+    if let Some(timeout) = request_timeout {
+        endpoint = endpoint.timeout(timeout);
+    }
+    let tls = match tls {
+        Some(tls) => Some(tls.client_config()),
+        None if address.starts_with("https://") => Some(ClientTlsConfig::new().with_native_roots()),
+        None => None,
+    };
+    match tls {
+        Some(config) => endpoint.tls_config(config).map_err(|e| ClientError::Tls(e.to_string())),
+        None => Ok(endpoint),
+    }
+}
+
+/// `size` lazily connected HTTP/2 connections to one endpoint, with calls sent to the
+/// least loaded. A connection that drops reconnects on its next call.
+pub(crate) fn pool(endpoint: Endpoint, size: usize) -> Channel {
+    let size = size.max(1);
+    let (channel, connections) = Channel::balance_channel::<usize>(size);
+    for key in 0..size {
+        // Cannot fail: the buffer holds exactly `size` changes
+        let _ = connections.try_send(Change::Insert(key, endpoint.clone()));
+    }
+    channel
+}
+
+#[derive(Clone)]
+pub(crate) struct SessionInterceptor {
+    pub(crate) session: SessionStore,
+}
+
+impl Interceptor for SessionInterceptor {
+    fn call(&mut self, mut request: tonic::Request<()>) -> std::result::Result<tonic::Request<()>, tonic::Status> {
+        // Gateway spans join the caller's trace
+        if let Some(value) = trace::traceparent().and_then(|traceparent| MetadataValue::try_from(traceparent).ok()) {
+            request.metadata_mut().insert(trace::TRACEPARENT, value);
+        }
+        if let Some(token) = self.session.access_token() {
+            if let Ok(value) = MetadataValue::try_from(format!("Bearer {}", token)) {
+                request.metadata_mut().insert("authorization", value);
+            }
+        }
+        Ok(request)
+    }
+}
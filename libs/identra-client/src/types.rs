@@ -0,0 +1,1022 @@
+//! Domain types returned by the client, converted from the wire messages.
+
+use crate::error::{ClientError, Result};
+use chrono::{DateTime, Utc};
+use identra_proto::{health, memory as proto};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Range;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Wire timestamp as UTC; unset timestamps become the Unix epoch
+pub(crate) fn timestamp(value: Option<prost_types::Timestamp>) -> DateTime<Utc> {
+    value
+        .and_then(|ts| DateTime::from_timestamp(ts.seconds, ts.nanos.clamp(0, 999_999_999) as u32))
+        .unwrap_or_default()
+}
+
+pub(crate) fn to_timestamp(value: DateTime<Utc>) -> prost_types::Timestamp {
+    prost_types::Timestamp {
+        seconds: value.timestamp(),
+        nanos: value.timestamp_subsec_nanos() as i32,
+    }
+}
+
+/// A field the gateway always sets, missing from its response
+pub(crate) fn required<T>(value: Option<T>, field: &str) -> Result<T> {
+    value.ok_or_else(|| ClientError::InvalidResponse(format!("missing `{}`", field)))
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Memory {
+    pub id: String,
+    pub content: String,
+    pub metadata: HashMap<String, String>,
+    pub tags: Vec<String>,
+    pub namespace: String,
+    /// Empty unless the gateway returned the stored vector
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub embedding: Vec<f32>,
+    /// Leading content on one line, when requested through `MemoryFields::snippet_length`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub snippet: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl From<proto::Memory> for Memory {
+    fn from(memory: proto::Memory) -> Self {
+        Self {
+            id: memory.id,
+            content: memory.content,
+            metadata: memory.metadata,
+            tags: memory.tags,
+            namespace: memory.namespace,
+            embedding: memory.embedding,
+            snippet: (!memory.snippet.is_empty()).then_some(memory.snippet),
+            created_at: timestamp(memory.created_at),
+            updated_at: timestamp(memory.updated_at),
+        }
+    }
+}
+
+/// Content and labels of a memory to store
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct NewMemory {
+    pub content: String,
+    #[serde(default)]
+    pub metadata: HashMap<String, String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Deduplicates stores across restarts, e.g. a key derived from the source document;
+    /// `None` uses a fresh key per `Memories::store` call
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idempotency_key: Option<String>,
+}
+
+impl NewMemory {
+    pub fn new(content: impl Into<String>) -> Self {
+        Self { content: content.into(), ..Default::default() }
+    }
+
+    pub fn tag(mut self, tag: impl Into<String>) -> Self {
+        self.tags.push(tag.into());
+        self
+    }
+
+    pub fn metadata(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.metadata.insert(key.into(), value.into());
+        self
+    }
+
+    /// Up to 128 printable ASCII characters without spaces
+    pub fn idempotency_key(mut self, key: impl Into<String>) -> Self {
+        self.idempotency_key = Some(key.into());
+        self
+    }
+}
+
+/// A memory found by vector search, with its final score and the parts it was computed from
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScoredMemory {
+    pub memory: Memory,
+    /// What results are ordered by; the cosine similarity unless re-ranked
+    pub score: f32,
+    /// Cosine similarity to the query
+    pub similarity: f32,
+    /// Time decay between 0 and 1; 1 without recency weighting
+    pub recency: f32,
+    /// Similarity blended with recency
+    pub relevance: f32,
+    /// Highest similarity to a better ranked result; 0 without diversification
+    pub redundancy: f32,
+    /// Cross-encoder relevance between 0 and 1, when re-ranked with one
+    pub rerank_score: Option<f32>,
+}
+
+impl TryFrom<proto::MemoryMatch> for ScoredMemory {
+    type Error = ClientError;
+
+    fn try_from(found: proto::MemoryMatch) -> Result<Self> {
+        Ok(Self {
+            memory: required(found.memory, "memory")?.into(),
+            score: found.score,
+            similarity: found.similarity_score,
+            recency: found.recency,
+            relevance: found.relevance,
+            redundancy: found.redundancy,
+            rerank_score: (found.rerank_score > 0.0).then_some(found.rerank_score),
+        })
+    }
+}
+
+/// Which time recency weighting decays from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RecencyField {
+    #[default]
+    CreatedAt,
+    /// When the memory was last returned by a read; its creation if never read
+    LastAccessedAt,
+}
+
+/// Favour recent memories: a memory's score halves its recency share every `half_life_days`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Recency {
+    /// 0 uses the gateway default of 30 days
+    pub half_life_days: f32,
+    /// Share of the score given to recency, 0-1; 0 uses the gateway default of 0.3
+    pub weight: f32,
+    pub field: RecencyField,
+}
+
+/// Re-score the nearest matches against the query text with a cross-encoder: more precise
+/// than embedding similarity, at the cost of a model pass per match
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CrossEncoder {
+    /// The text the search embedding was computed from
+    pub query: String,
+    /// `bge-reranker-base`, `bge-reranker-v2-m3`, `jina-reranker-v1-turbo-en` or
+    /// `jina-reranker-v2-base-multilingual`; `None` uses `bge-reranker-base`
+    pub model: Option<String>,
+    /// Matches re-scored, at most 100; only these are returned. 0 uses the result count but at least 20
+    pub top_k: u32,
+}
+
+impl CrossEncoder {
+    pub fn new(query: impl Into<String>) -> Self {
+        Self { query: query.into(), ..Default::default() }
+    }
+}
+
+/// How `Memories::search_ranked` re-ranks the nearest matches; the default ranks by similarity alone
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SearchRanking {
+    /// Diversify with maximal marginal relevance: the relevance weight between 0 and 1,
+    /// where lower values penalize near-duplicates harder; `Some(0.0)` uses the gateway default of 0.5
+    pub mmr_lambda: Option<f32>,
+    pub recency: Option<Recency>,
+    /// Nearest matches to re-rank; 0 lets the gateway fetch four times the result count
+    pub candidates: u32,
+    /// Off by default: it adds a model pass per re-scored match to the search
+    pub rerank: Option<CrossEncoder>,
+}
+
+impl From<SearchRanking> for proto::SearchRanking {
+    fn from(ranking: SearchRanking) -> Self {
+        Self {
+            mmr: ranking.mmr_lambda.map(|lambda| proto::MmrRanking { lambda }),
+            recency: ranking.recency.map(|recency| proto::RecencyRanking {
+                half_life_days: recency.half_life_days,
+                weight: recency.weight,
+                field: match recency.field {
+                    RecencyField::CreatedAt => proto::RecencyField::CreatedAt,
+                    RecencyField::LastAccessedAt => proto::RecencyField::LastAccessedAt,
+                }
+                .into(),
+            }),
+            candidates: ranking.candidates.min(i32::MAX as u32) as i32,
+            rerank: ranking.rerank.map(|rerank| proto::CrossEncoderRanking {
+                query: rerank.query,
+                model: rerank.model.unwrap_or_default(),
+                top_k: rerank.top_k.min(i32::MAX as u32) as i32,
+            }),
+        }
+    }
+}
+
+/// A window of time: `start` inclusive, `end` exclusive, either side open when `None`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TimeRange {
+    pub start: Option<DateTime<Utc>>,
+    pub end: Option<DateTime<Utc>>,
+}
+
+impl TimeRange {
+    /// From `start` onwards
+    pub fn since(start: DateTime<Utc>) -> Self {
+        Self { start: Some(start), end: None }
+    }
+
+    /// The last `duration` up to now
+    pub fn last(duration: Duration) -> Self {
+        let start = chrono::Duration::from_std(duration)
+            .ok()
+            .and_then(|d| Utc::now().checked_sub_signed(d))
+            .unwrap_or_default();
+        Self::since(start)
+    }
+}
+
+impl From<TimeRange> for proto::TimeRange {
+    fn from(range: TimeRange) -> Self {
+        Self { start: range.start.map(to_timestamp), end: range.end.map(to_timestamp) }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortField {
+    #[default]
+    CreatedAt,
+    UpdatedAt,
+    /// Ranking score; only searches accept it
+    Relevance,
+}
+
+/// Result order; ties are broken by memory id so repeated listings come back the same
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Sort {
+    pub field: SortField,
+    pub ascending: bool,
+}
+
+impl Sort {
+    pub fn ascending(field: SortField) -> Self {
+        Self { field, ascending: true }
+    }
+
+    pub fn descending(field: SortField) -> Self {
+        Self { field, ascending: false }
+    }
+}
+
+impl From<Sort> for proto::SortOrder {
+    fn from(sort: Sort) -> Self {
+        let field = match sort.field {
+            SortField::CreatedAt => proto::SortField::CreatedAt,
+            SortField::UpdatedAt => proto::SortField::UpdatedAt,
+            SortField::Relevance => proto::SortField::Relevance,
+        };
+        let direction = if sort.ascending { proto::SortDirection::Ascending } else { proto::SortDirection::Descending };
+        Self { field: field.into(), direction: direction.into() }
+    }
+}
+
+/// Narrows and orders `Memories::query`, `recent` and `search_ranked`; see `Memories::filter`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MemoryFilter {
+    pub created: Option<TimeRange>,
+    pub updated: Option<TimeRange>,
+    /// `None` keeps each call's own order: newest first for listings, best score first for searches
+    pub sort: Option<Sort>,
+}
+
+/// Which parts of each memory `Memories::get`, `query`, `recent` and `search_ranked` return;
+/// see `Memories::fields`. The default is the full content without the embedding.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MemoryFields {
+    pub embedding: bool,
+    /// Leave `content` empty, e.g. for listing views that only show a snippet
+    pub omit_content: bool,
+    /// Fill `snippet` with up to this many characters, at most 1000; 0 for none
+    pub snippet_length: u32,
+}
+
+impl MemoryFields {
+    /// A snippet in place of the content
+    pub fn snippet(length: u32) -> Self {
+        Self { omit_content: true, snippet_length: length, ..Default::default() }
+    }
+}
+
+impl From<MemoryFields> for proto::MemoryFields {
+    fn from(fields: MemoryFields) -> Self {
+        Self {
+            include_embedding: fields.embedding,
+            omit_content: fields.omit_content,
+            snippet_length: fields.snippet_length.min(i32::MAX as u32) as i32,
+        }
+    }
+}
+
+/// Memories sharing a tag, metadata type or creation month
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StatsBucket {
+    pub key: String,
+    pub count: u64,
+    /// Content size in bytes
+    pub bytes: u64,
+}
+
+impl From<proto::StatsBucket> for StatsBucket {
+    fn from(bucket: proto::StatsBucket) -> Self {
+        Self { key: bucket.key, count: bucket.count.max(0) as u64, bytes: bucket.bytes.max(0) as u64 }
+    }
+}
+
+/// Counts and sizes of the memories in a namespace
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MemoryStats {
+    /// Live memories, excluding the trash
+    pub total_count: u64,
+    pub total_bytes: u64,
+    /// Memories with a stored embedding
+    pub embedded_count: u64,
+    /// Share of memories with an embedding, 1 for an empty namespace
+    pub embedding_coverage: f32,
+    pub untagged_count: u64,
+    pub trashed_count: u64,
+    pub oldest: Option<DateTime<Utc>>,
+    pub newest: Option<DateTime<Utc>>,
+    /// Most used first
+    pub by_tag: Vec<StatsBucket>,
+    /// By metadata `type`, most used first; `""` for memories without one
+    pub by_type: Vec<StatsBucket>,
+    /// By creation month as `YYYY-MM` (UTC), oldest first
+    pub by_month: Vec<StatsBucket>,
+}
+
+impl From<proto::MemoryStats> for MemoryStats {
+    fn from(stats: proto::MemoryStats) -> Self {
+        Self {
+            total_count: stats.total_count.max(0) as u64,
+            total_bytes: stats.total_bytes.max(0) as u64,
+            embedded_count: stats.embedded_count.max(0) as u64,
+            embedding_coverage: stats.embedding_coverage,
+            untagged_count: stats.untagged_count.max(0) as u64,
+            trashed_count: stats.trashed_count.max(0) as u64,
+            oldest: stats.oldest.map(|ts| timestamp(Some(ts))),
+            newest: stats.newest.map(|ts| timestamp(Some(ts))),
+            by_tag: stats.by_tag.into_iter().map(StatsBucket::from).collect(),
+            by_type: stats.by_type.into_iter().map(StatsBucket::from).collect(),
+            by_month: stats.by_month.into_iter().map(StatsBucket::from).collect(),
+        }
+    }
+}
+
+/// A tag and how many live memories carry it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TagCount {
+    pub tag: String,
+    pub count: u64,
+}
+
+impl From<proto::TagCount> for TagCount {
+    fn from(tag: proto::TagCount) -> Self {
+        Self { tag: tag.tag, count: tag.count.max(0) as u64 }
+    }
+}
+
+/// A deleted memory, restorable until `purge_at`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TrashedMemory {
+    pub memory: Memory,
+    pub deleted_at: DateTime<Utc>,
+    pub purge_at: DateTime<Utc>,
+}
+
+impl TryFrom<proto::TrashedMemory> for TrashedMemory {
+    type Error = ClientError;
+
+    fn try_from(trashed: proto::TrashedMemory) -> Result<Self> {
+        Ok(Self {
+            memory: required(trashed.memory, "memory")?.into(),
+            deleted_at: timestamp(trashed.deleted_at),
+            purge_at: timestamp(trashed.purge_at),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MemoryEvent {
+    Created { memory: Memory, occurred_at: DateTime<Utc> },
+    /// Moved to the trash
+    Deleted { memory_id: String, occurred_at: DateTime<Utc> },
+    /// Back from the trash
+    Restored { memory: Memory, occurred_at: DateTime<Utc> },
+}
+
+impl TryFrom<proto::MemoryEvent> for MemoryEvent {
+    type Error = ClientError;
+
+    fn try_from(event: proto::MemoryEvent) -> Result<Self> {
+        let occurred_at = timestamp(event.occurred_at);
+        match proto::MemoryEventType::try_from(event.r#type) {
+            Ok(proto::MemoryEventType::Created) => Ok(Self::Created {
+                memory: required(event.memory, "memory")?.into(),
+                occurred_at,
+            }),
+            Ok(proto::MemoryEventType::Deleted) => Ok(Self::Deleted { memory_id: event.memory_id, occurred_at }),
+            Ok(proto::MemoryEventType::Restored) => Ok(Self::Restored {
+                memory: required(event.memory, "memory")?.into(),
+                occurred_at,
+            }),
+            _ => Err(ClientError::InvalidResponse(format!("unknown memory event type {}", event.r#type))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ContextOrder {
+    /// Most relevant memory first
+    #[default]
+    Relevance,
+    /// Oldest memory first, reads like a history
+    Chronological,
+}
+
+/// How `Memories::context` picks and lays out memories; the defaults suit most prompts
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ContextPolicy {
+    /// Memories retrieved before de-duplication; 0 uses the gateway default of 50
+    pub max_candidates: u32,
+    /// Skip memories less similar to the query
+    pub min_similarity: f32,
+    /// Skip memories this similar to one already picked; 0 uses the gateway default of 0.95
+    pub duplicate_similarity: f32,
+    pub order: ContextOrder,
+    /// `cl100k_base` or `o200k_base`; `None` uses `cl100k_base`
+    pub tokenizer: Option<String>,
+    /// Skip memories that do not fit instead of cutting the last one short
+    pub whole_memories_only: bool,
+}
+
+impl From<ContextPolicy> for proto::ContextPolicy {
+    fn from(policy: ContextPolicy) -> Self {
+        let order = match policy.order {
+            ContextOrder::Relevance => proto::ContextOrder::Relevance,
+            ContextOrder::Chronological => proto::ContextOrder::Chronological,
+        };
+        Self {
+            max_candidates: policy.max_candidates.min(i32::MAX as u32) as i32,
+            min_similarity: policy.min_similarity,
+            duplicate_similarity: policy.duplicate_similarity,
+            order: order.into(),
+            tokenizer: policy.tokenizer.unwrap_or_default(),
+            whole_memories_only: policy.whole_memories_only,
+        }
+    }
+}
+
+/// Where a memory appears in an assembled context
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Citation {
+    /// The `[n]` marker in front of the memory
+    pub index: u32,
+    pub memory_id: String,
+    /// Byte range of the memory's text within `Context::text`
+    pub span: Range<usize>,
+    pub score: f32,
+    /// Only the start of the memory fit in the budget
+    pub truncated: bool,
+}
+
+/// Prompt context assembled by the gateway from the memories most relevant to a query
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Context {
+    pub text: String,
+    /// In the order the memories appear in `text`
+    pub citations: Vec<Citation>,
+    pub token_count: u32,
+    /// Memories retrieved for the query
+    pub candidates: u32,
+    /// Candidates skipped as duplicates of a picked memory
+    pub duplicates: u32,
+    /// Candidates that did not fit in the budget
+    pub omitted: u32,
+}
+
+impl Context {
+    /// The cited text of `citation`
+    pub fn cited(&self, citation: &Citation) -> &str {
+        self.text.get(citation.span.clone()).unwrap_or_default()
+    }
+}
+
+impl TryFrom<proto::BuildContextResponse> for Context {
+    type Error = ClientError;
+
+    fn try_from(response: proto::BuildContextResponse) -> Result<Self> {
+        let citations = response.citations
+            .into_iter()
+            .map(|citation| {
+                let span = citation.start.max(0) as usize..citation.end.max(0) as usize;
+                if span.start > span.end || !response.context.is_char_boundary(span.start) || !response.context.is_char_boundary(span.end) {
+                    return Err(ClientError::InvalidResponse(format!("citation span {:?} outside the context", span)));
+                }
+                Ok(Citation {
+                    index: citation.index.max(0) as u32,
+                    memory_id: citation.memory_id,
+                    span,
+                    score: citation.similarity_score,
+                    truncated: citation.truncated,
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            text: response.context,
+            citations,
+            token_count: response.token_count.max(0) as u32,
+            candidates: response.candidates.max(0) as u32,
+            duplicates: response.duplicates.max(0) as u32,
+            omitted: response.omitted.max(0) as u32,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    User,
+    Assistant,
+    System,
+}
+
+impl Role {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::User => "user",
+            Self::Assistant => "assistant",
+            Self::System => "system",
+        }
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Role {
+    type Err = ClientError;
+
+    fn from_str(role: &str) -> Result<Self> {
+        match role {
+            "user" => Ok(Self::User),
+            "assistant" => Ok(Self::Assistant),
+            "system" => Ok(Self::System),
+            other => Err(ClientError::InvalidResponse(format!("unknown turn role `{}`", other))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Turn {
+    pub id: String,
+    /// 1-based position within the conversation
+    pub sequence: u32,
+    pub role: Role,
+    pub content: String,
+    pub model: String,
+    pub metadata: HashMap<String, String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl TryFrom<proto::ConversationTurn> for Turn {
+    type Error = ClientError;
+
+    fn try_from(turn: proto::ConversationTurn) -> Result<Self> {
+        Ok(Self {
+            id: turn.id,
+            sequence: turn.sequence.max(0) as u32,
+            role: turn.role.parse()?,
+            content: turn.content,
+            model: turn.model,
+            metadata: turn.metadata,
+            created_at: timestamp(turn.created_at),
+        })
+    }
+}
+
+/// A turn to append to a conversation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NewTurn {
+    pub role: Role,
+    pub content: String,
+    #[serde(default)]
+    pub model: String,
+    #[serde(default)]
+    pub metadata: HashMap<String, String>,
+}
+
+impl NewTurn {
+    pub fn user(content: impl Into<String>) -> Self {
+        Self { role: Role::User, content: content.into(), model: String::new(), metadata: HashMap::new() }
+    }
+
+    /// A model reply, labelled with the model that wrote it
+    pub fn assistant(content: impl Into<String>, model: impl Into<String>) -> Self {
+        Self { role: Role::Assistant, content: content.into(), model: model.into(), metadata: HashMap::new() }
+    }
+
+    pub fn system(content: impl Into<String>) -> Self {
+        Self { role: Role::System, content: content.into(), model: String::new(), metadata: HashMap::new() }
+    }
+
+    pub fn metadata(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.metadata.insert(key.into(), value.into());
+        self
+    }
+}
+
+impl From<NewTurn> for proto::NewConversationTurn {
+    fn from(turn: NewTurn) -> Self {
+        Self {
+            role: turn.role.as_str().to_string(),
+            content: turn.content,
+            model: turn.model,
+            metadata: turn.metadata,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Conversation {
+    pub id: String,
+    pub title: String,
+    pub model: String,
+    pub metadata: HashMap<String, String>,
+    pub namespace: String,
+    pub turn_count: u32,
+    /// Oldest first; only filled in by [`Memories::conversation`](crate::Memories::conversation)
+    pub turns: Vec<Turn>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl TryFrom<proto::Conversation> for Conversation {
+    type Error = ClientError;
+
+    fn try_from(conversation: proto::Conversation) -> Result<Self> {
+        Ok(Self {
+            id: conversation.id,
+            title: conversation.title,
+            model: conversation.model,
+            metadata: conversation.metadata,
+            namespace: conversation.namespace,
+            turn_count: conversation.turn_count.max(0) as u32,
+            turns: conversation.turns.into_iter().map(Turn::try_from).collect::<Result<_>>()?,
+            created_at: timestamp(conversation.created_at),
+            updated_at: timestamp(conversation.updated_at),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Permission {
+    /// Get, query, search and watch
+    Read,
+    /// Read plus store and delete
+    Write,
+    /// Write plus managing grants
+    Admin,
+}
+
+impl TryFrom<i32> for Permission {
+    type Error = ClientError;
+
+    fn try_from(value: i32) -> Result<Self> {
+        match proto::Permission::try_from(value) {
+            Ok(proto::Permission::Read) => Ok(Self::Read),
+            Ok(proto::Permission::Write) => Ok(Self::Write),
+            Ok(proto::Permission::Admin) => Ok(Self::Admin),
+            _ => Err(ClientError::InvalidResponse(format!("unknown permission {}", value))),
+        }
+    }
+}
+
+impl From<Permission> for proto::Permission {
+    fn from(permission: Permission) -> Self {
+        match permission {
+            Permission::Read => Self::Read,
+            Permission::Write => Self::Write,
+            Permission::Admin => Self::Admin,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Sharing {
+    /// Never visible to other users
+    #[default]
+    Private,
+    /// The owner may grant access to other users
+    Shareable,
+}
+
+/// What the gateway does with plaintext content that looks like it holds a credential.
+/// Findings are listed in the memory's `secrets` metadata entry.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SecretPolicy {
+    /// Replace each finding with `[REDACTED:<kind>]` before storing it
+    #[default]
+    Redact,
+    /// Store the content as sent and only record the findings
+    Flag,
+    /// Refuse the memory with `ErrorCode::SecretDetected`
+    Reject,
+    /// Don't scan
+    Off,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NamespaceSettings {
+    /// Memories expire after this long; `None` keeps them forever
+    pub retention_days: Option<u32>,
+    /// `None` uses the gateway default embedding model
+    pub embedding_model: Option<String>,
+    pub sharing: Sharing,
+    #[serde(default)]
+    pub secret_policy: SecretPolicy,
+}
+
+impl From<proto::NamespaceSettings> for NamespaceSettings {
+    fn from(settings: proto::NamespaceSettings) -> Self {
+        Self {
+            retention_days: (settings.default_retention_days > 0).then_some(settings.default_retention_days as u32),
+            embedding_model: (!settings.embedding_model.is_empty()).then_some(settings.embedding_model),
+            sharing: match proto::NamespaceSharing::try_from(settings.sharing) {
+                Ok(proto::NamespaceSharing::Shareable) => Sharing::Shareable,
+                _ => Sharing::Private,
+            },
+            secret_policy: match proto::SecretPolicy::try_from(settings.secret_policy) {
+                Ok(proto::SecretPolicy::Flag) => SecretPolicy::Flag,
+                Ok(proto::SecretPolicy::Reject) => SecretPolicy::Reject,
+                Ok(proto::SecretPolicy::Off) => SecretPolicy::Off,
+                _ => SecretPolicy::Redact,
+            },
+        }
+    }
+}
+
+impl From<NamespaceSettings> for proto::NamespaceSettings {
+    fn from(settings: NamespaceSettings) -> Self {
+        let sharing = match settings.sharing {
+            Sharing::Private => proto::NamespaceSharing::Private,
+            Sharing::Shareable => proto::NamespaceSharing::Shareable,
+        };
+        let secret_policy = match settings.secret_policy {
+            SecretPolicy::Redact => proto::SecretPolicy::Redact,
+            SecretPolicy::Flag => proto::SecretPolicy::Flag,
+            SecretPolicy::Reject => proto::SecretPolicy::Reject,
+            SecretPolicy::Off => proto::SecretPolicy::Off,
+        };
+        Self {
+            default_retention_days: settings.retention_days.map_or(0, |days| days.min(i32::MAX as u32) as i32),
+            embedding_model: settings.embedding_model.unwrap_or_default(),
+            sharing: sharing.into(),
+            secret_policy: secret_policy.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Namespace {
+    pub id: String,
+    pub name: String,
+    pub settings: NamespaceSettings,
+    pub memory_count: u64,
+    pub owner_id: String,
+    /// The caller's access; `Admin` for namespaces they own
+    pub permission: Permission,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl TryFrom<proto::Namespace> for Namespace {
+    type Error = ClientError;
+
+    fn try_from(namespace: proto::Namespace) -> Result<Self> {
+        Ok(Self {
+            id: namespace.id,
+            name: namespace.name,
+            settings: namespace.settings.unwrap_or_default().into(),
+            memory_count: namespace.memory_count.max(0) as u64,
+            owner_id: namespace.owner_id,
+            permission: Permission::try_from(namespace.permission)?,
+            created_at: timestamp(namespace.created_at),
+            updated_at: timestamp(namespace.updated_at),
+        })
+    }
+}
+
+/// Who a grant gives access to
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(tag = "type", content = "id", rename_all = "lowercase")]
+pub enum Grantee {
+    /// A user id
+    User(String),
+    /// An entry of the users' `app_metadata.groups`
+    Group(String),
+}
+
+impl TryFrom<proto::Grantee> for Grantee {
+    type Error = ClientError;
+
+    fn try_from(grantee: proto::Grantee) -> Result<Self> {
+        match proto::PrincipalType::try_from(grantee.r#type) {
+            Ok(proto::PrincipalType::User) => Ok(Self::User(grantee.id)),
+            Ok(proto::PrincipalType::Group) => Ok(Self::Group(grantee.id)),
+            _ => Err(ClientError::InvalidResponse(format!("unknown grantee type {}", grantee.r#type))),
+        }
+    }
+}
+
+impl From<Grantee> for proto::Grantee {
+    fn from(grantee: Grantee) -> Self {
+        let (principal, id) = match grantee {
+            Grantee::User(id) => (proto::PrincipalType::User, id),
+            Grantee::Group(id) => (proto::PrincipalType::Group, id),
+        };
+        Self { r#type: principal.into(), id }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Grant {
+    pub id: String,
+    pub namespace_id: String,
+    /// `None` for a grant on the whole namespace
+    pub memory_id: Option<String>,
+    pub grantee: Grantee,
+    pub permission: Permission,
+    pub granted_by: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl TryFrom<proto::Grant> for Grant {
+    type Error = ClientError;
+
+    fn try_from(grant: proto::Grant) -> Result<Self> {
+        Ok(Self {
+            id: grant.id,
+            namespace_id: grant.namespace_id,
+            memory_id: (!grant.memory_id.is_empty()).then_some(grant.memory_id),
+            grantee: required(grant.grantee, "grantee")?.try_into()?,
+            permission: Permission::try_from(grant.permission)?,
+            granted_by: grant.granted_by,
+            created_at: timestamp(grant.created_at),
+        })
+    }
+}
+
+/// The user behind a valid access token
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Identity {
+    pub user_id: String,
+    pub username: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// A key held by the gateway's VaultService
+#[derive(Clone, PartialEq, Eq)]
+pub struct StoredKey {
+    pub data: Vec<u8>,
+    pub metadata: HashMap<String, String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl fmt::Debug for StoredKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StoredKey")
+            .field("data", &format_args!("<{} bytes>", self.data.len()))
+            .field("metadata", &self.metadata)
+            .field("created_at", &self.created_at)
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ServingStatus {
+    Unknown,
+    Serving,
+    NotServing,
+    /// The gateway has no such service
+    ServiceUnknown,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HealthReport {
+    pub status: ServingStatus,
+    /// Which dependency is failing, when not serving
+    pub message: String,
+    pub uptime: Duration,
+}
+
+impl HealthReport {
+    pub fn is_serving(&self) -> bool {
+        self.status == ServingStatus::Serving
+    }
+}
+
+impl From<health::HealthCheckResponse> for HealthReport {
+    fn from(response: health::HealthCheckResponse) -> Self {
+        use health::health_check_response::ServingStatus as Wire;
+        let status = match Wire::try_from(response.status) {
+            Ok(Wire::Serving) => ServingStatus::Serving,
+            Ok(Wire::NotServing) => ServingStatus::NotServing,
+            Ok(Wire::ServiceUnknown) => ServingStatus::ServiceUnknown,
+            _ => ServingStatus::Unknown,
+        };
+        Self {
+            status,
+            message: response.message,
+            uptime: Duration::from_secs(response.uptime_seconds.max(0) as u64),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_wire_messages() {
+        let memory = Memory::from(proto::Memory {
+            id: "m1".to_string(),
+            content: "hello".to_string(),
+            created_at: Some(prost_types::Timestamp { seconds: 1_700_000_000, nanos: 5 }),
+            ..Default::default()
+        });
+        assert_eq!(memory.created_at.timestamp(), 1_700_000_000);
+        assert_eq!(memory.updated_at, DateTime::<Utc>::UNIX_EPOCH);
+
+        let turn = Turn::try_from(proto::ConversationTurn {
+            role: "assistant".to_string(),
+            sequence: 2,
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!((turn.role, turn.sequence), (Role::Assistant, 2));
+        assert!(Turn::try_from(proto::ConversationTurn { role: "robot".to_string(), ..Default::default() }).is_err());
+
+        let grant = Grant::try_from(proto::Grant {
+            grantee: Some(Grantee::Group("team".to_string()).into()),
+            permission: proto::Permission::Write.into(),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(grant.grantee, Grantee::Group("team".to_string()));
+        assert_eq!(grant.permission, Permission::Write);
+        assert_eq!(grant.memory_id, None);
+
+        let settings = NamespaceSettings::from(proto::NamespaceSettings::from(NamespaceSettings {
+            retention_days: Some(30),
+            embedding_model: None,
+            sharing: Sharing::Shareable,
+            secret_policy: SecretPolicy::Reject,
+        }));
+        assert_eq!(settings.retention_days, Some(30));
+        assert_eq!(settings.embedding_model, None);
+        assert_eq!(settings.sharing, Sharing::Shareable);
+        assert_eq!(settings.secret_policy, SecretPolicy::Reject);
+
+        let context = Context::try_from(proto::BuildContextResponse {
+            context: "[1] café".to_string(),
+            citations: vec![proto::ContextCitation { index: 1, memory_id: "m1".to_string(), start: 4, end: 9, ..Default::default() }],
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(context.cited(&context.citations[0]), "café");
+        assert!(Context::try_from(proto::BuildContextResponse {
+            context: "[1] café".to_string(),
+            citations: vec![proto::ContextCitation { start: 4, end: 8, ..Default::default() }],
+            ..Default::default()
+        })
+        .is_err());
+
+        let sort = proto::SortOrder::from(Sort::ascending(SortField::UpdatedAt));
+        assert_eq!(sort.field(), proto::SortField::UpdatedAt);
+        assert_eq!(sort.direction(), proto::SortDirection::Ascending);
+        let range = proto::TimeRange::from(TimeRange::since(DateTime::<Utc>::UNIX_EPOCH));
+        assert_eq!((range.start.map(|ts| ts.seconds), range.end), (Some(0), None));
+    }
+}
@@ -0,0 +1,92 @@
+use crate::error::Result;
+use crate::types::{timestamp, to_timestamp, StoredKey};
+use crate::Client;
+use chrono::{DateTime, Utc};
+use identra_proto::vault::{
+    vault_service_client::VaultServiceClient, DeleteKeyRequest, KeyExistsRequest, ListKeysRequest,
+    RetrieveKeyRequest, StoreKeyRequest,
+};
+use std::collections::HashMap;
+
+const LIST_PAGE_SIZE: i32 = 100;
+
+/// Keys held by the gateway's VaultService
+#[derive(Clone)]
+pub struct Vault {
+    client: Client,
+}
+
+impl Vault {
+    pub(crate) fn new(client: Client) -> Self {
+        Self { client }
+    }
+
+    pub async fn put(
+        &self,
+        key_id: &str,
+        data: Vec<u8>,
+        metadata: HashMap<String, String>,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> Result<()> {
+        let request = StoreKeyRequest {
+            key_id: key_id.to_string(),
+            key_data: data,
+            metadata,
+            expires_at: expires_at.map(to_timestamp),
+        };
+        self.client.call(|channel| {
+            let request = request.clone();
+            async move { VaultServiceClient::new(channel).store_key(request).await }
+        }).await?;
+        Ok(())
+    }
+
+    pub async fn get(&self, key_id: &str) -> Result<StoredKey> {
+        let request = RetrieveKeyRequest { key_id: key_id.to_string() };
+        let response = self.client.call_idempotent(|channel| {
+            let request = request.clone();
+            async move { VaultServiceClient::new(channel).retrieve_key(request).await }
+        }).await?;
+        Ok(StoredKey {
+            data: response.key_data,
+            metadata: response.metadata,
+            created_at: timestamp(response.created_at),
+        })
+    }
+
+    pub async fn delete(&self, key_id: &str) -> Result<()> {
+        let request = DeleteKeyRequest { key_id: key_id.to_string() };
+        self.client.call(|channel| {
+            let request = request.clone();
+            async move { VaultServiceClient::new(channel).delete_key(request).await }
+        }).await?;
+        Ok(())
+    }
+
+    pub async fn exists(&self, key_id: &str) -> Result<bool> {
+        let request = KeyExistsRequest { key_id: key_id.to_string() };
+        let response = self.client.call_idempotent(|channel| {
+            let request = request.clone();
+            async move { VaultServiceClient::new(channel).key_exists(request).await }
+        }).await?;
+        Ok(response.exists)
+    }
+
+    /// Ids of every key, following the pages
+    pub async fn list(&self) -> Result<Vec<String>> {
+        let mut key_ids = Vec::new();
+        let mut page_token = String::new();
+        loop {
+            let request = ListKeysRequest { page_size: LIST_PAGE_SIZE, page_token };
+            let page = self.client.call_idempotent(|channel| {
+                let request = request.clone();
+                async move { VaultServiceClient::new(channel).list_keys(request).await }
+            }).await?;
+            key_ids.extend(page.key_ids);
+            if page.next_page_token.is_empty() {
+                return Ok(key_ids);
+            }
+            page_token = page.next_page_token;
+        }
+    }
+}
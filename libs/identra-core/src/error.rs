@@ -0,0 +1,450 @@
+//! Error model shared by every Identra service.
+//!
+//! Each error carries a stable [`ErrorCode`] that clients match on instead of the
+//! message text. On the wire it becomes a gRPC status with `google.rpc` details: an
+//! `ErrorInfo` whose `reason` is the code, plus `BadRequest` field violations and
+//! `RetryInfo` where they apply. Internal details (SQL errors, IO failures) are logged
+//! when the error is converted and never sent to the client.
+
+use prost::Message;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::Duration;
+use tonic::{Code, Status};
+
+/// `ErrorInfo.domain` of every Identra error
+pub const DOMAIN: &str = "identra";
+
+/// Metadata key carrying the retry delay in whole seconds, for REST clients and proxies
+pub const RETRY_AFTER: &str = "retry-after";
+
+const ERROR_INFO_TYPE: &str = "type.googleapis.com/google.rpc.ErrorInfo";
+const BAD_REQUEST_TYPE: &str = "type.googleapis.com/google.rpc.BadRequest";
+const RETRY_INFO_TYPE: &str = "type.googleapis.com/google.rpc.RetryInfo";
+
+/// Stable machine-readable error codes. The string form is part of the API: codes
+/// may be added, but never renamed or given a different gRPC code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorCode {
+    InvalidArgument,
+    Unauthenticated,
+    InvalidCredentials,
+    InvalidToken,
+    PermissionDenied,
+    NotFound,
+    NamespaceNotFound,
+    MemoryNotFound,
+    ConversationNotFound,
+    KeyNotFound,
+    AlreadyExists,
+    NamespaceExists,
+    FailedPrecondition,
+    UnsupportedEmbeddingModel,
+    RateLimited,
+    QuotaExceeded,
+    SecretDetected,
+    Unimplemented,
+    Unavailable,
+    AuthUnavailable,
+    VaultUnavailable,
+    Internal,
+}
+
+impl ErrorCode {
+    const ALL: [ErrorCode; 22] = [
+        Self::InvalidArgument,
+        Self::Unauthenticated,
+        Self::InvalidCredentials,
+        Self::InvalidToken,
+        Self::PermissionDenied,
+        Self::NotFound,
+        Self::NamespaceNotFound,
+        Self::MemoryNotFound,
+        Self::ConversationNotFound,
+        Self::KeyNotFound,
+        Self::AlreadyExists,
+        Self::NamespaceExists,
+        Self::FailedPrecondition,
+        Self::UnsupportedEmbeddingModel,
+        Self::RateLimited,
+        Self::QuotaExceeded,
+        Self::SecretDetected,
+        Self::Unimplemented,
+        Self::Unavailable,
+        Self::AuthUnavailable,
+        Self::VaultUnavailable,
+        Self::Internal,
+    ];
+
+    /// The code as sent in `ErrorInfo.reason`
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::InvalidArgument => "INVALID_ARGUMENT",
+            Self::Unauthenticated => "UNAUTHENTICATED",
+            Self::InvalidCredentials => "INVALID_CREDENTIALS",
+            Self::InvalidToken => "INVALID_TOKEN",
+            Self::PermissionDenied => "PERMISSION_DENIED",
+            Self::NotFound => "NOT_FOUND",
+            Self::NamespaceNotFound => "NAMESPACE_NOT_FOUND",
+            Self::MemoryNotFound => "MEMORY_NOT_FOUND",
+            Self::ConversationNotFound => "CONVERSATION_NOT_FOUND",
+            Self::KeyNotFound => "KEY_NOT_FOUND",
+            Self::AlreadyExists => "ALREADY_EXISTS",
+            Self::NamespaceExists => "NAMESPACE_EXISTS",
+            Self::FailedPrecondition => "FAILED_PRECONDITION",
+            Self::UnsupportedEmbeddingModel => "UNSUPPORTED_EMBEDDING_MODEL",
+            Self::RateLimited => "RATE_LIMITED",
+            Self::QuotaExceeded => "QUOTA_EXCEEDED",
+            Self::SecretDetected => "SECRET_DETECTED",
+            Self::Unimplemented => "UNIMPLEMENTED",
+            Self::Unavailable => "UNAVAILABLE",
+            Self::AuthUnavailable => "AUTH_UNAVAILABLE",
+            Self::VaultUnavailable => "VAULT_UNAVAILABLE",
+            Self::Internal => "INTERNAL",
+        }
+    }
+
+    /// Parse a code received in `ErrorInfo.reason`
+    pub fn parse(reason: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|code| code.as_str() == reason)
+    }
+
+    /// gRPC status code the error is sent with
+    pub fn grpc_code(self) -> Code {
+        match self {
+            Self::InvalidArgument | Self::SecretDetected => Code::InvalidArgument,
+            Self::Unauthenticated | Self::InvalidCredentials | Self::InvalidToken => Code::Unauthenticated,
+            Self::PermissionDenied => Code::PermissionDenied,
+            Self::NotFound
+            | Self::NamespaceNotFound
+            | Self::MemoryNotFound
+            | Self::ConversationNotFound
+            | Self::KeyNotFound => Code::NotFound,
+            Self::AlreadyExists | Self::NamespaceExists => Code::AlreadyExists,
+            Self::FailedPrecondition | Self::UnsupportedEmbeddingModel => Code::FailedPrecondition,
+            Self::RateLimited | Self::QuotaExceeded => Code::ResourceExhausted,
+            Self::Unimplemented => Code::Unimplemented,
+            Self::Unavailable | Self::AuthUnavailable | Self::VaultUnavailable => Code::Unavailable,
+            Self::Internal => Code::Internal,
+        }
+    }
+
+    /// Generic code for a status from a service that sent no `ErrorInfo`
+    fn from_grpc(code: Code) -> Self {
+        match code {
+            Code::InvalidArgument | Code::OutOfRange => Self::InvalidArgument,
+            Code::Unauthenticated => Self::Unauthenticated,
+            Code::PermissionDenied => Self::PermissionDenied,
+            Code::NotFound => Self::NotFound,
+            Code::AlreadyExists | Code::Aborted => Self::AlreadyExists,
+            Code::FailedPrecondition => Self::FailedPrecondition,
+            Code::ResourceExhausted => Self::RateLimited,
+            Code::Unimplemented => Self::Unimplemented,
+            Code::Unavailable | Code::DeadlineExceeded | Code::Cancelled => Self::Unavailable,
+            Code::Ok | Code::Unknown | Code::Internal | Code::DataLoss => Self::Internal,
+        }
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// One invalid request field, sent as a `google.rpc.BadRequest.FieldViolation`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldViolation {
+    pub field: String,
+    pub description: String,
+}
+
+/// An error that can be returned to a client
+#[derive(Debug, Clone)]
+pub struct Error {
+    code: ErrorCode,
+    message: String,
+    violations: Vec<FieldViolation>,
+    retry_after: Option<Duration>,
+    metadata: BTreeMap<String, String>,
+    internal: Option<String>,
+}
+
+impl Error {
+    /// An error whose `message` is safe to show to the caller
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            violations: Vec::new(),
+            retry_after: None,
+            metadata: BTreeMap::new(),
+            internal: None,
+        }
+    }
+
+    /// An invalid request that isn't about a single field
+    pub fn invalid_argument(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::InvalidArgument, message)
+    }
+
+    /// An invalid request field; `description` doubles as the message
+    pub fn invalid_field(field: impl Into<String>, description: impl Into<String>) -> Self {
+        let description = description.into();
+        Self::invalid_argument(description.clone()).with_field(field, description)
+    }
+
+    pub fn permission_denied(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::PermissionDenied, message)
+    }
+
+    pub fn failed_precondition(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::FailedPrecondition, message)
+    }
+
+    /// A failure the caller can do nothing about. `detail` is logged, the client only
+    /// sees "Internal error".
+    pub fn internal(detail: impl fmt::Display) -> Self {
+        Self::new(ErrorCode::Internal, "Internal error").with_internal(detail)
+    }
+
+    /// Add a field violation
+    pub fn with_field(mut self, field: impl Into<String>, description: impl Into<String>) -> Self {
+        self.violations.push(FieldViolation { field: field.into(), description: description.into() });
+        self
+    }
+
+    /// Tell the client how long to wait before retrying
+    pub fn with_retry_after(mut self, delay: Duration) -> Self {
+        self.retry_after = Some(delay);
+        self
+    }
+
+    /// Add a key to `ErrorInfo.metadata`, such as the name of a missing resource
+    pub fn with_metadata(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.metadata.insert(key.into(), value.into());
+        self
+    }
+
+    /// Attach a detail for the logs that is never sent to the client
+    pub fn with_internal(mut self, detail: impl fmt::Display) -> Self {
+        self.internal = Some(detail.to_string());
+        self
+    }
+
+    pub fn code(&self) -> ErrorCode {
+        self.code
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    pub fn field_violations(&self) -> &[FieldViolation] {
+        &self.violations
+    }
+
+    pub fn retry_after(&self) -> Option<Duration> {
+        self.retry_after
+    }
+
+    pub fn metadata(&self) -> &BTreeMap<String, String> {
+        &self.metadata
+    }
+
+    /// Rebuild an error from a status received from an Identra service. Statuses
+    /// without `ErrorInfo` get the generic code for their gRPC code.
+    pub fn from_status(status: &Status) -> Self {
+        let mut error = Self::new(ErrorCode::from_grpc(status.code()), status.message());
+        let Ok(details) = RpcStatus::decode(status.details()) else {
+            return error;
+        };
+        for detail in details.details {
+            match detail.type_url.as_str() {
+                ERROR_INFO_TYPE => {
+                    if let Ok(info) = ErrorInfo::decode(detail.value.as_slice()) {
+                        if info.domain == DOMAIN {
+                            error.code = ErrorCode::parse(&info.reason).unwrap_or(error.code);
+                        }
+                        error.metadata.extend(info.metadata);
+                    }
+                }
+                BAD_REQUEST_TYPE => {
+                    if let Ok(bad_request) = BadRequest::decode(detail.value.as_slice()) {
+                        error.violations.extend(bad_request.field_violations.into_iter().map(|v| {
+                            FieldViolation { field: v.field, description: v.description }
+                        }));
+                    }
+                }
+                RETRY_INFO_TYPE => {
+                    if let Ok(Some(delay)) = RetryInfo::decode(detail.value.as_slice()).map(|r| r.retry_delay) {
+                        error.retry_after = Some(Duration::new(
+                            delay.seconds.max(0) as u64,
+                            delay.nanos.clamp(0, 999_999_999) as u32,
+                        ));
+                    }
+                }
+                _ => {}
+            }
+        }
+        error
+    }
+
+    /// `google.rpc.Status` describing this error, as sent in `grpc-status-details-bin`
+    fn rpc_status(&self) -> RpcStatus {
+        let info = ErrorInfo {
+            reason: self.code.as_str().to_string(),
+            domain: DOMAIN.to_string(),
+            metadata: self.metadata.clone().into_iter().collect(),
+        };
+        let mut details = vec![any(ERROR_INFO_TYPE, &info)];
+        if !self.violations.is_empty() {
+            let bad_request = BadRequest {
+                field_violations: self.violations.iter()
+                    .map(|v| BadRequestFieldViolation { field: v.field.clone(), description: v.description.clone() })
+                    .collect(),
+            };
+            details.push(any(BAD_REQUEST_TYPE, &bad_request));
+        }
+        if let Some(delay) = self.retry_after {
+            let retry = RetryInfo {
+                retry_delay: Some(prost_types::Duration {
+                    seconds: delay.as_secs() as i64,
+                    nanos: delay.subsec_nanos() as i32,
+                }),
+            };
+            details.push(any(RETRY_INFO_TYPE, &retry));
+        }
+        RpcStatus {
+            code: self.code.grpc_code() as i32,
+            message: self.message.clone(),
+            details,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.code, self.message)?;
+        if let Some(detail) = &self.internal {
+            write!(f, " ({})", detail)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<Error> for Status {
+    fn from(error: Error) -> Self {
+        if let Some(detail) = &error.internal {
+            if error.code == ErrorCode::Internal {
+                tracing::error!(code = error.code.as_str(), "{}", detail);
+            } else {
+                tracing::warn!(code = error.code.as_str(), "{}: {}", error.message, detail);
+            }
+        }
+
+        let details = error.rpc_status().encode_to_vec();
+        let mut status = Status::with_details(error.code.grpc_code(), error.message, details.into());
+        if let Some(delay) = error.retry_after {
+            let seconds = delay.as_secs() + u64::from(delay.subsec_nanos() > 0);
+            status.metadata_mut().insert(RETRY_AFTER, seconds.max(1).into());
+        }
+        status
+    }
+}
+
+fn any(type_url: &str, message: &impl Message) -> prost_types::Any {
+    prost_types::Any { type_url: type_url.to_string(), value: message.encode_to_vec() }
+}
+
+// The subset of google/rpc/status.proto and google/rpc/error_details.proto we send
+
+#[derive(Clone, PartialEq, Message)]
+struct RpcStatus {
+    #[prost(int32, tag = "1")]
+    code: i32,
+    #[prost(string, tag = "2")]
+    message: String,
+    #[prost(message, repeated, tag = "3")]
+    details: Vec<prost_types::Any>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct ErrorInfo {
+    #[prost(string, tag = "1")]
+    reason: String,
+    #[prost(string, tag = "2")]
+    domain: String,
+    #[prost(map = "string, string", tag = "3")]
+    metadata: std::collections::HashMap<String, String>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct BadRequest {
+    #[prost(message, repeated, tag = "1")]
+    field_violations: Vec<BadRequestFieldViolation>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct BadRequestFieldViolation {
+    #[prost(string, tag = "1")]
+    field: String,
+    #[prost(string, tag = "2")]
+    description: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct RetryInfo {
+    #[prost(message, optional, tag = "1")]
+    retry_delay: Option<prost_types::Duration>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn codes_round_trip_through_their_names() {
+        for code in ErrorCode::ALL {
+            assert_eq!(ErrorCode::parse(code.as_str()), Some(code));
+        }
+        assert_eq!(ErrorCode::parse("NO_SUCH_CODE"), None);
+    }
+
+    #[test]
+    fn details_survive_the_wire() {
+        let error = Error::invalid_field("content", "Content required")
+            .with_field("namespace", "Too long")
+            .with_metadata("namespace", "work")
+            .with_retry_after(Duration::from_millis(1500));
+        let status = Status::from(error);
+        assert_eq!(status.code(), Code::InvalidArgument);
+        assert_eq!(status.message(), "Content required");
+        assert_eq!(status.metadata().get(RETRY_AFTER).unwrap(), "2");
+
+        let decoded = Error::from_status(&status);
+        assert_eq!(decoded.code(), ErrorCode::InvalidArgument);
+        assert_eq!(decoded.field_violations().len(), 2);
+        assert_eq!(decoded.field_violations()[1].field, "namespace");
+        assert_eq!(decoded.metadata().get("namespace").map(String::as_str), Some("work"));
+        assert_eq!(decoded.retry_after(), Some(Duration::from_millis(1500)));
+    }
+
+    #[test]
+    fn internal_details_stay_on_the_server() {
+        let status = Status::from(Error::internal("relation \"memories\" does not exist"));
+        assert_eq!(status.code(), Code::Internal);
+        assert_eq!(status.message(), "Internal error");
+        assert!(!String::from_utf8_lossy(status.details()).contains("memories"));
+        assert_eq!(Error::from_status(&status).code(), ErrorCode::Internal);
+    }
+
+    #[test]
+    fn plain_statuses_get_a_generic_code() {
+        let error = Error::from_status(&Status::not_found("gone"));
+        assert_eq!(error.code(), ErrorCode::NotFound);
+        assert_eq!(error.message(), "gone");
+    }
+}
@@ -0,0 +1,128 @@
+//! Tracing setup shared by the gateway, vault daemon and desktop client.
+//!
+//! Every binary logs through `tracing` and records spans in OpenTelemetry, so trace
+//! ids exist and propagate even when nothing is exported. With
+//! `telemetry.otlp_endpoint` set, spans are also batched to that collector. Traces
+//! cross process boundaries as a W3C `traceparent`: in gRPC metadata and in the
+//! vault IPC envelope.
+
+use crate::config::TelemetryConfig;
+use opentelemetry::propagation::TextMapPropagator;
+use opentelemetry::trace::{TraceError, TracerProvider as _};
+use opentelemetry::{KeyValue, global};
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::Resource;
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::TracerProvider;
+use std::collections::HashMap;
+use thiserror::Error;
+use tracing::Span;
+use tracing::level_filters::LevelFilter;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::{SubscriberInitExt, TryInitError};
+use tracing_subscriber::{EnvFilter, Layer};
+
+/// Header and metadata key carrying the W3C trace context
+pub const TRACEPARENT: &str = "traceparent";
+
+#[derive(Error, Debug)]
+pub enum InitError {
+    #[error("Failed to create OTLP exporter: {0}")]
+    Exporter(#[from] TraceError),
+
+    #[error("Failed to install tracing subscriber: {0}")]
+    Subscriber(#[from] TryInitError),
+}
+
+/// Flushes buffered spans when dropped; hold it until the process exits
+pub struct TraceGuard {
+    provider: TracerProvider,
+}
+
+impl Drop for TraceGuard {
+    fn drop(&mut self) {
+        if let Err(e) = self.provider.shutdown() {
+            eprintln!("Failed to flush traces: {}", e);
+        }
+    }
+}
+
+/// Install the global subscriber for `service`. Log output is filtered by `RUST_LOG`;
+/// spans at INFO and above are recorded for export regardless. Must be called
+/// inside a Tokio runtime when an OTLP endpoint is configured.
+pub fn init(service: &'static str, config: &TelemetryConfig) -> Result<TraceGuard, InitError> {
+    let mut builder = TracerProvider::builder()
+        .with_resource(Resource::new([KeyValue::new("service.name", service)]));
+    if let Some(endpoint) = &config.otlp_endpoint {
+        let exporter = opentelemetry_otlp::SpanExporter::builder()
+            .with_tonic()
+            .with_endpoint(endpoint)
+            .build()?;
+        builder = builder.with_batch_exporter(exporter, opentelemetry_sdk::runtime::Tokio);
+    }
+    let provider = builder.build();
+
+    global::set_text_map_propagator(TraceContextPropagator::new());
+    global::set_tracer_provider(provider.clone());
+
+    tracing_subscriber::registry()
+        .with(tracing_subscriber::fmt::layer().with_filter(EnvFilter::from_default_env()))
+        .with(
+            tracing_opentelemetry::layer()
+                .with_tracer(provider.tracer(service))
+                .with_filter(LevelFilter::INFO),
+        )
+        .try_init()?;
+
+    Ok(TraceGuard { provider })
+}
+
+/// `traceparent` of the current span, to hand to the next hop
+pub fn traceparent() -> Option<String> {
+    let context = Span::current().context();
+    let mut carrier = HashMap::new();
+    TraceContextPropagator::new().inject_context(&context, &mut carrier);
+    carrier.remove(TRACEPARENT)
+}
+
+/// Make `span` a child of the remote span described by `traceparent`.
+/// Malformed values are ignored and `span` starts a new trace.
+pub fn set_parent(span: &Span, traceparent: &str) {
+    let carrier = HashMap::from([(TRACEPARENT.to_string(), traceparent.to_string())]);
+    let context = TraceContextPropagator::new().extract(&carrier);
+    span.set_parent(context);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opentelemetry::trace::TraceContextExt;
+
+    fn trace_id(span: &Span) -> String {
+        span.context().span().span_context().trace_id().to_string()
+    }
+
+    #[test]
+    fn traceparent_round_trips_between_spans() {
+        let provider = TracerProvider::builder().build();
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+
+        tracing::subscriber::with_default(subscriber, || {
+            assert_eq!(traceparent(), None);
+
+            let caller = tracing::info_span!("caller");
+            let header = caller.in_scope(traceparent).expect("caller has a trace context");
+            assert!(header.starts_with(&format!("00-{}-", trace_id(&caller))), "{}", header);
+
+            let callee = tracing::info_span!("callee");
+            set_parent(&callee, &header);
+            assert_eq!(trace_id(&callee), trace_id(&caller));
+
+            let unrelated = tracing::info_span!("unrelated");
+            set_parent(&unrelated, "not a traceparent");
+            assert_ne!(trace_id(&unrelated), trace_id(&caller));
+        });
+    }
+}
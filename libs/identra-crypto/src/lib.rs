@@ -6,11 +6,31 @@ use aes_gcm::{
     aead::{Aead, AeadCore, KeyInit, OsRng}, 
 };
 use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+use thiserror::Error;
 
 pub struct MemoryVault;
 
-// Define a simple result alias for cleaner code
-type Result<T> = std::result::Result<T, String>;
+/// Why a packet could not be sealed or opened. Messages never include key material
+/// or plaintext.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum CryptoError {
+    #[error("Encryption failed")]
+    Encrypt,
+
+    #[error("Base64 decode failed: {0}")]
+    Encoding(String),
+
+    #[error("Packet too short")]
+    Truncated,
+
+    #[error("Decryption failed (Wrong Key or Corrupted Data)")]
+    Decrypt,
+
+    #[error("UTF-8 Error: {0}")]
+    Utf8(#[from] std::string::FromUtf8Error),
+}
+
+type Result<T> = std::result::Result<T, CryptoError>;
 
 impl MemoryVault {
     /// Generates a cryptographically secure 256-bit key
@@ -25,7 +45,7 @@ impl MemoryVault {
         let nonce = Aes256Gcm::generate_nonce(&mut OsRng); 
 
         let ciphertext = cipher.encrypt(&nonce, data.as_bytes())
-            .map_err(|_| "Encryption failed".to_string())?;
+            .map_err(|_| CryptoError::Encrypt)?;
 
         // Combine Nonce + Ciphertext
         let mut packet = Vec::with_capacity(nonce.len() + ciphertext.len());
@@ -40,11 +60,11 @@ impl MemoryVault {
     pub fn open(enc_packet: &str, key: &Key<Aes256Gcm>) -> Result<String> {
         // 1. Decode Base64
         let packet_bytes = BASE64.decode(enc_packet)
-            .map_err(|e| format!("Base64 decode failed: {}", e))?;
+            .map_err(|e| CryptoError::Encoding(e.to_string()))?;
 
         // 2. Extract Nonce (First 12 bytes) and Ciphertext
         if packet_bytes.len() < 12 {
-            return Result::Err("Packet too short".to_string());
+            return Err(CryptoError::Truncated);
         }
         let (nonce_bytes, ciphertext) = packet_bytes.split_at(12);
         let nonce = Nonce::from_slice(nonce_bytes);
@@ -54,10 +74,9 @@ impl MemoryVault {
 
         // 4. Decrypt
         let plaintext_bytes = cipher.decrypt(nonce, ciphertext)
-            .map_err(|_| "Decryption failed (Wrong Key or Corrupted Data)".to_string())?;
+            .map_err(|_| CryptoError::Decrypt)?;
 
         // 5. Convert to String
-        String::from_utf8(plaintext_bytes)
-            .map_err(|e| format!("UTF-8 Error: {}", e))
+        Ok(String::from_utf8(plaintext_bytes)?)
     }
 }
@@ -1,17 +1,62 @@
+use std::path::PathBuf;
+
+// Timestamp and bytes fields need explicit JSON encodings, see src/json.rs
+const TIMESTAMP_FIELDS: &[&str] = &[
+    ".identra.memory.v1.Memory.created_at",
+    ".identra.memory.v1.Memory.updated_at",
+    ".identra.memory.v1.TrashedMemory.deleted_at",
+    ".identra.memory.v1.TrashedMemory.purge_at",
+    ".identra.memory.v1.Conversation.created_at",
+    ".identra.memory.v1.Conversation.updated_at",
+    ".identra.memory.v1.ConversationTurn.created_at",
+    ".identra.memory.v1.Namespace.created_at",
+    ".identra.memory.v1.Namespace.updated_at",
+    ".identra.memory.v1.Grant.created_at",
+    ".identra.memory.v1.MemoryEvent.occurred_at",
+    ".identra.memory.v1.MemoryStats.oldest",
+    ".identra.memory.v1.MemoryStats.newest",
+    ".identra.memory.v1.TimeRange.start",
+    ".identra.memory.v1.TimeRange.end",
+    ".identra.vault.v1.StoreKeyRequest.expires_at",
+    ".identra.vault.v1.RetrieveKeyResponse.created_at",
+    ".identra.audit.v1.AuditEntry.occurred_at",
+    ".identra.audit.v1.AuditFilter.since",
+    ".identra.audit.v1.AuditFilter.until",
+];
+
+const BYTES_FIELDS: &[&str] = &[
+    ".identra.vault.v1.StoreKeyRequest.key_data",
+    ".identra.vault.v1.RetrieveKeyResponse.key_data",
+    ".identra.audit.v1.ExportAuditLogChunk.data",
+];
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // Compile proto files (output goes to OUT_DIR by default)
-    tonic_build::configure()
+    let out_dir = PathBuf::from(std::env::var("OUT_DIR")?);
+
+    // Messages get serde impls using proto field names, so the gateway can serve them as JSON
+    let mut builder = tonic_build::configure()
         .build_server(true)
         .build_client(true)
-        .compile_protos(
-            &[
-                "proto/vault.proto",
-                "proto/memory.proto",
-                "proto/health.proto",
-                "proto/auth.proto",
-            ],
-            &["proto"],
-        )?;
+        .file_descriptor_set_path(out_dir.join("identra_descriptor.bin"))
+        .message_attribute(".", "#[derive(serde::Serialize, serde::Deserialize)] #[serde(default)]");
+    for field in TIMESTAMP_FIELDS {
+        builder = builder.field_attribute(field, "#[serde(with = \"crate::json::timestamp\")]");
+    }
+    for field in BYTES_FIELDS {
+        builder = builder.field_attribute(field, "#[serde(with = \"crate::json::base64_bytes\")]");
+    }
+
+    // Compile proto files (output goes to OUT_DIR by default)
+    builder.compile_protos(
+        &[
+            "proto/vault.proto",
+            "proto/memory.proto",
+            "proto/health.proto",
+            "proto/auth.proto",
+            "proto/audit.proto",
+        ],
+        &["proto"],
+    )?;
     
     Ok(())
 }
@@ -0,0 +1,61 @@
+//! JSON encodings for proto types without serde support, following the proto3 JSON mapping
+
+/// `google.protobuf.Timestamp` as an RFC 3339 string
+pub mod timestamp {
+    use chrono::{DateTime, SecondsFormat};
+    use prost_types::Timestamp;
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &Option<Timestamp>, serializer: S) -> Result<S::Ok, S::Error> {
+        match value.as_ref().and_then(|t| DateTime::from_timestamp(t.seconds, t.nanos.max(0) as u32)) {
+            Some(time) => serializer.serialize_str(&time.to_rfc3339_opts(SecondsFormat::AutoSi, true)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Timestamp>, D::Error> {
+        let Some(value) = Option::<String>::deserialize(deserializer)? else { return Ok(None) };
+        let time = DateTime::parse_from_rfc3339(&value)
+            .map_err(|e| D::Error::custom(format!("invalid timestamp '{}': {}", value, e)))?;
+        Ok(Some(Timestamp { seconds: time.timestamp(), nanos: time.timestamp_subsec_nanos() as i32 }))
+    }
+}
+
+/// `bytes` as a standard base64 string
+pub mod base64_bytes {
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&STANDARD.encode(value))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        STANDARD.decode(value).map_err(|e| D::Error::custom(format!("invalid base64: {}", e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::memory::Memory;
+    use crate::vault::StoreKeyRequest;
+
+    #[test]
+    fn timestamps_and_bytes_round_trip() {
+        let memory = Memory {
+            id: "m1".into(),
+            created_at: Some(prost_types::Timestamp { seconds: 1_700_000_000, nanos: 0 }),
+            ..Default::default()
+        };
+        let json = serde_json::to_value(&memory).unwrap();
+        assert_eq!(json["created_at"], "2023-11-14T22:13:20Z");
+        assert!(json["updated_at"].is_null());
+        let back: Memory = serde_json::from_value(json).unwrap();
+        assert_eq!(back, memory);
+
+        let key: StoreKeyRequest = serde_json::from_str(r#"{"key_id":"k","key_data":"AQID"}"#).unwrap();
+        assert_eq!(key.key_data, vec![1, 2, 3]);
+        assert!(key.expires_at.is_none());
+    }
+}
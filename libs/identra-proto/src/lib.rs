@@ -1,3 +1,8 @@
+pub mod json;
+
+/// Encoded `FileDescriptorSet` of all Identra protos, for reflection and schema generation
+pub const FILE_DESCRIPTOR_SET: &[u8] = tonic::include_file_descriptor_set!("identra_descriptor");
+
 // Include generated protobuf code from build.rs
 pub mod health {
     tonic::include_proto!("identra.health.v1");
@@ -15,3 +20,7 @@ pub mod auth {
     tonic::include_proto!("identra.auth");
 }
 
+pub mod audit {
+    tonic::include_proto!("identra.audit.v1");
+}
+
@@ -15,7 +15,7 @@ NC='\033[0m'
 # Build both components
 echo -e "${YELLOW}📦 Building components...${NC}"
 cargo build --bin vault-daemon --release --quiet
-cargo build --example test_vault_ipc --quiet
+cargo build -p identra-client --example test_vault_ipc --quiet
 
 # Kill any existing vault-daemon
 pkill -9 vault-daemon 2>/dev/null || true